@@ -0,0 +1,108 @@
+//! Appending one block of many transfers, which is dominated by signature checks, and
+//! building plus appending it the way `/seal` does. Both report transfers per second.
+//!
+//! Run with `cargo bench --bench append_block`.
+
+use axa_network::chain::{ATokenChain, ChainConfig};
+use axa_network::crypto::Wallet;
+use axa_network::model::{Block, SignedTx, TokenMetadata, UnsignedTx};
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+const CHAIN_ID: &str = "AToken-bench";
+/// Minted up front, so transfers run against a ledger of realistic size
+const SUPPLY: u64 = 100_000;
+
+/// Chain holding a single mint of `SUPPLY`, the issuer, and `txs` signed one-token
+/// transfers from the issuer.
+fn chain_and_transfers(txs: u64) -> (ATokenChain, Wallet, Vec<SignedTx>) {
+    let issuer = Wallet::generate();
+    let alice = Wallet::generate();
+    let mut chain = ATokenChain::new(ChainConfig::new(CHAIN_ID, issuer.address()));
+    let metadata = TokenMetadata {
+        name: "AToken".to_string(),
+        symbol: "ATKN".to_string(),
+        description: "Bench token".to_string(),
+        decimals: 0,
+        issuer: String::new(),
+    };
+    let mint = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, SUPPLY, metadata),
+        &issuer,
+    )
+    .unwrap();
+    let genesis = chain.build_block(&issuer, vec![mint]).unwrap();
+    chain.append_block(genesis).unwrap();
+
+    let transfers = (0..txs)
+        .map(|token_id| {
+            SignedTx::sign(
+                UnsignedTx::transfer(
+                    CHAIN_ID,
+                    issuer.address(),
+                    token_id + 2,
+                    0,
+                    alice.address(),
+                    vec![token_id],
+                ),
+                &issuer,
+            )
+            .unwrap()
+        })
+        .collect();
+    (chain, issuer, transfers)
+}
+
+/// `chain_and_transfers` with the transfers already sealed into a block.
+fn chain_and_block(txs: u64) -> (ATokenChain, Block) {
+    let (chain, issuer, transfers) = chain_and_transfers(txs);
+    let block = chain.build_block(&issuer, transfers).unwrap();
+    (chain, block)
+}
+
+fn append_block(c: &mut Criterion) {
+    let mut group = c.benchmark_group("append_block");
+    for txs in [10, 100, 1_000] {
+        let (chain, block) = chain_and_block(txs);
+        group.throughput(Throughput::Elements(txs));
+        group.bench_with_input(BenchmarkId::from_parameter(txs), &block, |b, block| {
+            b.iter_batched(
+                || (chain.clone(), block.clone()),
+                // The chain is handed back so it is dropped outside the measurement
+                |(mut chain, block)| {
+                    chain.append_block(block).unwrap();
+                    chain
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Merkle root, proposer signature and block hash on top of `append_block`.
+fn build_and_append(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_and_append");
+    for txs in [100, 1_000] {
+        let (chain, issuer, transfers) = chain_and_transfers(txs);
+        group.throughput(Throughput::Elements(txs));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(txs),
+            &transfers,
+            |b, transfers| {
+                b.iter_batched(
+                    || (chain.clone(), transfers.clone()),
+                    |(mut chain, transfers)| {
+                        let block = chain.build_block(&issuer, transfers).unwrap();
+                        chain.append_block(block).unwrap();
+                        chain
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, append_block, build_and_append);
+criterion_main!(benches);
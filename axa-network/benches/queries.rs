@@ -0,0 +1,76 @@
+//! Holdings lookups on a large asset spread over many holders. They are served by the
+//! owner -> tokens index; to see what it saves, run the same bench on a checkout from before
+//! it (the parent of "Keep an owner -> tokens index for balance and holdings lookups"), where
+//! each lookup scans every token id.
+//!
+//! Run with `cargo bench --bench queries`.
+
+use std::hint::black_box;
+
+use axa_network::chain::{ATokenChain, ChainConfig};
+use axa_network::crypto::Wallet;
+use axa_network::model::{SignedTx, TokenMetadata, UnsignedTx};
+use criterion::{Criterion, criterion_group, criterion_main};
+
+const CHAIN_ID: &str = "AToken-bench";
+const SUPPLY: u64 = 1_000_000;
+const HOLDERS: u64 = 1_000;
+
+/// Chain with `SUPPLY` tokens split evenly between `HOLDERS` addresses, and those addresses.
+fn distributed_chain() -> (ATokenChain, Vec<String>) {
+    let issuer = Wallet::generate();
+    let holders: Vec<String> = (0..HOLDERS).map(|_| Wallet::generate().address()).collect();
+    let mut config = ChainConfig::new(CHAIN_ID, issuer.address());
+    config.max_mint_amount = SUPPLY;
+    let mut chain = ATokenChain::new(config);
+    let metadata = TokenMetadata {
+        name: "AToken".to_string(),
+        symbol: "ATKN".to_string(),
+        description: "Bench token".to_string(),
+        decimals: 0,
+        issuer: String::new(),
+    };
+    let distribution = holders
+        .iter()
+        .map(|holder| (holder.clone(), SUPPLY / HOLDERS))
+        .collect();
+    let mint = SignedTx::sign(
+        UnsignedTx::mint_distributed(
+            CHAIN_ID,
+            issuer.address(),
+            1,
+            SUPPLY,
+            metadata,
+            distribution,
+        ),
+        &issuer,
+    )
+    .unwrap();
+    let genesis = chain.build_block(&issuer, vec![mint]).unwrap();
+    chain.append_block(genesis).unwrap();
+    (chain, holders)
+}
+
+fn queries(c: &mut Criterion) {
+    let (chain, holders) = distributed_chain();
+    let holder = &holders[holders.len() / 2];
+    let stranger = Wallet::generate().address();
+
+    let mut group = c.benchmark_group("queries");
+    group.bench_function("balance_of", |b| {
+        b.iter(|| chain.balance_of(0, black_box(holder)))
+    });
+    group.bench_function("balance_of/no_tokens", |b| {
+        b.iter(|| chain.balance_of(0, black_box(&stranger)))
+    });
+    group.bench_function("tokens_of", |b| {
+        b.iter(|| chain.tokens_of(0, black_box(holder)).len())
+    });
+    group.bench_function("owner_of", |b| {
+        b.iter(|| chain.owner_of(0, black_box(SUPPLY / 2)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
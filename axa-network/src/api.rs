@@ -1,67 +1,354 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
 
 use axum::{
     Json, Router,
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{
+        DefaultBodyLimit, FromRequest, Path, Query, Request, State,
+        rejection::JsonRejection,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderValue, Method, StatusCode, Uri, header},
     response::{IntoResponse, Response},
     routing::{get, post},
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex as AsyncMutex, RwLock, Semaphore, broadcast};
+use tokio::time::MissedTickBehavior;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
+use tracing::{Level, error, info, warn};
 
-use crate::chain::{ATokenChain, ChainConfig};
-use crate::crypto::{Address, Wallet};
-use crate::errors::ATokenError;
-use crate::model::{SignedTx, TokenMetadata, UnsignedTx};
-use crate::storage::{BlockStore, InMemoryBlockStore};
+use crate::api_types::{
+    BalanceResponse, ChainInfoResponse, ErrorResponse, IssueRequest, NonceResponse,
+    PendingTxResponse, TransferRequest, TxAcceptedResponse,
+};
+use crate::chain::{ATokenChain, Asset, BalanceChange, ChainConfig};
+use crate::crypto::{self, Address, HashAlgorithm, Wallet, validate_address};
+use crate::errors::{ATokenError, Result};
+use crate::genesis::GenesisConfig;
+use crate::keystore::Keystore;
+use crate::mempool::Mempool;
+use crate::model::{
+    AssetId, Block, BlockHeader, MerkleProofStep, SignedTx, TokenMetadata, TxPayload, UnsignedTx,
+};
+use crate::snapshot::SnapshotFile;
+use crate::storage::{BlockStore, InMemoryBlockStore, restore_chain, restore_chain_from};
+
+mod openapi;
 
 #[derive(Clone)]
 pub struct AppState {
     inner: Arc<RwLock<AppInner>>,
+    /// Held for a whole commit, so blocks reach the store in the order they were appended,
+    /// while `inner` is only write-locked to apply them and reads go on during the save
+    store: Arc<AsyncMutex<Box<dyn BlockStore>>>,
+    /// Node key that seals every block; a chain started here lists it as a proposer
+    proposer: Arc<Wallet>,
+    /// The chain's, fixed at genesis; SHA-256 until a chain is started here, which is
+    /// always SHA-256
+    hash_algorithm: HashAlgorithm,
+    /// Deprecated `/issue`, `/transfer` and `/burn`, which take private keys and sign server-side
+    server_signing: bool,
+    /// Origins allowed to call the API from a browser; `*` allows any
+    cors_origins: Vec<HeaderValue>,
+    /// Largest request body read, so a huge one is refused before it is parsed
+    max_body_bytes: usize,
+    /// Every committed block, for `/ws/blocks` subscribers
+    block_feed: broadcast::Sender<Arc<Block>>,
+    /// Balance changes of watched addresses, for `/ws/address/{address}` subscribers
+    address_feed: Arc<AddressFeed>,
+    /// Checkpoint file for the chain ledger, see `with_snapshots`
+    snapshots: Option<Arc<SnapshotFile>>,
+    /// One `/verify` at a time, as each replays a copy of the whole chain
+    verify_permit: Arc<Semaphore>,
+}
+
+/// Default of `AppState::with_max_body_bytes`, far above any valid tx
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Blocks a `/ws/blocks` subscriber may fall behind by before it is dropped
+const BLOCK_FEED_CAPACITY: usize = 64;
+/// Events a `/ws/address/{address}` subscriber may fall behind by before it is dropped
+const ADDRESS_FEED_CAPACITY: usize = 64;
+
+/// One channel per watched address, so an address's event is built once per block however
+/// many sockets watch it, and blocks only cost work for addresses someone is watching.
+#[derive(Default)]
+struct AddressFeed {
+    channels: Mutex<HashMap<Address, broadcast::Sender<Arc<BalanceEvent>>>>,
+}
+
+/// What `/ws/address/{address}` pushes for each block that changes the address's tokens.
+#[derive(Debug, Serialize, JsonSchema)]
+struct BalanceEvent {
+    address: Address,
+    block_height: u64,
+    block_hash: String,
+    changes: Vec<BalanceChange>,
+}
+
+impl AddressFeed {
+    fn subscribe(&self, address: Address) -> broadcast::Receiver<Arc<BalanceEvent>> {
+        self.channels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(address)
+            .or_insert_with(|| broadcast::channel(ADDRESS_FEED_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Sends each watched address its share of `changes`, which come ordered by address.
+    fn publish(&self, block: &Block, changes: Vec<BalanceChange>) {
+        let mut channels = self.channels.lock().unwrap_or_else(PoisonError::into_inner);
+        // Forget addresses whose last subscriber went away
+        channels.retain(|_, sender| sender.receiver_count() > 0);
+        if channels.is_empty() {
+            return;
+        }
+        for changes in changes.chunk_by(|a, b| a.address == b.address) {
+            if let Some(sender) = channels.get(&changes[0].address) {
+                let _ = sender.send(Arc::new(BalanceEvent {
+                    address: changes[0].address.clone(),
+                    block_height: block.header.height,
+                    block_hash: block.hash.clone(),
+                    changes: changes.to_vec(),
+                }));
+            }
+        }
+    }
 }
 
-#[derive(Debug)]
 struct AppInner {
     chain_id: String,
     chain: Option<ATokenChain>,
-    store: InMemoryBlockStore,
+    mempool: Mempool,
 }
 
 impl AppState {
     pub fn new(chain_id: String) -> Self {
+        Self::from_parts(chain_id, None, Box::new(InMemoryBlockStore::default()))
+    }
+
+    /// State over a persistent store, with the chain rebuilt from the blocks already in it.
+    pub async fn with_store<S>(chain_id: String, store: S) -> Result<Self>
+    where
+        S: BlockStore + 'static,
+    {
+        let chain = restore_chain(&chain_id, &store).await?;
+        Ok(Self::from_parts(chain_id, chain, Box::new(store)))
+    }
+
+    /// State over `store` for the chain fixed by `genesis`; the chain exists from the start,
+    /// so only the configured issuer can mint.
+    pub async fn with_genesis<S>(genesis: &GenesisConfig, store: S) -> Result<Self>
+    where
+        S: BlockStore + 'static,
+    {
+        let chain = genesis.restore(&store).await?;
+        Ok(Self::from_parts(
+            genesis.chain_id.clone(),
+            Some(chain),
+            Box::new(store),
+        ))
+    }
+
+    /// `with_genesis`, or `with_store` without a genesis, that starts from the snapshot in
+    /// `snapshots` if there is a usable one and checkpoints to it from then on.
+    pub async fn with_snapshots<S>(
+        chain_id: String,
+        genesis: Option<&GenesisConfig>,
+        store: S,
+        snapshots: SnapshotFile,
+    ) -> Result<Self>
+    where
+        S: BlockStore + 'static,
+    {
+        let snapshot = snapshots.load().await.unwrap_or_else(|e| {
+            warn!("cannot read the snapshot, replaying every block: {e}");
+            None
+        });
+        let mut state = match genesis {
+            Some(genesis) => {
+                let chain = genesis.restore_from(&store, snapshot).await?;
+                Self::from_parts(genesis.chain_id.clone(), Some(chain), Box::new(store))
+            }
+            None => {
+                let chain = restore_chain_from(&chain_id, &store, snapshot).await?;
+                Self::from_parts(chain_id, chain, Box::new(store))
+            }
+        };
+        state.snapshots = Some(Arc::new(snapshots));
+        Ok(state)
+    }
+
+    fn from_parts(
+        chain_id: String,
+        chain: Option<ATokenChain>,
+        store: Box<dyn BlockStore>,
+    ) -> Self {
+        let hash_algorithm = chain
+            .as_ref()
+            .map(|chain| chain.config.hash_algorithm)
+            .unwrap_or_default();
         Self {
             inner: Arc::new(RwLock::new(AppInner {
                 chain_id,
-                chain: None,
-                store: InMemoryBlockStore::default(),
+                chain,
+                mempool: Mempool::default(),
             })),
+            store: Arc::new(AsyncMutex::new(store)),
+            proposer: Arc::new(Wallet::generate()),
+            hash_algorithm,
+            server_signing: true,
+            cors_origins: Vec::new(),
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            block_feed: broadcast::channel(BLOCK_FEED_CAPACITY).0,
+            address_feed: Arc::default(),
+            snapshots: None,
+            verify_permit: Arc::new(Semaphore::new(1)),
+        }
+    }
+
+    /// Seals blocks with `proposer` instead of a key generated at startup.
+    pub fn with_proposer(mut self, proposer: Wallet) -> Self {
+        self.proposer = Arc::new(proposer);
+        self
+    }
+
+    pub fn with_server_signing(mut self, enabled: bool) -> Self {
+        self.server_signing = enabled;
+        self
+    }
+
+    pub fn with_cors_origins(mut self, origins: Vec<HeaderValue>) -> Self {
+        self.cors_origins = origins;
+        self
+    }
+
+    /// Holds at most `max_txs` pending txs; `/tx` answers `503` once that many are queued.
+    pub fn with_max_pending(self, max_txs: usize) -> Self {
+        self.inner
+            .try_write()
+            .expect("configured before serving")
+            .mempool
+            .set_max_pending(max_txs);
+        self
+    }
+
+    /// Refuses request bodies over `bytes` with a `413`.
+    pub fn with_max_body_bytes(mut self, bytes: usize) -> Self {
+        self.max_body_bytes = bytes;
+        self
+    }
+
+    pub fn proposer_address(&self) -> Address {
+        self.proposer.address_for(self.hash_algorithm)
+    }
+
+    /// Fails when a restored chain would refuse every block this node seals, which is what
+    /// a key generated at startup gets on a chain it did not start.
+    pub async fn check_proposer(&self) -> Result<()> {
+        let proposer = self.proposer_address();
+        match &self.inner.read().await.chain {
+            Some(chain) if !chain.config.is_authorized_proposer(&proposer) => {
+                Err(ATokenError::UnauthorizedProposer(proposer))
+            }
+            _ => Ok(()),
         }
     }
+
+    /// Address of `wallet` on this node's chain.
+    fn address_of(&self, wallet: &Wallet) -> Address {
+        wallet.address_for(self.hash_algorithm)
+    }
 }
 
 pub fn router(state: AppState) -> Router {
-    Router::new()
+    let mut router = Router::new()
         .route("/health", get(health))
+        .route("/openapi.json", get(openapi_json))
         .route("/wallet/generate", post(wallet_generate))
         .route("/wallet/from-private-key", post(wallet_from_private_key))
-        .route("/issue", post(issue))
-        .route("/transfer", post(transfer))
-        .route("/metadata", get(metadata))
-        .route("/balance/{address}", get(balance))
-        .route("/tokens/{address}", get(tokens))
-        .route("/owner/{token_id}", get(owner_of))
+        .route("/wallet/from-mnemonic", post(wallet_from_mnemonic))
+        .route("/wallet/export", post(wallet_export))
+        .route("/wallet/import", post(wallet_import))
+        .route("/tx/submit", post(submit_signed_tx))
+        .route("/tx/{tx_id}", get(tx_by_id))
+        .route("/proof/tx/{tx_id}", get(tx_proof))
+        .route("/tx", post(submit_tx))
+        .route("/simulate", post(simulate))
+        .route("/seal", post(seal))
+        .route("/mempool", get(mempool))
+        .route("/mempool/count", get(mempool_count))
+        .route("/assets", get(assets))
+        .route("/metadata/{asset_id}", get(metadata))
+        .route("/balance/{asset_id}/{address}", get(balance))
+        .route("/nonce/{address}", get(nonce))
+        .route("/tokens/{asset_id}/{address}", get(tokens))
+        .route("/owner/{asset_id}/{token_id}", get(owner_of))
+        .route("/supply/{asset_id}", get(supply))
+        .route("/holders/{asset_id}/count", get(holders_count))
+        .route("/holders/{asset_id}/top", get(top_holders))
         .route("/chain", get(chain_info))
-        .with_state(state)
+        .route("/verify", get(verify))
+        .route("/verify-tx", post(verify_tx))
+        .route("/verify-personal", post(verify_personal))
+        .route("/blocks", get(blocks))
+        .route("/block/height/{height}", get(block_by_height))
+        .route("/block/hash/{hash}", get(block_by_hash))
+        .route("/ws/blocks", get(ws_blocks))
+        .route("/ws/address/{address}", get(ws_address));
+    if state.server_signing {
+        router = router
+            .route("/issue", post(issue))
+            .route("/transfer", post(transfer))
+            .route("/burn", post(burn))
+            .route("/metadata/{asset_id}", post(update_metadata));
+    }
+    router = router
+        .fallback(not_found)
+        .method_not_allowed_fallback(method_not_allowed)
+        .layer(DefaultBodyLimit::max(state.max_body_bytes));
+    if !state.cors_origins.is_empty() {
+        let origins = if state.cors_origins.iter().any(|origin| origin == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(state.cors_origins.clone())
+        };
+        router = router.layer(
+            CorsLayer::new()
+                .allow_origin(origins)
+                .allow_methods([Method::GET, Method::POST])
+                .allow_headers([header::CONTENT_TYPE]),
+        );
+    }
+    router.with_state(state).layer(
+        TraceLayer::new_for_http()
+            .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+            .on_response(DefaultOnResponse::new().level(Level::INFO)),
+    )
 }
 
-type ApiResult<T> = std::result::Result<Json<T>, ApiError>;
-
-#[derive(Debug, Serialize)]
-struct ErrorResponse {
-    error: String,
+/// Serves the API on `listener` until `shutdown` resolves. In-flight requests finish, then
+/// the store is flushed once no commit holds it.
+pub async fn serve<F>(listener: TcpListener, state: AppState, shutdown: F) -> Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    axum::serve(listener, router(state.clone()))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    state.store.lock().await.flush().await?;
+    info!("AToken API stopped, store flushed");
+    Ok(())
 }
 
+type ApiResult<T> = std::result::Result<Json<T>, ApiError>;
+
 #[derive(Debug)]
 struct ApiError {
     status: StatusCode,
@@ -71,10 +358,17 @@ struct ApiError {
 impl From<ATokenError> for ApiError {
     fn from(value: ATokenError) -> Self {
         let status = match value {
-            ATokenError::AlreadyIssued => StatusCode::CONFLICT,
-            ATokenError::MintNotAllowed => StatusCode::FORBIDDEN,
+            ATokenError::MintNotAllowed
+            | ATokenError::MetadataUpdateNotAllowed
+            | ATokenError::UnauthorizedProposer(_) => StatusCode::FORBIDDEN,
             ATokenError::TokenNotIssued => StatusCode::CONFLICT,
-            ATokenError::UnknownToken(_) => StatusCode::NOT_FOUND,
+            ATokenError::MempoolFull(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ATokenError::UnknownToken(_)
+            | ATokenError::UnknownAsset(_)
+            | ATokenError::UnknownBlockHeight(_)
+            | ATokenError::UnknownBlockHash(_)
+            | ATokenError::UnknownTransaction(_) => StatusCode::NOT_FOUND,
+            ATokenError::ConflictingTx { .. } => StatusCode::CONFLICT,
             ATokenError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
             _ => StatusCode::BAD_REQUEST,
         };
@@ -85,8 +379,60 @@ impl From<ATokenError> for ApiError {
     }
 }
 
+/// `Json` body whose rejections, an oversized or malformed body, answer with an
+/// `ErrorResponse` like every other failure.
+struct ApiJson<T>(T);
+
+impl<T> FromRequest<AppState> for ApiJson<T>
+where
+    Json<T>: FromRequest<AppState, Rejection = JsonRejection>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &AppState) -> std::result::Result<Self, ApiError> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                Err(ApiError {
+                    status: StatusCode::PAYLOAD_TOO_LARGE,
+                    message: format!(
+                        "request body is over the {}-byte limit",
+                        state.max_body_bytes
+                    ),
+                })
+            }
+            Err(rejection) => Err(ApiError {
+                status: rejection.status(),
+                message: rejection.body_text(),
+            }),
+        }
+    }
+}
+
+/// Unknown paths and wrong methods answer with an `ErrorResponse` like every other failure,
+/// instead of axum's empty bodies.
+async fn not_found(uri: Uri) -> ApiError {
+    ApiError {
+        status: StatusCode::NOT_FOUND,
+        message: format!("no route for {}", uri.path()),
+    }
+}
+
+async fn method_not_allowed(method: Method, uri: Uri) -> ApiError {
+    ApiError {
+        status: StatusCode::METHOD_NOT_ALLOWED,
+        message: format!("{method} is not allowed on {}", uri.path()),
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        // Logged inside the request span, so the method and path come along
+        if self.status.is_server_error() {
+            error!(status = %self.status, error = %self.message, "request failed");
+        } else {
+            warn!(status = %self.status, error = %self.message, "request rejected");
+        }
         (
             self.status,
             Json(ErrorResponse {
@@ -97,7 +443,7 @@ impl IntoResponse for ApiError {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 struct HealthResponse {
     status: &'static str,
 }
@@ -106,73 +452,254 @@ async fn health() -> Json<HealthResponse> {
     Json(HealthResponse { status: "ok" })
 }
 
-#[derive(Debug, Serialize)]
+/// OpenAPI document of the routes this node serves.
+async fn openapi_json(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(openapi::document(state.server_signing))
+}
+
+/// `/wallet/generate`, `/wallet/from-private-key` and `/wallet/from-mnemonic` hand out the
+/// private key in plain JSON; `/wallet/export` and `/wallet/import` deal in
+/// password-encrypted keystores.
+#[derive(Debug, Serialize, JsonSchema)]
 struct WalletResponse {
     address: Address,
     private_key_hex: String,
     public_key_hex: String,
+    /// Recovery phrase of a freshly generated wallet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mnemonic: Option<String>,
 }
 
-async fn wallet_generate() -> Json<WalletResponse> {
-    let wallet = Wallet::generate();
+async fn wallet_generate(State(state): State<AppState>) -> Json<WalletResponse> {
+    let (mnemonic, wallet) = Wallet::generate_mnemonic();
     Json(WalletResponse {
-        address: wallet.address(),
+        address: state.address_of(&wallet),
         private_key_hex: wallet.private_key_hex(),
         public_key_hex: wallet.public_key_hex(),
+        mnemonic: Some(mnemonic),
     })
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 struct WalletByPrivateKeyRequest {
     private_key_hex: String,
 }
 
 async fn wallet_from_private_key(
-    Json(req): Json<WalletByPrivateKeyRequest>,
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<WalletByPrivateKeyRequest>,
 ) -> ApiResult<WalletResponse> {
     let wallet = Wallet::from_private_key_hex(&req.private_key_hex)?;
     Ok(Json(WalletResponse {
-        address: wallet.address(),
+        address: state.address_of(&wallet),
         private_key_hex: wallet.private_key_hex(),
         public_key_hex: wallet.public_key_hex(),
+        mnemonic: None,
     }))
 }
 
-#[derive(Debug, Deserialize)]
-struct MetadataInput {
-    name: String,
-    symbol: String,
-    description: String,
-    decimals: u8,
+#[derive(Debug, Deserialize, JsonSchema)]
+struct WalletByMnemonicRequest {
+    mnemonic: String,
+    #[serde(default)]
+    passphrase: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct IssueRequest {
-    issuer_private_key_hex: String,
-    amount: u64,
-    metadata: MetadataInput,
+async fn wallet_from_mnemonic(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<WalletByMnemonicRequest>,
+) -> ApiResult<WalletResponse> {
+    let wallet = Wallet::from_mnemonic(&req.mnemonic, &req.passphrase)?;
+    Ok(Json(WalletResponse {
+        address: state.address_of(&wallet),
+        private_key_hex: wallet.private_key_hex(),
+        public_key_hex: wallet.public_key_hex(),
+        mnemonic: None,
+    }))
 }
 
-#[derive(Debug, Serialize)]
-struct TxAcceptedResponse {
-    block_height: u64,
-    block_hash: String,
-    tx_id: String,
+#[derive(Debug, Deserialize, JsonSchema)]
+struct WalletExportRequest {
+    private_key_hex: String,
+    password: String,
+}
+
+/// Encrypts a private key into a keystore that is safe to store.
+async fn wallet_export(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<WalletExportRequest>,
+) -> ApiResult<Keystore> {
+    let wallet = Wallet::from_private_key_hex(&req.private_key_hex)?;
+    let hash_algorithm = state.hash_algorithm;
+    // scrypt takes a while on purpose; keep it off the async workers
+    let keystore =
+        tokio::task::spawn_blocking(move || wallet.to_keystore(&req.password, hash_algorithm))
+            .await
+            .expect("keystore encryption panicked")?;
+    Ok(Json(keystore))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct WalletImportRequest {
+    keystore: Keystore,
+    password: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct WalletImportResponse {
+    address: Address,
+    public_key_hex: String,
+}
+
+/// Unlocks a keystore to check its password; the private key is not sent back.
+async fn wallet_import(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<WalletImportRequest>,
+) -> ApiResult<WalletImportResponse> {
+    let wallet =
+        tokio::task::spawn_blocking(move || Wallet::from_keystore(&req.keystore, &req.password))
+            .await
+            .expect("keystore decryption panicked")?;
+    Ok(Json(WalletImportResponse {
+        address: state.address_of(&wallet),
+        public_key_hex: wallet.public_key_hex(),
+    }))
+}
+
+impl AppState {
+    /// Logs every tx of a freshly committed block, pushes the block to `/ws/blocks` and its
+    /// `changes` to `/ws/address/{address}`, and writes a snapshot when one is due. Called
+    /// with the store lock held, so no other block can land in between.
+    async fn committed(&self, block: &Block, changes: Vec<BalanceChange>) {
+        for tx in &block.txs {
+            info!(
+                tx_id = %tx.id,
+                block_height = block.header.height,
+                sender = %tx.unsigned.from,
+                "tx accepted"
+            );
+        }
+        // No subscribers is not an error
+        let _ = self.block_feed.send(Arc::new(block.clone()));
+        self.address_feed.publish(block, changes);
+
+        if let Some(snapshots) = &self.snapshots
+            && snapshots.is_due(block.header.height)
+        {
+            let snapshot = self
+                .inner
+                .read()
+                .await
+                .chain
+                .as_ref()
+                .and_then(ATokenChain::export_snapshot);
+            if let Some(snapshot) = snapshot {
+                match snapshots.save(&snapshot).await {
+                    Ok(()) => info!(height = snapshot.height, "snapshot written"),
+                    // The block itself is safely stored, so only the next restart gets slower
+                    Err(e) => warn!(height = snapshot.height, "cannot write the snapshot: {e}"),
+                }
+            }
+        }
+    }
+}
+
+impl AppState {
+    /// Seals up to `max_txs` pending txs into a block and commits it. Without pending txs
+    /// that is `None`, unless `allow_empty` asks for an empty block anyway.
+    pub async fn seal_pending(&self, max_txs: usize, allow_empty: bool) -> Result<Option<Block>> {
+        let store = self.store.lock().await;
+        let mut guard = self.inner.write().await;
+        let inner = &mut *guard;
+        let chain = inner.chain.as_mut().ok_or(ATokenError::TokenNotIssued)?;
+
+        // Pick and stamp at one instant, or a tx valid until then expires in between
+        let block_time_ms = chain.next_block_time();
+        let txs = inner.mempool.take_valid_at(chain, max_txs, block_time_ms);
+        if txs.is_empty() && !allow_empty {
+            return Ok(None);
+        }
+        let sealed = chain
+            .build_block_at(&self.proposer, txs.clone(), block_time_ms)
+            .and_then(|block| Ok((chain.append_block(block.clone())?, block)));
+        let (changes, block) = match sealed {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                // Nothing was committed, so the txs wait for the next block
+                inner.mempool.restore(chain, txs);
+                return Err(e);
+            }
+        };
+        drop(guard);
+        store.save_block(&block).await?;
+        self.committed(&block, changes).await;
+        Ok(Some(block))
+    }
+}
+
+/// How `produce_blocks` seals the mempool
+#[derive(Debug, Clone)]
+pub struct BlockProduction {
+    pub interval: Duration,
+    /// Txs per block; the rest wait for the next tick
+    pub max_txs: usize,
+    /// Seal a block every tick even with nothing pending
+    pub produce_empty: bool,
+}
+
+/// Seals the mempool into a block every `production.interval` until `shutdown` resolves.
+/// A commit in progress is finished first, so the store never sees half of one.
+pub async fn produce_blocks<F>(state: AppState, production: BlockProduction, shutdown: F)
+where
+    F: Future<Output = ()>,
+{
+    let mut ticks = tokio::time::interval_at(
+        tokio::time::Instant::now() + production.interval,
+        production.interval,
+    );
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            biased;
+            () = &mut shutdown => break,
+            _ = ticks.tick() => {}
+        }
+        match state
+            .seal_pending(production.max_txs, production.produce_empty)
+            .await
+        {
+            Ok(Some(block)) => info!(
+                block_height = block.header.height,
+                txs = block.txs.len(),
+                "block sealed"
+            ),
+            // Nothing pending, or no chain to extend until the first mint
+            Ok(None) | Err(ATokenError::TokenNotIssued) => {}
+            Err(e) => warn!("cannot seal a block: {e}"),
+        }
+    }
 }
 
 async fn issue(
     State(state): State<AppState>,
-    Json(req): Json<IssueRequest>,
+    ApiJson(req): ApiJson<IssueRequest>,
 ) -> ApiResult<TxAcceptedResponse> {
     let issuer_wallet = Wallet::from_private_key_hex(&req.issuer_private_key_hex)?;
-    let issuer_address = issuer_wallet.address();
+    let issuer_address = state.address_of(&issuer_wallet);
+    let distribution = req
+        .distribution
+        .into_iter()
+        .map(|share| Ok((validate_address(&share.address)?, share.count)))
+        .collect::<Result<Vec<_>>>()?;
 
+    let store = state.store.lock().await;
     let mut guard = state.inner.write().await;
     if guard.chain.is_none() {
-        guard.chain = Some(ATokenChain::new(ChainConfig::new(
-            guard.chain_id.clone(),
-            issuer_address.clone(),
-        )));
+        guard.chain = Some(ATokenChain::new(
+            ChainConfig::new(guard.chain_id.clone(), issuer_address.clone())
+                .with_proposer(state.proposer_address()),
+        ));
     }
 
     let chain = guard
@@ -184,7 +711,8 @@ async fn issue(
     }
 
     let mint_tx = SignedTx::sign(
-        UnsignedTx::mint(
+        UnsignedTx::mint_distributed(
+            chain.config.chain_id.clone(),
             issuer_address.clone(),
             chain.next_nonce(&issuer_address),
             req.amount,
@@ -195,140 +723,893 @@ async fn issue(
                 decimals: req.metadata.decimals,
                 issuer: String::new(),
             },
+            distribution,
         ),
         &issuer_wallet,
     )?;
     let tx_id = mint_tx.id.clone();
-    let block = chain.build_block(&issuer_wallet, vec![mint_tx])?;
-    chain.append_block(block.clone())?;
-    guard.store.save_block(&block)?;
+    let asset_id = chain.next_asset_id();
+    let block = chain.build_block(&state.proposer, vec![mint_tx])?;
+    let changes = chain.append_block(block.clone())?;
+    drop(guard);
+    store.save_block(&block).await?;
+    state.committed(&block, changes).await;
+
+    Ok(Json(TxAcceptedResponse {
+        block_height: block.header.height,
+        block_hash: block.hash,
+        tx_id,
+        asset_id: Some(asset_id),
+    }))
+}
+
+async fn transfer(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<TransferRequest>,
+) -> ApiResult<TxAcceptedResponse> {
+    let from_wallet = Wallet::from_private_key_hex(&req.from_private_key_hex)?;
+    let from_address = state.address_of(&from_wallet);
+    let to_address = validate_address(&req.to_address)?;
+
+    let store = state.store.lock().await;
+    let mut guard = state.inner.write().await;
+    let chain = guard.chain.as_mut().ok_or(ATokenError::TokenNotIssued)?;
+    chain.check_transfer_size(req.token_ids.len() as u64)?;
+
+    let tx = SignedTx::sign(
+        UnsignedTx::transfer_with_memo(
+            chain.config.chain_id.clone(),
+            from_address.clone(),
+            chain.next_nonce(&from_address),
+            req.asset_id,
+            to_address,
+            req.token_ids,
+            req.memo,
+        )
+        .with_lock_until(req.locked_until_ms),
+        &from_wallet,
+    )?;
+    let tx_id = tx.id.clone();
+    let block = chain.build_block(&state.proposer, vec![tx])?;
+    let changes = chain.append_block(block.clone())?;
+    drop(guard);
+    store.save_block(&block).await?;
+    state.committed(&block, changes).await;
 
     Ok(Json(TxAcceptedResponse {
         block_height: block.header.height,
         block_hash: block.hash,
         tx_id,
+        asset_id: None,
     }))
 }
 
-#[derive(Debug, Deserialize)]
-struct TransferRequest {
+#[derive(Debug, Deserialize, JsonSchema)]
+struct BurnRequest {
     from_private_key_hex: String,
-    to_address: Address,
+    asset_id: AssetId,
     token_ids: Vec<u64>,
 }
 
-async fn transfer(
+async fn burn(
     State(state): State<AppState>,
-    Json(req): Json<TransferRequest>,
+    ApiJson(req): ApiJson<BurnRequest>,
 ) -> ApiResult<TxAcceptedResponse> {
     let from_wallet = Wallet::from_private_key_hex(&req.from_private_key_hex)?;
-    let from_address = from_wallet.address();
+    let from_address = state.address_of(&from_wallet);
 
+    let store = state.store.lock().await;
     let mut guard = state.inner.write().await;
     let chain = guard.chain.as_mut().ok_or(ATokenError::TokenNotIssued)?;
+    chain.check_transfer_size(req.token_ids.len() as u64)?;
 
     let tx = SignedTx::sign(
-        UnsignedTx::transfer(
+        UnsignedTx::burn(
+            chain.config.chain_id.clone(),
             from_address.clone(),
             chain.next_nonce(&from_address),
-            req.to_address,
+            req.asset_id,
             req.token_ids,
         ),
         &from_wallet,
     )?;
     let tx_id = tx.id.clone();
-    let block = chain.build_block(&from_wallet, vec![tx])?;
-    chain.append_block(block.clone())?;
-    guard.store.save_block(&block)?;
+    let block = chain.build_block(&state.proposer, vec![tx])?;
+    let changes = chain.append_block(block.clone())?;
+    drop(guard);
+    store.save_block(&block).await?;
+    state.committed(&block, changes).await;
 
     Ok(Json(TxAcceptedResponse {
         block_height: block.header.height,
         block_hash: block.hash,
         tx_id,
+        asset_id: None,
+    }))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct UpdateMetadataRequest {
+    issuer_private_key_hex: String,
+    name: String,
+    description: String,
+}
+
+async fn update_metadata(
+    State(state): State<AppState>,
+    Path(asset_id): Path<AssetId>,
+    ApiJson(req): ApiJson<UpdateMetadataRequest>,
+) -> ApiResult<TxAcceptedResponse> {
+    let issuer_wallet = Wallet::from_private_key_hex(&req.issuer_private_key_hex)?;
+    let issuer_address = state.address_of(&issuer_wallet);
+
+    let store = state.store.lock().await;
+    let mut guard = state.inner.write().await;
+    let chain = guard.chain.as_mut().ok_or(ATokenError::TokenNotIssued)?;
+
+    let tx = SignedTx::sign(
+        UnsignedTx::update_metadata(
+            chain.config.chain_id.clone(),
+            issuer_address.clone(),
+            chain.next_nonce(&issuer_address),
+            asset_id,
+            req.name,
+            req.description,
+        ),
+        &issuer_wallet,
+    )?;
+    let tx_id = tx.id.clone();
+    let block = chain.build_block(&state.proposer, vec![tx])?;
+    let changes = chain.append_block(block.clone())?;
+    drop(guard);
+    store.save_block(&block).await?;
+    state.committed(&block, changes).await;
+
+    Ok(Json(TxAcceptedResponse {
+        block_height: block.header.height,
+        block_hash: block.hash,
+        tx_id,
+        asset_id: None,
+    }))
+}
+
+/// Appends a transaction signed by the client in a block of its own. A mint on a fresh
+/// chain makes its sender the issuer; every mint creates a new asset.
+async fn submit_signed_tx(
+    State(state): State<AppState>,
+    ApiJson(tx): ApiJson<SignedTx>,
+) -> ApiResult<TxAcceptedResponse> {
+    let store = state.store.lock().await;
+    let mut guard = state.inner.write().await;
+    let inner = &mut *guard;
+
+    let mut fresh_chain = None;
+    if inner.chain.is_none() {
+        if !matches!(tx.unsigned.payload, TxPayload::Mint { .. }) {
+            return Err(ATokenError::TokenNotIssued.into());
+        }
+        fresh_chain = Some(ATokenChain::new(
+            ChainConfig::new(inner.chain_id.clone(), tx.unsigned.from.clone())
+                .with_proposer(state.proposer_address()),
+        ));
+    }
+
+    let tx_id = tx.id.clone();
+    let is_mint = matches!(tx.unsigned.payload, TxPayload::Mint { .. });
+    let (block, asset_id, changes) = {
+        let chain = match fresh_chain.as_mut() {
+            Some(chain) => chain,
+            None => inner.chain.as_mut().ok_or(ATokenError::TokenNotIssued)?,
+        };
+        let asset_id = is_mint.then(|| chain.next_asset_id());
+        let block = chain.build_block(&state.proposer, vec![tx])?;
+        let changes = chain.append_block(block.clone())?;
+        (block, asset_id, changes)
+    };
+    if fresh_chain.is_some() {
+        inner.chain = fresh_chain;
+    }
+    drop(guard);
+    store.save_block(&block).await?;
+    state.committed(&block, changes).await;
+
+    Ok(Json(TxAcceptedResponse {
+        block_height: block.header.height,
+        block_hash: block.hash,
+        tx_id,
+        asset_id,
+    }))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum SimulateRequest {
+    Signed(SignedTx),
+    /// Deprecated like `/transfer`: signed here with the sender's key
+    Unsigned {
+        unsigned: UnsignedTx,
+        private_key_hex: String,
+    },
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct SimulateResponse {
+    tx_id: String,
+    ok: bool,
+    /// The error the tx would be rejected with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// `ATokenError` variant of `error`, e.g. `NotTokenOwner`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<String>,
+    /// Holdings the tx would change
+    changes: Vec<BalanceChange>,
+}
+
+/// Checks `tx` against the live chain as if `/tx/submit` committed it in a block of its
+/// own, and reports the outcome with the holdings it would change. Nothing is built,
+/// stored, published or taken from the mempool.
+async fn simulate(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<SimulateRequest>,
+) -> ApiResult<SimulateResponse> {
+    let tx = match req {
+        SimulateRequest::Signed(tx) => tx,
+        SimulateRequest::Unsigned {
+            unsigned,
+            private_key_hex,
+        } => {
+            if !state.server_signing {
+                return Err(ApiError {
+                    status: StatusCode::BAD_REQUEST,
+                    message: "server-side signing is disabled, submit a SignedTx".to_string(),
+                });
+            }
+            SignedTx::sign(unsigned, &Wallet::from_private_key_hex(&private_key_hex)?)?
+        }
+    };
+    let tx_id = tx.id.clone();
+
+    let outcome = {
+        let guard = state.inner.read().await;
+        match &guard.chain {
+            Some(chain) => chain.simulate_tx(&tx),
+            // The first mint starts the chain, so it is checked against an empty one
+            None if matches!(tx.unsigned.payload, TxPayload::Mint { .. }) => ATokenChain::new(
+                ChainConfig::new(guard.chain_id.clone(), tx.unsigned.from.clone()),
+            )
+            .simulate_tx(&tx),
+            None => Err(ATokenError::TokenNotIssued),
+        }
+    };
+
+    Ok(Json(match outcome {
+        Ok(changes) => SimulateResponse {
+            tx_id,
+            ok: true,
+            error: None,
+            error_kind: None,
+            changes,
+        },
+        Err(e) => SimulateResponse {
+            tx_id,
+            ok: false,
+            error_kind: Some(error_kind(&e)),
+            error: Some(e.to_string()),
+            changes: Vec::new(),
+        },
+    }))
+}
+
+/// Variant name of `error`, taken from its `Debug` form.
+fn error_kind(error: &ATokenError) -> String {
+    format!("{error:?}")
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+async fn submit_tx(
+    State(state): State<AppState>,
+    ApiJson(tx): ApiJson<SignedTx>,
+) -> ApiResult<PendingTxResponse> {
+    let mut guard = state.inner.write().await;
+    let inner = &mut *guard;
+    let chain = inner.chain.as_ref().ok_or(ATokenError::TokenNotIssued)?;
+
+    let tx_id = tx.id.clone();
+    let sender = tx.unsigned.from.clone();
+    inner.mempool.submit(chain, tx)?;
+    info!(%tx_id, %sender, pending = inner.mempool.len(), "tx queued");
+    Ok(Json(PendingTxResponse {
+        tx_id,
+        pending: inner.mempool.len(),
+    }))
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct SealedBlockResponse {
+    block_height: u64,
+    block_hash: String,
+    tx_ids: Vec<String>,
+}
+
+async fn seal(State(state): State<AppState>) -> ApiResult<SealedBlockResponse> {
+    let block = state
+        .seal_pending(usize::MAX, false)
+        .await?
+        .ok_or(ATokenError::EmptyMempool)?;
+    Ok(Json(SealedBlockResponse {
+        block_height: block.header.height,
+        tx_ids: block.txs.iter().map(|tx| tx.id.clone()).collect(),
+        block_hash: block.hash,
     }))
 }
 
-#[derive(Debug, Serialize)]
-struct MetadataResponse {
-    metadata: Option<TokenMetadata>,
+#[derive(Debug, Serialize, JsonSchema)]
+struct MempoolTx {
+    tx_id: String,
+    sender: Address,
+    nonce: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct MempoolResponse {
+    pending: usize,
+    /// In the order `/seal` puts them in the block: first submitted, first sealed
+    txs: Vec<MempoolTx>,
+}
+
+async fn mempool(State(state): State<AppState>) -> Json<MempoolResponse> {
+    let guard = state.inner.read().await;
+    let txs = guard
+        .mempool
+        .pending()
+        .iter()
+        .map(|tx| MempoolTx {
+            tx_id: tx.id.clone(),
+            sender: tx.unsigned.from.clone(),
+            nonce: tx.unsigned.nonce,
+        })
+        .collect::<Vec<_>>();
+    Json(MempoolResponse {
+        pending: txs.len(),
+        txs,
+    })
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct MempoolCountResponse {
+    pending: usize,
+}
+
+async fn mempool_count(State(state): State<AppState>) -> Json<MempoolCountResponse> {
+    let guard = state.inner.read().await;
+    Json(MempoolCountResponse {
+        pending: guard.mempool.len(),
+    })
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct AssetResponse {
+    asset_id: AssetId,
+    metadata: TokenMetadata,
+    total_supply: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct AssetsResponse {
+    assets: Vec<AssetResponse>,
 }
 
-async fn metadata(State(state): State<AppState>) -> Json<MetadataResponse> {
+async fn assets(State(state): State<AppState>) -> Json<AssetsResponse> {
     let guard = state.inner.read().await;
-    let metadata = guard
+    let assets = guard
         .chain
         .as_ref()
-        .and_then(|chain| chain.metadata().cloned());
-    Json(MetadataResponse { metadata })
+        .map(|chain| {
+            chain
+                .assets()
+                .iter()
+                .zip(0..)
+                .map(|(asset, asset_id)| AssetResponse {
+                    asset_id,
+                    metadata: asset.metadata().clone(),
+                    total_supply: asset.total_supply(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Json(AssetsResponse { assets })
 }
 
-#[derive(Debug, Serialize)]
-struct BalanceResponse {
-    address: Address,
-    balance: u64,
+async fn metadata(
+    State(state): State<AppState>,
+    Path(asset_id): Path<AssetId>,
+) -> ApiResult<AssetResponse> {
+    let guard = state.inner.read().await;
+    let asset = guard
+        .chain
+        .as_ref()
+        .and_then(|chain| chain.asset(asset_id))
+        .ok_or(ATokenError::UnknownAsset(asset_id))?;
+    Ok(Json(AssetResponse {
+        asset_id,
+        metadata: asset.metadata().clone(),
+        total_supply: asset.total_supply(),
+    }))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct BalanceQuery {
+    /// Balance as of this block instead of the tip
+    height: Option<u64>,
 }
 
+/// Current balance, or the balance at `?height=N`; see `ATokenChain::balance_at` for what
+/// a historical query costs.
 async fn balance(
+    State(state): State<AppState>,
+    Path((asset_id, address)): Path<(AssetId, Address)>,
+    Query(query): Query<BalanceQuery>,
+) -> ApiResult<BalanceResponse> {
+    let address = validate_address(&address)?;
+    let guard = state.inner.read().await;
+    let balance = match (guard.chain.as_ref(), query.height) {
+        (Some(chain), Some(height)) => chain.balance_at(asset_id, &address, height)?,
+        (Some(chain), None) => chain.balance_of(asset_id, &address),
+        (None, Some(height)) => return Err(ATokenError::UnknownBlockHeight(height).into()),
+        (None, None) => 0,
+    };
+
+    Ok(Json(BalanceResponse {
+        asset_id,
+        address,
+        balance,
+        height: query.height,
+    }))
+}
+
+/// Nonce the address's next transaction must carry, for clients that sign offline.
+async fn nonce(
     State(state): State<AppState>,
     Path(address): Path<Address>,
-) -> Json<BalanceResponse> {
+) -> ApiResult<NonceResponse> {
+    let address = validate_address(&address)?;
     let guard = state.inner.read().await;
-    let balance = guard
-        .chain
-        .as_ref()
-        .map(|chain| chain.balance_of(&address))
-        .unwrap_or(0);
+    let next_nonce = match guard.chain.as_ref() {
+        Some(chain) => chain.next_nonce(&address),
+        None => 1,
+    };
 
-    Json(BalanceResponse { address, balance })
+    Ok(Json(NonceResponse {
+        address,
+        next_nonce,
+    }))
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 struct TokensResponse {
+    asset_id: AssetId,
     address: Address,
     token_ids: Vec<u64>,
 }
 
 async fn tokens(
     State(state): State<AppState>,
-    Path(address): Path<Address>,
-) -> Json<TokensResponse> {
+    Path((asset_id, address)): Path<(AssetId, Address)>,
+) -> ApiResult<TokensResponse> {
+    let address = validate_address(&address)?;
     let guard = state.inner.read().await;
     let token_ids = guard
         .chain
         .as_ref()
-        .map(|chain| chain.tokens_of(&address))
+        .map(|chain| {
+            chain
+                .tokens_of(asset_id, &address)
+                .iter()
+                .copied()
+                .collect()
+        })
         .unwrap_or_default();
 
-    Json(TokensResponse { address, token_ids })
+    Ok(Json(TokensResponse {
+        asset_id,
+        address,
+        token_ids,
+    }))
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 struct OwnerResponse {
+    asset_id: AssetId,
     token_id: u64,
     owner: Address,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locked_until_ms: Option<u64>,
 }
 
 async fn owner_of(
     State(state): State<AppState>,
-    Path(token_id): Path<u64>,
+    Path((asset_id, token_id)): Path<(AssetId, u64)>,
 ) -> ApiResult<OwnerResponse> {
     let guard = state.inner.read().await;
     let chain = guard.chain.as_ref().ok_or(ATokenError::TokenNotIssued)?;
-    let owner = chain
+    let asset = chain
+        .asset(asset_id)
+        .ok_or(ATokenError::UnknownAsset(asset_id))?;
+    let owner = asset
         .owner_of(token_id)
         .cloned()
         .ok_or(ATokenError::UnknownToken(token_id))?;
 
-    Ok(Json(OwnerResponse { token_id, owner }))
+    Ok(Json(OwnerResponse {
+        asset_id,
+        token_id,
+        owner,
+        locked_until_ms: asset.locked_until(token_id),
+    }))
 }
 
-#[derive(Debug, Serialize)]
-struct ChainInfoResponse {
-    chain_id: String,
-    initialized: bool,
-    issued: bool,
-    total_supply: u64,
+#[derive(Debug, Serialize, JsonSchema)]
+struct SupplyResponse {
+    asset_id: AssetId,
+    total: u64,
+    issuer_held: u64,
+    /// Everything not on the issuer's balance
+    circulating: u64,
+}
+
+async fn supply(
+    State(state): State<AppState>,
+    Path(asset_id): Path<AssetId>,
+) -> ApiResult<SupplyResponse> {
+    let guard = state.inner.read().await;
+    let asset = guard
+        .chain
+        .as_ref()
+        .and_then(|chain| chain.asset(asset_id))
+        .ok_or(ATokenError::UnknownAsset(asset_id))?;
+
+    let total = asset.total_supply();
+    let issuer_held = asset.balance_of(&asset.metadata().issuer);
+    Ok(Json(SupplyResponse {
+        asset_id,
+        total,
+        issuer_held,
+        circulating: total - issuer_held,
+    }))
+}
+
+/// Most entries `/holders/{asset_id}/top` returns
+const MAX_TOP_HOLDERS: usize = 100;
+const DEFAULT_TOP_HOLDERS: usize = 10;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct HoldersQuery {
+    limit: Option<usize>,
+    /// Leave the issuer out, since it usually holds the undistributed supply
+    #[serde(default)]
+    exclude_issuer: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct HoldersCountResponse {
+    asset_id: AssetId,
+    holders: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct Holder {
+    address: Address,
+    balance: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct TopHoldersResponse {
+    asset_id: AssetId,
+    holders: Vec<Holder>,
+}
+
+/// Non-zero balances of `asset`, without the issuer if `exclude_issuer` is set.
+fn holders_of(asset: &Asset, exclude_issuer: bool) -> impl Iterator<Item = (&Address, u64)> {
+    let issuer = &asset.metadata().issuer;
+    asset
+        .holders()
+        .filter(move |(owner, _)| !(exclude_issuer && *owner == issuer))
+}
+
+async fn holders_count(
+    State(state): State<AppState>,
+    Path(asset_id): Path<AssetId>,
+    Query(query): Query<HoldersQuery>,
+) -> ApiResult<HoldersCountResponse> {
+    let guard = state.inner.read().await;
+    let asset = guard
+        .chain
+        .as_ref()
+        .and_then(|chain| chain.asset(asset_id))
+        .ok_or(ATokenError::UnknownAsset(asset_id))?;
+
+    Ok(Json(HoldersCountResponse {
+        asset_id,
+        holders: holders_of(asset, query.exclude_issuer).count(),
+    }))
+}
+
+/// Largest holders first; equal balances are ordered by address so pages are stable.
+async fn top_holders(
+    State(state): State<AppState>,
+    Path(asset_id): Path<AssetId>,
+    Query(query): Query<HoldersQuery>,
+) -> ApiResult<TopHoldersResponse> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_TOP_HOLDERS)
+        .min(MAX_TOP_HOLDERS);
+    let guard = state.inner.read().await;
+    let asset = guard
+        .chain
+        .as_ref()
+        .and_then(|chain| chain.asset(asset_id))
+        .ok_or(ATokenError::UnknownAsset(asset_id))?;
+
+    let mut holders: Vec<_> = holders_of(asset, query.exclude_issuer).collect();
+    holders.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let holders = holders
+        .into_iter()
+        .take(limit)
+        .map(|(address, balance)| Holder {
+            address: address.clone(),
+            balance,
+        })
+        .collect();
+    Ok(Json(TopHoldersResponse { asset_id, holders }))
+}
+
+/// Most headers a single `/blocks` page returns
+const MAX_BLOCKS_PAGE: usize = 200;
+const DEFAULT_BLOCKS_PAGE: usize = 50;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct BlocksQuery {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct BlockSummary {
+    #[serde(flatten)]
+    header: BlockHeader,
+    hash: String,
+    tx_count: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct BlocksResponse {
+    total: usize,
+    offset: usize,
+    limit: usize,
+    blocks: Vec<BlockSummary>,
+}
+
+async fn blocks(
+    State(state): State<AppState>,
+    Query(query): Query<BlocksQuery>,
+) -> Json<BlocksResponse> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_BLOCKS_PAGE)
+        .min(MAX_BLOCKS_PAGE);
+    let guard = state.inner.read().await;
+    let all = guard
+        .chain
+        .as_ref()
+        .map_or(&[][..], |chain| chain.blocks.as_slice());
+
+    let blocks = all
+        .iter()
+        .skip(query.offset)
+        .take(limit)
+        .map(|block| BlockSummary {
+            header: block.header.clone(),
+            hash: block.hash.clone(),
+            tx_count: block.txs.len(),
+        })
+        .collect();
+    Json(BlocksResponse {
+        total: all.len(),
+        offset: query.offset,
+        limit,
+        blocks,
+    })
+}
+
+async fn block_by_height(
+    State(state): State<AppState>,
+    Path(height): Path<u64>,
+) -> ApiResult<Block> {
+    let guard = state.inner.read().await;
+    let block = guard
+        .chain
+        .as_ref()
+        .and_then(|chain| chain.block_at(height))
+        .ok_or(ATokenError::UnknownBlockHeight(height))?;
+    Ok(Json(block.clone()))
+}
+
+async fn block_by_hash(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> ApiResult<Block> {
+    let guard = state.inner.read().await;
+    let block = guard
+        .chain
+        .as_ref()
+        .and_then(|chain| chain.block_by_hash(&hash))
+        .cloned();
+    Ok(Json(block.ok_or(ATokenError::UnknownBlockHash(hash))?))
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct TxLookupResponse {
+    block_height: u64,
+    tx: SignedTx,
+}
+
+async fn tx_by_id(
+    State(state): State<AppState>,
+    Path(tx_id): Path<String>,
+) -> ApiResult<TxLookupResponse> {
+    let guard = state.inner.read().await;
+    let found = guard
+        .chain
+        .as_ref()
+        .and_then(|chain| chain.find_tx(&tx_id))
+        .map(|(block_height, tx)| TxLookupResponse {
+            block_height,
+            tx: tx.clone(),
+        });
+    Ok(Json(found.ok_or(ATokenError::UnknownTransaction(tx_id))?))
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct TxProofResponse {
+    tx_id: String,
+    block_height: u64,
+    tx_index: usize,
+    tx_merkle_root: String,
+    proof: Vec<MerkleProofStep>,
+}
+
+/// Inclusion proof of a committed tx against its block's `tx_merkle_root`, checkable with
+/// the block header alone (see `model::verify_tx_merkle_proof`).
+async fn tx_proof(
+    State(state): State<AppState>,
+    Path(tx_id): Path<String>,
+) -> ApiResult<TxProofResponse> {
+    let guard = state.inner.read().await;
+    let Some((block, tx_index)) = guard.chain.as_ref().and_then(|chain| {
+        let (height, index) = chain.tx_location(&tx_id)?;
+        Some((chain.block_at(height)?, index))
+    }) else {
+        return Err(ATokenError::UnknownTransaction(tx_id).into());
+    };
+
+    let proof = Block::tx_merkle_proof(&block.txs, tx_index)?;
+    Ok(Json(TxProofResponse {
+        tx_id,
+        block_height: block.header.height,
+        tx_index,
+        tx_merkle_root: block.header.tx_merkle_root.clone(),
+        proof,
+    }))
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct VerifyResponse {
+    ok: bool,
     blocks: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed_height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Re-validates the whole chain held by the node, e.g. after a restore. The replay runs on
+/// a copy off the async workers, one at a time, so writers are only held up by the copy.
+async fn verify(State(state): State<AppState>) -> Json<VerifyResponse> {
+    let _permit = state
+        .verify_permit
+        .acquire()
+        .await
+        .expect("the verify semaphore is never closed");
+    let check = state
+        .inner
+        .read()
+        .await
+        .chain
+        .as_ref()
+        .map(ATokenChain::integrity_check);
+    let Some(check) = check else {
+        return Json(VerifyResponse {
+            ok: true,
+            blocks: 0,
+            failed_height: None,
+            error: None,
+        });
+    };
+
+    let blocks = check.blocks();
+    let outcome = tokio::task::spawn_blocking(move || check.run())
+        .await
+        .expect("integrity check panicked");
+    let (failed_height, error) = match outcome {
+        Ok(()) => (None, None),
+        Err(ATokenError::IntegrityFailure { height, reason }) => (Some(height), Some(reason)),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    Json(VerifyResponse {
+        ok: error.is_none(),
+        blocks,
+        failed_height,
+        error,
+    })
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct VerifyTxResponse {
+    tx_id: String,
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Checks a tx on its own (version, signature, sender address under the chain's hash
+/// algorithm, id) without looking at the ledger; `/simulate` covers nonces, ownership and
+/// the rest.
+async fn verify_tx(
+    State(state): State<AppState>,
+    ApiJson(tx): ApiJson<SignedTx>,
+) -> Json<VerifyTxResponse> {
+    let reason = tx
+        .verify_for(state.hash_algorithm)
+        .err()
+        .map(|e| e.to_string());
+    Json(VerifyTxResponse {
+        tx_id: tx.id,
+        valid: reason.is_none(),
+        reason,
+    })
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct VerifyPersonalRequest {
+    address: String,
+    message: String,
+    signature_hex: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct VerifyPersonalResponse {
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Checks a `Wallet::sign_personal` signature, e.g. of a login challenge.
+async fn verify_personal(
+    ApiJson(req): ApiJson<VerifyPersonalRequest>,
+) -> Json<VerifyPersonalResponse> {
+    let reason = crypto::verify_personal(&req.address, &req.message, &req.signature_hex)
+        .err()
+        .map(|e| e.to_string());
+    Json(VerifyPersonalResponse {
+        valid: reason.is_none(),
+        reason,
+    })
 }
 
 async fn chain_info(State(state): State<AppState>) -> Json<ChainInfoResponse> {
@@ -337,16 +1618,68 @@ async fn chain_info(State(state): State<AppState>) -> Json<ChainInfoResponse> {
         Some(chain) => Json(ChainInfoResponse {
             chain_id: chain.config.chain_id.clone(),
             initialized: true,
-            issued: chain.metadata().is_some(),
-            total_supply: chain.total_supply(),
+            assets: chain.assets().len(),
             blocks: chain.blocks.len(),
+            hash_algorithm: chain.config.hash_algorithm,
         }),
         None => Json(ChainInfoResponse {
             chain_id: guard.chain_id.clone(),
             initialized: false,
-            issued: false,
-            total_supply: 0,
+            assets: 0,
             blocks: 0,
+            hash_algorithm: state.hash_algorithm,
         }),
     }
 }
+
+/// Streams every block committed from now on as a JSON text message. A client that falls
+/// more than `BLOCK_FEED_CAPACITY` blocks behind is disconnected and should resync with
+/// `/blocks`.
+async fn ws_blocks(State(state): State<AppState>, ws: WebSocketUpgrade) -> Response {
+    let blocks = state.block_feed.subscribe();
+    ws.on_upgrade(move |socket| feed_json(socket, blocks, "/ws/blocks"))
+}
+
+/// Streams a `BalanceEvent` for every block from now on that changes the tokens `address`
+/// holds. Lagging clients are dropped as on `/ws/blocks`.
+async fn ws_address(
+    State(state): State<AppState>,
+    Path(address): Path<Address>,
+    ws: WebSocketUpgrade,
+) -> std::result::Result<Response, ApiError> {
+    let address = validate_address(&address)?;
+    let events = state.address_feed.subscribe(address);
+    Ok(ws.on_upgrade(move |socket| feed_json(socket, events, "/ws/address")))
+}
+
+/// Sends each item of `feed` as a JSON text message until either side goes away.
+async fn feed_json<T: Serialize>(
+    mut socket: WebSocket,
+    mut feed: broadcast::Receiver<Arc<T>>,
+    route: &'static str,
+) {
+    loop {
+        tokio::select! {
+            item = feed.recv() => match item {
+                Ok(item) => {
+                    let Ok(json) = serde_json::to_string(&*item) else {
+                        continue;
+                    };
+                    if socket.send(Message::Text(json.into())).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(missed, route, "dropping a websocket subscriber that fell behind");
+                    let _ = socket.send(Message::Close(None)).await;
+                    return;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
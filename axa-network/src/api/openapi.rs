@@ -0,0 +1,349 @@
+//! OpenAPI 3.1 document of the API, served at `/openapi.json`. Body schemas come from the
+//! `JsonSchema` derives on the request and response types and land in `components/schemas`;
+//! the routes themselves are listed in `document`, so a route added to `router` needs a line
+//! there too.
+
+use schemars::JsonSchema;
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use serde_json::{Map, Value, json};
+
+use super::{
+    AssetResponse, AssetsResponse, BalanceEvent, BalanceQuery, BlocksQuery, BlocksResponse,
+    BurnRequest, HealthResponse, HoldersCountResponse, HoldersQuery, MempoolCountResponse,
+    MempoolResponse, OwnerResponse, SealedBlockResponse, SimulateRequest, SimulateResponse,
+    SupplyResponse, TokensResponse, TopHoldersResponse, TxLookupResponse, TxProofResponse,
+    UpdateMetadataRequest, VerifyPersonalRequest, VerifyPersonalResponse, VerifyResponse,
+    VerifyTxResponse, WalletByMnemonicRequest, WalletByPrivateKeyRequest, WalletExportRequest,
+    WalletImportRequest, WalletImportResponse, WalletResponse,
+};
+use crate::api_types::{
+    BalanceResponse, ChainInfoResponse, ErrorResponse, IssueRequest, NonceResponse,
+    PendingTxResponse, TransferRequest, TxAcceptedResponse,
+};
+use crate::keystore::Keystore;
+use crate::model::{Block, SignedTx};
+
+/// Statuses an `ApiError` is answered with, as mapped from `ATokenError`
+const ERROR_STATUSES: [(&str, &str); 6] = [
+    ("400", "Malformed input, or a tx or block the chain rejects"),
+    (
+        "403",
+        "Only the issuer may mint or update metadata, and only listed proposers may seal",
+    ),
+    ("404", "Unknown asset, token, block or tx"),
+    (
+        "409",
+        "No chain until the first mint, or a tx conflicting with one in the mempool",
+    ),
+    ("500", "The block store failed"),
+    ("503", "The mempool is full"),
+];
+
+/// Path parameters that are numbers; the rest are strings
+const INTEGER_PARAMS: [&str; 3] = ["asset_id", "token_id", "height"];
+
+/// The document for a node with server-side signing on or off, which decides whether
+/// `/issue`, `/transfer`, `/burn` and `POST /metadata/{asset_id}` are served.
+pub(super) fn document(server_signing: bool) -> Value {
+    let mut spec = Spec::new();
+
+    spec.get::<HealthResponse>("/health", "Liveness probe");
+    spec.get::<Value>("/openapi.json", "This document");
+
+    spec.post_empty::<WalletResponse>("/wallet/generate", "New wallet with its recovery phrase");
+    spec.post::<WalletByPrivateKeyRequest, WalletResponse>(
+        "/wallet/from-private-key",
+        "Wallet of a private key",
+    )
+    .fallible();
+    spec.post::<WalletByMnemonicRequest, WalletResponse>(
+        "/wallet/from-mnemonic",
+        "Wallet of a BIP39 phrase",
+    )
+    .fallible();
+    spec.post::<WalletExportRequest, Keystore>(
+        "/wallet/export",
+        "Encrypts a private key into a keystore",
+    )
+    .fallible();
+    spec.post::<WalletImportRequest, WalletImportResponse>(
+        "/wallet/import",
+        "Unlocks a keystore to check its password",
+    )
+    .fallible();
+
+    spec.post::<SignedTx, TxAcceptedResponse>(
+        "/tx/submit",
+        "Commits a client-signed tx in a block of its own",
+    )
+    .fallible();
+    spec.get::<TxLookupResponse>("/tx/{tx_id}", "Committed tx and its block height")
+        .fallible();
+    spec.get::<TxProofResponse>(
+        "/proof/tx/{tx_id}",
+        "Merkle inclusion proof of a committed tx",
+    )
+    .fallible();
+    spec.post::<SignedTx, PendingTxResponse>("/tx", "Queues a client-signed tx in the mempool")
+        .fallible();
+    spec.post::<SimulateRequest, SimulateResponse>(
+        "/simulate",
+        "Dry run of a tx against the current state",
+    )
+    .fallible();
+    spec.post_empty::<SealedBlockResponse>("/seal", "Seals the mempool into a block")
+        .fallible();
+    spec.get::<MempoolResponse>("/mempool", "Pending txs in the order they will be sealed");
+    spec.get::<MempoolCountResponse>("/mempool/count", "Number of pending txs");
+
+    spec.get::<AssetsResponse>("/assets", "Every issued asset");
+    spec.get::<AssetResponse>("/metadata/{asset_id}", "Metadata and supply of an asset")
+        .fallible();
+    spec.get::<BalanceResponse>(
+        "/balance/{asset_id}/{address}",
+        "Balance of an address, now or at a past height",
+    )
+    .query::<BalanceQuery>()
+    .fallible();
+    spec.get::<NonceResponse>("/nonce/{address}", "Next nonce of an address")
+        .fallible();
+    spec.get::<TokensResponse>(
+        "/tokens/{asset_id}/{address}",
+        "Token ids held by an address",
+    )
+    .fallible();
+    spec.get::<OwnerResponse>("/owner/{asset_id}/{token_id}", "Holder of a token id")
+        .fallible();
+    spec.get::<SupplyResponse>("/supply/{asset_id}", "Supply of an asset")
+        .fallible();
+    spec.get::<HoldersCountResponse>(
+        "/holders/{asset_id}/count",
+        "Number of addresses holding an asset",
+    )
+    .query::<HoldersQuery>()
+    .fallible();
+    spec.get::<TopHoldersResponse>("/holders/{asset_id}/top", "Largest holders of an asset")
+        .query::<HoldersQuery>()
+        .fallible();
+
+    spec.get::<ChainInfoResponse>("/chain", "Chain id, size and hash algorithm");
+    spec.get::<VerifyResponse>("/verify", "Replays the whole chain to check its integrity");
+    spec.post::<SignedTx, VerifyTxResponse>("/verify-tx", "Checks a tx on its own");
+    spec.post::<VerifyPersonalRequest, VerifyPersonalResponse>(
+        "/verify-personal",
+        "Checks a personal-message signature",
+    );
+    spec.get::<BlocksResponse>("/blocks", "Page of block headers")
+        .query::<BlocksQuery>();
+    spec.get::<Block>("/block/height/{height}", "Block at a height")
+        .fallible();
+    spec.get::<Block>("/block/hash/{hash}", "Block with a hash")
+        .fallible();
+    spec.websocket::<Block>("/ws/blocks", "Every committed block");
+    spec.websocket::<BalanceEvent>("/ws/address/{address}", "Balance changes of one address")
+        .fallible();
+
+    if server_signing {
+        spec.post::<IssueRequest, TxAcceptedResponse>(
+            "/issue",
+            "Issues an asset, signed on the node",
+        )
+        .deprecated();
+        spec.post::<TransferRequest, TxAcceptedResponse>(
+            "/transfer",
+            "Transfers tokens, signed on the node",
+        )
+        .deprecated();
+        spec.post::<BurnRequest, TxAcceptedResponse>("/burn", "Burns tokens, signed on the node")
+            .deprecated();
+        spec.post::<UpdateMetadataRequest, TxAcceptedResponse>(
+            "/metadata/{asset_id}",
+            "Updates an asset's metadata, signed on the node",
+        )
+        .deprecated();
+    }
+
+    spec.finish()
+}
+
+struct Spec {
+    generator: SchemaGenerator,
+    paths: Map<String, Value>,
+    error_schema: Value,
+}
+
+impl Spec {
+    fn new() -> Self {
+        let mut generator = SchemaSettings::draft2020_12()
+            .with(|settings| {
+                settings.definitions_path = "/components/schemas".into();
+                settings.meta_schema = None;
+            })
+            .into_generator();
+        let error_schema = generator.subschema_for::<ErrorResponse>().to_value();
+        Self {
+            generator,
+            paths: Map::new(),
+            error_schema,
+        }
+    }
+
+    fn get<Res: JsonSchema>(&mut self, path: &str, summary: &str) -> Operation<'_> {
+        let response = json_content(self.generator.subschema_for::<Res>().to_value());
+        self.operation("get", path, summary, ("200", "OK", response))
+    }
+
+    fn post<Req: JsonSchema, Res: JsonSchema>(
+        &mut self,
+        path: &str,
+        summary: &str,
+    ) -> Operation<'_> {
+        let body = json_content(self.generator.subschema_for::<Req>().to_value());
+        let mut operation = self.post_empty::<Res>(path, summary);
+        operation.op.insert(
+            "requestBody".to_string(),
+            json!({ "required": true, "content": body }),
+        );
+        operation.error(
+            "413",
+            "The body is over the node's limit, `ATOKEN_MAX_BODY_BYTES`",
+        );
+        operation
+    }
+
+    /// A POST without a body.
+    fn post_empty<Res: JsonSchema>(&mut self, path: &str, summary: &str) -> Operation<'_> {
+        let response = json_content(self.generator.subschema_for::<Res>().to_value());
+        self.operation("post", path, summary, ("200", "OK", response))
+    }
+
+    /// A WebSocket upgrade; every message is a JSON `Msg`.
+    fn websocket<Msg: JsonSchema>(&mut self, path: &str, summary: &str) -> Operation<'_> {
+        let message = json_content(self.generator.subschema_for::<Msg>().to_value());
+        self.operation(
+            "get",
+            path,
+            summary,
+            ("101", "WebSocket of JSON messages", message),
+        )
+    }
+
+    fn operation(
+        &mut self,
+        method: &str,
+        path: &str,
+        summary: &str,
+        (status, description, content): (&str, &str, Value),
+    ) -> Operation<'_> {
+        let parameters: Vec<Value> = path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .map(|name| {
+                let kind = if INTEGER_PARAMS.contains(&name) {
+                    "integer"
+                } else {
+                    "string"
+                };
+                json!({ "name": name, "in": "path", "required": true, "schema": { "type": kind } })
+            })
+            .collect();
+        let op = json!({
+            "summary": summary,
+            "parameters": parameters,
+            "responses": {
+                status: { "description": description, "content": content },
+            },
+        });
+
+        let item = self
+            .paths
+            .entry(path.to_string())
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .expect("path items are objects");
+        item.insert(method.to_string(), op);
+        Operation {
+            generator: &mut self.generator,
+            error_schema: &self.error_schema,
+            op: item[method]
+                .as_object_mut()
+                .expect("operations are objects"),
+        }
+    }
+
+    fn finish(mut self) -> Value {
+        json!({
+            "openapi": "3.1.0",
+            "info": {
+                "title": "AToken API",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "paths": self.paths,
+            "components": { "schemas": self.generator.take_definitions(true) },
+        })
+    }
+}
+
+struct Operation<'a> {
+    generator: &'a mut SchemaGenerator,
+    error_schema: &'a Value,
+    op: &'a mut Map<String, Value>,
+}
+
+impl Operation<'_> {
+    /// Adds the fields of `Q` as query parameters.
+    fn query<Q: JsonSchema>(self) -> Self {
+        let schema = Q::json_schema(self.generator).to_value();
+        let required = schema["required"].as_array().cloned().unwrap_or_default();
+        let properties = schema["properties"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        let parameters = self.op["parameters"]
+            .as_array_mut()
+            .expect("parameters are an array");
+        for (name, property) in properties {
+            let mut parameter = json!({
+                "name": name,
+                "in": "query",
+                "required": required.contains(&Value::String(name.clone())),
+            });
+            if let Some(description) = property.get("description") {
+                parameter["description"] = description.clone();
+            }
+            parameter["schema"] = property;
+            parameters.push(parameter);
+        }
+        self
+    }
+
+    /// Answers with an `ErrorResponse` on failure.
+    fn fallible(mut self) -> Self {
+        for (status, description) in ERROR_STATUSES {
+            self.error(status, description);
+        }
+        self
+    }
+
+    /// Answers with an `ErrorResponse` under `status`.
+    fn error(&mut self, status: &str, description: &str) {
+        let content = json_content(self.error_schema.clone());
+        self.op["responses"]
+            .as_object_mut()
+            .expect("responses are an object")
+            .insert(
+                status.to_string(),
+                json!({ "description": description, "content": content }),
+            );
+    }
+
+    /// Server-signing routes, which take private keys.
+    fn deprecated(self) -> Self {
+        self.op.insert("deprecated".to_string(), Value::Bool(true));
+        self.fallible()
+    }
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
@@ -0,0 +1,101 @@
+//! JSON bodies of the HTTP API, shared by the server in `api` and the typed `client`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{Address, HashAlgorithm};
+use crate::model::AssetId;
+
+/// Body of every non-2xx response
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MetadataInput {
+    pub name: String,
+    pub symbol: String,
+    pub description: String,
+    pub decimals: u8,
+}
+
+/// `POST /issue`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct IssueRequest {
+    pub issuer_private_key_hex: String,
+    pub amount: u64,
+    pub metadata: MetadataInput,
+    /// Initial holders; without it every token goes to the issuer
+    #[serde(default)]
+    pub distribution: Vec<DistributionInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DistributionInput {
+    pub address: Address,
+    pub count: u64,
+}
+
+/// `POST /transfer`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TransferRequest {
+    pub from_private_key_hex: String,
+    pub asset_id: AssetId,
+    pub to_address: Address,
+    pub token_ids: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// Recipient cannot move the tokens before this timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_until_ms: Option<u64>,
+}
+
+/// Answer to a tx committed in a block of its own: `/issue`, `/transfer`, `/burn`,
+/// `/tx/submit` and the like
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TxAcceptedResponse {
+    pub block_height: u64,
+    pub block_hash: String,
+    pub tx_id: String,
+    /// Asset created by the tx, for a mint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_id: Option<AssetId>,
+}
+
+/// `POST /tx`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PendingTxResponse {
+    pub tx_id: String,
+    pub pending: usize,
+}
+
+/// `GET /balance/{asset_id}/{address}[?height=N]`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BalanceResponse {
+    pub asset_id: AssetId,
+    pub address: Address,
+    pub balance: u64,
+    /// Block the balance was taken at, for a historical query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+}
+
+/// `GET /nonce/{address}`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NonceResponse {
+    pub address: Address,
+    pub next_nonce: u64,
+}
+
+/// `GET /chain`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChainInfoResponse {
+    pub chain_id: String,
+    pub initialized: bool,
+    pub assets: usize,
+    pub blocks: usize,
+    /// Hash addresses are derived with; nodes predating the field are SHA-256
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+}
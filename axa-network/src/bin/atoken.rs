@@ -0,0 +1,152 @@
+//! Command-line wallet for the AToken API. Transactions are signed here and sent to
+//! `/tx/submit`, so private keys never leave the machine. Prints JSON.
+
+use std::error::Error;
+
+use axa_network::client::Client;
+use axa_network::crypto::{Address, Wallet, validate_address};
+use axa_network::model::{AssetId, SignedTx, TokenMetadata, UnsignedTx};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use serde_json::json;
+
+/// AToken wallet. Each flag falls back to its environment variable.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Base URL of the node's API
+    #[arg(
+        long,
+        env = "ATOKEN_URL",
+        default_value = "http://127.0.0.1:8080",
+        global = true
+    )]
+    url: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Wallet management
+    Wallet {
+        #[command(subcommand)]
+        command: WalletCommand,
+    },
+    /// Issues a new asset, owned by the signer
+    Issue {
+        #[arg(long, env = "ATOKEN_PRIVATE_KEY", hide_env_values = true)]
+        private_key: String,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long)]
+        symbol: String,
+        /// Defaults to the symbol
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, default_value = "")]
+        description: String,
+        #[arg(long, default_value_t = 0)]
+        decimals: u8,
+    },
+    /// Transfers tokens of one asset
+    Transfer {
+        #[arg(long, env = "ATOKEN_PRIVATE_KEY", hide_env_values = true)]
+        private_key: String,
+        #[arg(long, default_value_t = 0)]
+        asset: AssetId,
+        /// Recipient address
+        #[arg(long)]
+        to: String,
+        /// Token ids to send; repeat or comma-separate
+        #[arg(long, required = true, value_delimiter = ',')]
+        ids: Vec<u64>,
+        #[arg(long)]
+        memo: Option<String>,
+    },
+    /// Balance of an address
+    Balance {
+        address: String,
+        #[arg(long, default_value_t = 0)]
+        asset: AssetId,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum WalletCommand {
+    /// Generates a wallet along with its recovery phrase; nothing is sent to the node
+    New,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let client = Client::new(cli.url);
+
+    match cli.command {
+        Command::Wallet {
+            command: WalletCommand::New,
+        } => {
+            let (mnemonic, wallet) = Wallet::generate_mnemonic();
+            print_json(&json!({
+                "address": wallet.address(),
+                "public_key_hex": wallet.public_key_hex(),
+                "private_key_hex": wallet.private_key_hex(),
+                "mnemonic": mnemonic,
+            }))
+        }
+        Command::Issue {
+            private_key,
+            amount,
+            symbol,
+            name,
+            description,
+            decimals,
+        } => {
+            let wallet = Wallet::from_private_key_hex(&private_key)?;
+            let metadata = TokenMetadata {
+                name: name.unwrap_or_else(|| symbol.clone()),
+                symbol,
+                description,
+                decimals,
+                issuer: String::new(),
+            };
+            let (chain_id, from, nonce) = sender_on_chain(&client, &wallet).await?;
+            let tx = UnsignedTx::mint(chain_id, from, nonce, amount, metadata);
+            print_json(&client.submit_tx(&SignedTx::sign(tx, &wallet)?).await?)
+        }
+        Command::Transfer {
+            private_key,
+            asset,
+            to,
+            ids,
+            memo,
+        } => {
+            let wallet = Wallet::from_private_key_hex(&private_key)?;
+            let to = validate_address(&to)?;
+            let (chain_id, from, nonce) = sender_on_chain(&client, &wallet).await?;
+            let tx = UnsignedTx::transfer_with_memo(chain_id, from, nonce, asset, to, ids, memo);
+            print_json(&client.submit_tx(&SignedTx::sign(tx, &wallet)?).await?)
+        }
+        Command::Balance { address, asset } => {
+            print_json(&client.balance(asset, &validate_address(&address)?).await?)
+        }
+    }
+}
+
+/// What a tx from `wallet` must be signed over besides its payload: the chain id, the
+/// wallet's address under the chain's hash algorithm and its next nonce.
+async fn sender_on_chain(
+    client: &Client,
+    wallet: &Wallet,
+) -> axa_network::errors::Result<(String, Address, u64)> {
+    let chain = client.chain_info().await?;
+    let from = wallet.address_for(chain.hash_algorithm);
+    let nonce = client.nonce(&from).await?.next_nonce;
+    Ok((chain.chain_id, from, nonce))
+}
+
+fn print_json(value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
@@ -0,0 +1,419 @@
+//! Byte encoding of everything that gets signed or hashed, written out field by field so
+//! that the protocol does not depend on what the serde derives happen to produce.
+//!
+//! Integers are big-endian and fixed width, strings and raw bytes carry a `u64` length prefix,
+//! sequences a `u64` item count, options a `0`/`1` tag and enum variants a `u8` tag. Every
+//! message starts with a domain string, so bytes signed for one purpose can never be read
+//! as another. The impls destructure each struct in full: a new field does not compile
+//! until it is given a place here.
+
+use crate::crypto::HashAlgorithm;
+use crate::model::{BlockHeader, SignedTx, TokenMetadata, TxPayload, UnsignedTx};
+
+pub const TX_SIGNING_DOMAIN: &str = "AToken/tx";
+pub const TX_ID_DOMAIN: &str = "AToken/tx-id";
+pub const BLOCK_SIGNING_DOMAIN: &str = "AToken/block-signature";
+pub const BLOCK_HASH_DOMAIN: &str = "AToken/block";
+pub const MERKLE_LEAF_DOMAIN: &str = "AToken/merkle-leaf";
+pub const MERKLE_NODE_DOMAIN: &str = "AToken/merkle-node";
+pub const ADDRESS_DOMAIN: &str = "AToken/address";
+
+pub struct CanonicalWriter {
+    bytes: Vec<u8>,
+}
+
+impl CanonicalWriter {
+    pub fn new(domain: &str) -> Self {
+        let mut writer = Self { bytes: Vec::new() };
+        writer.str(domain);
+        writer
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.raw(value.as_bytes());
+    }
+
+    pub fn raw(&mut self, value: &[u8]) {
+        self.u64(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    pub fn option<T>(&mut self, value: Option<T>, mut write: impl FnMut(&mut Self, T)) {
+        match value {
+            None => self.u8(0),
+            Some(value) => {
+                self.u8(1);
+                write(self, value);
+            }
+        }
+    }
+
+    pub fn seq<T>(&mut self, items: &[T], mut write: impl FnMut(&mut Self, &T)) {
+        self.u64(items.len() as u64);
+        for item in items {
+            write(self, item);
+        }
+    }
+
+    pub fn encode(&mut self, value: &impl CanonicalEncode) {
+        value.encode(self);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub trait CanonicalEncode {
+    fn encode(&self, w: &mut CanonicalWriter);
+}
+
+impl CanonicalEncode for TokenMetadata {
+    fn encode(&self, w: &mut CanonicalWriter) {
+        let TokenMetadata {
+            name,
+            symbol,
+            description,
+            decimals,
+            issuer,
+        } = self;
+        w.str(name);
+        w.str(symbol);
+        w.str(description);
+        w.u8(*decimals);
+        w.str(issuer);
+    }
+}
+
+impl CanonicalEncode for TxPayload {
+    fn encode(&self, w: &mut CanonicalWriter) {
+        match self {
+            TxPayload::Mint {
+                amount,
+                metadata,
+                distribution,
+            } => {
+                w.u8(0);
+                w.u64(*amount);
+                w.encode(metadata);
+                w.seq(distribution, |w, (address, count)| {
+                    w.str(address);
+                    w.u64(*count);
+                });
+            }
+            TxPayload::Transfer {
+                asset_id,
+                token_ids,
+                to,
+                memo,
+                locked_until_ms,
+            } => {
+                w.u8(1);
+                w.u64(*asset_id);
+                w.seq(token_ids, |w, token_id| w.u64(*token_id));
+                w.str(to);
+                w.option(memo.as_deref(), CanonicalWriter::str);
+                w.option(*locked_until_ms, CanonicalWriter::u64);
+            }
+            TxPayload::TransferRanges {
+                asset_id,
+                token_ranges,
+                to,
+            } => {
+                w.u8(2);
+                w.u64(*asset_id);
+                w.seq(token_ranges, |w, (start, end)| {
+                    w.u64(*start);
+                    w.u64(*end);
+                });
+                w.str(to);
+            }
+            TxPayload::Burn {
+                asset_id,
+                token_ids,
+            } => {
+                w.u8(3);
+                w.u64(*asset_id);
+                w.seq(token_ids, |w, token_id| w.u64(*token_id));
+            }
+            TxPayload::UpdateMetadata {
+                asset_id,
+                name,
+                description,
+            } => {
+                w.u8(4);
+                w.u64(*asset_id);
+                w.str(name);
+                w.str(description);
+            }
+        }
+    }
+}
+
+impl CanonicalEncode for UnsignedTx {
+    fn encode(&self, w: &mut CanonicalWriter) {
+        let UnsignedTx {
+            version,
+            chain_id,
+            from,
+            nonce,
+            timestamp_ms,
+            valid_until_ms,
+            payload,
+        } = self;
+        w.u16(*version);
+        w.str(chain_id);
+        w.str(from);
+        w.u64(*nonce);
+        w.u64(*timestamp_ms);
+        w.option(*valid_until_ms, CanonicalWriter::u64);
+        w.encode(payload);
+    }
+}
+
+impl CanonicalEncode for SignedTx {
+    fn encode(&self, w: &mut CanonicalWriter) {
+        let SignedTx {
+            id,
+            unsigned,
+            public_key_hex,
+            signature_hex,
+        } = self;
+        w.str(id);
+        w.encode(unsigned);
+        w.str(public_key_hex);
+        w.str(signature_hex);
+    }
+}
+
+impl CanonicalEncode for BlockHeader {
+    fn encode(&self, w: &mut CanonicalWriter) {
+        let BlockHeader {
+            version,
+            chain_id,
+            height,
+            previous_hash,
+            previous_three_hashes,
+            proposer,
+            proposer_public_key_hex,
+            timestamp_ms,
+            tx_merkle_root,
+            hash_algorithm,
+        } = self;
+        w.u16(*version);
+        w.str(chain_id);
+        w.u64(*height);
+        w.option(previous_hash.as_deref(), CanonicalWriter::str);
+        w.seq(previous_three_hashes, |w, hash| w.str(hash));
+        w.str(proposer);
+        w.str(proposer_public_key_hex);
+        w.u64(*timestamp_ms);
+        w.str(tx_merkle_root);
+        match hash_algorithm {
+            // Headers of SHA-256 chains predate the field and keep hashing as before
+            HashAlgorithm::Sha256 => {}
+            HashAlgorithm::Blake3 => w.u8(1),
+        }
+    }
+}
+
+/// What a tx sender signs.
+pub fn tx_signing_bytes(unsigned: &UnsignedTx) -> Vec<u8> {
+    let mut w = CanonicalWriter::new(TX_SIGNING_DOMAIN);
+    w.encode(unsigned);
+    w.finish()
+}
+
+/// Preimage of a tx id: the tx together with the key and signature over it.
+pub fn tx_id_bytes(unsigned: &UnsignedTx, public_key_hex: &str, signature_hex: &str) -> Vec<u8> {
+    let mut w = CanonicalWriter::new(TX_ID_DOMAIN);
+    w.encode(unsigned);
+    w.str(public_key_hex);
+    w.str(signature_hex);
+    w.finish()
+}
+
+/// What a block proposer signs, see [`crate::model::Block::previous_signature_message`]:
+/// the whole header, so no field can be changed without the proposer's key.
+pub fn block_signing_bytes(header: &BlockHeader) -> Vec<u8> {
+    let mut w = CanonicalWriter::new(BLOCK_SIGNING_DOMAIN);
+    w.encode(header);
+    w.finish()
+}
+
+/// Preimage of a block hash, see [`crate::model::Block::calculate_hash`].
+pub fn block_hash_bytes(
+    header: &BlockHeader,
+    txs: &[SignedTx],
+    previous_signature_hex: &str,
+) -> Vec<u8> {
+    let mut w = CanonicalWriter::new(BLOCK_HASH_DOMAIN);
+    w.encode(header);
+    w.seq(txs, |w, tx| w.encode(tx));
+    w.str(previous_signature_hex);
+    w.finish()
+}
+
+/// Preimage of a Merkle leaf, see [`crate::model::Block::tx_merkle_root`]: the raw tx id.
+pub fn merkle_leaf_bytes(tx_id: &[u8]) -> Vec<u8> {
+    let mut w = CanonicalWriter::new(MERKLE_LEAF_DOMAIN);
+    w.raw(tx_id);
+    w.finish()
+}
+
+/// Preimage of an inner Merkle node. Its own domain keeps a pair of nodes from passing
+/// for a leaf, or the other way round.
+pub fn merkle_node_bytes(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut w = CanonicalWriter::new(MERKLE_NODE_DOMAIN);
+    w.raw(left);
+    w.raw(right);
+    w.finish()
+}
+
+/// What an address is hashed from, see [`crate::crypto::address_from_public_key_with`].
+pub fn address_bytes(public_key: &[u8]) -> Vec<u8> {
+    let mut w = CanonicalWriter::new(ADDRESS_DOMAIN);
+    w.raw(public_key);
+    w.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::sha256_hex;
+
+    fn golden_tx() -> UnsignedTx {
+        UnsignedTx {
+            version: 4,
+            chain_id: "AToken-test".to_string(),
+            from: "34750F98bd59fCfC946dA45AAAbE933be154a4b5".to_string(),
+            nonce: 2,
+            timestamp_ms: 1_700_000_000_000,
+            valid_until_ms: None,
+            payload: TxPayload::Transfer {
+                asset_id: 0,
+                token_ids: vec![1, 2],
+                to: "ab".to_string(),
+                memo: Some("hi".to_string()),
+                locked_until_ms: None,
+            },
+        }
+    }
+
+    /// Pins the signed bytes of a known tx: if this changes, every signature and tx id on
+    /// existing chains changes with it.
+    #[test]
+    fn tx_signing_bytes_match_the_golden_vector() {
+        let expected = [
+            // domain "AToken/tx"
+            "0000000000000009 41546f6b656e2f7478",
+            // version
+            "0004",
+            // chain_id
+            "000000000000000b 41546f6b656e2d74657374",
+            // from
+            "0000000000000028 3334373530463938626435396643664339343664\
+             4134354141416245393333626531353461346235",
+            // nonce, timestamp_ms, valid_until_ms: None
+            "0000000000000002 0000018bcfe56800 00",
+            // Transfer: tag, asset_id, token_ids [1, 2]
+            "01 0000000000000000 0000000000000002 0000000000000001 0000000000000002",
+            // to
+            "0000000000000002 6162",
+            // memo: Some("hi"), locked_until_ms: None
+            "01 0000000000000002 6869 00",
+        ]
+        .concat()
+        .replace(' ', "");
+        assert_eq!(hex::encode(tx_signing_bytes(&golden_tx())), expected);
+    }
+
+    fn golden_header(hash_algorithm: HashAlgorithm) -> BlockHeader {
+        BlockHeader {
+            version: 2,
+            chain_id: "AToken-test".to_string(),
+            height: 1,
+            previous_hash: Some("aa".to_string()),
+            previous_three_hashes: vec!["aa".to_string()],
+            proposer: "34750F98bd59fCfC946dA45AAAbE933be154a4b5".to_string(),
+            proposer_public_key_hex: "cc".to_string(),
+            timestamp_ms: 1_700_000_000_000,
+            tx_merkle_root: "dd".to_string(),
+            hash_algorithm,
+        }
+    }
+
+    /// Pins the digest of every other domain's bytes, so a change to any of them, or a
+    /// message moving to another domain, shows up here.
+    #[test]
+    fn every_domain_matches_its_golden_digest() {
+        let tx = SignedTx {
+            id: "ignored".to_string(),
+            unsigned: golden_tx(),
+            public_key_hex: "bb".to_string(),
+            signature_hex: "ee".to_string(),
+        };
+        let header = golden_header(HashAlgorithm::Sha256);
+        let digests = [
+            tx_id_bytes(&tx.unsigned, &tx.public_key_hex, &tx.signature_hex),
+            block_signing_bytes(&header),
+            block_hash_bytes(&header, std::slice::from_ref(&tx), "ff"),
+            block_hash_bytes(
+                &golden_header(HashAlgorithm::Blake3),
+                std::slice::from_ref(&tx),
+                "ff",
+            ),
+            merkle_leaf_bytes(&[0xaa]),
+            merkle_node_bytes(&[0xaa], &[0xbb]),
+            address_bytes(&[0xcc]),
+        ]
+        .map(|bytes| sha256_hex(&bytes));
+        assert_eq!(
+            digests,
+            [
+                // tx id
+                "fde887995a70eb5c4ee35ef60a611f54a616734b4810ba5eac6e116691cbfb8b",
+                // block signature
+                "654ba0ee5291914d9387948319fcd5ffe896de1b46165af0f50b1970b343db03",
+                // block hash
+                "c5c383da4c641e1cca4862ffede0ce16d4b9354e60688d39d0e1f68f4ebcb396",
+                // block hash, BLAKE3 chain
+                "bad1ef2ab7966297760bf80b9a92536df1ab5b7d1990261c5b3ea9408a7ea43d",
+                // merkle leaf
+                "726198f6e8199429efa30addc314278ac7f9fe6e4e30ef5327211adbe5e47f0b",
+                // merkle node
+                "bfacef614338d125420eb489a66de71002d91c85171e7811a635e095cecfad4a",
+                // address
+                "f3df3f668deb72baf74728e34606621ed5289cb12c575b1c919d9f44a4b8c784",
+            ]
+        );
+
+        // Each message opens with its own domain
+        for (bytes, domain) in [
+            (tx_signing_bytes(&tx.unsigned), TX_SIGNING_DOMAIN),
+            (tx_id_bytes(&tx.unsigned, "bb", "ee"), TX_ID_DOMAIN),
+            (block_signing_bytes(&header), BLOCK_SIGNING_DOMAIN),
+            (block_hash_bytes(&header, &[], "ff"), BLOCK_HASH_DOMAIN),
+            (merkle_leaf_bytes(&[0xaa]), MERKLE_LEAF_DOMAIN),
+            (merkle_node_bytes(&[0xaa], &[0xbb]), MERKLE_NODE_DOMAIN),
+            (address_bytes(&[0xcc]), ADDRESS_DOMAIN),
+        ] {
+            assert!(
+                bytes.starts_with(&CanonicalWriter::new(domain).finish()),
+                "{domain}"
+            );
+        }
+    }
+}
@@ -1,35 +1,400 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
 
-use crate::crypto::{Address, Wallet, address_from_public_key, verify_signature_hex};
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::{
+    Address, HashAlgorithm, Wallet, address_from_public_key_with, validate_address,
+    verify_signature_hex,
+};
 use crate::errors::{ATokenError, Result};
-use crate::model::{Block, BlockHeader, SignedTx, TokenMetadata, TxPayload};
+use crate::model::{
+    AssetId, BLOCK_VERSION, Block, BlockHeader, SignedTx, TokenMetadata, TxPayload,
+};
+use crate::snapshot::ChainSnapshot;
+
+/// Blocks back that each block header commits to, unless a genesis config says otherwise
+pub const DEFAULT_REQUIRED_PREVIOUS_BLOCKS: usize = 3;
 
 #[derive(Debug, Clone)]
 pub struct ChainConfig {
     pub chain_id: String,
     pub issuer: Address,
     pub required_previous_blocks: usize,
+    /// How far past the chain's clock a block timestamp may be
+    pub max_future_skew_ms: u64,
+    /// Most token ids one transfer or burn may touch, ranges counted expanded, so a single
+    /// tx can't stall the node while its block is built and hashed
+    pub max_transfer_ids: usize,
+    pub max_mint_amount: u64,
+    /// Addresses allowed to propose blocks after the genesis block
+    pub proposers: Vec<Address>,
+    /// Hash of block headers and addresses; every block must carry the genesis block's
+    pub hash_algorithm: HashAlgorithm,
 }
 
 impl ChainConfig {
     pub fn new(chain_id: impl Into<String>, issuer: Address) -> Self {
         Self {
             chain_id: chain_id.into(),
+            required_previous_blocks: DEFAULT_REQUIRED_PREVIOUS_BLOCKS,
+            max_future_skew_ms: 60_000,
+            max_transfer_ids: 1_000,
+            max_mint_amount: 1_000_000,
+            proposers: vec![issuer.clone()],
             issuer,
-            required_previous_blocks: 3,
+            hash_algorithm: HashAlgorithm::default(),
+        }
+    }
+
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Also lets `proposer` seal blocks.
+    pub fn with_proposer(mut self, proposer: Address) -> Self {
+        if !self.proposers.contains(&proposer) {
+            self.proposers.push(proposer);
+        }
+        self
+    }
+
+    pub fn is_authorized_proposer(&self, address: &Address) -> bool {
+        self.proposers.contains(address)
+    }
+
+    /// Config of an existing chain: the issuer is whoever signed the genesis mint, the node
+    /// that sealed the genesis block keeps proposing alongside the issuer and the hash
+    /// algorithm is the genesis block's.
+    pub fn from_genesis(chain_id: impl Into<String>, genesis: &Block) -> Result<Self> {
+        let issuer = genesis
+            .txs
+            .iter()
+            .find_map(|tx| match tx.unsigned.payload {
+                TxPayload::Mint { .. } => Some(tx.unsigned.from.clone()),
+                TxPayload::Transfer { .. }
+                | TxPayload::TransferRanges { .. }
+                | TxPayload::Burn { .. }
+                | TxPayload::UpdateMetadata { .. } => None,
+            })
+            .ok_or(ATokenError::MissingGenesisMint)?;
+        Ok(Self::new(chain_id, issuer)
+            .with_proposer(genesis.header.proposer.clone())
+            .with_hash_algorithm(genesis.header.hash_algorithm))
+    }
+}
+
+/// Ledger of one asset: its fixed metadata and who holds which of its token ids.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    metadata: TokenMetadata,
+    total_supply: u64,
+    token_owner_by_id: BTreeMap<u64, Address>,
+    /// Reverse of `token_owner_by_id`, so balances and holdings don't scan the whole supply.
+    /// Left out of snapshots and rebuilt by `index_owners`.
+    #[serde(skip)]
+    tokens_by_owner: HashMap<Address, BTreeSet<u64>>,
+    /// Token id -> timestamp before which its holder cannot move it, set by time-locked
+    /// transfers and cleared when the token moves again
+    locked_until_by_id: BTreeMap<u64, u64>,
+}
+
+static NO_TOKENS: BTreeSet<u64> = BTreeSet::new();
+
+impl Asset {
+    /// New asset whose ids are handed out in order following `distribution`, or all to
+    /// `issuer` if it is empty.
+    fn mint(
+        issuer: &Address,
+        amount: u64,
+        metadata: TokenMetadata,
+        distribution: &[(Address, u64)],
+    ) -> Self {
+        let mut asset = Self {
+            metadata,
+            total_supply: amount,
+            token_owner_by_id: BTreeMap::new(),
+            tokens_by_owner: HashMap::new(),
+            locked_until_by_id: BTreeMap::new(),
+        };
+        for (owner, token_ids) in mint_shares(issuer, amount, distribution) {
+            asset
+                .token_owner_by_id
+                .extend(token_ids.clone().map(|token_id| (token_id, owner.clone())));
+            asset
+                .tokens_by_owner
+                .entry(owner.clone())
+                .or_default()
+                .extend(token_ids);
+        }
+        asset
+    }
+
+    pub fn metadata(&self) -> &TokenMetadata {
+        &self.metadata
+    }
+
+    pub fn total_supply(&self) -> u64 {
+        self.total_supply
+    }
+
+    pub fn balance_of(&self, address: &Address) -> u64 {
+        self.tokens_of(address).len() as u64
+    }
+
+    pub fn owner_of(&self, token_id: u64) -> Option<&Address> {
+        self.token_owner_by_id.get(&token_id)
+    }
+
+    pub fn tokens_of(&self, address: &Address) -> &BTreeSet<u64> {
+        self.tokens_by_owner.get(address).unwrap_or(&NO_TOKENS)
+    }
+
+    /// Timestamp before which the holder of `token_id` cannot move it, if it is locked.
+    /// Expired locks stay listed until the token moves.
+    pub fn locked_until(&self, token_id: u64) -> Option<u64> {
+        self.locked_until_by_id.get(&token_id).copied()
+    }
+
+    /// Addresses holding at least one token, with their balances, in no particular order.
+    pub fn holders(&self) -> impl Iterator<Item = (&Address, u64)> {
+        self.tokens_by_owner
+            .iter()
+            .map(|(owner, token_ids)| (owner, token_ids.len() as u64))
+    }
+
+    fn transfer(
+        &mut self,
+        from: &Address,
+        token_ids: &[u64],
+        to: &Address,
+        locked_until_ms: Option<u64>,
+    ) {
+        self.release(from, token_ids);
+        let received = self.tokens_by_owner.entry(to.clone()).or_default();
+        for token_id in token_ids {
+            self.token_owner_by_id.insert(*token_id, to.clone());
+            received.insert(*token_id);
+            match locked_until_ms {
+                Some(until) => self.locked_until_by_id.insert(*token_id, until),
+                None => self.locked_until_by_id.remove(token_id),
+            };
+        }
+    }
+
+    fn burn(&mut self, from: &Address, token_ids: &[u64]) {
+        self.release(from, token_ids);
+        for token_id in token_ids {
+            self.token_owner_by_id.remove(token_id);
+            self.locked_until_by_id.remove(token_id);
+        }
+        self.total_supply -= token_ids.len() as u64;
+    }
+
+    /// Current lock of each of `token_ids`, to hand back to `restore`.
+    fn locks_of(&self, token_ids: &[u64]) -> Vec<Option<u64>> {
+        token_ids
+            .iter()
+            .map(|token_id| self.locked_until(*token_id))
+            .collect()
+    }
+
+    /// Gives `token_ids` back to `owner` with their earlier `locks`, undoing a transfer
+    /// once the recipient has released them, or a burn.
+    fn restore(&mut self, owner: &Address, token_ids: &[u64], locks: &[Option<u64>]) {
+        let held = self.tokens_by_owner.entry(owner.clone()).or_default();
+        for (token_id, lock) in token_ids.iter().zip(locks) {
+            self.token_owner_by_id.insert(*token_id, owner.clone());
+            held.insert(*token_id);
+            match lock {
+                Some(until) => self.locked_until_by_id.insert(*token_id, *until),
+                None => self.locked_until_by_id.remove(token_id),
+            };
+        }
+    }
+
+    /// Rebuilds `tokens_by_owner` from `token_owner_by_id`.
+    fn index_owners(&mut self) {
+        self.tokens_by_owner.clear();
+        for (token_id, owner) in &self.token_owner_by_id {
+            self.tokens_by_owner
+                .entry(owner.clone())
+                .or_default()
+                .insert(*token_id);
+        }
+    }
+
+    /// Drops `token_ids` from `owner`'s entry in the owner index.
+    fn release(&mut self, owner: &Address, token_ids: &[u64]) {
+        if let Some(held) = self.tokens_by_owner.get_mut(owner) {
+            for token_id in token_ids {
+                held.remove(token_id);
+            }
+            if held.is_empty() {
+                self.tokens_by_owner.remove(owner);
+            }
+        }
+    }
+}
+
+/// How a block changed one address's holdings of one asset. Tokens that came and went
+/// within the same block show up in neither list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct BalanceChange {
+    pub address: Address,
+    pub asset_id: AssetId,
+    pub gained: Vec<u64>,
+    pub lost: Vec<u64>,
+    /// Balance once the whole block is applied
+    pub balance: u64,
+}
+
+/// Ids a mint hands to each holder: consecutive ranges from 0 in `distribution` order, or
+/// all of them to `issuer` without one.
+fn mint_shares<'a>(
+    issuer: &'a Address,
+    amount: u64,
+    distribution: &'a [(Address, u64)],
+) -> Vec<(&'a Address, std::ops::Range<u64>)> {
+    let shares: Vec<(&Address, u64)> = if distribution.is_empty() {
+        vec![(issuer, amount)]
+    } else {
+        distribution
+            .iter()
+            .map(|(owner, count)| (owner, *count))
+            .collect()
+    };
+    let mut next_token_id = 0;
+    shares
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(owner, count)| {
+            let token_ids = next_token_id..next_token_id + count;
+            next_token_id += count;
+            (owner, token_ids)
+        })
+        .collect()
+}
+
+/// Tokens gained and lost per (address, asset) while a block is being applied.
+#[derive(Debug, Default)]
+struct HoldingsDelta {
+    by_holder: BTreeMap<(Address, AssetId), (BTreeSet<u64>, BTreeSet<u64>)>,
+}
+
+impl HoldingsDelta {
+    fn gain(&mut self, address: &Address, asset_id: AssetId, token_ids: &[u64]) {
+        let (gained, lost) = self
+            .by_holder
+            .entry((address.clone(), asset_id))
+            .or_default();
+        for token_id in token_ids {
+            if !lost.remove(token_id) {
+                gained.insert(*token_id);
+            }
+        }
+    }
+
+    fn lose(&mut self, address: &Address, asset_id: AssetId, token_ids: &[u64]) {
+        let (gained, lost) = self
+            .by_holder
+            .entry((address.clone(), asset_id))
+            .or_default();
+        for token_id in token_ids {
+            if !gained.remove(token_id) {
+                lost.insert(*token_id);
+            }
         }
     }
+
+    /// Changes of a delta already applied to `chain`.
+    fn into_changes(self, chain: &ATokenChain) -> Vec<BalanceChange> {
+        self.changes_with(|address, asset_id, _, _| chain.balance_of(asset_id, address))
+    }
+
+    /// Changes of a delta not applied to `chain`, with the balances it would leave.
+    fn into_projected_changes(self, chain: &ATokenChain) -> Vec<BalanceChange> {
+        self.changes_with(|address, asset_id, gained, lost| {
+            chain.balance_of(asset_id, address) + gained - lost
+        })
+    }
+
+    fn changes_with(
+        self,
+        balance: impl Fn(&Address, AssetId, u64, u64) -> u64,
+    ) -> Vec<BalanceChange> {
+        self.by_holder
+            .into_iter()
+            .filter(|(_, (gained, lost))| !gained.is_empty() || !lost.is_empty())
+            .map(|((address, asset_id), (gained, lost))| BalanceChange {
+                balance: balance(&address, asset_id, gained.len() as u64, lost.len() as u64),
+                address,
+                asset_id,
+                gained: gained.into_iter().collect(),
+                lost: lost.into_iter().collect(),
+            })
+            .collect()
+    }
+}
+
+/// What applying one tx changed, so a block that fails partway through can be taken back.
+struct AppliedTx {
+    from: Address,
+    previous_nonce: Option<u64>,
+    change: LedgerChange,
+}
+
+enum LedgerChange {
+    /// Pushed a new asset
+    Mint,
+    Transfer {
+        asset_id: AssetId,
+        to: Address,
+        token_ids: Vec<u64>,
+        locks: Vec<Option<u64>>,
+    },
+    Burn {
+        asset_id: AssetId,
+        token_ids: Vec<u64>,
+        locks: Vec<Option<u64>>,
+    },
+    Metadata {
+        asset_id: AssetId,
+        name: String,
+        description: String,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub struct ATokenChain {
     pub config: ChainConfig,
     pub blocks: Vec<Block>,
-    token_metadata: Option<TokenMetadata>,
-    issued_once: bool,
-    total_supply: u64,
-    token_owner_by_id: BTreeMap<u64, Address>,
+    /// Indexed by `AssetId`, in mint order
+    assets: Vec<Asset>,
+    height_by_hash: HashMap<String, u64>,
+    /// Tx id -> (block height, position in the block); also the set of applied tx ids
+    tx_location_by_id: HashMap<String, (u64, usize)>,
     last_nonce_by_address: HashMap<Address, u64>,
+    /// Time for new block timestamps and for txs validated before they are in a block
+    clock: Arc<dyn Clock>,
+}
+
+/// A chain copied by [`ATokenChain::integrity_check`], good for nothing but the check.
+pub struct IntegrityCheck(ATokenChain);
+
+impl IntegrityCheck {
+    pub fn blocks(&self) -> usize {
+        self.0.blocks.len()
+    }
+
+    /// [`ATokenChain::verify_integrity`] of the copied chain.
+    pub fn run(&self) -> Result<()> {
+        self.0.verify_integrity()
+    }
 }
 
 impl ATokenChain {
@@ -37,14 +402,29 @@ impl ATokenChain {
         Self {
             config,
             blocks: Vec::new(),
-            token_metadata: None,
-            issued_once: false,
-            total_supply: 0,
-            token_owner_by_id: BTreeMap::new(),
+            assets: Vec::new(),
+            height_by_hash: HashMap::new(),
+            tx_location_by_id: HashMap::new(),
             last_nonce_by_address: HashMap::new(),
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Reads the time from `clock` instead of the system clock. Applying a block only
+    /// looks at its own timestamp, so replays agree whatever the clock says.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Timestamp the next block gets: the clock, but never behind the parent, even if the
+    /// clock stepped back.
+    pub fn next_block_time(&self) -> u64 {
+        self.clock
+            .now_ms()
+            .max(self.blocks.last().map_or(0, |b| b.header.timestamp_ms))
+    }
+
     pub fn next_nonce(&self, address: &Address) -> u64 {
         self.last_nonce_by_address
             .get(address)
@@ -53,54 +433,168 @@ impl ATokenChain {
             + 1
     }
 
-    pub fn metadata(&self) -> Option<&TokenMetadata> {
-        self.token_metadata.as_ref()
+    pub fn assets(&self) -> &[Asset] {
+        &self.assets
     }
 
-    pub fn total_supply(&self) -> u64 {
-        self.total_supply
+    pub fn asset(&self, asset_id: AssetId) -> Option<&Asset> {
+        usize::try_from(asset_id)
+            .ok()
+            .and_then(|index| self.assets.get(index))
     }
 
-    pub fn balance_of(&self, address: &Address) -> u64 {
-        self.token_owner_by_id
-            .values()
-            .filter(|owner| *owner == address)
-            .count() as u64
+    /// Id the next applied `Mint` will create.
+    pub fn next_asset_id(&self) -> AssetId {
+        self.assets.len() as AssetId
     }
 
-    pub fn owner_of(&self, token_id: u64) -> Option<&Address> {
-        self.token_owner_by_id.get(&token_id)
+    pub fn metadata(&self, asset_id: AssetId) -> Option<&TokenMetadata> {
+        self.asset(asset_id).map(Asset::metadata)
     }
 
-    pub fn tokens_of(&self, address: &Address) -> Vec<u64> {
-        self.token_owner_by_id
-            .iter()
-            .filter_map(|(token_id, owner)| {
-                if owner == address {
-                    Some(*token_id)
-                } else {
-                    None
+    pub fn total_supply(&self, asset_id: AssetId) -> u64 {
+        self.asset(asset_id).map_or(0, Asset::total_supply)
+    }
+
+    pub fn balance_of(&self, asset_id: AssetId, address: &Address) -> u64 {
+        self.tokens_of(asset_id, address).len() as u64
+    }
+
+    /// Balance of `address` once the block at `height` was applied. Walks the txs of blocks
+    /// `0..=height` and adds up what each one moved to or from the address, so a query costs
+    /// one pass over those txs, without re-checking them and without keeping any history.
+    pub fn balance_at(&self, asset_id: AssetId, address: &Address, height: u64) -> Result<u64> {
+        if height >= self.blocks.len() as u64 {
+            return Err(ATokenError::UnknownBlockHeight(height));
+        }
+        let mut balance = 0u64;
+        let mut next_asset_id: AssetId = 0;
+        for tx in self.blocks[..=height as usize].iter().flat_map(|b| &b.txs) {
+            let from = &tx.unsigned.from;
+            match &tx.unsigned.payload {
+                TxPayload::Mint {
+                    amount,
+                    distribution,
+                    ..
+                } => {
+                    if next_asset_id == asset_id {
+                        balance += if distribution.is_empty() {
+                            if from == address { *amount } else { 0 }
+                        } else {
+                            distribution
+                                .iter()
+                                .filter(|(recipient, _)| recipient == address)
+                                .map(|(_, count)| count)
+                                .sum()
+                        };
+                    }
+                    next_asset_id += 1;
                 }
-            })
-            .collect()
+                TxPayload::Transfer {
+                    asset_id: moved,
+                    token_ids,
+                    to,
+                    ..
+                } if *moved == asset_id => {
+                    let count = token_ids.len() as u64;
+                    if to == address {
+                        balance += count;
+                    }
+                    if from == address {
+                        balance -= count;
+                    }
+                }
+                TxPayload::TransferRanges {
+                    asset_id: moved,
+                    token_ranges,
+                    to,
+                } if *moved == asset_id => {
+                    let count = token_ranges
+                        .iter()
+                        .map(|(first, last)| last - first + 1)
+                        .sum::<u64>();
+                    if to == address {
+                        balance += count;
+                    }
+                    if from == address {
+                        balance -= count;
+                    }
+                }
+                TxPayload::Burn {
+                    asset_id: burned,
+                    token_ids,
+                } if *burned == asset_id && from == address => {
+                    balance -= token_ids.len() as u64;
+                }
+                _ => {}
+            }
+        }
+        Ok(balance)
+    }
+
+    pub fn owner_of(&self, asset_id: AssetId, token_id: u64) -> Option<&Address> {
+        self.asset(asset_id)?.owner_of(token_id)
+    }
+
+    pub fn tokens_of(&self, asset_id: AssetId, address: &Address) -> &BTreeSet<u64> {
+        self.asset(asset_id)
+            .map_or(&NO_TOKENS, |asset| asset.tokens_of(address))
+    }
+
+    pub fn block_at(&self, height: u64) -> Option<&Block> {
+        usize::try_from(height)
+            .ok()
+            .and_then(|height| self.blocks.get(height))
+    }
+
+    pub fn block_by_hash(&self, hash: &str) -> Option<&Block> {
+        self.height_by_hash
+            .get(hash)
+            .and_then(|height| self.block_at(*height))
+    }
+
+    /// Height of the block holding `tx_id` and the tx's position in it.
+    pub fn tx_location(&self, tx_id: &str) -> Option<(u64, usize)> {
+        self.tx_location_by_id.get(tx_id).copied()
+    }
+
+    /// The tx with `tx_id` and the height of the block it landed in.
+    pub fn find_tx(&self, tx_id: &str) -> Option<(u64, &SignedTx)> {
+        let (height, index) = self.tx_location(tx_id)?;
+        let tx = self.block_at(height)?.txs.get(index)?;
+        Some((height, tx))
     }
 
     pub fn build_block(&self, proposer: &Wallet, txs: Vec<SignedTx>) -> Result<Block> {
+        self.build_block_at(proposer, txs, self.next_block_time())
+    }
+
+    /// `build_block` stamped `timestamp_ms`, e.g. the time `txs` were validated for, so
+    /// the block is checked at the same instant they were.
+    pub fn build_block_at(
+        &self,
+        proposer: &Wallet,
+        txs: Vec<SignedTx>,
+        timestamp_ms: u64,
+    ) -> Result<Block> {
         let previous_hash = self.blocks.last().map(|b| b.hash.clone());
         let previous_three_hashes = self.expected_previous_three_hashes();
         let header = BlockHeader {
+            version: BLOCK_VERSION,
             chain_id: self.config.chain_id.clone(),
             height: self.blocks.len() as u64,
             previous_hash,
             previous_three_hashes,
-            proposer: proposer.address(),
+            proposer: proposer.address_for(self.config.hash_algorithm),
             proposer_public_key_hex: proposer.public_key_hex(),
-            timestamp_ms: crate::crypto::now_ms(),
+            timestamp_ms,
+            tx_merkle_root: Block::tx_merkle_root(&txs)?,
+            hash_algorithm: self.config.hash_algorithm,
         };
 
-        let sign_message = Block::previous_signature_message(&header)?;
+        let sign_message = Block::previous_signature_message(&header);
         let previous_signature_hex = proposer.sign_hex(&sign_message);
-        let hash = Block::calculate_hash(&header, &txs, &previous_signature_hex)?;
+        let hash = Block::calculate_hash(&header, &txs, &previous_signature_hex);
 
         Ok(Block {
             header,
@@ -110,19 +604,171 @@ impl ATokenChain {
         })
     }
 
-    pub fn append_block(&mut self, block: Block) -> Result<()> {
+    /// Replays every stored block on a fresh chain, re-checking linkage, signatures, hashes
+    /// and txs, then compares the replayed ledger with the current one.
+    /// Fails with the height of the first block that does not hold up.
+    pub fn verify_integrity(&self) -> Result<()> {
+        let mut replayed = ATokenChain::new(self.config.clone());
+        replayed.clock = self.clock.clone();
+        for (height, block) in self.blocks.iter().enumerate() {
+            if let Err(e) = replayed.append_block(block.clone()) {
+                return Err(ATokenError::IntegrityFailure {
+                    height: height as u64,
+                    reason: e.to_string(),
+                });
+            }
+        }
+
+        let consistent = replayed.assets == self.assets
+            && replayed.last_nonce_by_address == self.last_nonce_by_address;
+        if !consistent {
+            return Err(ATokenError::IntegrityFailure {
+                height: self.blocks.len().saturating_sub(1) as u64,
+                reason: "replayed ledger does not match the current state".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Copy of what `verify_integrity` reads, the blocks and the ledger without the lookup
+    /// indexes, so a long replay can run once the chain is released.
+    pub fn integrity_check(&self) -> IntegrityCheck {
+        IntegrityCheck(Self {
+            config: self.config.clone(),
+            blocks: self.blocks.clone(),
+            assets: self.assets.clone(),
+            height_by_hash: HashMap::new(),
+            tx_location_by_id: HashMap::new(),
+            last_nonce_by_address: self.last_nonce_by_address.clone(),
+            clock: self.clock.clone(),
+        })
+    }
+
+    /// Ledger as of the last block, or `None` before block 0.
+    pub fn export_snapshot(&self) -> Option<ChainSnapshot> {
+        let last = self.blocks.last()?;
+        Some(ChainSnapshot {
+            chain_id: self.config.chain_id.clone(),
+            height: last.header.height,
+            block_hash: last.hash.clone(),
+            assets: self.assets.clone(),
+            nonces: self
+                .last_nonce_by_address
+                .iter()
+                .map(|(address, nonce)| (address.clone(), *nonce))
+                .collect(),
+        })
+    }
+
+    /// Takes the ledger from `snapshot` instead of replaying `blocks`, the stored blocks up
+    /// to and including its height; later blocks go through `append_block` as usual. Only
+    /// the linkage of `blocks` is checked, not their signatures or txs: the snapshot is
+    /// trusted like the store it was taken from. Leaves the chain untouched on error.
+    pub fn import_snapshot(&mut self, snapshot: ChainSnapshot, blocks: Vec<Block>) -> Result<()> {
+        if !self.blocks.is_empty() {
+            return Err(ATokenError::InvalidSnapshot(
+                "the chain already has blocks".to_string(),
+            ));
+        }
+        if snapshot.chain_id != self.config.chain_id {
+            return Err(ATokenError::InvalidSnapshot(format!(
+                "taken on chain {}, expected {}",
+                snapshot.chain_id, self.config.chain_id
+            )));
+        }
+        if blocks.len() as u64 != snapshot.height + 1 {
+            return Err(ATokenError::InvalidSnapshot(format!(
+                "covers {} blocks, got {}",
+                snapshot.height + 1,
+                blocks.len()
+            )));
+        }
+        let mut previous_hash = None;
+        for (height, block) in blocks.iter().enumerate() {
+            if block.header.height != height as u64
+                || block.header.previous_hash.as_ref() != previous_hash
+            {
+                return Err(ATokenError::InvalidSnapshot(format!(
+                    "stored block {height} does not follow its parent"
+                )));
+            }
+            previous_hash = Some(&block.hash);
+        }
+        if previous_hash != Some(&snapshot.block_hash) {
+            return Err(ATokenError::InvalidSnapshot(format!(
+                "taken at block {}, the store has another block at height {}",
+                snapshot.block_hash, snapshot.height
+            )));
+        }
+
+        self.assets = snapshot.assets;
+        for asset in &mut self.assets {
+            asset.index_owners();
+        }
+        self.last_nonce_by_address = snapshot.nonces.into_iter().collect();
+        for block in &blocks {
+            self.height_by_hash
+                .insert(block.hash.clone(), block.header.height);
+            for (index, tx) in block.txs.iter().enumerate() {
+                self.tx_location_by_id
+                    .insert(tx.id.clone(), (block.header.height, index));
+            }
+        }
+        self.blocks = blocks;
+        Ok(())
+    }
+
+    /// Validates and applies `block`, returning the holdings it changed, ordered by address
+    /// and asset. Either every tx is applied or, if one fails, none are.
+    pub fn append_block(&mut self, block: Block) -> Result<Vec<BalanceChange>> {
         self.validate_block_header(&block)?;
         self.validate_block_signature(&block)?;
         self.validate_block_hash(&block)?;
+        self.validate_tx_merkle_root(&block)?;
+
+        let mut block_tx_ids = HashSet::new();
+        for tx in &block.txs {
+            if !block_tx_ids.insert(tx.id.as_str()) {
+                return Err(ATokenError::DuplicateTransaction(tx.id.clone()));
+            }
+        }
+        // Signatures do not depend on the ledger, so check them all up front and in parallel;
+        // the first bad one in block order is reported, as a serial check would
+        if let Some(Err(e)) = block
+            .txs
+            .par_iter()
+            .map(|tx| tx.verify_for(self.config.hash_algorithm))
+            .find_first(Result::is_err)
+        {
+            return Err(e);
+        }
 
+        let mut delta = HoldingsDelta::default();
+        let mut applied = Vec::with_capacity(block.txs.len());
         for tx in &block.txs {
-            self.apply_signed_tx(tx)?;
+            match self.apply_signed_tx(tx, block.header.timestamp_ms, &mut delta) {
+                Ok(change) => applied.push(change),
+                Err(e) => {
+                    self.roll_back(applied);
+                    return Err(e);
+                }
+            }
+        }
+        self.height_by_hash
+            .insert(block.hash.clone(), block.header.height);
+        for (index, tx) in block.txs.iter().enumerate() {
+            self.tx_location_by_id
+                .insert(tx.id.clone(), (block.header.height, index));
         }
         self.blocks.push(block);
-        Ok(())
+        Ok(delta.into_changes(self))
     }
 
     fn validate_block_header(&self, block: &Block) -> Result<()> {
+        if block.header.version != BLOCK_VERSION {
+            return Err(ATokenError::UnsupportedBlockVersion(block.header.version));
+        }
+
         let expected_height = self.blocks.len() as u64;
         if block.header.height != expected_height {
             return Err(ATokenError::InvalidBlockHeight {
@@ -138,6 +784,30 @@ impl ATokenChain {
             });
         }
 
+        let timestamp_ms = block.header.timestamp_ms;
+        if let Some(parent) = self.blocks.last()
+            && timestamp_ms < parent.header.timestamp_ms
+        {
+            return Err(ATokenError::BlockTimestampBeforeParent {
+                parent: parent.header.timestamp_ms,
+                actual: timestamp_ms,
+            });
+        }
+        let latest_allowed = self.clock.now_ms() + self.config.max_future_skew_ms;
+        if timestamp_ms > latest_allowed {
+            return Err(ATokenError::BlockTimestampInFuture {
+                latest_allowed,
+                actual: timestamp_ms,
+            });
+        }
+
+        if block.header.hash_algorithm != self.config.hash_algorithm {
+            return Err(ATokenError::HashAlgorithmMismatch {
+                expected: self.config.hash_algorithm,
+                actual: block.header.hash_algorithm,
+            });
+        }
+
         let expected_previous_hash = self.blocks.last().map(|b| b.hash.clone());
         if block.header.previous_hash != expected_previous_hash {
             return Err(ATokenError::PreviousHashMismatch);
@@ -160,16 +830,21 @@ impl ATokenChain {
             .map_err(|_| ATokenError::InvalidPublicKeyLength(public_key_len))?;
         let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(&public_key_arr)
             .map_err(|_| ATokenError::InvalidPublicKeyLength(public_key_len))?;
-        let proposer_address = address_from_public_key(&verifying_key);
+        let proposer_address =
+            address_from_public_key_with(&verifying_key, self.config.hash_algorithm);
         if proposer_address != block.header.proposer {
             return Err(ATokenError::InvalidSender);
         }
+        // Block 0 is fixed by the genesis config or the first mint, before any authority
+        if block.header.height > 0 && !self.config.is_authorized_proposer(&proposer_address) {
+            return Err(ATokenError::UnauthorizedProposer(proposer_address));
+        }
 
         Ok(())
     }
 
     fn validate_block_signature(&self, block: &Block) -> Result<()> {
-        let sign_message = Block::previous_signature_message(&block.header)?;
+        let sign_message = Block::previous_signature_message(&block.header);
         verify_signature_hex(
             &block.header.proposer_public_key_hex,
             &block.previous_signature_hex,
@@ -180,13 +855,20 @@ impl ATokenChain {
 
     fn validate_block_hash(&self, block: &Block) -> Result<()> {
         let expected =
-            Block::calculate_hash(&block.header, &block.txs, &block.previous_signature_hex)?;
+            Block::calculate_hash(&block.header, &block.txs, &block.previous_signature_hex);
         if expected != block.hash {
             return Err(ATokenError::BlockHashMismatch);
         }
         Ok(())
     }
 
+    fn validate_tx_merkle_root(&self, block: &Block) -> Result<()> {
+        if Block::tx_merkle_root(&block.txs)? != block.header.tx_merkle_root {
+            return Err(ATokenError::TxMerkleRootMismatch);
+        }
+        Ok(())
+    }
+
     fn expected_previous_three_hashes(&self) -> Vec<String> {
         let keep = self.config.required_previous_blocks;
         let start = self.blocks.len().saturating_sub(keep);
@@ -196,101 +878,496 @@ impl ATokenChain {
             .collect()
     }
 
-    fn apply_signed_tx(&mut self, tx: &SignedTx) -> Result<()> {
-        tx.verify()?;
+    /// Checks `tx` against the current state as if it were applied next with
+    /// `expected_nonce`, in a block built now, without changing anything.
+    pub fn validate_tx(&self, tx: &SignedTx, expected_nonce: u64) -> Result<()> {
+        self.validate_tx_at(tx, expected_nonce, self.next_block_time())
+    }
 
-        let expected_nonce = self.next_nonce(&tx.unsigned.from);
-        if tx.unsigned.nonce != expected_nonce {
-            return Err(ATokenError::NonceMismatch {
-                expected: expected_nonce,
-                actual: tx.unsigned.nonce,
-            });
-        }
+    /// What committing `tx` in a block of its own built now would change, worked out from
+    /// the ledger without building or applying anything.
+    pub fn simulate_tx(&self, tx: &SignedTx) -> Result<Vec<BalanceChange>> {
+        let from = &tx.unsigned.from;
+        self.validate_tx(tx, self.next_nonce(from))?;
 
+        let mut delta = HoldingsDelta::default();
         match &tx.unsigned.payload {
-            TxPayload::Mint { amount, metadata } => {
-                self.apply_mint(tx, *amount, metadata.clone())?
+            TxPayload::Mint {
+                amount,
+                distribution,
+                ..
+            } => {
+                let asset_id = self.next_asset_id();
+                for (owner, token_ids) in mint_shares(from, *amount, distribution) {
+                    delta.gain(owner, asset_id, &token_ids.collect::<Vec<_>>());
+                }
             }
-            TxPayload::Transfer { token_ids, to } => self.apply_transfer(tx, token_ids, to)?,
+            TxPayload::Transfer { asset_id, to, .. }
+            | TxPayload::TransferRanges { asset_id, to, .. } => {
+                let token_ids = self.token_ids_of(tx)?;
+                delta.lose(from, *asset_id, &token_ids);
+                delta.gain(to, *asset_id, &token_ids);
+            }
+            TxPayload::Burn {
+                asset_id,
+                token_ids,
+            } => delta.lose(from, *asset_id, token_ids),
+            TxPayload::UpdateMetadata { .. } => {}
         }
+        Ok(delta.into_projected_changes(self))
+    }
 
-        self.last_nonce_by_address
-            .insert(tx.unsigned.from.clone(), tx.unsigned.nonce);
-        Ok(())
+    /// `validate_tx` for a block with timestamp `block_time_ms`, which expiry and time locks
+    /// are checked against.
+    pub fn validate_tx_at(
+        &self,
+        tx: &SignedTx,
+        expected_nonce: u64,
+        block_time_ms: u64,
+    ) -> Result<()> {
+        tx.verify_for(self.config.hash_algorithm)?;
+        self.validate_verified_tx(tx, expected_nonce, block_time_ms)
     }
 
-    fn apply_mint(
-        &mut self,
+    /// Everything `validate_tx_at` checks besides `SignedTx::verify_for`.
+    fn validate_verified_tx(
+        &self,
         tx: &SignedTx,
-        amount: u64,
-        mut metadata: TokenMetadata,
+        expected_nonce: u64,
+        block_time_ms: u64,
     ) -> Result<()> {
-        if self.issued_once {
-            return Err(ATokenError::AlreadyIssued);
-        }
-        if tx.unsigned.from != self.config.issuer {
-            return Err(ATokenError::MintNotAllowed);
-        }
-        if amount == 0 {
-            return Err(ATokenError::MintAmountMustBePositive);
+        if self.tx_location_by_id.contains_key(&tx.id) {
+            return Err(ATokenError::DuplicateTransaction(tx.id.clone()));
         }
 
-        metadata.issuer = self.config.issuer.clone();
-        for token_id in 0..amount {
-            self.token_owner_by_id
-                .insert(token_id, self.config.issuer.clone());
+        if tx.unsigned.chain_id != self.config.chain_id {
+            return Err(ATokenError::InvalidChainId {
+                expected: self.config.chain_id.clone(),
+                actual: tx.unsigned.chain_id.clone(),
+            });
         }
-        self.total_supply = amount;
-        self.token_metadata = Some(metadata);
-        self.issued_once = true;
-        Ok(())
-    }
 
-    fn apply_transfer(&mut self, tx: &SignedTx, token_ids: &[u64], to: &Address) -> Result<()> {
-        if !self.issued_once {
-            return Err(ATokenError::TokenNotIssued);
+        if tx.unsigned.nonce != expected_nonce {
+            return Err(ATokenError::NonceMismatch {
+                expected: expected_nonce,
+                actual: tx.unsigned.nonce,
+            });
         }
-        if token_ids.is_empty() {
-            return Err(ATokenError::EmptyTransfer);
+
+        if let Some(valid_until_ms) = tx.unsigned.valid_until_ms
+            && valid_until_ms < block_time_ms
+        {
+            return Err(ATokenError::Expired {
+                valid_until_ms,
+                block_time_ms,
+            });
         }
 
-        let mut seen = HashSet::new();
-        for token_id in token_ids {
-            if !seen.insert(*token_id) {
-                return Err(ATokenError::DuplicateTokenId(*token_id));
+        let from = &tx.unsigned.from;
+        match &tx.unsigned.payload {
+            TxPayload::Mint {
+                amount,
+                metadata,
+                distribution,
+            } => self.validate_mint(from, *amount, metadata, distribution),
+            TxPayload::Transfer {
+                asset_id,
+                token_ids,
+                to,
+                memo,
+                ..
+            } => {
+                validate_recipient(to)?;
+                if let Some(memo) = memo {
+                    TxPayload::validate_memo(memo)?;
+                }
+                self.validate_spend(
+                    from,
+                    *asset_id,
+                    token_ids,
+                    ATokenError::EmptyTransfer,
+                    block_time_ms,
+                )
             }
-            let owner = self
-                .token_owner_by_id
-                .get(token_id)
-                .ok_or(ATokenError::UnknownToken(*token_id))?;
-            if owner != &tx.unsigned.from {
-                return Err(ATokenError::NotTokenOwner {
-                    token_id: *token_id,
-                });
+            TxPayload::TransferRanges {
+                asset_id,
+                token_ranges,
+                to,
+            } => {
+                validate_recipient(to)?;
+                let token_ids = self.expand_token_ranges(*asset_id, token_ranges)?;
+                self.validate_spend(
+                    from,
+                    *asset_id,
+                    &token_ids,
+                    ATokenError::EmptyTransfer,
+                    block_time_ms,
+                )
+            }
+            TxPayload::Burn {
+                asset_id,
+                token_ids,
+            } => self.validate_spend(
+                from,
+                *asset_id,
+                token_ids,
+                ATokenError::EmptyBurn,
+                block_time_ms,
+            ),
+            TxPayload::UpdateMetadata {
+                asset_id,
+                name,
+                description,
+            } => {
+                self.validate_metadata_update(from, *asset_id)?;
+                TokenMetadata::validate_editable(name, description)
             }
         }
+    }
 
-        for token_id in token_ids {
-            self.token_owner_by_id.insert(*token_id, to.clone());
+    /// Ids of `tx.unsigned.payload.asset_id()` moved or burned by `tx`, with ranges
+    /// expanded. Only meaningful for a valid tx.
+    pub fn token_ids_of(&self, tx: &SignedTx) -> Result<Vec<u64>> {
+        match &tx.unsigned.payload {
+            TxPayload::Mint { .. } | TxPayload::UpdateMetadata { .. } => Ok(Vec::new()),
+            TxPayload::Transfer { token_ids, .. } | TxPayload::Burn { token_ids, .. } => {
+                Ok(token_ids.clone())
+            }
+            TxPayload::TransferRanges {
+                asset_id,
+                token_ranges,
+                ..
+            } => self.expand_token_ranges(*asset_id, token_ranges),
+        }
+    }
+
+    /// Applies a tx whose signature `append_block` has already checked, returning how to
+    /// undo it. Nothing is changed if the tx is invalid.
+    fn apply_signed_tx(
+        &mut self,
+        tx: &SignedTx,
+        block_time_ms: u64,
+        delta: &mut HoldingsDelta,
+    ) -> Result<AppliedTx> {
+        let from = &tx.unsigned.from;
+        self.validate_verified_tx(tx, self.next_nonce(from), block_time_ms)?;
+
+        let change = match &tx.unsigned.payload {
+            TxPayload::Mint {
+                amount,
+                metadata,
+                distribution,
+            } => {
+                let asset_id = self.next_asset_id();
+                self.apply_mint(from, *amount, metadata.clone(), distribution);
+                for (owner, token_ids) in &self.assets[asset_id as usize].tokens_by_owner {
+                    let token_ids: Vec<u64> = token_ids.iter().copied().collect();
+                    delta.gain(owner, asset_id, &token_ids);
+                }
+                LedgerChange::Mint
+            }
+            TxPayload::Transfer {
+                asset_id,
+                token_ids,
+                to,
+                locked_until_ms,
+                ..
+            } => {
+                let asset = self.asset_mut(*asset_id)?;
+                let locks = asset.locks_of(token_ids);
+                asset.transfer(from, token_ids, to, *locked_until_ms);
+                delta.lose(from, *asset_id, token_ids);
+                delta.gain(to, *asset_id, token_ids);
+                LedgerChange::Transfer {
+                    asset_id: *asset_id,
+                    to: to.clone(),
+                    token_ids: token_ids.clone(),
+                    locks,
+                }
+            }
+            TxPayload::TransferRanges {
+                asset_id,
+                token_ranges,
+                to,
+            } => {
+                let token_ids = self.expand_token_ranges(*asset_id, token_ranges)?;
+                let asset = self.asset_mut(*asset_id)?;
+                let locks = asset.locks_of(&token_ids);
+                asset.transfer(from, &token_ids, to, None);
+                delta.lose(from, *asset_id, &token_ids);
+                delta.gain(to, *asset_id, &token_ids);
+                LedgerChange::Transfer {
+                    asset_id: *asset_id,
+                    to: to.clone(),
+                    token_ids,
+                    locks,
+                }
+            }
+            TxPayload::Burn {
+                asset_id,
+                token_ids,
+            } => {
+                let asset = self.asset_mut(*asset_id)?;
+                let locks = asset.locks_of(token_ids);
+                asset.burn(from, token_ids);
+                delta.lose(from, *asset_id, token_ids);
+                LedgerChange::Burn {
+                    asset_id: *asset_id,
+                    token_ids: token_ids.clone(),
+                    locks,
+                }
+            }
+            TxPayload::UpdateMetadata {
+                asset_id,
+                name,
+                description,
+            } => {
+                let metadata = &mut self.asset_mut(*asset_id)?.metadata;
+                let name = std::mem::replace(&mut metadata.name, name.clone());
+                let description = std::mem::replace(&mut metadata.description, description.clone());
+                LedgerChange::Metadata {
+                    asset_id: *asset_id,
+                    name,
+                    description,
+                }
+            }
+        };
+
+        let previous_nonce = self
+            .last_nonce_by_address
+            .insert(from.clone(), tx.unsigned.nonce);
+        Ok(AppliedTx {
+            from: from.clone(),
+            previous_nonce,
+            change,
+        })
+    }
+
+    /// Undoes `applied`, the txs of a block applied so far, latest first.
+    fn roll_back(&mut self, applied: Vec<AppliedTx>) {
+        for AppliedTx {
+            from,
+            previous_nonce,
+            change,
+        } in applied.into_iter().rev()
+        {
+            match change {
+                LedgerChange::Mint => {
+                    self.assets.pop();
+                }
+                LedgerChange::Transfer {
+                    asset_id,
+                    to,
+                    token_ids,
+                    locks,
+                } => {
+                    let asset = &mut self.assets[asset_id as usize];
+                    asset.release(&to, &token_ids);
+                    asset.restore(&from, &token_ids, &locks);
+                }
+                LedgerChange::Burn {
+                    asset_id,
+                    token_ids,
+                    locks,
+                } => {
+                    let asset = &mut self.assets[asset_id as usize];
+                    asset.restore(&from, &token_ids, &locks);
+                    asset.total_supply += token_ids.len() as u64;
+                }
+                LedgerChange::Metadata {
+                    asset_id,
+                    name,
+                    description,
+                } => {
+                    let metadata = &mut self.assets[asset_id as usize].metadata;
+                    metadata.name = name;
+                    metadata.description = description;
+                }
+            }
+            match previous_nonce {
+                Some(nonce) => self.last_nonce_by_address.insert(from, nonce),
+                None => self.last_nonce_by_address.remove(&from),
+            };
+        }
+    }
+
+    fn validate_mint(
+        &self,
+        from: &Address,
+        amount: u64,
+        metadata: &TokenMetadata,
+        distribution: &[(Address, u64)],
+    ) -> Result<()> {
+        if from != &self.config.issuer {
+            return Err(ATokenError::MintNotAllowed);
+        }
+        if amount == 0 {
+            return Err(ATokenError::MintAmountMustBePositive);
+        }
+        if amount > self.config.max_mint_amount {
+            return Err(ATokenError::MintAmountTooLarge {
+                max: self.config.max_mint_amount,
+                actual: amount,
+            });
+        }
+        metadata.validate()?;
+        for (to, _) in distribution {
+            validate_recipient(to)?;
+        }
+        if !distribution.is_empty() {
+            let distributed = distribution
+                .iter()
+                .try_fold(0u64, |sum, (_, count)| sum.checked_add(*count));
+            if distributed != Some(amount) {
+                return Err(ATokenError::DistributionMismatch {
+                    amount,
+                    distributed: distributed.unwrap_or(u64::MAX),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_metadata_update(&self, from: &Address, asset_id: AssetId) -> Result<()> {
+        let asset = self
+            .asset(asset_id)
+            .ok_or(ATokenError::UnknownAsset(asset_id))?;
+        if from != &asset.metadata.issuer {
+            return Err(ATokenError::MetadataUpdateNotAllowed);
         }
         Ok(())
     }
+
+    /// `owner` can give up `token_ids` of `asset_id` at `now_ms`: at least one, each listed
+    /// once, held by `owner` and not time-locked.
+    fn validate_spend(
+        &self,
+        owner: &Address,
+        asset_id: AssetId,
+        token_ids: &[u64],
+        empty: ATokenError,
+        now_ms: u64,
+    ) -> Result<()> {
+        let asset = self
+            .asset(asset_id)
+            .ok_or(ATokenError::UnknownAsset(asset_id))?;
+        if token_ids.is_empty() {
+            return Err(empty);
+        }
+        self.check_transfer_size(token_ids.len() as u64)?;
+
+        let mut seen = HashSet::new();
+        for token_id in token_ids {
+            if !seen.insert(*token_id) {
+                return Err(ATokenError::DuplicateTokenId(*token_id));
+            }
+            let current = asset
+                .owner_of(*token_id)
+                .ok_or(ATokenError::UnknownToken(*token_id))?;
+            if current != owner {
+                return Err(ATokenError::NotTokenOwner {
+                    token_id: *token_id,
+                });
+            }
+            if let Some(locked_until_ms) = asset.locked_until(*token_id)
+                && now_ms < locked_until_ms
+            {
+                return Err(ATokenError::TokenLocked {
+                    token_id: *token_id,
+                    locked_until_ms,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Ids covered by `token_ranges`. Ranges must not overlap and must end on an existing
+    /// token, which also bounds how much a single transfer can expand to.
+    fn expand_token_ranges(
+        &self,
+        asset_id: AssetId,
+        token_ranges: &[(u64, u64)],
+    ) -> Result<Vec<u64>> {
+        let asset = self
+            .asset(asset_id)
+            .ok_or(ATokenError::UnknownAsset(asset_id))?;
+        let mut sorted = token_ranges.to_vec();
+        sorted.sort_unstable();
+        let mut previous_end = None;
+        let mut expanded_len = 0u64;
+        for &(start, end) in &sorted {
+            if start > end {
+                return Err(ATokenError::InvalidTokenRange { start, end });
+            }
+            // Checked before expanding, so a huge range is never materialized
+            expanded_len = expanded_len.saturating_add((end - start).saturating_add(1));
+            self.check_transfer_size(expanded_len)?;
+            if previous_end.is_some_and(|previous_end| start <= previous_end) {
+                return Err(ATokenError::DuplicateTokenId(start));
+            }
+            if asset.owner_of(end).is_none() {
+                return Err(ATokenError::UnknownToken(end));
+            }
+            previous_end = Some(end);
+        }
+        Ok(token_ranges
+            .iter()
+            .flat_map(|&(start, end)| start..=end)
+            .collect())
+    }
+
+    /// Rejects a transfer or burn of `token_count` ids over `max_transfer_ids`; handlers
+    /// call it before signing so an oversized request costs nothing under the lock.
+    pub fn check_transfer_size(&self, token_count: u64) -> Result<()> {
+        let max = self.config.max_transfer_ids;
+        if token_count > max as u64 {
+            return Err(ATokenError::TooManyTokenIds {
+                max,
+                actual: token_count,
+            });
+        }
+        Ok(())
+    }
+
+    /// Creates asset `next_asset_id()` with `amount` tokens, held by `issuer` or split
+    /// following `distribution`.
+    fn apply_mint(
+        &mut self,
+        issuer: &Address,
+        amount: u64,
+        mut metadata: TokenMetadata,
+        distribution: &[(Address, u64)],
+    ) {
+        metadata.issuer = issuer.clone();
+        self.assets
+            .push(Asset::mint(issuer, amount, metadata, distribution));
+    }
+
+    fn asset_mut(&mut self, asset_id: AssetId) -> Result<&mut Asset> {
+        usize::try_from(asset_id)
+            .ok()
+            .and_then(|index| self.assets.get_mut(index))
+            .ok_or(ATokenError::UnknownAsset(asset_id))
+    }
+}
+
+/// A recipient must be spelled in its checksummed form, the one [`Wallet::address_for`]
+/// gives its owner; tokens sent to any other spelling could never be spent.
+fn validate_recipient(to: &Address) -> Result<()> {
+    if validate_address(to)? != *to {
+        return Err(ATokenError::NonCanonicalAddress(to.clone()));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{SignedTx, TokenMetadata, UnsignedTx};
-
-    fn metadata() -> TokenMetadata {
-        TokenMetadata {
-            name: "AToken".to_string(),
-            symbol: "ATKN".to_string(),
-            description: "Test token".to_string(),
-            decimals: 0,
-            issuer: String::new(),
-        }
-    }
+    use crate::canonical;
+    use crate::clock::MockClock;
+    use crate::crypto::sha256_hex;
+    use crate::model::{SignedTx, TX_VERSION, UnsignedTx};
+    use crate::test_support::{metadata, minted_chain};
 
     #[test]
     fn mint_once_and_transfer_works() {
@@ -301,6 +1378,7 @@ mod tests {
 
         let mint = SignedTx::sign(
             UnsignedTx::mint(
+                "AToken-local",
                 issuer.address(),
                 chain.next_nonce(&issuer.address()),
                 10,
@@ -312,13 +1390,15 @@ mod tests {
         let b0 = chain.build_block(&issuer, vec![mint]).unwrap();
         chain.append_block(b0).unwrap();
 
-        let token_ids = chain.tokens_of(&issuer.address());
+        let token_ids = chain.tokens_of(0, &issuer.address());
         let transfer = SignedTx::sign(
             UnsignedTx::transfer(
+                "AToken-local",
                 issuer.address(),
                 chain.next_nonce(&issuer.address()),
+                0,
                 alice.address(),
-                token_ids.into_iter().take(4).collect(),
+                token_ids.iter().copied().take(4).collect(),
             ),
             &issuer,
         )
@@ -326,19 +1406,220 @@ mod tests {
         let b1 = chain.build_block(&issuer, vec![transfer]).unwrap();
         chain.append_block(b1).unwrap();
 
-        assert_eq!(chain.total_supply(), 10);
-        assert_eq!(chain.balance_of(&issuer.address()), 6);
-        assert_eq!(chain.balance_of(&alice.address()), 4);
+        assert_eq!(chain.total_supply(0), 10);
+        assert_eq!(chain.balance_of(0, &issuer.address()), 6);
+        assert_eq!(chain.balance_of(0, &alice.address()), 4);
     }
 
     #[test]
-    fn second_mint_is_rejected() {
+    fn each_mint_creates_an_independent_asset() {
         let issuer = Wallet::generate();
+        let alice = Wallet::generate();
         let config = ChainConfig::new("AToken-local", issuer.address());
         let mut chain = ATokenChain::new(config);
 
-        let mint1 = SignedTx::sign(
+        let sealed = |chain: &mut ATokenChain, from: &Wallet, unsigned| {
+            let tx = SignedTx::sign(unsigned, from).unwrap();
+            let block = chain.build_block(&issuer, vec![tx]).unwrap();
+            chain.append_block(block)
+        };
+        for (nonce, amount) in [(1, 3), (2, 5)] {
+            let mint =
+                UnsignedTx::mint("AToken-local", issuer.address(), nonce, amount, metadata());
+            sealed(&mut chain, &issuer, mint).unwrap();
+        }
+        assert_eq!(chain.assets().len(), 2);
+        assert_eq!(chain.next_asset_id(), 2);
+        assert_eq!(chain.total_supply(0), 3);
+        assert_eq!(chain.total_supply(1), 5);
+        assert_eq!(chain.metadata(1).unwrap().issuer, issuer.address());
+
+        // The same token id moves only in the asset the transfer names
+        let to_alice = |nonce, asset_id| {
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                nonce,
+                asset_id,
+                alice.address(),
+                vec![2],
+            )
+        };
+        sealed(&mut chain, &issuer, to_alice(3, 1)).unwrap();
+        assert_eq!(chain.owner_of(1, 2), Some(&alice.address()));
+        assert_eq!(chain.owner_of(0, 2), Some(&issuer.address()));
+        sealed(&mut chain, &issuer, to_alice(4, 0)).unwrap();
+        assert_eq!(chain.tokens_of(0, &alice.address()), &BTreeSet::from([2]));
+        assert_eq!(chain.tokens_of(1, &alice.address()), &BTreeSet::from([2]));
+
+        let burn = UnsignedTx::burn("AToken-local", alice.address(), 1, 1, vec![2]);
+        sealed(&mut chain, &alice, burn).unwrap();
+        assert_eq!(chain.total_supply(0), 3);
+        assert_eq!(chain.total_supply(1), 4);
+        assert_eq!(chain.balance_of(0, &alice.address()), 1);
+        assert_eq!(chain.balance_of(1, &alice.address()), 0);
+
+        let err = sealed(&mut chain, &issuer, to_alice(5, 2)).unwrap_err();
+        assert!(matches!(err, ATokenError::UnknownAsset(2)));
+        let mint = UnsignedTx::mint("AToken-local", alice.address(), 2, 1, metadata());
+        let err = sealed(&mut chain, &alice, mint).unwrap_err();
+        assert!(matches!(err, ATokenError::MintNotAllowed));
+        chain.verify_integrity().unwrap();
+    }
+
+    #[test]
+    fn mint_distribution_splits_contiguous_ranges() {
+        let issuer = Wallet::generate();
+        let (alice, bob) = (Wallet::generate(), Wallet::generate());
+        let mut chain = ATokenChain::new(ChainConfig::new("AToken-local", issuer.address()));
+        let mint = |nonce, distribution| {
+            let unsigned = UnsignedTx::mint_distributed(
+                "AToken-local",
+                issuer.address(),
+                nonce,
+                10,
+                metadata(),
+                distribution,
+            );
+            let tx = SignedTx::sign(unsigned, &issuer).unwrap();
+            chain.build_block(&issuer, vec![tx]).unwrap()
+        };
+
+        let uneven = mint(1, vec![(alice.address(), 4), (bob.address(), 5)]);
+        let err = chain.clone().append_block(uneven).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::DistributionMismatch {
+                amount: 10,
+                distributed: 9
+            }
+        ));
+
+        let split = mint(
+            1,
+            vec![
+                (alice.address(), 2),
+                (bob.address(), 5),
+                (issuer.address(), 3),
+            ],
+        );
+        chain.append_block(split).unwrap();
+        assert_eq!(
+            chain.tokens_of(0, &alice.address()),
+            &BTreeSet::from([0, 1])
+        );
+        assert_eq!(
+            chain.tokens_of(0, &bob.address()),
+            &BTreeSet::from([2, 3, 4, 5, 6])
+        );
+        assert_eq!(
+            chain.tokens_of(0, &issuer.address()),
+            &BTreeSet::from([7, 8, 9])
+        );
+        assert_eq!(chain.total_supply(0), 10);
+        assert_eq!(chain.owner_of(0, 6), Some(&bob.address()));
+    }
+
+    #[test]
+    fn only_the_issuer_updates_metadata() {
+        let (issuer, mut chain) = minted_chain(3);
+        let mallory = Wallet::generate();
+        let update = |chain: &mut ATokenChain, from: &Wallet, name: &str| {
+            let tx = SignedTx::sign(
+                UnsignedTx::update_metadata(
+                    "AToken-local",
+                    from.address(),
+                    chain.next_nonce(&from.address()),
+                    0,
+                    name.to_string(),
+                    "Corrected description".to_string(),
+                ),
+                from,
+            )
+            .unwrap();
+            let block = chain.build_block(&issuer, vec![tx]).unwrap();
+            chain.append_block(block)
+        };
+
+        let err = update(&mut chain, &mallory, "Mallory").unwrap_err();
+        assert!(matches!(err, ATokenError::MetadataUpdateNotAllowed));
+        let err = update(&mut chain, &issuer, "").unwrap_err();
+        assert!(matches!(err, ATokenError::InvalidMetadata(_)));
+        assert_eq!(chain.metadata(0).unwrap().name, "AToken");
+
+        update(&mut chain, &issuer, "AToken v2").unwrap();
+        let metadata = chain.metadata(0).unwrap();
+        assert_eq!(metadata.name, "AToken v2");
+        assert_eq!(metadata.description, "Corrected description");
+        assert_eq!(metadata.symbol, "ATKN");
+        assert_eq!(metadata.issuer, issuer.address());
+        chain.verify_integrity().unwrap();
+    }
+
+    #[test]
+    fn oversized_transfers_and_mints_are_rejected_up_front() {
+        let (issuer, mut chain) = minted_chain(10);
+        chain.config.max_transfer_ids = 4;
+        chain.config.max_mint_amount = 100;
+        let alice = Wallet::generate();
+        let before = chain.clone();
+
+        let transfer = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                2,
+                0,
+                alice.address(),
+                (0..5).collect(),
+            ),
+            &issuer,
+        )
+        .unwrap();
+        let block = chain.build_block(&issuer, vec![transfer]).unwrap();
+        let err = chain.append_block(block).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::TooManyTokenIds { max: 4, actual: 5 }
+        ));
+
+        // Counted before expanding, even for a range far past the supply
+        let err =
+            transfer_ranges(&mut chain, &issuer, &alice, vec![(0, 1), (2, u64::MAX)]).unwrap_err();
+        assert!(matches!(err, ATokenError::TooManyTokenIds { max: 4, .. }));
+
+        let mint = SignedTx::sign(
+            UnsignedTx::mint("AToken-local", issuer.address(), 2, 101, metadata()),
+            &issuer,
+        )
+        .unwrap();
+        let block = chain.build_block(&issuer, vec![mint]).unwrap();
+        let err = chain.append_block(block).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::MintAmountTooLarge {
+                max: 100,
+                actual: 101
+            }
+        ));
+
+        assert_eq!(chain.blocks.len(), before.blocks.len());
+        assert_eq!(chain.assets, before.assets);
+        assert_eq!(chain.next_nonce(&issuer.address()), 2);
+
+        transfer_ranges(&mut chain, &issuer, &alice, vec![(0, 3)]).unwrap();
+        assert_eq!(chain.balance_of(0, &alice.address()), 4);
+    }
+
+    #[test]
+    fn tx_signed_for_another_chain_is_rejected() {
+        let issuer = Wallet::generate();
+        let config = ChainConfig::new("AToken-local", issuer.address());
+        let mut chain = ATokenChain::new(config);
+
+        let mint = SignedTx::sign(
             UnsignedTx::mint(
+                "AToken-other",
                 issuer.address(),
                 chain.next_nonce(&issuer.address()),
                 3,
@@ -347,21 +1628,1389 @@ mod tests {
             &issuer,
         )
         .unwrap();
-        let b0 = chain.build_block(&issuer, vec![mint1]).unwrap();
+        let b0 = chain.build_block(&issuer, vec![mint]).unwrap();
+        let err = chain.append_block(b0).unwrap_err();
+        assert!(matches!(err, ATokenError::InvalidChainId { .. }));
+    }
+
+    #[test]
+    fn chain_id_and_version_are_signed() {
+        let issuer = Wallet::generate();
+        let mint = SignedTx::sign(
+            UnsignedTx::mint("AToken-local", issuer.address(), 1, 3, metadata()),
+            &issuer,
+        )
+        .unwrap();
+        mint.verify().unwrap();
+
+        let mut replayed = mint.clone();
+        replayed.unsigned.chain_id = "AToken-other".to_string();
+        assert!(matches!(
+            replayed.verify().unwrap_err(),
+            ATokenError::InvalidSignature
+        ));
+
+        let mut future = mint;
+        future.unsigned.version = TX_VERSION + 1;
+        assert!(matches!(
+            future.verify().unwrap_err(),
+            ATokenError::UnsupportedTxVersion(v) if v == TX_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn owner_index_matches_a_recount() {
+        let issuer = Wallet::generate();
+        let alice = Wallet::generate();
+        let bob = Wallet::generate();
+        let mut config = ChainConfig::new("AToken-local", issuer.address());
+        config.max_transfer_ids = 3_000;
+        let mut chain = ATokenChain::new(config);
+
+        let send = |chain: &mut ATokenChain, from: &Wallet, to: &Wallet, token_ids: Vec<u64>| {
+            let tx = SignedTx::sign(
+                UnsignedTx::transfer(
+                    "AToken-local",
+                    from.address(),
+                    chain.next_nonce(&from.address()),
+                    0,
+                    to.address(),
+                    token_ids,
+                ),
+                from,
+            )
+            .unwrap();
+            let block = chain.build_block(&issuer, vec![tx]).unwrap();
+            chain.append_block(block).unwrap();
+        };
+
+        let mint = SignedTx::sign(
+            UnsignedTx::mint("AToken-local", issuer.address(), 1, 10_000, metadata()),
+            &issuer,
+        )
+        .unwrap();
+        let b0 = chain.build_block(&issuer, vec![mint]).unwrap();
         chain.append_block(b0).unwrap();
 
-        let mint2 = SignedTx::sign(
-            UnsignedTx::mint(
+        send(&mut chain, &issuer, &alice, (0..3_000).collect());
+        send(&mut chain, &alice, &bob, (0..3_000).step_by(3).collect());
+        send(&mut chain, &bob, &issuer, (0..3_000).step_by(6).collect());
+        // Everything alice has left, so she drops out of the index
+        let rest: Vec<u64> = chain
+            .tokens_of(0, &alice.address())
+            .iter()
+            .copied()
+            .collect();
+        send(&mut chain, &alice, &bob, rest);
+
+        let mut recount: HashMap<Address, BTreeSet<u64>> = HashMap::new();
+        for token_id in 0..chain.total_supply(0) {
+            let owner = chain.owner_of(0, token_id).unwrap().clone();
+            recount.entry(owner).or_default().insert(token_id);
+        }
+        assert_eq!(chain.assets[0].tokens_by_owner, recount);
+        for wallet in [&issuer, &alice, &bob] {
+            let address = wallet.address();
+            let expected = recount.get(&address).map_or(0, BTreeSet::len) as u64;
+            assert_eq!(chain.balance_of(0, &address), expected);
+        }
+        assert_eq!(chain.balance_of(0, &alice.address()), 0);
+        assert_eq!(chain.balance_of(0, &bob.address()), 2_500);
+        assert_eq!(chain.balance_of(0, &issuer.address()), 7_500);
+    }
+
+    fn transfer_ranges(
+        chain: &mut ATokenChain,
+        from: &Wallet,
+        to: &Wallet,
+        token_ranges: Vec<(u64, u64)>,
+    ) -> Result<Vec<BalanceChange>> {
+        let tx = SignedTx::sign(
+            UnsignedTx::transfer_ranges(
+                "AToken-local",
+                from.address(),
+                chain.next_nonce(&from.address()),
+                0,
+                to.address(),
+                token_ranges,
+            ),
+            from,
+        )
+        .unwrap();
+        let block = chain.build_block(from, vec![tx]).unwrap();
+        chain.append_block(block)
+    }
+
+    #[test]
+    fn appended_blocks_report_net_balance_changes() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let bob = Wallet::generate();
+        let to_alice = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                2,
+                0,
+                alice.address(),
+                vec![0, 1, 2],
+            ),
+            &issuer,
+        )
+        .unwrap();
+        let to_bob = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                alice.address(),
+                1,
+                0,
+                bob.address(),
+                vec![2],
+            ),
+            &alice,
+        )
+        .unwrap();
+        let block = chain.build_block(&issuer, vec![to_alice, to_bob]).unwrap();
+        let changes = chain.append_block(block).unwrap();
+
+        let change_of = |wallet: &Wallet| {
+            changes
+                .iter()
+                .find(|change| change.address == wallet.address())
+                .unwrap()
+        };
+        assert_eq!(changes.len(), 3);
+        assert_eq!(change_of(&issuer).lost, vec![0, 1, 2]);
+        assert_eq!(change_of(&issuer).balance, 7);
+        // Token 2 only passed through alice
+        assert_eq!(change_of(&alice).gained, vec![0, 1]);
+        assert!(change_of(&alice).lost.is_empty());
+        assert_eq!(change_of(&alice).balance, 2);
+        assert_eq!(change_of(&bob).gained, vec![2]);
+        assert_eq!(change_of(&bob).balance, 1);
+    }
+
+    #[test]
+    fn simulated_changes_match_the_committed_ones() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+
+        // Nonces are filled in below, one block per tx
+        let mut unsigned_txs = vec![
+            UnsignedTx::mint_distributed(
+                "AToken-local",
+                issuer.address(),
+                0,
+                3,
+                metadata(),
+                vec![(alice.address(), 2), (issuer.address(), 1)],
+            ),
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                0,
+                0,
+                alice.address(),
+                vec![0, 1],
+            ),
+            UnsignedTx::transfer_ranges(
+                "AToken-local",
+                issuer.address(),
+                0,
+                0,
+                issuer.address(),
+                vec![(2, 3)],
+            ),
+            UnsignedTx::burn("AToken-local", issuer.address(), 0, 0, vec![4]),
+        ];
+        let first_nonce = chain.next_nonce(&issuer.address());
+        for (offset, unsigned) in unsigned_txs.iter_mut().enumerate() {
+            unsigned.nonce = first_nonce + offset as u64;
+        }
+
+        for unsigned in unsigned_txs {
+            let tx = SignedTx::sign(unsigned, &issuer).unwrap();
+            let simulated = chain.simulate_tx(&tx).unwrap();
+            let block = chain.build_block(&issuer, vec![tx]).unwrap();
+            assert_eq!(simulated, chain.append_block(block).unwrap());
+        }
+    }
+
+    #[test]
+    fn balance_at_height_follows_every_kind_of_tx() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let txs = [
+            (
+                &issuer,
+                UnsignedTx::transfer_ranges(
+                    "AToken-local",
+                    issuer.address(),
+                    2,
+                    0,
+                    alice.address(),
+                    vec![(0, 3)],
+                ),
+            ),
+            (
+                &alice,
+                UnsignedTx::burn("AToken-local", alice.address(), 1, 0, vec![1]),
+            ),
+            (
+                &alice,
+                UnsignedTx::transfer(
+                    "AToken-local",
+                    alice.address(),
+                    2,
+                    0,
+                    issuer.address(),
+                    vec![2],
+                ),
+            ),
+        ];
+        for (signer, tx) in txs {
+            let tx = SignedTx::sign(tx, signer).unwrap();
+            let block = chain.build_block(&issuer, vec![tx]).unwrap();
+            chain.append_block(block).unwrap();
+        }
+
+        let history = |wallet: &Wallet| {
+            (0..chain.blocks.len() as u64)
+                .map(|height| chain.balance_at(0, &wallet.address(), height).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(history(&issuer), vec![10, 6, 6, 7]);
+        assert_eq!(history(&alice), vec![0, 4, 3, 2]);
+        assert_eq!(chain.balance_at(1, &issuer.address(), 3).unwrap(), 0);
+        assert!(matches!(
+            chain.balance_at(0, &alice.address(), 4),
+            Err(ATokenError::UnknownBlockHeight(4))
+        ));
+    }
+
+    #[test]
+    fn range_transfer_moves_boundary_ranges() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+
+        // First and last token, single-id range and a range ending on the last id
+        transfer_ranges(&mut chain, &issuer, &alice, vec![(5, 9), (0, 0)]).unwrap();
+        assert_eq!(
+            chain.tokens_of(0, &alice.address()),
+            &BTreeSet::from([0, 5, 6, 7, 8, 9])
+        );
+        assert_eq!(
+            chain.tokens_of(0, &issuer.address()),
+            &BTreeSet::from([1, 2, 3, 4])
+        );
+
+        let err = transfer_ranges(&mut chain, &issuer, &alice, vec![(1, 10)]).unwrap_err();
+        assert!(matches!(err, ATokenError::UnknownToken(10)));
+        let err = transfer_ranges(&mut chain, &issuer, &alice, vec![(4, 5)]).unwrap_err();
+        assert!(matches!(err, ATokenError::NotTokenOwner { token_id: 5 }));
+        assert_eq!(chain.blocks.len(), 2);
+    }
+
+    #[test]
+    fn recipients_must_be_checksummed() {
+        let (issuer, chain) = minted_chain(10);
+        let alice = Wallet::generate().address();
+        let lowercase = alice.to_ascii_lowercase();
+        let nonce = chain.next_nonce(&issuer.address());
+
+        let to_lowercase = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                nonce,
+                0,
+                lowercase.clone(),
+                vec![0],
+            ),
+            &issuer,
+        )
+        .unwrap();
+        assert!(matches!(
+            chain.validate_tx(&to_lowercase, nonce),
+            Err(ATokenError::NonCanonicalAddress(address)) if address == lowercase
+        ));
+
+        let to_malformed = SignedTx::sign(
+            UnsignedTx::transfer_ranges(
+                "AToken-local",
+                issuer.address(),
+                nonce,
+                0,
+                "not an address".to_string(),
+                vec![(0, 1)],
+            ),
+            &issuer,
+        )
+        .unwrap();
+        assert!(matches!(
+            chain.validate_tx(&to_malformed, nonce),
+            Err(ATokenError::InvalidAddress(_))
+        ));
+
+        let distributed = SignedTx::sign(
+            UnsignedTx::mint_distributed(
+                "AToken-local",
+                issuer.address(),
+                nonce,
+                2,
+                metadata(),
+                vec![(alice, 1), (lowercase, 1)],
+            ),
+            &issuer,
+        )
+        .unwrap();
+        assert!(matches!(
+            chain.validate_tx(&distributed, nonce),
+            Err(ATokenError::NonCanonicalAddress(_))
+        ));
+    }
+
+    #[test]
+    fn overlapping_ranges_are_rejected() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+
+        let err = transfer_ranges(&mut chain, &issuer, &alice, vec![(0, 4), (4, 6)]).unwrap_err();
+        assert!(matches!(err, ATokenError::DuplicateTokenId(4)));
+        let err = transfer_ranges(&mut chain, &issuer, &alice, vec![(6, 8), (0, 9)]).unwrap_err();
+        assert!(matches!(err, ATokenError::DuplicateTokenId(6)));
+        let err = transfer_ranges(&mut chain, &issuer, &alice, vec![(3, 2)]).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::InvalidTokenRange { start: 3, end: 2 }
+        ));
+
+        assert_eq!(chain.balance_of(0, &issuer.address()), 10);
+        assert_eq!(chain.balance_of(0, &alice.address()), 0);
+    }
+
+    fn burn(
+        chain: &mut ATokenChain,
+        from: &Wallet,
+        token_ids: Vec<u64>,
+    ) -> Result<Vec<BalanceChange>> {
+        let tx = SignedTx::sign(
+            UnsignedTx::burn(
+                "AToken-local",
+                from.address(),
+                chain.next_nonce(&from.address()),
+                0,
+                token_ids,
+            ),
+            from,
+        )
+        .unwrap();
+        let block = chain.build_block(from, vec![tx]).unwrap();
+        chain.append_block(block)
+    }
+
+    #[test]
+    fn burn_removes_tokens_from_supply() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        transfer_ranges(&mut chain, &issuer, &alice, vec![(8, 9)]).unwrap();
+
+        burn(&mut chain, &issuer, vec![0, 3]).unwrap();
+        assert_eq!(chain.total_supply(0), 8);
+        assert_eq!(chain.balance_of(0, &issuer.address()), 6);
+        assert_eq!(chain.owner_of(0, 0), None);
+        assert_eq!(chain.owner_of(0, 3), None);
+
+        let err = burn(&mut chain, &issuer, vec![3]).unwrap_err();
+        assert!(matches!(err, ATokenError::UnknownToken(3)));
+        let err = burn(&mut chain, &issuer, vec![1, 8]).unwrap_err();
+        assert!(matches!(err, ATokenError::NotTokenOwner { token_id: 8 }));
+        let err = burn(&mut chain, &issuer, vec![1, 1]).unwrap_err();
+        assert!(matches!(err, ATokenError::DuplicateTokenId(1)));
+        let err = burn(&mut chain, &issuer, Vec::new()).unwrap_err();
+        assert!(matches!(err, ATokenError::EmptyBurn));
+
+        // Burned ids can't be transferred either
+        let err = transfer_ranges(&mut chain, &issuer, &alice, vec![(0, 1)]).unwrap_err();
+        assert!(matches!(err, ATokenError::UnknownToken(0)));
+        assert_eq!(chain.total_supply(0), 8);
+        assert_eq!(chain.balance_of(0, &alice.address()), 2);
+    }
+
+    #[test]
+    fn applied_tx_cannot_be_replayed() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let tx = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                2,
+                0,
+                alice.address(),
+                vec![0],
+            ),
+            &issuer,
+        )
+        .unwrap();
+        let b1 = chain.build_block(&issuer, vec![tx.clone()]).unwrap();
+        chain.append_block(b1).unwrap();
+
+        let replay = chain.build_block(&issuer, vec![tx.clone()]).unwrap();
+        let err = chain.append_block(replay).unwrap_err();
+        assert!(matches!(err, ATokenError::DuplicateTransaction(id) if id == tx.id));
+        assert_eq!(chain.blocks.len(), 2);
+    }
+
+    #[test]
+    fn duplicate_tx_within_a_block_is_rejected() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let tx = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                2,
+                0,
+                alice.address(),
+                vec![0],
+            ),
+            &issuer,
+        )
+        .unwrap();
+
+        let block = chain
+            .build_block(&issuer, vec![tx.clone(), tx.clone()])
+            .unwrap();
+        let err = chain.append_block(block).unwrap_err();
+        assert!(matches!(err, ATokenError::DuplicateTransaction(id) if id == tx.id));
+        // Rejected before anything was applied
+        assert_eq!(chain.balance_of(0, &alice.address()), 0);
+        assert_eq!(chain.next_nonce(&issuer.address()), 2);
+    }
+
+    #[test]
+    fn one_bad_signature_fails_a_large_block() {
+        let (issuer, mut chain) = minted_chain(100);
+        let alice = Wallet::generate();
+        let mut txs: Vec<SignedTx> = (0..50)
+            .map(|i| {
+                SignedTx::sign(
+                    UnsignedTx::transfer(
+                        "AToken-local",
+                        issuer.address(),
+                        i + 2,
+                        0,
+                        alice.address(),
+                        vec![i],
+                    ),
+                    &issuer,
+                )
+                .unwrap()
+            })
+            .collect();
+        txs[37].signature_hex = alice.sign_hex(&txs[37].unsigned.signing_bytes());
+
+        let block = chain.build_block(&issuer, txs.clone()).unwrap();
+        let err = chain.append_block(block).unwrap_err();
+        assert!(matches!(err, ATokenError::InvalidSignature));
+        // Caught before the txs ahead of it were applied
+        assert_eq!(chain.balance_of(0, &alice.address()), 0);
+        assert_eq!(chain.next_nonce(&issuer.address()), 2);
+
+        txs.truncate(37);
+        let block = chain.build_block(&issuer, txs).unwrap();
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.balance_of(0, &alice.address()), 37);
+    }
+
+    #[test]
+    fn failing_tx_rolls_back_the_rest_of_its_block() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let assets = chain.assets.clone();
+        let nonces = chain.last_nonce_by_address.clone();
+        let sign = |unsigned| SignedTx::sign(unsigned, &issuer).unwrap();
+        let mut txs = vec![
+            sign(
+                UnsignedTx::transfer(
+                    "AToken-local",
+                    issuer.address(),
+                    2,
+                    0,
+                    alice.address(),
+                    vec![0, 1],
+                )
+                .with_lock_until(Some(u64::MAX)),
+            ),
+            sign(UnsignedTx::transfer_ranges(
+                "AToken-local",
                 issuer.address(),
-                chain.next_nonce(&issuer.address()),
                 3,
+                0,
+                alice.address(),
+                vec![(2, 4)],
+            )),
+            sign(UnsignedTx::burn(
+                "AToken-local",
+                issuer.address(),
+                4,
+                0,
+                vec![5, 6],
+            )),
+            sign(UnsignedTx::update_metadata(
+                "AToken-local",
+                issuer.address(),
+                5,
+                0,
+                "Renamed".to_string(),
+                "Renamed token".to_string(),
+            )),
+            sign(UnsignedTx::mint(
+                "AToken-local",
+                issuer.address(),
+                6,
+                5,
                 metadata(),
+            )),
+            sign(UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                9,
+                0,
+                alice.address(),
+                vec![7],
+            )),
+        ];
+
+        let block = chain.build_block(&issuer, txs.clone()).unwrap();
+        let err = chain.append_block(block).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::NonceMismatch {
+                expected: 7,
+                actual: 9
+            }
+        ));
+        assert_eq!(chain.blocks.len(), 1);
+        assert_eq!(chain.assets, assets);
+        assert_eq!(chain.last_nonce_by_address, nonces);
+        assert_eq!(chain.balance_of(0, &alice.address()), 0);
+        assert_eq!(chain.next_nonce(&issuer.address()), 2);
+        chain.verify_integrity().unwrap();
+
+        // The valid txs still go through on their own
+        txs.pop();
+        let block = chain.build_block(&issuer, txs).unwrap();
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.balance_of(0, &alice.address()), 5);
+        assert_eq!(chain.total_supply(0), 8);
+        assert_eq!(chain.total_supply(1), 5);
+        chain.verify_integrity().unwrap();
+    }
+
+    /// A block for `txs` re-signed with another timestamp
+    fn block_at_time(
+        chain: &ATokenChain,
+        proposer: &Wallet,
+        txs: Vec<SignedTx>,
+        timestamp_ms: u64,
+    ) -> Block {
+        let mut block = chain.build_block(proposer, txs).unwrap();
+        block.header.timestamp_ms = timestamp_ms;
+        let message = Block::previous_signature_message(&block.header);
+        block.previous_signature_hex = proposer.sign_hex(&message);
+        block.hash =
+            Block::calculate_hash(&block.header, &block.txs, &block.previous_signature_hex);
+        block
+    }
+
+    #[test]
+    fn block_timestamps_must_not_go_back_or_run_ahead() {
+        let (issuer, chain) = minted_chain(10);
+        let clock = MockClock::new(chain.blocks[0].header.timestamp_ms);
+        let mut chain = chain.with_clock(clock.clone());
+        let alice = Wallet::generate();
+        let parent_time = chain.blocks[0].header.timestamp_ms;
+        let tx = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                2,
+                0,
+                alice.address(),
+                vec![0],
             ),
             &issuer,
         )
         .unwrap();
-        let b1 = chain.build_block(&issuer, vec![mint2]).unwrap();
-        let err = chain.append_block(b1).unwrap_err();
-        assert!(matches!(err, ATokenError::AlreadyIssued));
+
+        let backward = block_at_time(&chain, &issuer, vec![tx.clone()], parent_time - 1);
+        let err = chain.append_block(backward).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::BlockTimestampBeforeParent { parent, .. } if parent == parent_time
+        ));
+
+        let far_future = parent_time + 10 * 60_000;
+        let ahead = block_at_time(&chain, &issuer, vec![tx.clone()], far_future);
+        let err = chain.append_block(ahead.clone()).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::BlockTimestampInFuture { latest_allowed, .. }
+                if latest_allowed == parent_time + 60_000
+        ));
+
+        // The same block is fine once the clock catches up to within the allowed skew
+        clock.advance(10 * 60_000 - 30_000);
+        chain.append_block(ahead).unwrap();
+        assert_eq!(chain.balance_of(0, &alice.address()), 1);
+    }
+
+    #[test]
+    fn relayed_block_with_another_timestamp_fails_its_signature() {
+        let (issuer, chain) = minted_chain(10);
+        let parent_time = chain.blocks[0].header.timestamp_ms;
+        let mut chain = chain.with_clock(MockClock::new(parent_time + 5_000));
+        let alice = Wallet::generate();
+        let tx = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                2,
+                0,
+                alice.address(),
+                vec![0],
+            ),
+            &issuer,
+        )
+        .unwrap();
+
+        // Moved within the allowed window and rehashed, but not re-signed
+        let mut block = chain.build_block(&issuer, vec![tx]).unwrap();
+        block.header.timestamp_ms += 1_000;
+        block.hash =
+            Block::calculate_hash(&block.header, &block.txs, &block.previous_signature_hex);
+        assert!(chain.validate_block_header(&block).is_ok());
+        assert!(matches!(
+            chain.validate_block_signature(&block),
+            Err(ATokenError::InvalidSignature)
+        ));
+        assert!(matches!(
+            chain.append_block(block),
+            Err(ATokenError::InvalidSignature)
+        ));
+        assert_eq!(chain.blocks.len(), 1);
+    }
+
+    #[test]
+    fn new_blocks_take_their_timestamp_from_the_clock() {
+        let (issuer, chain) = minted_chain(10);
+        let parent_time = chain.blocks[0].header.timestamp_ms;
+        let clock = MockClock::new(parent_time + 5_000);
+        let mut chain = chain.with_clock(clock.clone());
+        let alice = Wallet::generate();
+        let transfer = |nonce, token_id| {
+            SignedTx::sign(
+                UnsignedTx::transfer(
+                    "AToken-local",
+                    issuer.address(),
+                    nonce,
+                    0,
+                    alice.address(),
+                    vec![token_id],
+                ),
+                &issuer,
+            )
+            .unwrap()
+        };
+
+        let block = chain.build_block(&issuer, vec![transfer(2, 0)]).unwrap();
+        assert_eq!(block.header.timestamp_ms, parent_time + 5_000);
+        chain.append_block(block).unwrap();
+
+        // A clock that steps back still never yields a block older than its parent
+        clock.set(parent_time);
+        let block = chain.build_block(&issuer, vec![transfer(3, 1)]).unwrap();
+        assert_eq!(block.header.timestamp_ms, parent_time + 5_000);
+        chain.append_block(block).unwrap();
+    }
+
+    #[test]
+    fn locked_tokens_move_only_after_the_lock_expires() {
+        let (issuer, chain) = minted_chain(10);
+        let clock = MockClock::new(chain.blocks[0].header.timestamp_ms);
+        let mut chain = chain.with_clock(clock.clone());
+        let alice = Wallet::generate();
+        let bob = Wallet::generate();
+        let unlock_at = clock.now_ms() + 3_600_000;
+
+        let locked = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                2,
+                0,
+                alice.address(),
+                vec![0, 1],
+            )
+            .with_lock_until(Some(unlock_at)),
+            &issuer,
+        )
+        .unwrap();
+        let block = chain.build_block(&issuer, vec![locked]).unwrap();
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.asset(0).unwrap().locked_until(0), Some(unlock_at));
+        // Tokens the issuer still holds were never locked
+        assert_eq!(chain.asset(0).unwrap().locked_until(2), None);
+
+        let early = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                alice.address(),
+                1,
+                0,
+                bob.address(),
+                vec![0],
+            ),
+            &alice,
+        )
+        .unwrap();
+        let err = chain.validate_tx(&early, 1).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::TokenLocked { token_id: 0, locked_until_ms } if locked_until_ms == unlock_at
+        ));
+        let block = chain.build_block(&issuer, vec![early.clone()]).unwrap();
+        assert!(chain.append_block(block).is_err());
+        assert_eq!(chain.owner_of(0, 0), Some(&alice.address()));
+
+        clock.set(unlock_at);
+        let block = chain.build_block(&issuer, vec![early]).unwrap();
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.owner_of(0, 0), Some(&bob.address()));
+        assert_eq!(chain.asset(0).unwrap().locked_until(0), None);
+
+        // Replay checks locks against block timestamps, not the clock
+        clock.set(unlock_at - 1);
+        chain.verify_integrity().unwrap();
+    }
+
+    #[test]
+    fn blocks_of_another_version_are_refused() {
+        let (issuer, mut chain) = minted_chain(1);
+        let mut block = chain.build_block(&issuer, Vec::new()).unwrap();
+        assert_eq!(block.header.version, BLOCK_VERSION);
+
+        // Re-signed, so only the version is wrong
+        block.header.version = BLOCK_VERSION - 1;
+        block.previous_signature_hex =
+            issuer.sign_hex(&Block::previous_signature_message(&block.header));
+        block.hash =
+            Block::calculate_hash(&block.header, &block.txs, &block.previous_signature_hex);
+        assert!(matches!(
+            chain.append_block(block).unwrap_err(),
+            ATokenError::UnsupportedBlockVersion(v) if v == BLOCK_VERSION - 1
+        ));
+    }
+
+    #[test]
+    fn transactions_expire_after_valid_until() {
+        let (issuer, chain) = minted_chain(10);
+        let start = chain.blocks[0].header.timestamp_ms;
+        let clock = MockClock::new(start);
+        let mut chain = chain.with_clock(clock.clone());
+        let alice = Wallet::generate();
+        let transfer = |nonce, token_id, valid_until_ms| {
+            SignedTx::sign(
+                UnsignedTx::transfer(
+                    "AToken-local",
+                    issuer.address(),
+                    nonce,
+                    0,
+                    alice.address(),
+                    vec![token_id],
+                )
+                .with_valid_until(Some(valid_until_ms)),
+                &issuer,
+            )
+            .unwrap()
+        };
+
+        // Valid up to and including its deadline
+        let deadline = start + 1_000;
+        clock.set(deadline);
+        let block = chain
+            .build_block(&issuer, vec![transfer(2, 0, deadline)])
+            .unwrap();
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.balance_of(0, &alice.address()), 1);
+
+        let late = transfer(3, 1, deadline);
+        clock.advance(1);
+        let err = chain.validate_tx(&late, 3).unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::Expired { valid_until_ms, block_time_ms }
+                if valid_until_ms == deadline && block_time_ms == deadline + 1
+        ));
+        let block = chain.build_block(&issuer, vec![late.clone()]).unwrap();
+        assert!(matches!(
+            chain.append_block(block).unwrap_err(),
+            ATokenError::Expired { .. }
+        ));
+
+        // The deadline is signed, so it cannot be pushed back
+        let mut extended = late;
+        extended.unsigned.valid_until_ms = Some(deadline + 60_000);
+        assert!(chain.validate_tx(&extended, 3).is_err());
+        assert_eq!(chain.balance_of(0, &alice.address()), 1);
+    }
+
+    #[test]
+    fn blake3_chain_hashes_blocks_and_addresses_with_blake3() {
+        let issuer = Wallet::from_private_key_hex(&"11".repeat(32)).unwrap();
+        let alice = Wallet::generate();
+        let block_zero = |hash_algorithm| {
+            let from = issuer.address_for(hash_algorithm);
+            let config =
+                ChainConfig::new("AToken-local", from.clone()).with_hash_algorithm(hash_algorithm);
+            let chain = ATokenChain::new(config).with_clock(MockClock::new(1_000));
+            let mut mint = UnsignedTx::mint("AToken-local", from, 1, 10, metadata());
+            mint.timestamp_ms = 1_000;
+            let mint = SignedTx::sign(mint, &issuer).unwrap();
+            (chain.build_block(&issuer, vec![mint]).unwrap(), chain)
+        };
+
+        // Same inputs, same hash
+        let (block, mut chain) = block_zero(HashAlgorithm::Blake3);
+        let (again, _) = block_zero(HashAlgorithm::Blake3);
+        let (sha256, _) = block_zero(HashAlgorithm::Sha256);
+        assert_eq!(block.hash, again.hash);
+        assert_ne!(block.hash, sha256.hash);
+        assert_eq!(
+            block.header.proposer,
+            issuer.address_for(HashAlgorithm::Blake3)
+        );
+        assert_ne!(block.header.proposer, issuer.address());
+        chain.append_block(block).unwrap();
+
+        // Senders and recipients are BLAKE3 addresses, and the SHA-256 one is not the sender
+        let to = alice.address_for(HashAlgorithm::Blake3);
+        let transfer = |from: Address| {
+            SignedTx::sign(
+                UnsignedTx::transfer("AToken-local", from, 2, 0, to.clone(), vec![0, 1]),
+                &issuer,
+            )
+            .unwrap()
+        };
+        assert!(matches!(
+            chain.validate_tx(&transfer(issuer.address()), 2),
+            Err(ATokenError::InvalidSender)
+        ));
+        let block = chain
+            .build_block(
+                &issuer,
+                vec![transfer(issuer.address_for(HashAlgorithm::Blake3))],
+            )
+            .unwrap();
+
+        // The algorithm is fixed at genesis, so a block claiming another one is refused
+        let mut switched = block.clone();
+        switched.header.hash_algorithm = HashAlgorithm::Sha256;
+        switched.hash = Block::calculate_hash(
+            &switched.header,
+            &switched.txs,
+            &switched.previous_signature_hex,
+        );
+        assert!(matches!(
+            chain.append_block(switched).unwrap_err(),
+            ATokenError::HashAlgorithmMismatch {
+                expected: HashAlgorithm::Blake3,
+                actual: HashAlgorithm::Sha256,
+            }
+        ));
+
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.balance_of(0, &to), 2);
+        assert_eq!(
+            ChainConfig::from_genesis("AToken-local", &chain.blocks[0])
+                .unwrap()
+                .hash_algorithm,
+            HashAlgorithm::Blake3
+        );
+        chain.verify_integrity().unwrap();
+    }
+
+    #[test]
+    fn only_authorized_proposers_extend_the_chain() {
+        let issuer = Wallet::generate();
+        let node = Wallet::generate();
+        let stranger = Wallet::generate();
+        let config =
+            ChainConfig::new("AToken-local", issuer.address()).with_proposer(node.address());
+        assert!(config.is_authorized_proposer(&issuer.address()));
+        let mut chain = ATokenChain::new(config);
+
+        // Anyone may seal the genesis block
+        let mint = SignedTx::sign(
+            UnsignedTx::mint("AToken-local", issuer.address(), 1, 10, metadata()),
+            &issuer,
+        )
+        .unwrap();
+        let b0 = chain.build_block(&stranger, vec![mint]).unwrap();
+        chain.append_block(b0).unwrap();
+
+        let tx = SignedTx::sign(
+            UnsignedTx::transfer(
+                "AToken-local",
+                issuer.address(),
+                2,
+                0,
+                stranger.address(),
+                vec![0],
+            ),
+            &issuer,
+        )
+        .unwrap();
+        let forged = chain.build_block(&stranger, vec![tx.clone()]).unwrap();
+        let err = chain.append_block(forged).unwrap_err();
+        assert!(
+            matches!(err, ATokenError::UnauthorizedProposer(address) if address == stranger.address())
+        );
+
+        let block = chain.build_block(&node, vec![tx]).unwrap();
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.balance_of(0, &stranger.address()), 1);
+    }
+
+    #[test]
+    fn integrity_check_reports_the_tampered_height() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        for range in [(0, 1), (2, 3), (4, 5)] {
+            transfer_ranges(&mut chain, &issuer, &alice, vec![range]).unwrap();
+        }
+        chain.verify_integrity().unwrap();
+
+        let mut tampered = chain.clone();
+        tampered.blocks[2].hash = "00".repeat(32);
+        let err = tampered.verify_integrity().unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::IntegrityFailure { height: 2, .. }
+        ));
+
+        // Blocks intact but the ledger drifted
+        let mut drifted = chain.clone();
+        drifted.assets[0].total_supply += 1;
+        let err = drifted.verify_integrity().unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::IntegrityFailure { height: 3, .. }
+        ));
+
+        // The copy taken for `/verify` checks the same
+        let check = drifted.integrity_check();
+        assert_eq!(check.blocks(), 4);
+        let err = check.run().unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::IntegrityFailure { height: 3, .. }
+        ));
+        chain.integrity_check().run().unwrap();
+    }
+
+    #[test]
+    fn tx_merkle_root_commits_to_tx_ids() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let txs: Vec<SignedTx> = [(2, 0), (3, 1)]
+            .into_iter()
+            .map(|(nonce, token_id)| {
+                SignedTx::sign(
+                    UnsignedTx::transfer(
+                        "AToken-local",
+                        issuer.address(),
+                        nonce,
+                        0,
+                        alice.address(),
+                        vec![token_id],
+                    ),
+                    &issuer,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        // Leaves and the node above them each hash under their own domain
+        let leaf = |tx: &SignedTx| {
+            let id = hex::decode(&tx.id).unwrap();
+            hex::decode(sha256_hex(&canonical::merkle_leaf_bytes(&id))).unwrap()
+        };
+        let expected = sha256_hex(&canonical::merkle_node_bytes(
+            &leaf(&txs[0]),
+            &leaf(&txs[1]),
+        ));
+        let block = chain.build_block(&issuer, txs.clone()).unwrap();
+        assert_eq!(block.header.tx_merkle_root, expected);
+
+        let mut flipped = txs.clone();
+        let first = if flipped[1].id.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        flipped[1].id.replace_range(..1, first);
+        assert_ne!(Block::tx_merkle_root(&flipped).unwrap(), expected);
+
+        // A header root that does not match the txs is rejected
+        let mut forged = block.clone();
+        forged.header.tx_merkle_root = Block::tx_merkle_root(&txs[..1]).unwrap();
+        let message = Block::previous_signature_message(&forged.header);
+        forged.previous_signature_hex = issuer.sign_hex(&message);
+        forged.hash =
+            Block::calculate_hash(&forged.header, &forged.txs, &forged.previous_signature_hex);
+        let err = chain.append_block(forged).unwrap_err();
+        assert!(matches!(err, ATokenError::TxMerkleRootMismatch));
+
+        chain.append_block(block).unwrap();
+    }
+
+    mod invariants {
+        use proptest::prelude::*;
+        use proptest::sample::Index;
+
+        use super::*;
+
+        const HOLDERS: usize = 4;
+
+        /// One step of a random history. The first four are valid whenever the sender
+        /// holds something, the rest must always be rejected.
+        #[derive(Debug, Clone)]
+        enum Op {
+            /// The issuer mints a new asset
+            Mint {
+                amount: u64,
+            },
+            Transfer {
+                asset: Index,
+                from: Index,
+                to: Index,
+                tokens: Vec<Index>,
+            },
+            TransferRange {
+                asset: Index,
+                from: Index,
+                to: Index,
+                start: Index,
+                len: u64,
+            },
+            Burn {
+                asset: Index,
+                from: Index,
+                tokens: Vec<Index>,
+            },
+            MintByHolder {
+                from: Index,
+            },
+            TransferUnowned {
+                asset: Index,
+                from: Index,
+                token: Index,
+            },
+            WrongNonce {
+                from: Index,
+                skip: bool,
+            },
+        }
+
+        fn op() -> impl Strategy<Value = Op> {
+            let tokens = || prop::collection::vec(any::<Index>(), 1..4);
+            prop_oneof![
+                1 => (1..20u64).prop_map(|amount| Op::Mint { amount }),
+                4 => (any::<Index>(), any::<Index>(), any::<Index>(), tokens()).prop_map(
+                    |(asset, from, to, tokens)| Op::Transfer { asset, from, to, tokens }
+                ),
+                2 => (any::<Index>(), any::<Index>(), any::<Index>(), any::<Index>(), 1..6u64)
+                    .prop_map(|(asset, from, to, start, len)| Op::TransferRange {
+                        asset,
+                        from,
+                        to,
+                        start,
+                        len,
+                    }),
+                2 => (any::<Index>(), any::<Index>(), tokens())
+                    .prop_map(|(asset, from, tokens)| Op::Burn { asset, from, tokens }),
+                1 => any::<Index>().prop_map(|from| Op::MintByHolder { from }),
+                1 => (any::<Index>(), any::<Index>(), any::<Index>())
+                    .prop_map(|(asset, from, token)| Op::TransferUnowned { asset, from, token }),
+                1 => (any::<Index>(), any::<bool>())
+                    .prop_map(|(from, skip)| Op::WrongNonce { from, skip }),
+            ]
+        }
+
+        /// What the ledger should hold: owner of every live token id per asset, as an
+        /// index into the wallets, and each wallet's last nonce
+        struct Model {
+            owners: Vec<BTreeMap<u64, usize>>,
+            supply: Vec<u64>,
+            nonces: [u64; HOLDERS + 1],
+        }
+
+        impl Model {
+            fn tokens_of(&self, asset: usize, holder: usize) -> Vec<u64> {
+                self.owners[asset]
+                    .iter()
+                    .filter(|(_, owner)| **owner == holder)
+                    .map(|(token_id, _)| *token_id)
+                    .collect()
+            }
+        }
+
+        /// Signs `unsigned` and appends it in a block of its own.
+        fn apply(
+            chain: &mut ATokenChain,
+            issuer: &Wallet,
+            sender: &Wallet,
+            unsigned: UnsignedTx,
+        ) -> Result<()> {
+            let tx = SignedTx::sign(unsigned, sender)?;
+            let block = chain.build_block(issuer, vec![tx])?;
+            chain.append_block(block).map(|_| ())
+        }
+
+        fn check(chain: &ATokenChain, wallets: &[Wallet], model: &Model) {
+            assert_eq!(chain.assets().len(), model.owners.len());
+            for (asset_id, owners) in model.owners.iter().enumerate() {
+                let asset_id = asset_id as AssetId;
+                // Supply only moves with mints and burns, and every token is held
+                let supply = model.supply[asset_id as usize];
+                assert_eq!(chain.total_supply(asset_id), supply);
+                let held: u64 = wallets
+                    .iter()
+                    .map(|wallet| chain.balance_of(asset_id, &wallet.address()))
+                    .sum();
+                assert_eq!(held, supply);
+
+                // One owner per token, agreeing with the index both ways
+                let mut seen = BTreeSet::new();
+                for (holder, wallet) in wallets.iter().enumerate() {
+                    let tokens = chain.tokens_of(asset_id, &wallet.address());
+                    for token_id in tokens {
+                        assert!(seen.insert(*token_id), "token {token_id} held twice");
+                        assert_eq!(chain.owner_of(asset_id, *token_id), Some(&wallet.address()));
+                    }
+                    let expected: BTreeSet<u64> = owners
+                        .iter()
+                        .filter(|(_, owner)| **owner == holder)
+                        .map(|(token_id, _)| *token_id)
+                        .collect();
+                    assert_eq!(tokens, &expected);
+                }
+            }
+            for (wallet, nonce) in wallets.iter().zip(model.nonces) {
+                assert_eq!(chain.next_nonce(&wallet.address()), nonce + 1);
+            }
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(16))]
+
+            #[test]
+            fn random_histories_keep_the_ledger_consistent(
+                ops in prop::collection::vec(op(), 1..30),
+            ) {
+                // Wallet 0 is the issuer
+                let wallets: Vec<Wallet> = (0..=HOLDERS).map(|_| Wallet::generate()).collect();
+                let issuer = &wallets[0];
+                let mut chain =
+                    ATokenChain::new(ChainConfig::new("AToken-local", issuer.address()));
+                let mut model = Model {
+                    owners: Vec::new(),
+                    supply: Vec::new(),
+                    nonces: [0; HOLDERS + 1],
+                };
+                let mint = |model: &Model, amount| {
+                    UnsignedTx::mint(
+                        "AToken-local",
+                        issuer.address(),
+                        model.nonces[0] + 1,
+                        amount,
+                        metadata(),
+                    )
+                };
+                apply(&mut chain, issuer, issuer, mint(&model, 10)).unwrap();
+                model.owners.push((0..10).map(|token_id| (token_id, 0)).collect());
+                model.supply.push(10);
+                model.nonces[0] = 1;
+
+                for op in ops {
+                    let before = chain.export_snapshot();
+                    let assets = model.owners.len();
+                    let (sender, unsigned, valid) = match &op {
+                        Op::Mint { amount } => (0, mint(&model, *amount), true),
+                        Op::Transfer { asset, from, to, tokens } => {
+                            let (asset, from) = (asset.index(assets), from.index(HOLDERS + 1));
+                            let held = model.tokens_of(asset, from);
+                            if held.is_empty() {
+                                continue;
+                            }
+                            let mut picked: Vec<u64> =
+                                tokens.iter().map(|token| *token.get(&held)).collect();
+                            picked.sort_unstable();
+                            picked.dedup();
+                            let tx = UnsignedTx::transfer(
+                                "AToken-local",
+                                wallets[from].address(),
+                                model.nonces[from] + 1,
+                                asset as AssetId,
+                                wallets[to.index(HOLDERS + 1)].address(),
+                                picked,
+                            );
+                            (from, tx, true)
+                        }
+                        Op::TransferRange { asset, from, to, start, len } => {
+                            let (asset, from) = (asset.index(assets), from.index(HOLDERS + 1));
+                            let held = model.tokens_of(asset, from);
+                            if held.is_empty() {
+                                continue;
+                            }
+                            // Longest run of consecutive ids from `start`, up to `len`
+                            let first = start.index(held.len());
+                            let run = held[first..]
+                                .iter()
+                                .zip(held[first]..)
+                                .take_while(|(held, expected)| **held == *expected)
+                                .take(*len as usize)
+                                .count() as u64;
+                            let tx = UnsignedTx::transfer_ranges(
+                                "AToken-local",
+                                wallets[from].address(),
+                                model.nonces[from] + 1,
+                                asset as AssetId,
+                                wallets[to.index(HOLDERS + 1)].address(),
+                                vec![(held[first], held[first] + run - 1)],
+                            );
+                            (from, tx, true)
+                        }
+                        Op::Burn { asset, from, tokens } => {
+                            let (asset, from) = (asset.index(assets), from.index(HOLDERS + 1));
+                            let held = model.tokens_of(asset, from);
+                            if held.is_empty() {
+                                continue;
+                            }
+                            let mut picked: Vec<u64> =
+                                tokens.iter().map(|token| *token.get(&held)).collect();
+                            picked.sort_unstable();
+                            picked.dedup();
+                            let tx = UnsignedTx::burn(
+                                "AToken-local",
+                                wallets[from].address(),
+                                model.nonces[from] + 1,
+                                asset as AssetId,
+                                picked,
+                            );
+                            (from, tx, true)
+                        }
+                        Op::MintByHolder { from } => {
+                            let from = 1 + from.index(HOLDERS);
+                            let tx = UnsignedTx::mint(
+                                "AToken-local",
+                                wallets[from].address(),
+                                model.nonces[from] + 1,
+                                5,
+                                metadata(),
+                            );
+                            (from, tx, false)
+                        }
+                        Op::TransferUnowned { asset, from, token } => {
+                            let (asset, from) = (asset.index(assets), from.index(HOLDERS + 1));
+                            let others: Vec<u64> = model.owners[asset]
+                                .iter()
+                                .filter(|(_, owner)| **owner != from)
+                                .map(|(token_id, _)| *token_id)
+                                .collect();
+                            if others.is_empty() {
+                                continue;
+                            }
+                            let tx = UnsignedTx::transfer(
+                                "AToken-local",
+                                wallets[from].address(),
+                                model.nonces[from] + 1,
+                                asset as AssetId,
+                                wallets[(from + 1) % (HOLDERS + 1)].address(),
+                                vec![*token.get(&others)],
+                            );
+                            (from, tx, false)
+                        }
+                        Op::WrongNonce { from, skip } => {
+                            let from = from.index(HOLDERS + 1);
+                            // A skipped nonce, or a replay of the last one
+                            let nonce = match skip {
+                                true => model.nonces[from] + 2,
+                                false if model.nonces[from] > 0 => model.nonces[from],
+                                false => continue,
+                            };
+                            let tx = UnsignedTx::mint(
+                                "AToken-local",
+                                wallets[from].address(),
+                                nonce,
+                                1,
+                                metadata(),
+                            );
+                            (from, tx, false)
+                        }
+                    };
+
+                    let payload = unsigned.payload.clone();
+                    let to = match &payload {
+                        TxPayload::Transfer { to, .. } | TxPayload::TransferRanges { to, .. } => {
+                            wallets.iter().position(|wallet| wallet.address() == *to)
+                        }
+                        _ => None,
+                    };
+                    let result = apply(&mut chain, issuer, &wallets[sender], unsigned);
+                    if !valid {
+                        prop_assert!(result.is_err(), "{op:?} was accepted");
+                        prop_assert_eq!(chain.export_snapshot(), before);
+                        continue;
+                    }
+                    prop_assert!(result.is_ok(), "{:?} rejected: {:?}", op, result);
+
+                    // Nonces only ever advance by one
+                    model.nonces[sender] += 1;
+                    match payload {
+                        TxPayload::Mint { amount, .. } => {
+                            model.owners.push((0..amount).map(|token_id| (token_id, 0)).collect());
+                            model.supply.push(amount);
+                        }
+                        TxPayload::Transfer { asset_id, token_ids, .. } => {
+                            let to = to.expect("transfers go to a known wallet");
+                            for token_id in token_ids {
+                                model.owners[asset_id as usize].insert(token_id, to);
+                            }
+                        }
+                        TxPayload::TransferRanges { asset_id, token_ranges, .. } => {
+                            let to = to.expect("transfers go to a known wallet");
+                            for (start, end) in token_ranges {
+                                for token_id in start..=end {
+                                    model.owners[asset_id as usize].insert(token_id, to);
+                                }
+                            }
+                        }
+                        TxPayload::Burn { asset_id, token_ids } => {
+                            for token_id in &token_ids {
+                                model.owners[asset_id as usize].remove(token_id);
+                            }
+                            model.supply[asset_id as usize] -= token_ids.len() as u64;
+                        }
+                        TxPayload::UpdateMetadata { .. } => unreachable!("not generated"),
+                    }
+                    check(&chain, &wallets, &model);
+                }
+                chain.verify_integrity().unwrap();
+            }
+        }
     }
 }
@@ -0,0 +1,100 @@
+//! Typed async client for the HTTP API in `api`.
+
+use reqwest::RequestBuilder;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::api_types::{
+    BalanceResponse, ChainInfoResponse, ErrorResponse, IssueRequest, NonceResponse,
+    PendingTxResponse, TransferRequest, TxAcceptedResponse,
+};
+use crate::errors::{ATokenError, Result};
+use crate::model::{AssetId, SignedTx};
+
+/// Client of one node, e.g. `Client::new("http://127.0.0.1:8080")`. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http(reqwest::Client::new(), base_url)
+    }
+
+    /// Client over a preconfigured `reqwest::Client`, e.g. one with timeouts.
+    pub fn with_http(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Issues a new asset, signed on the node. Only served with server signing on.
+    pub async fn issue(&self, req: &IssueRequest) -> Result<TxAcceptedResponse> {
+        self.post("/issue", req).await
+    }
+
+    /// Transfers tokens, signed on the node. Only served with server signing on.
+    pub async fn transfer(&self, req: &TransferRequest) -> Result<TxAcceptedResponse> {
+        self.post("/transfer", req).await
+    }
+
+    pub async fn balance(&self, asset_id: AssetId, address: &str) -> Result<BalanceResponse> {
+        self.get(&format!("/balance/{asset_id}/{address}")).await
+    }
+
+    pub async fn nonce(&self, address: &str) -> Result<NonceResponse> {
+        self.get(&format!("/nonce/{address}")).await
+    }
+
+    pub async fn chain_info(&self) -> Result<ChainInfoResponse> {
+        self.get("/chain").await
+    }
+
+    /// Commits a client-signed tx in a block of its own (`/tx/submit`).
+    pub async fn submit_tx(&self, tx: &SignedTx) -> Result<TxAcceptedResponse> {
+        self.post("/tx/submit", tx).await
+    }
+
+    /// Queues a client-signed tx in the mempool until the next `/seal` (`/tx`).
+    pub async fn queue_tx(&self, tx: &SignedTx) -> Result<PendingTxResponse> {
+        self.post("/tx", tx).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        send(self.http.get(self.url(path))).await
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        send(self.http.post(self.url(path)).json(body)).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+}
+
+/// Sends the request and decodes the body, turning a non-2xx answer into
+/// `ATokenError::Api` with the node's error message.
+async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+    let response = request.send().await.map_err(http_error)?;
+    let status = response.status();
+    if status.is_success() {
+        return response.json().await.map_err(http_error);
+    }
+    let message = match response.json::<ErrorResponse>().await {
+        Ok(body) => body.error,
+        // Not one of ours, e.g. a proxy in between
+        Err(_) => status.to_string(),
+    };
+    Err(ATokenError::Api {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+fn http_error(error: reqwest::Error) -> ATokenError {
+    ATokenError::Http(error.to_string())
+}
@@ -1,8 +1,12 @@
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand_core::OsRng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::canonical::address_bytes;
 use crate::errors::{ATokenError, Result};
 
 pub type Address = String;
@@ -19,9 +23,89 @@ pub fn sha256_hex(bytes: &[u8]) -> String {
     hex::encode(digest)
 }
 
+/// Hash behind block hashes and address derivation, fixed per chain by its genesis block.
+/// Tx ids, their Merkle tree and address checksums are SHA-256 on every chain, so they can
+/// be checked without knowing which chain a tx is for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [Self; 2] = [Self::Sha256, Self::Blake3];
+
+    pub fn is_sha256(&self) -> bool {
+        *self == Self::Sha256
+    }
+
+    pub fn digest(self, bytes: &[u8]) -> [u8; 32] {
+        match self {
+            Self::Sha256 => Sha256::digest(bytes).into(),
+            Self::Blake3 => *blake3::hash(bytes).as_bytes(),
+        }
+    }
+
+    pub fn hex(self, bytes: &[u8]) -> String {
+        hex::encode(self.digest(bytes))
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+        })
+    }
+}
+
+/// Address of a key on a SHA-256 chain.
 pub fn address_from_public_key(verifying_key: &VerifyingKey) -> Address {
-    let digest = Sha256::digest(verifying_key.to_bytes());
-    hex::encode(&digest[..20])
+    address_from_public_key_with(verifying_key, HashAlgorithm::Sha256)
+}
+
+/// First 20 bytes of the `hash_algorithm` digest of the key, under its own domain,
+/// checksummed.
+pub fn address_from_public_key_with(
+    verifying_key: &VerifyingKey,
+    hash_algorithm: HashAlgorithm,
+) -> Address {
+    let digest = hash_algorithm.digest(&address_bytes(&verifying_key.to_bytes()));
+    checksum_address(&hex::encode(&digest[..20]))
+}
+
+/// EIP-55-style checksum over a lowercase hex address: a letter is uppercased when the
+/// matching nibble of `sha256(lowercase address)` is 8 or more.
+fn checksum_address(lower_hex: &str) -> Address {
+    let digest = Sha256::digest(lower_hex.as_bytes());
+    lower_hex
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (digest[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Parses a user-supplied address into its checksummed form. All-lowercase input carries
+/// no checksum and is accepted as is; mixed case must match the checksum exactly.
+pub fn validate_address(address: &str) -> Result<Address> {
+    if address.len() != 40 || !address.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ATokenError::InvalidAddress(address.to_string()));
+    }
+    let checksummed = checksum_address(&address.to_ascii_lowercase());
+    if address.chars().any(|c| c.is_ascii_uppercase()) && address != checksummed {
+        return Err(ATokenError::AddressChecksumMismatch(address.to_string()));
+    }
+    Ok(checksummed)
 }
 
 #[derive(Clone)]
@@ -47,27 +131,88 @@ impl Wallet {
             .as_slice()
             .try_into()
             .map_err(|_| ATokenError::InvalidPrivateKeyLength(key_len))?;
-        Ok(Self {
-            signing_key: SigningKey::from_bytes(&arr),
-        })
+        Ok(Self::from_private_key_bytes(&arr))
+    }
+
+    pub(crate) fn from_private_key_bytes(bytes: &[u8; 32]) -> Self {
+        Self {
+            signing_key: SigningKey::from_bytes(bytes),
+        }
+    }
+
+    pub(crate) fn private_key_bytes(&self) -> [u8; 32] {
+        self.signing_key.to_bytes()
     }
 
     pub fn private_key_hex(&self) -> String {
-        hex::encode(self.signing_key.to_bytes())
+        hex::encode(self.private_key_bytes())
     }
 
     pub fn public_key_hex(&self) -> String {
         hex::encode(self.signing_key.verifying_key().to_bytes())
     }
 
+    /// Address on a SHA-256 chain.
     pub fn address(&self) -> Address {
-        address_from_public_key(&self.signing_key.verifying_key())
+        self.address_for(HashAlgorithm::Sha256)
+    }
+
+    pub fn address_for(&self, hash_algorithm: HashAlgorithm) -> Address {
+        address_from_public_key_with(&self.signing_key.verifying_key(), hash_algorithm)
     }
 
     pub fn sign_hex(&self, message: &[u8]) -> String {
         let signature: Signature = self.signing_key.sign(message);
         hex::encode(signature.to_bytes())
     }
+
+    /// Signs an arbitrary message, e.g. a login challenge, to prove ownership of the
+    /// address. The result is the hex of the public key followed by the signature, as
+    /// ed25519 cannot recover the key from a signature; see [`verify_personal`].
+    pub fn sign_personal(&self, message: &str) -> String {
+        format!(
+            "{}{}",
+            self.public_key_hex(),
+            self.sign_hex(&personal_message_bytes(message))
+        )
+    }
+}
+
+/// `message` behind the `"\x19AToken Signed Message:\n{len}"` prefix. Transaction and
+/// block signing bytes never start with `0x19`, so a signed message cannot pass for one.
+fn personal_message_bytes(message: &str) -> Vec<u8> {
+    let mut bytes = format!("\x19AToken Signed Message:\n{}", message.len()).into_bytes();
+    bytes.extend_from_slice(message.as_bytes());
+    bytes
+}
+
+/// Checks a [`Wallet::sign_personal`] signature of `message` by `address`, an address of
+/// the key under either hash algorithm.
+pub fn verify_personal(address: &str, message: &str, signature_hex: &str) -> Result<()> {
+    let address = validate_address(address)?;
+    let bytes = hex::decode(signature_hex)
+        .map_err(|e| ATokenError::HexDecode(format!("signature: {e}")))?;
+    let Some((public_key, signature)) = bytes.split_first_chunk::<32>() else {
+        return Err(ATokenError::InvalidPersonalSignatureLength(bytes.len()));
+    };
+    let signature: [u8; 64] = signature
+        .try_into()
+        .map_err(|_| ATokenError::InvalidPersonalSignatureLength(bytes.len()))?;
+
+    let verifying_key = VerifyingKey::from_bytes(public_key)
+        .map_err(|_| ATokenError::InvalidPublicKeyLength(public_key.len()))?;
+    if !HashAlgorithm::ALL
+        .into_iter()
+        .any(|algorithm| address_from_public_key_with(&verifying_key, algorithm) == address)
+    {
+        return Err(ATokenError::InvalidSender);
+    }
+    verifying_key
+        .verify(
+            &personal_message_bytes(message),
+            &Signature::from_bytes(&signature),
+        )
+        .map_err(|_| ATokenError::InvalidSignature)
 }
 
 pub fn verify_signature_hex(
@@ -105,3 +250,87 @@ pub fn verify_signature_hex(
         .verify(message, &signature)
         .map_err(|_| ATokenError::InvalidSignature)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed_address() -> Address {
+        Wallet::from_private_key_hex(&"01".repeat(32))
+            .unwrap()
+            .address()
+    }
+
+    #[test]
+    fn addresses_carry_a_checksum() {
+        let address = fixed_address();
+        assert_eq!(address, "31c3d6435d6ea56b68baFdcFec30109043e621A8");
+        assert_eq!(validate_address(&address).unwrap(), address);
+
+        // No checksum at all is still accepted and normalized
+        let lower = address.to_ascii_lowercase();
+        assert_eq!(validate_address(&lower).unwrap(), address);
+    }
+
+    #[test]
+    fn corrupted_checksum_is_rejected() {
+        // The checksummed form has `F` at index 20
+        let corrupted = "31c3d6435d6ea56b68bafdcFec30109043e621A8";
+        assert!(matches!(
+            validate_address(corrupted),
+            Err(ATokenError::AddressChecksumMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn personal_signatures_bind_the_message_and_the_address() {
+        let wallet = Wallet::from_private_key_hex(&"01".repeat(32)).unwrap();
+        let challenge = "login to example.org, nonce 42";
+        let signature = wallet.sign_personal(challenge);
+        verify_personal(&wallet.address(), challenge, &signature).unwrap();
+        verify_personal(
+            &wallet.address().to_ascii_lowercase(),
+            challenge,
+            &signature,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            verify_personal(
+                &wallet.address(),
+                "login to example.org, nonce 43",
+                &signature
+            ),
+            Err(ATokenError::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify_personal(&Wallet::generate().address(), challenge, &signature),
+            Err(ATokenError::InvalidSender)
+        ));
+        assert!(matches!(
+            verify_personal(&wallet.address(), challenge, &signature[64..]),
+            Err(ATokenError::InvalidPersonalSignatureLength(64))
+        ));
+
+        // The prefix keeps a personal signature from verifying as a raw one
+        assert!(
+            verify_signature_hex(
+                &wallet.public_key_hex(),
+                &signature[64..],
+                challenge.as_bytes()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn malformed_addresses_are_rejected() {
+        let address = fixed_address();
+        for bad in [&address[..39], &format!("{address}0"), &"g".repeat(40), ""] {
+            assert!(matches!(
+                validate_address(bad),
+                Err(ATokenError::InvalidAddress(_))
+            ));
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::crypto::HashAlgorithm;
+
 pub type Result<T> = std::result::Result<T, ATokenError>;
 
 #[derive(Debug, Error)]
@@ -12,42 +14,122 @@ pub enum ATokenError {
     InvalidPublicKeyLength(usize),
     #[error("invalid signature length: expected 64 bytes, got {0}")]
     InvalidSignatureLength(usize),
+    #[error("invalid personal signature length: expected 96 bytes, got {0}")]
+    InvalidPersonalSignatureLength(usize),
     #[error("signature verification failed")]
     InvalidSignature,
     #[error("serialization error: {0}")]
     Serialization(String),
     #[error("invalid sender")]
     InvalidSender,
+    #[error("invalid address {0}: expected 40 hex characters")]
+    InvalidAddress(String),
+    #[error("address {0} has a bad checksum")]
+    AddressChecksumMismatch(String),
+    #[error("address {0} is not in its checksummed form")]
+    NonCanonicalAddress(String),
     #[error("invalid block height: expected {expected}, got {actual}")]
     InvalidBlockHeight { expected: u64, actual: u64 },
+    #[error("block timestamp {actual} is before its parent's {parent}")]
+    BlockTimestampBeforeParent { parent: u64, actual: u64 },
+    #[error("block timestamp {actual} is too far in the future (latest allowed {latest_allowed})")]
+    BlockTimestampInFuture { latest_allowed: u64, actual: u64 },
     #[error("previous hash mismatch")]
     PreviousHashMismatch,
     #[error("previous three hashes mismatch")]
     PreviousThreeMismatch,
     #[error("invalid chain id: expected {expected}, got {actual}")]
     InvalidChainId { expected: String, actual: String },
+    #[error("block hashed with {actual}, the chain uses {expected}")]
+    HashAlgorithmMismatch {
+        expected: HashAlgorithm,
+        actual: HashAlgorithm,
+    },
+    #[error("unsupported transaction version: {0}")]
+    UnsupportedTxVersion(u16),
+    #[error("unsupported block version: {0}")]
+    UnsupportedBlockVersion(u16),
     #[error("token has not been issued yet")]
     TokenNotIssued,
-    #[error("token already issued, additional mint is forbidden")]
-    AlreadyIssued,
+    #[error("genesis block has no mint transaction")]
+    MissingGenesisMint,
+    #[error("invalid genesis: {0}")]
+    InvalidGenesis(String),
+    #[error("invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("invalid keystore: {0}")]
+    InvalidKeystore(String),
+    #[error("wrong keystore password")]
+    KeystorePasswordMismatch,
+    #[error("invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
     #[error("mint amount must be greater than zero")]
     MintAmountMustBePositive,
+    #[error("invalid token metadata: {0}")]
+    InvalidMetadata(String),
+    #[error("mint amount {actual} exceeds the limit of {max}")]
+    MintAmountTooLarge { max: u64, actual: u64 },
+    #[error("transaction touches {actual} token ids, at most {max} allowed")]
+    TooManyTokenIds { max: usize, actual: u64 },
+    #[error("transaction expired at {valid_until_ms}, block time is {block_time_ms}")]
+    Expired {
+        valid_until_ms: u64,
+        block_time_ms: u64,
+    },
+    #[error("token {token_id} is locked until {locked_until_ms}")]
+    TokenLocked { token_id: u64, locked_until_ms: u64 },
+    #[error("memo is {actual} characters, at most {max} allowed")]
+    MemoTooLong { max: usize, actual: usize },
+    #[error("mint distribution hands out {distributed} tokens, expected {amount}")]
+    DistributionMismatch { amount: u64, distributed: u64 },
     #[error("only issuer can mint")]
     MintNotAllowed,
+    #[error("only issuer can update metadata")]
+    MetadataUpdateNotAllowed,
+    #[error("{0} is not allowed to propose blocks")]
+    UnauthorizedProposer(String),
     #[error("transaction nonce mismatch: expected {expected}, got {actual}")]
     NonceMismatch { expected: u64, actual: u64 },
     #[error("transfer must contain at least one token id")]
     EmptyTransfer,
+    #[error("burn must contain at least one token id")]
+    EmptyBurn,
+    #[error("invalid token range: {start}..={end}")]
+    InvalidTokenRange { start: u64, end: u64 },
     #[error("duplicate token id in transfer: {0}")]
     DuplicateTokenId(u64),
     #[error("token id {0} not found")]
     UnknownToken(u64),
+    #[error("asset {0} not found")]
+    UnknownAsset(u64),
+    #[error("no block at height {0}")]
+    UnknownBlockHeight(u64),
+    #[error("no block with hash {0}")]
+    UnknownBlockHash(String),
+    #[error("transaction {0} not found")]
+    UnknownTransaction(String),
     #[error("sender does not own token id {token_id}")]
     NotTokenOwner { token_id: u64 },
+    #[error("token id {token_id} of asset {asset_id} is already used by a pending transaction")]
+    ConflictingTx { asset_id: u64, token_id: u64 },
+    #[error("no pending transactions to seal")]
+    EmptyMempool,
+    #[error("mempool is full: {0} transactions are already pending")]
+    MempoolFull(usize),
+    #[error("duplicate transaction: {0}")]
+    DuplicateTransaction(String),
     #[error("integrity mismatch: hash does not match block payload")]
     BlockHashMismatch,
+    #[error("integrity mismatch: tx merkle root does not match block txs")]
+    TxMerkleRootMismatch,
     #[error("integrity mismatch: transaction id does not match payload")]
     TransactionIdMismatch,
+    #[error("integrity check failed at height {height}: {reason}")]
+    IntegrityFailure { height: u64, reason: String },
     #[error("storage error: {0}")]
     Storage(String),
+    #[error("http request failed: {0}")]
+    Http(String),
+    #[error("node answered {status}: {message}")]
+    Api { status: u16, message: String },
 }
@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chain::{ATokenChain, ChainConfig, DEFAULT_REQUIRED_PREVIOUS_BLOCKS};
+use crate::crypto::{Address, HashAlgorithm, Wallet, sha256_hex, validate_address};
+use crate::errors::{ATokenError, Result};
+use crate::model::{BLOCK_VERSION, Block, BlockHeader};
+use crate::snapshot::ChainSnapshot;
+use crate::storage::{BlockStore, replay_from_snapshot};
+
+/// Chain parameters fixed before any block, instead of being decided by the first mint.
+/// Read from the JSON file named by `ATOKEN_GENESIS_PATH`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisConfig {
+    pub chain_id: String,
+    /// The only address allowed to mint
+    pub issuer: Address,
+    #[serde(default = "default_required_previous_blocks")]
+    pub required_previous_blocks: usize,
+    /// Timestamp of the genesis block
+    #[serde(default)]
+    pub timestamp_ms: u64,
+    /// Addresses allowed to propose blocks; just the issuer when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proposers: Vec<Address>,
+    /// Hash of block headers and addresses for the chain's whole life; the issuer and
+    /// proposers must be addresses under it
+    #[serde(default, skip_serializing_if = "HashAlgorithm::is_sha256")]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+fn default_required_previous_blocks() -> usize {
+    DEFAULT_REQUIRED_PREVIOUS_BLOCKS
+}
+
+impl GenesisConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)?;
+        let mut genesis: Self =
+            serde_json::from_str(&raw).map_err(|e| ATokenError::InvalidGenesis(e.to_string()))?;
+        genesis.issuer = validate_address(&genesis.issuer)?;
+        genesis.proposers = genesis
+            .proposers
+            .iter()
+            .map(|proposer| validate_address(proposer))
+            .collect::<Result<_>>()?;
+        if genesis.required_previous_blocks == 0 {
+            return Err(ATokenError::InvalidGenesis(
+                "required_previous_blocks must be at least 1".to_string(),
+            ));
+        }
+        Ok(genesis)
+    }
+
+    pub fn chain_config(&self) -> ChainConfig {
+        let mut config = ChainConfig::new(self.chain_id.clone(), self.issuer.clone())
+            .with_hash_algorithm(self.hash_algorithm);
+        config.required_previous_blocks = self.required_previous_blocks;
+        if !self.proposers.is_empty() {
+            config.proposers = self.proposers.clone();
+        }
+        config
+    }
+
+    /// Block 0: no txs, sealed by a key derived from the config itself, so every node with
+    /// the same file builds the same block and a different config gives a different hash.
+    pub fn block(&self) -> Result<Block> {
+        let seed =
+            serde_json::to_vec(self).map_err(|e| ATokenError::Serialization(e.to_string()))?;
+        let proposer = Wallet::from_private_key_hex(&sha256_hex(&seed))?;
+        let header = BlockHeader {
+            version: BLOCK_VERSION,
+            chain_id: self.chain_id.clone(),
+            height: 0,
+            previous_hash: None,
+            previous_three_hashes: Vec::new(),
+            proposer: proposer.address_for(self.hash_algorithm),
+            proposer_public_key_hex: proposer.public_key_hex(),
+            timestamp_ms: self.timestamp_ms,
+            tx_merkle_root: Block::tx_merkle_root(&[])?,
+            hash_algorithm: self.hash_algorithm,
+        };
+
+        let sign_message = Block::previous_signature_message(&header);
+        let previous_signature_hex = proposer.sign_hex(&sign_message);
+        let hash = Block::calculate_hash(&header, &[], &previous_signature_hex);
+        Ok(Block {
+            header,
+            txs: Vec::new(),
+            previous_signature_hex,
+            hash,
+        })
+    }
+
+    /// Chain holding only the genesis block.
+    pub fn chain(&self) -> Result<ATokenChain> {
+        let mut chain = ATokenChain::new(self.chain_config());
+        chain.append_block(self.block()?)?;
+        Ok(chain)
+    }
+
+    /// Rebuilds the chain in `store`, writing the genesis block first if the store is empty.
+    /// A store that starts with another genesis block belongs to a different chain.
+    pub async fn restore<S>(&self, store: &S) -> Result<ATokenChain>
+    where
+        S: BlockStore + ?Sized,
+    {
+        self.restore_from(store, None).await
+    }
+
+    /// `restore`, starting from `snapshot` when it matches the store.
+    pub async fn restore_from<S>(
+        &self,
+        store: &S,
+        snapshot: Option<ChainSnapshot>,
+    ) -> Result<ATokenChain>
+    where
+        S: BlockStore + ?Sized,
+    {
+        let genesis = self.block()?;
+        match store.load_genesis().await? {
+            None => {
+                let chain = self.chain()?;
+                store.save_block(&genesis).await?;
+                Ok(chain)
+            }
+            Some(stored) if stored.hash != genesis.hash => {
+                Err(ATokenError::InvalidGenesis(format!(
+                    "store starts with block {}, expected {}",
+                    stored.hash, genesis.hash
+                )))
+            }
+            Some(_) => {
+                let mut chain = ATokenChain::new(self.chain_config());
+                replay_from_snapshot(&mut chain, store, snapshot).await?;
+                Ok(chain)
+            }
+        }
+    }
+}
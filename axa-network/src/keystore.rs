@@ -0,0 +1,260 @@
+//! Password-encrypted wallet files in the shape of Ethereum keystores: the private key is
+//! sealed with AES-256-GCM under a key stretched from the password with scrypt. The
+//! address, on the chain the keystore was made for, goes in as associated data, so a
+//! keystore cannot be relabelled.
+
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload, rand_core::RngCore};
+use aes_gcm::{Aes256Gcm, Nonce};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{Address, HashAlgorithm, Wallet, validate_address};
+use crate::errors::{ATokenError, Result};
+
+pub const KEYSTORE_VERSION: u32 = 1;
+const CIPHER: &str = "aes-256-gcm";
+const KDF: &str = "scrypt";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// scrypt cost of new keystores: `n = 2^15`, `r = 8`, `p = 1`, about 32 MiB per unlock.
+/// It is also the largest cost accepted when unlocking, so a crafted keystore cannot make
+/// the node spend more on it than on one of its own.
+pub const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Keystore {
+    pub version: u32,
+    pub address: Address,
+    pub crypto: KeystoreCrypto,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    /// Encrypted private key followed by the GCM tag, hex
+    pub ciphertext: String,
+    pub cipherparams: CipherParams,
+    pub kdf: String,
+    pub kdfparams: ScryptParams,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CipherParams {
+    /// Hex
+    pub nonce: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ScryptParams {
+    pub dklen: usize,
+    /// A power of two
+    pub n: u64,
+    pub r: u32,
+    pub p: u32,
+    /// Hex
+    pub salt: String,
+}
+
+impl Wallet {
+    /// Encrypts the private key under `password`, labelled with its address on a
+    /// `hash_algorithm` chain.
+    pub fn to_keystore(&self, password: &str, hash_algorithm: HashAlgorithm) -> Result<Keystore> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let kdfparams = ScryptParams {
+            dklen: KEY_LEN,
+            n: 1 << SCRYPT_LOG_N,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            salt: hex::encode(salt),
+        };
+
+        let address = self.address_for(hash_algorithm);
+        let cipher = Aes256Gcm::new_from_slice(&derive_key(password, &kdfparams)?)
+            .map_err(|e| ATokenError::InvalidKeystore(e.to_string()))?;
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &self.private_key_bytes(),
+                    aad: address.as_bytes(),
+                },
+            )
+            .map_err(|e| ATokenError::InvalidKeystore(e.to_string()))?;
+
+        Ok(Keystore {
+            version: KEYSTORE_VERSION,
+            address,
+            crypto: KeystoreCrypto {
+                cipher: CIPHER.to_string(),
+                ciphertext: hex::encode(ciphertext),
+                cipherparams: CipherParams {
+                    nonce: hex::encode(nonce),
+                },
+                kdf: KDF.to_string(),
+                kdfparams,
+            },
+        })
+    }
+
+    /// Decrypts `keystore` with `password`. A wrong password and a tampered keystore look
+    /// the same: the GCM tag does not check out. The address may be the key's on a chain of
+    /// either hash algorithm.
+    pub fn from_keystore(keystore: &Keystore, password: &str) -> Result<Self> {
+        if keystore.version != KEYSTORE_VERSION {
+            return Err(ATokenError::InvalidKeystore(format!(
+                "unsupported version {}",
+                keystore.version
+            )));
+        }
+        let crypto = &keystore.crypto;
+        if crypto.cipher != CIPHER || crypto.kdf != KDF {
+            return Err(ATokenError::InvalidKeystore(format!(
+                "unsupported {} with {}, expected {CIPHER} with {KDF}",
+                crypto.cipher, crypto.kdf
+            )));
+        }
+        let address = validate_address(&keystore.address)?;
+        let nonce = decode_hex("nonce", &crypto.cipherparams.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(ATokenError::InvalidKeystore(format!(
+                "nonce must be {NONCE_LEN} bytes"
+            )));
+        }
+
+        let cipher = Aes256Gcm::new_from_slice(&derive_key(password, &crypto.kdfparams)?)
+            .map_err(|e| ATokenError::InvalidKeystore(e.to_string()))?;
+        let private_key = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &decode_hex("ciphertext", &crypto.ciphertext)?,
+                    aad: keystore.address.as_bytes(),
+                },
+            )
+            .map_err(|_| ATokenError::KeystorePasswordMismatch)?;
+
+        let private_key: [u8; 32] = private_key
+            .try_into()
+            .map_err(|key: Vec<u8>| ATokenError::InvalidPrivateKeyLength(key.len()))?;
+        let wallet = Self::from_private_key_bytes(&private_key);
+        if !HashAlgorithm::ALL
+            .into_iter()
+            .any(|algorithm| wallet.address_for(algorithm) == address)
+        {
+            return Err(ATokenError::InvalidKeystore(
+                "key does not match the address".to_string(),
+            ));
+        }
+        Ok(wallet)
+    }
+}
+
+fn derive_key(password: &str, params: &ScryptParams) -> Result<Vec<u8>> {
+    if params.dklen != KEY_LEN {
+        return Err(ATokenError::InvalidKeystore(format!(
+            "dklen must be {KEY_LEN}"
+        )));
+    }
+    if !params.n.is_power_of_two() || params.n < 2 {
+        return Err(ATokenError::InvalidKeystore(
+            "n must be a power of two".to_string(),
+        ));
+    }
+    let log_n = params.n.trailing_zeros() as u8;
+    if log_n > SCRYPT_LOG_N || params.r > SCRYPT_R || params.p > SCRYPT_P {
+        return Err(ATokenError::InvalidKeystore(format!(
+            "scrypt cost above n = 2^{SCRYPT_LOG_N}, r = {SCRYPT_R}, p = {SCRYPT_P}"
+        )));
+    }
+    let scrypt_params = scrypt::Params::new(log_n, params.r, params.p, KEY_LEN)
+        .map_err(|e| ATokenError::InvalidKeystore(e.to_string()))?;
+
+    let mut key = vec![0u8; KEY_LEN];
+    scrypt::scrypt(
+        password.as_bytes(),
+        &decode_hex("salt", &params.salt)?,
+        &scrypt_params,
+        &mut key,
+    )
+    .map_err(|e| ATokenError::InvalidKeystore(e.to_string()))?;
+    Ok(key)
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>> {
+    hex::decode(value).map_err(|e| ATokenError::HexDecode(format!("keystore {field}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keystore_round_trips_only_with_the_password() {
+        let wallet = Wallet::generate();
+        let keystore = wallet
+            .to_keystore("correct horse", HashAlgorithm::Sha256)
+            .unwrap();
+        assert_eq!(keystore.address, wallet.address());
+        assert!(
+            !serde_json::to_string(&keystore)
+                .unwrap()
+                .contains(&wallet.private_key_hex())
+        );
+
+        let json = serde_json::to_string(&keystore).unwrap();
+        let unlocked =
+            Wallet::from_keystore(&serde_json::from_str(&json).unwrap(), "correct horse").unwrap();
+        assert_eq!(unlocked.private_key_hex(), wallet.private_key_hex());
+
+        assert!(matches!(
+            Wallet::from_keystore(&keystore, "battery staple"),
+            Err(ATokenError::KeystorePasswordMismatch)
+        ));
+    }
+
+    #[test]
+    fn relabelled_or_overpriced_keystores_are_rejected() {
+        let keystore = Wallet::generate()
+            .to_keystore("pw", HashAlgorithm::Sha256)
+            .unwrap();
+
+        let mut relabelled = keystore.clone();
+        relabelled.address = Wallet::generate().address();
+        assert!(matches!(
+            Wallet::from_keystore(&relabelled, "pw"),
+            Err(ATokenError::KeystorePasswordMismatch)
+        ));
+
+        // Anything above the cost of an exported keystore, in any parameter
+        for overprice in [
+            |params: &mut ScryptParams| params.n = 1 << (SCRYPT_LOG_N + 1),
+            |params: &mut ScryptParams| params.r = SCRYPT_R + 1,
+            |params: &mut ScryptParams| params.p = SCRYPT_P + 1,
+        ] {
+            let mut overpriced = keystore.clone();
+            overprice(&mut overpriced.crypto.kdfparams);
+            assert!(matches!(
+                Wallet::from_keystore(&overpriced, "pw"),
+                Err(ATokenError::InvalidKeystore(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn keystores_carry_the_address_of_their_chain() {
+        let wallet = Wallet::generate();
+        let keystore = wallet.to_keystore("pw", HashAlgorithm::Blake3).unwrap();
+        assert_eq!(keystore.address, wallet.address_for(HashAlgorithm::Blake3));
+        assert_ne!(keystore.address, wallet.address());
+
+        let unlocked = Wallet::from_keystore(&keystore, "pw").unwrap();
+        assert_eq!(unlocked.private_key_hex(), wallet.private_key_hex());
+    }
+}
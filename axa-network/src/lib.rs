@@ -1,9 +1,24 @@
 pub mod api;
+pub mod api_types;
+pub mod canonical;
 pub mod chain;
+pub mod client;
+pub mod clock;
 pub mod crypto;
 pub mod errors;
+pub mod genesis;
+pub mod keystore;
+pub mod mempool;
+pub mod mnemonic;
 pub mod model;
+pub mod snapshot;
 pub mod storage;
 
+#[cfg(test)]
+mod test_support;
+
 #[cfg(feature = "scylla-store")]
 pub mod scylla_store;
+
+#[cfg(feature = "rocks-store")]
+pub mod rocks_store;
@@ -1,19 +1,285 @@
 use std::error::Error;
+use std::time::Duration;
 
-use axa_network::api::{AppState, router};
+use axa_network::api::{AppState, BlockProduction, DEFAULT_MAX_BODY_BYTES, produce_blocks, serve};
+use axa_network::crypto::Wallet;
+use axa_network::genesis::GenesisConfig;
+use axa_network::mempool::DEFAULT_MAX_PENDING;
+use axa_network::snapshot::SnapshotFile;
+use axa_network::storage::{BlockStore, FileBlockStore, InMemoryBlockStore};
+use axum::http::HeaderValue;
+use clap::Parser;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+/// AToken API server. Each flag falls back to its environment variable.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, env = "ATOKEN_BIND", default_value = "127.0.0.1:8080")]
+    bind: String,
+    /// Chain id; ignored when a genesis file sets one
+    #[arg(long, env = "ATOKEN_CHAIN_ID", default_value = "AToken-localnet")]
+    chain_id: String,
+    /// Block store; defaults to `file` when ATOKEN_DATA_PATH is set, else `memory`
+    #[arg(long, env = "ATOKEN_STORE", value_parser = ["memory", "file", "rocks", "scylla"])]
+    store: Option<String>,
+    /// Origin allowed to call the API from a browser, `*` for any; repeat or comma-separate
+    #[arg(long, env = "ATOKEN_CORS_ORIGIN", value_delimiter = ',')]
+    cors_origin: Vec<HeaderValue>,
+    /// Largest request body accepted, in bytes; bigger ones get a 413
+    #[arg(long, env = "ATOKEN_MAX_BODY_BYTES", default_value_t = DEFAULT_MAX_BODY_BYTES)]
+    max_body_bytes: usize,
+    /// Ledger snapshot to start from and checkpoint to; without it every block is replayed
+    #[arg(long, env = "ATOKEN_SNAPSHOT_PATH")]
+    snapshot_path: Option<String>,
+    /// Blocks between snapshots, 0 to only read the snapshot
+    #[arg(long, env = "ATOKEN_SNAPSHOT_EVERY", default_value_t = 1000)]
+    snapshot_every: u64,
+    /// Seal the mempool into a block this often; without it only `/seal` does
+    #[arg(long, env = "ATOKEN_BLOCK_INTERVAL_MS", value_parser = clap::value_parser!(u64).range(1..))]
+    block_interval_ms: Option<u64>,
+    /// Most txs sealed per interval
+    #[arg(long, env = "ATOKEN_BLOCK_MAX_TXS", default_value_t = 1000)]
+    block_max_txs: usize,
+    /// Most txs waiting in the mempool; `/tx` answers `503` beyond that
+    #[arg(long, env = "ATOKEN_MEMPOOL_MAX_TXS", default_value_t = DEFAULT_MAX_PENDING)]
+    mempool_max_txs: usize,
+    /// Seal a block every interval even when the mempool is empty
+    #[arg(long, env = "ATOKEN_PRODUCE_EMPTY")]
+    produce_empty: bool,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let bind_addr = std::env::var("ATOKEN_BIND").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
-    let chain_id =
-        std::env::var("ATOKEN_CHAIN_ID").unwrap_or_else(|_| "AToken-localnet".to_string());
+    let cli = Cli::parse();
+
+    // `RUST_LOG` overrides the default `info` level
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .init();
+
+    let genesis = match std::env::var("ATOKEN_GENESIS_PATH") {
+        Ok(path) => Some(GenesisConfig::load(&path)?),
+        Err(_) => None,
+    };
+    let chain_id = match &genesis {
+        Some(genesis) => genesis.chain_id.clone(),
+        None => cli.chain_id,
+    };
+    if let Some(genesis) = &genesis {
+        info!(issuer = %genesis.issuer, "AToken genesis loaded");
+    }
 
-    let state = AppState::new(chain_id.clone());
-    let app = router(state);
+    let snapshots = cli
+        .snapshot_path
+        .map(|path| SnapshotFile::new(path, cli.snapshot_every));
+    let mut state = open_state(chain_id.clone(), genesis.as_ref(), cli.store, snapshots).await?;
+    match std::env::var("ATOKEN_PROPOSER_PRIVATE_KEY") {
+        Ok(key) => state = state.with_proposer(Wallet::from_private_key_hex(&key)?),
+        Err(_) if cli.block_interval_ms.is_some() => {
+            return Err("ATOKEN_BLOCK_INTERVAL_MS requires ATOKEN_PROPOSER_PRIVATE_KEY".into());
+        }
+        Err(_) => warn!(
+            "ATOKEN_PROPOSER_PRIVATE_KEY is not set; the generated key cannot extend a chain \
+             it did not start or is not a proposer of"
+        ),
+    }
+    if let Err(e) = state.check_proposer().await {
+        return Err(format!(
+            "{e}: set ATOKEN_PROPOSER_PRIVATE_KEY to a proposer key of the restored chain"
+        )
+        .into());
+    }
+    info!(proposer = %state.proposer_address(), "AToken proposer");
 
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-    println!("AToken API listening on http://{bind_addr} (chain_id={chain_id})");
+    let server_signing = std::env::var("ATOKEN_SERVER_SIGNING")
+        .map(|value| !matches!(value.as_str(), "0" | "false"))
+        .unwrap_or(true);
+    if server_signing {
+        warn!(
+            "/issue, /transfer and /burn sign with client private keys and are deprecated; \
+             set ATOKEN_SERVER_SIGNING=false and use /tx/submit"
+        );
+    }
+    if !cli.cors_origin.is_empty() {
+        info!(origins = ?cli.cors_origin, "CORS enabled");
+    }
+    let state = state
+        .with_server_signing(server_signing)
+        .with_cors_origins(cli.cors_origin)
+        .with_max_body_bytes(cli.max_body_bytes)
+        .with_max_pending(cli.mempool_max_txs);
 
-    axum::serve(listener, app).await?;
+    let listener = tokio::net::TcpListener::bind(&cli.bind).await?;
+    info!(%chain_id, "AToken API listening on http://{}", cli.bind);
+
+    // Stopped before the API, so its last block is stored before the flush
+    let producer = cli.block_interval_ms.map(|interval_ms| {
+        let production = BlockProduction {
+            interval: Duration::from_millis(interval_ms),
+            max_txs: cli.block_max_txs,
+            produce_empty: cli.produce_empty,
+        };
+        info!(?production, "block production enabled");
+        let (stop, stopped) = oneshot::channel::<()>();
+        let task = tokio::spawn(produce_blocks(state.clone(), production, async {
+            let _ = stopped.await;
+        }));
+        (stop, task)
+    });
+    let shutdown = async move {
+        shutdown_signal().await;
+        if let Some((stop, task)) = producer {
+            let _ = stop.send(());
+            let _ = task.await;
+        }
+    };
+
+    serve(listener, state, shutdown).await?;
     Ok(())
 }
+
+/// Resolves on Ctrl+C, or SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("cannot listen for Ctrl+C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                warn!("cannot listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Ctrl+C received, shutting down"),
+        _ = terminate => info!("SIGTERM received, shutting down"),
+    }
+}
+
+/// Block store picked by `--store` (`memory`, `file`, `rocks` or `scylla`).
+/// Without it, `ATOKEN_DATA_PATH` selects the file store and memory is the fallback.
+async fn open_state(
+    chain_id: String,
+    genesis: Option<&GenesisConfig>,
+    store: Option<String>,
+    snapshots: Option<SnapshotFile>,
+) -> Result<AppState, Box<dyn Error>> {
+    let data_path = std::env::var("ATOKEN_DATA_PATH").ok();
+    let default_kind = if data_path.is_some() {
+        "file"
+    } else {
+        "memory"
+    };
+    let kind = store.unwrap_or_else(|| default_kind.to_string());
+
+    match kind.as_str() {
+        "memory" => {
+            warn!("AToken ledger is kept in memory only");
+            state_over(chain_id, genesis, InMemoryBlockStore::default(), snapshots).await
+        }
+        "file" => {
+            let path = data_path.ok_or("ATOKEN_STORE=file requires ATOKEN_DATA_PATH")?;
+            let state =
+                state_over(chain_id, genesis, FileBlockStore::new(&path), snapshots).await?;
+            info!("AToken ledger: {path}");
+            Ok(state)
+        }
+        "rocks" => {
+            let path = data_path.ok_or("ATOKEN_STORE=rocks requires ATOKEN_DATA_PATH")?;
+            open_rocks(chain_id, genesis, &path, snapshots).await
+        }
+        "scylla" => open_scylla(chain_id, genesis, snapshots).await,
+        other => Err(format!(
+            "unknown ATOKEN_STORE={other}, expected memory, file, rocks or scylla"
+        )
+        .into()),
+    }
+}
+
+/// The chain fixed by `genesis` if there is one, else whatever the first mint in `store` set up.
+async fn state_over<S>(
+    chain_id: String,
+    genesis: Option<&GenesisConfig>,
+    store: S,
+    snapshots: Option<SnapshotFile>,
+) -> Result<AppState, Box<dyn Error>>
+where
+    S: BlockStore + 'static,
+{
+    let state = match (genesis, snapshots) {
+        (genesis, Some(snapshots)) => {
+            AppState::with_snapshots(chain_id, genesis, store, snapshots).await?
+        }
+        (Some(genesis), None) => AppState::with_genesis(genesis, store).await?,
+        (None, None) => AppState::with_store(chain_id, store).await?,
+    };
+    Ok(state)
+}
+
+#[cfg(feature = "rocks-store")]
+async fn open_rocks(
+    chain_id: String,
+    genesis: Option<&GenesisConfig>,
+    path: &str,
+    snapshots: Option<SnapshotFile>,
+) -> Result<AppState, Box<dyn Error>> {
+    use axa_network::rocks_store::RocksBlockStore;
+
+    let state = state_over(chain_id, genesis, RocksBlockStore::open(path)?, snapshots).await?;
+    info!("AToken ledger: rocksdb {path}");
+    Ok(state)
+}
+
+#[cfg(not(feature = "rocks-store"))]
+async fn open_rocks(
+    _chain_id: String,
+    _genesis: Option<&GenesisConfig>,
+    _path: &str,
+    _snapshots: Option<SnapshotFile>,
+) -> Result<AppState, Box<dyn Error>> {
+    Err("ATOKEN_STORE=rocks requires building with --features rocks-store".into())
+}
+
+#[cfg(feature = "scylla-store")]
+async fn open_scylla(
+    chain_id: String,
+    genesis: Option<&GenesisConfig>,
+    snapshots: Option<SnapshotFile>,
+) -> Result<AppState, Box<dyn Error>> {
+    use axa_network::scylla_store::ScyllaBlockStore;
+
+    let node = std::env::var("ATOKEN_SCYLLA_NODE").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+    let keyspace = std::env::var("ATOKEN_SCYLLA_KEYSPACE").unwrap_or_else(|_| "atoken".to_string());
+
+    let store = ScyllaBlockStore::connect(&node, &keyspace).await?;
+    let state = state_over(chain_id, genesis, store, snapshots).await?;
+    info!("AToken ledger: scylla://{node}/{keyspace}");
+    Ok(state)
+}
+
+#[cfg(not(feature = "scylla-store"))]
+async fn open_scylla(
+    _chain_id: String,
+    _genesis: Option<&GenesisConfig>,
+    _snapshots: Option<SnapshotFile>,
+) -> Result<AppState, Box<dyn Error>> {
+    Err("ATOKEN_STORE=scylla requires building with --features scylla-store".into())
+}
@@ -0,0 +1,346 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::chain::ATokenChain;
+use crate::crypto::Address;
+use crate::errors::{ATokenError, Result};
+use crate::model::{AssetId, SignedTx};
+
+/// Pending txs a pool holds unless configured otherwise.
+pub const DEFAULT_MAX_PENDING: usize = 10_000;
+
+/// Transactions accepted by `/tx`, waiting to be sealed into one block.
+///
+/// Every pending tx is validated against the committed chain, so pending txs may not touch
+/// the same token of an asset: each one must still hold once the others ahead of it are
+/// applied.
+#[derive(Debug)]
+pub struct Mempool {
+    pending: Vec<SignedTx>,
+    /// Token ids of each asset that some pending tx moves or burns
+    in_use: HashMap<AssetId, HashSet<u64>>,
+    /// Pending txs of each sender, so the nonce its next tx needs
+    queued_by_sender: HashMap<Address, u64>,
+    max_pending: usize,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PENDING)
+    }
+}
+
+impl Mempool {
+    /// A pool that refuses new txs once `max_pending` are queued.
+    pub fn new(max_pending: usize) -> Self {
+        Self {
+            pending: Vec::new(),
+            in_use: HashMap::new(),
+            queued_by_sender: HashMap::new(),
+            max_pending,
+        }
+    }
+
+    pub fn set_max_pending(&mut self, max_pending: usize) {
+        self.max_pending = max_pending;
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Pending txs in submission order, which is the order `drain_valid` seals them in.
+    pub fn pending(&self) -> &[SignedTx] {
+        &self.pending
+    }
+
+    /// Queues `tx` if there is room, it is valid on `chain` after the sender's pending txs
+    /// and it touches no token that another pending tx does.
+    pub fn submit(&mut self, chain: &ATokenChain, tx: SignedTx) -> Result<()> {
+        if self.pending.len() >= self.max_pending {
+            return Err(ATokenError::MempoolFull(self.max_pending));
+        }
+        let from = &tx.unsigned.from;
+        let queued_by_sender = self.queued_by_sender.get(from).copied().unwrap_or(0);
+        chain.validate_tx(&tx, chain.next_nonce(from) + queued_by_sender)?;
+
+        if let Some(asset_id) = tx.unsigned.payload.asset_id() {
+            let token_ids = chain.token_ids_of(&tx)?;
+            if let Some(in_use) = self.in_use.get(&asset_id)
+                && let Some(token_id) = token_ids.iter().find(|token_id| in_use.contains(*token_id))
+            {
+                return Err(ATokenError::ConflictingTx {
+                    asset_id,
+                    token_id: *token_id,
+                });
+            }
+            self.in_use.entry(asset_id).or_default().extend(token_ids);
+        }
+
+        *self.queued_by_sender.entry(from.clone()).or_default() += 1;
+        self.pending.push(tx);
+        Ok(())
+    }
+
+    /// Puts `txs` taken for a block that could not be sealed back at the head of the queue,
+    /// ahead of the txs submitted after them.
+    pub fn restore(&mut self, chain: &ATokenChain, mut txs: Vec<SignedTx>) {
+        txs.append(&mut self.pending);
+        self.pending = txs;
+        self.reindex(chain);
+    }
+
+    /// Rebuilds the token and sender indexes from `pending`.
+    fn reindex(&mut self, chain: &ATokenChain) {
+        self.in_use.clear();
+        self.queued_by_sender.clear();
+        for tx in &self.pending {
+            if let Some(asset_id) = tx.unsigned.payload.asset_id() {
+                self.in_use
+                    .entry(asset_id)
+                    .or_default()
+                    .extend(chain.token_ids_of(tx).unwrap_or_default());
+            }
+            *self
+                .queued_by_sender
+                .entry(tx.unsigned.from.clone())
+                .or_default() += 1;
+        }
+    }
+
+    /// Empties the pool, returning the txs that are still valid on `chain` in submission
+    /// order. A tx made stale by a block committed meanwhile is dropped, along with the
+    /// sender's later txs that depended on its nonce.
+    pub fn drain_valid(&mut self, chain: &ATokenChain) -> Vec<SignedTx> {
+        self.take_valid(chain, usize::MAX)
+    }
+
+    /// `drain_valid` that stops at `max_txs` valid txs and leaves the ones after them queued.
+    pub fn take_valid(&mut self, chain: &ATokenChain, max_txs: usize) -> Vec<SignedTx> {
+        self.take_valid_at(chain, max_txs, chain.next_block_time())
+    }
+
+    /// `take_valid` for a block stamped `block_time_ms`. Sealing passes the same time to
+    /// `ATokenChain::build_block_at`, so no tx expires between being picked and applied.
+    pub fn take_valid_at(
+        &mut self,
+        chain: &ATokenChain,
+        max_txs: usize,
+        block_time_ms: u64,
+    ) -> Vec<SignedTx> {
+        let mut next_nonce: HashMap<Address, u64> = HashMap::new();
+        let mut stalled = HashSet::new();
+        let mut valid = Vec::new();
+        let mut rest = Vec::new();
+        for tx in std::mem::take(&mut self.pending) {
+            if valid.len() == max_txs {
+                rest.push(tx);
+                continue;
+            }
+            let from = tx.unsigned.from.clone();
+            if stalled.contains(&from) {
+                continue;
+            }
+            let expected = *next_nonce
+                .entry(from.clone())
+                .or_insert_with(|| chain.next_nonce(&from));
+            if chain.validate_tx_at(&tx, expected, block_time_ms).is_ok() {
+                next_nonce.insert(from, expected + 1);
+                valid.push(tx);
+            } else {
+                stalled.insert(from);
+            }
+        }
+        self.pending = rest;
+        self.reindex(chain);
+        valid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::crypto::Wallet;
+    use crate::model::UnsignedTx;
+    use crate::test_support::{CHAIN_ID, minted_chain};
+
+    fn transfer(from: &Wallet, nonce: u64, to: &Wallet, token_ids: Vec<u64>) -> SignedTx {
+        SignedTx::sign(
+            UnsignedTx::transfer(CHAIN_ID, from.address(), nonce, 0, to.address(), token_ids),
+            from,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn consecutive_transfers_land_in_one_block() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let mut pool = Mempool::default();
+
+        pool.submit(&chain, transfer(&issuer, 2, &alice, vec![0, 1]))
+            .unwrap();
+        pool.submit(&chain, transfer(&issuer, 3, &alice, vec![2]))
+            .unwrap();
+        assert_eq!(pool.len(), 2);
+
+        let txs = pool.drain_valid(&chain);
+        assert!(pool.is_empty());
+        let block = chain.build_block(&issuer, txs).unwrap();
+        assert_eq!(block.txs.len(), 2);
+        chain.append_block(block).unwrap();
+
+        assert_eq!(chain.blocks.len(), 2);
+        assert_eq!(chain.balance_of(0, &alice.address()), 3);
+        assert_eq!(chain.next_nonce(&issuer.address()), 4);
+    }
+
+    #[test]
+    fn conflicting_and_out_of_order_txs_are_rejected() {
+        let (issuer, chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let mut pool = Mempool::default();
+        pool.submit(&chain, transfer(&issuer, 2, &alice, vec![0, 1]))
+            .unwrap();
+
+        let err = pool
+            .submit(&chain, transfer(&issuer, 3, &alice, vec![1, 2]))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::ConflictingTx {
+                asset_id: 0,
+                token_id: 1
+            }
+        ));
+        let err = pool
+            .submit(&chain, transfer(&issuer, 2, &alice, vec![3]))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ATokenError::NonceMismatch {
+                expected: 3,
+                actual: 2
+            }
+        ));
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn a_full_pool_refuses_txs() {
+        let (issuer, chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let mut pool = Mempool::new(1);
+        pool.submit(&chain, transfer(&issuer, 2, &alice, vec![0]))
+            .unwrap();
+
+        let err = pool
+            .submit(&chain, transfer(&issuer, 3, &alice, vec![1]))
+            .unwrap_err();
+        assert!(matches!(err, ATokenError::MempoolFull(1)));
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn txs_of_a_failed_seal_are_restored_ahead_of_the_rest() {
+        let (issuer, chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let mut pool = Mempool::default();
+        for (nonce, token_id) in [(2, 0), (3, 1), (4, 2)] {
+            pool.submit(&chain, transfer(&issuer, nonce, &alice, vec![token_id]))
+                .unwrap();
+        }
+        let ids: Vec<String> = pool.pending().iter().map(|tx| tx.id.clone()).collect();
+
+        let taken = pool.take_valid(&chain, 2);
+        assert_eq!(pool.len(), 1);
+
+        pool.restore(&chain, taken);
+        let restored: Vec<String> = pool.pending().iter().map(|tx| tx.id.clone()).collect();
+        assert_eq!(restored, ids);
+        assert!(matches!(
+            pool.submit(&chain, transfer(&issuer, 5, &alice, vec![1])),
+            Err(ATokenError::ConflictingTx { token_id: 1, .. })
+        ));
+        pool.submit(&chain, transfer(&issuer, 5, &alice, vec![3]))
+            .unwrap();
+    }
+
+    #[test]
+    fn stale_txs_are_dropped_when_sealing() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let bob = Wallet::generate();
+        let mut pool = Mempool::default();
+        pool.submit(&chain, transfer(&issuer, 2, &alice, vec![0]))
+            .unwrap();
+        pool.submit(&chain, transfer(&issuer, 3, &alice, vec![1]))
+            .unwrap();
+
+        // A direct transfer takes nonce 2 first, so both pending txs from the issuer go stale
+        let direct = chain
+            .build_block(&issuer, vec![transfer(&issuer, 2, &bob, vec![5])])
+            .unwrap();
+        chain.append_block(direct).unwrap();
+
+        assert!(pool.drain_valid(&chain).is_empty());
+    }
+
+    #[test]
+    fn txs_past_the_limit_stay_queued() {
+        let (issuer, mut chain) = minted_chain(10);
+        let alice = Wallet::generate();
+        let mut pool = Mempool::default();
+        for (nonce, token_id) in [(2, 0), (3, 1), (4, 2)] {
+            pool.submit(&chain, transfer(&issuer, nonce, &alice, vec![token_id]))
+                .unwrap();
+        }
+
+        let first = pool.take_valid(&chain, 2);
+        assert_eq!(first.len(), 2);
+        assert_eq!(pool.len(), 1);
+        let block = chain.build_block(&issuer, first).unwrap();
+        chain.append_block(block).unwrap();
+
+        let rest = pool.take_valid(&chain, 2);
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].unsigned.nonce, 4);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn txs_are_applied_at_the_time_they_were_picked_for() {
+        let (issuer, chain) = minted_chain(10);
+        let deadline = chain.blocks[0].header.timestamp_ms + 1_000;
+        let clock = MockClock::new(deadline);
+        let mut chain = chain.with_clock(clock.clone());
+        let alice = Wallet::generate();
+        let mut pool = Mempool::default();
+        let expiring = SignedTx::sign(
+            UnsignedTx::transfer(CHAIN_ID, issuer.address(), 2, 0, alice.address(), vec![0])
+                .with_valid_until(Some(deadline)),
+            &issuer,
+        )
+        .unwrap();
+        pool.submit(&chain, expiring).unwrap();
+
+        let block_time_ms = chain.next_block_time();
+        let txs = pool.take_valid_at(&chain, 10, block_time_ms);
+        assert_eq!(txs.len(), 1);
+        // The clock ticks past the deadline before the block is stamped
+        clock.advance(1);
+
+        let restamped = chain.build_block(&issuer, txs.clone()).unwrap();
+        assert!(matches!(
+            chain.append_block(restamped).unwrap_err(),
+            ATokenError::Expired { .. }
+        ));
+        let block = chain.build_block_at(&issuer, txs, block_time_ms).unwrap();
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.balance_of(0, &alice.address()), 1);
+    }
+}
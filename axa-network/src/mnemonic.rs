@@ -0,0 +1,119 @@
+//! BIP39 recovery phrases. The phrase and passphrase give a 64-byte BIP39 seed, and the
+//! ed25519 key is derived from it with SLIP-0010 along [`DERIVATION_PATH`]. Every level is
+//! hardened, since SLIP-0010 has no normal derivation for ed25519.
+
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha2::Sha512;
+
+use crate::crypto::Wallet;
+use crate::errors::{ATokenError, Result};
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// SLIP-44 coin type of AToken, `"ATK"` in ASCII. Not registered.
+pub const COIN_TYPE: u32 = 0x0041_544b;
+/// `m/44'/COIN_TYPE'/0'/0'/0'`, hardened indices listed without the hardening bit
+pub const DERIVATION_PATH: [u32; 5] = [44, COIN_TYPE, 0, 0, 0];
+/// 256 bits of entropy, a 24-word phrase
+const ENTROPY_LEN: usize = 32;
+const HARDENED: u32 = 0x8000_0000;
+
+impl Wallet {
+    /// New wallet along with the 24-word English phrase it can be recovered from, under an
+    /// empty passphrase.
+    pub fn generate_mnemonic() -> (String, Self) {
+        let mut entropy = [0u8; ENTROPY_LEN];
+        OsRng.fill_bytes(&mut entropy);
+        let mnemonic =
+            Mnemonic::from_entropy(&entropy).expect("32 bytes is a valid BIP39 entropy length");
+        let wallet = Self::from_seed(&mnemonic.to_seed(""));
+        (mnemonic.to_string(), wallet)
+    }
+
+    /// Recovers the wallet of an English BIP39 phrase. A different `passphrase` gives a
+    /// different, equally valid wallet.
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self> {
+        let mnemonic =
+            Mnemonic::parse(phrase).map_err(|e| ATokenError::InvalidMnemonic(e.to_string()))?;
+        Ok(Self::from_seed(&mnemonic.to_seed(passphrase)))
+    }
+
+    fn from_seed(seed: &[u8]) -> Self {
+        Self::from_private_key_bytes(&derive_slip10(seed, &DERIVATION_PATH))
+    }
+}
+
+/// SLIP-0010 ed25519 private key at the hardened `path` below the master key of `seed`.
+fn derive_slip10(seed: &[u8], path: &[u32]) -> [u8; 32] {
+    let (mut key, mut chain_code) = hmac_split(b"ed25519 seed", &[seed]);
+    for index in path {
+        (key, chain_code) = hmac_split(
+            &chain_code,
+            &[&[0], &key, &(index | HARDENED).to_be_bytes()],
+        );
+    }
+    key
+}
+
+/// HMAC-SHA512 of `parts` under `key`, split into the child key and chain code.
+fn hmac_split(key: &[u8], parts: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = HmacSha512::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    let out = mac.finalize().into_bytes();
+    let (left, right) = out.split_at(32);
+    (
+        left.try_into().expect("32 bytes"),
+        right.try_into().expect("32 bytes"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn slip10_matches_the_published_ed25519_vector() {
+        // SLIP-0010 test vector 1 for ed25519
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        assert_eq!(
+            hex::encode(derive_slip10(&seed, &[])),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            hex::encode(derive_slip10(&seed, &[0])),
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"
+        );
+        assert_eq!(
+            hex::encode(derive_slip10(&seed, &[0, 1])),
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2"
+        );
+    }
+
+    #[test]
+    fn known_phrase_recovers_a_fixed_address() {
+        let wallet = Wallet::from_mnemonic(PHRASE, "").unwrap();
+        assert_eq!(wallet.address(), "F451AAdceffDeD8BA7e97BB1d064BefA2ccD9777");
+        assert_ne!(
+            Wallet::from_mnemonic(PHRASE, "TREZOR").unwrap().address(),
+            wallet.address()
+        );
+
+        let (phrase, generated) = Wallet::generate_mnemonic();
+        assert_eq!(phrase.split_whitespace().count(), 24);
+        assert_eq!(
+            Wallet::from_mnemonic(&phrase, "").unwrap().address(),
+            generated.address()
+        );
+
+        assert!(matches!(
+            Wallet::from_mnemonic(&PHRASE.replace("about", "abandon"), ""),
+            Err(ATokenError::InvalidMnemonic(_))
+        ));
+    }
+}
@@ -1,65 +1,317 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::canonical;
 use crate::crypto::{
-    Address, Wallet, address_from_public_key, now_ms, sha256_hex, verify_signature_hex,
+    Address, HashAlgorithm, Wallet, address_from_public_key_with, now_ms, sha256_hex,
+    verify_signature_hex,
 };
 use crate::errors::{ATokenError, Result};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Asset created by a `Mint`, numbered from 0 in the order the mints are applied.
+pub type AssetId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct TokenMetadata {
     pub name: String,
     pub symbol: String,
     pub description: String,
+    /// Display hint only: tokens are indivisible ids and balances are whole counts, so
+    /// anything but 0 just tells wallets where to put a decimal point.
     pub decimals: u8,
     pub issuer: Address,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TokenMetadata {
+    pub const MAX_SYMBOL_LEN: usize = 16;
+    pub const MAX_NAME_LEN: usize = 64;
+    pub const MAX_DESCRIPTION_LEN: usize = 1024;
+    pub const MAX_DECIMALS: u8 = 18;
+
+    /// Bounds checked at mint; lengths are in characters.
+    pub fn validate(&self) -> Result<()> {
+        check_len("symbol", &self.symbol, 1, Self::MAX_SYMBOL_LEN)?;
+        Self::validate_editable(&self.name, &self.description)?;
+        if self.decimals > Self::MAX_DECIMALS {
+            return Err(ATokenError::InvalidMetadata(format!(
+                "decimals must be at most {}, got {}",
+                Self::MAX_DECIMALS,
+                self.decimals
+            )));
+        }
+        Ok(())
+    }
+
+    /// Bounds of the fields `UpdateMetadata` can change.
+    pub fn validate_editable(name: &str, description: &str) -> Result<()> {
+        check_len("name", name, 1, Self::MAX_NAME_LEN)?;
+        check_len("description", description, 0, Self::MAX_DESCRIPTION_LEN)
+    }
+}
+
+fn check_len(field: &str, value: &str, min: usize, max: usize) -> Result<()> {
+    let len = value.chars().count();
+    if len < min || len > max {
+        return Err(ATokenError::InvalidMetadata(format!(
+            "{field} must be {min} to {max} characters, got {len}"
+        )));
+    }
+    Ok(())
+}
+
+/// Token ids are numbered per asset, so every payload except `Mint` names its asset.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum TxPayload {
+    /// Creates a new asset with `amount` tokens, owned by the sender unless `distribution`
+    /// hands out contiguous id ranges: `(recipient, count)` pairs in id order.
     Mint {
         amount: u64,
         metadata: TokenMetadata,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        distribution: Vec<(Address, u64)>,
     },
+    /// `memo` is a free-form note for the recipient's books, at most `MAX_MEMO_LEN`
+    /// characters; it is signed with the rest of the payload. With `locked_until_ms` the
+    /// recipient cannot move the tokens before a block with that timestamp.
     Transfer {
+        asset_id: AssetId,
         token_ids: Vec<u64>,
         to: Address,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        memo: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        locked_until_ms: Option<u64>,
     },
+    /// Transfer of whole inclusive ranges of ids, so large contiguous transfers stay small.
+    TransferRanges {
+        asset_id: AssetId,
+        token_ranges: Vec<(u64, u64)>,
+        to: Address,
+    },
+    /// Permanently removes the sender's tokens from the asset's supply.
+    Burn {
+        asset_id: AssetId,
+        token_ids: Vec<u64>,
+    },
+    /// Issuer-only correction of an asset's name and description. `symbol` and `decimals`
+    /// stay as minted: clients key on the symbol and decimals change what amounts mean.
+    UpdateMetadata {
+        asset_id: AssetId,
+        name: String,
+        description: String,
+    },
+}
+
+impl TxPayload {
+    pub const MAX_MEMO_LEN: usize = 256;
+
+    /// Asset the payload acts on; `None` for a mint.
+    pub fn asset_id(&self) -> Option<AssetId> {
+        match self {
+            TxPayload::Mint { .. } => None,
+            TxPayload::Transfer { asset_id, .. }
+            | TxPayload::TransferRanges { asset_id, .. }
+            | TxPayload::Burn { asset_id, .. }
+            | TxPayload::UpdateMetadata { asset_id, .. } => Some(*asset_id),
+        }
+    }
+
+    /// Length bound of a transfer memo, in characters.
+    pub fn validate_memo(memo: &str) -> Result<()> {
+        let len = memo.chars().count();
+        if len > Self::MAX_MEMO_LEN {
+            return Err(ATokenError::MemoTooLong {
+                max: Self::MAX_MEMO_LEN,
+                actual: len,
+            });
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Version of the signed transaction payload; bump on any change to what gets signed.
+/// Version 3 signs the field-by-field encoding from [`crate::canonical`]; version 4 came
+/// with domain-separated addresses, which changed every sender.
+pub const TX_VERSION: u16 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UnsignedTx {
+    pub version: u16,
+    /// Chain the signature is valid on, so a tx cannot be replayed on another chain.
+    pub chain_id: String,
     pub from: Address,
     pub nonce: u64,
     pub timestamp_ms: u64,
+    /// Last block timestamp the tx may land at, so a leaked signed tx is not valid forever
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until_ms: Option<u64>,
     pub payload: TxPayload,
 }
 
 impl UnsignedTx {
-    pub fn mint(from: Address, nonce: u64, amount: u64, metadata: TokenMetadata) -> Self {
+    pub fn mint(
+        chain_id: impl Into<String>,
+        from: Address,
+        nonce: u64,
+        amount: u64,
+        metadata: TokenMetadata,
+    ) -> Self {
+        Self::mint_distributed(chain_id, from, nonce, amount, metadata, Vec::new())
+    }
+
+    /// Mint that splits `amount` between recipients; the counts must sum to `amount`.
+    pub fn mint_distributed(
+        chain_id: impl Into<String>,
+        from: Address,
+        nonce: u64,
+        amount: u64,
+        metadata: TokenMetadata,
+        distribution: Vec<(Address, u64)>,
+    ) -> Self {
         Self {
+            version: TX_VERSION,
+            chain_id: chain_id.into(),
             from,
             nonce,
             timestamp_ms: now_ms(),
-            payload: TxPayload::Mint { amount, metadata },
+            valid_until_ms: None,
+            payload: TxPayload::Mint {
+                amount,
+                metadata,
+                distribution,
+            },
         }
     }
 
-    pub fn transfer(from: Address, nonce: u64, to: Address, token_ids: Vec<u64>) -> Self {
+    pub fn transfer(
+        chain_id: impl Into<String>,
+        from: Address,
+        nonce: u64,
+        asset_id: AssetId,
+        to: Address,
+        token_ids: Vec<u64>,
+    ) -> Self {
+        Self::transfer_with_memo(chain_id, from, nonce, asset_id, to, token_ids, None)
+    }
+
+    pub fn transfer_with_memo(
+        chain_id: impl Into<String>,
+        from: Address,
+        nonce: u64,
+        asset_id: AssetId,
+        to: Address,
+        token_ids: Vec<u64>,
+        memo: Option<String>,
+    ) -> Self {
         Self {
+            version: TX_VERSION,
+            chain_id: chain_id.into(),
             from,
             nonce,
             timestamp_ms: now_ms(),
-            payload: TxPayload::Transfer { token_ids, to },
+            valid_until_ms: None,
+            payload: TxPayload::Transfer {
+                asset_id,
+                token_ids,
+                to,
+                memo,
+                locked_until_ms: None,
+            },
         }
     }
 
-    pub fn signing_bytes(&self) -> Result<Vec<u8>> {
-        serde_json::to_vec(self).map_err(|e| ATokenError::Serialization(e.to_string()))
+    /// Expires the tx after `until_ms`: a block with a later timestamp rejects it.
+    pub fn with_valid_until(mut self, until_ms: Option<u64>) -> Self {
+        self.valid_until_ms = until_ms;
+        self
+    }
+
+    /// Locks the tokens of a `Transfer` at the recipient until `locked_until_ms`; other
+    /// payloads are returned unchanged.
+    pub fn with_lock_until(mut self, until_ms: Option<u64>) -> Self {
+        if let TxPayload::Transfer {
+            locked_until_ms, ..
+        } = &mut self.payload
+        {
+            *locked_until_ms = until_ms;
+        }
+        self
+    }
+
+    pub fn transfer_ranges(
+        chain_id: impl Into<String>,
+        from: Address,
+        nonce: u64,
+        asset_id: AssetId,
+        to: Address,
+        token_ranges: Vec<(u64, u64)>,
+    ) -> Self {
+        Self {
+            version: TX_VERSION,
+            chain_id: chain_id.into(),
+            from,
+            nonce,
+            timestamp_ms: now_ms(),
+            valid_until_ms: None,
+            payload: TxPayload::TransferRanges {
+                asset_id,
+                token_ranges,
+                to,
+            },
+        }
+    }
+
+    pub fn burn(
+        chain_id: impl Into<String>,
+        from: Address,
+        nonce: u64,
+        asset_id: AssetId,
+        token_ids: Vec<u64>,
+    ) -> Self {
+        Self {
+            version: TX_VERSION,
+            chain_id: chain_id.into(),
+            from,
+            nonce,
+            timestamp_ms: now_ms(),
+            valid_until_ms: None,
+            payload: TxPayload::Burn {
+                asset_id,
+                token_ids,
+            },
+        }
+    }
+
+    pub fn update_metadata(
+        chain_id: impl Into<String>,
+        from: Address,
+        nonce: u64,
+        asset_id: AssetId,
+        name: String,
+        description: String,
+    ) -> Self {
+        Self {
+            version: TX_VERSION,
+            chain_id: chain_id.into(),
+            from,
+            nonce,
+            timestamp_ms: now_ms(),
+            valid_until_ms: None,
+            payload: TxPayload::UpdateMetadata {
+                asset_id,
+                name,
+                description,
+            },
+        }
+    }
+
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        canonical::tx_signing_bytes(self)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SignedTx {
     pub id: String,
     pub unsigned: UnsignedTx,
@@ -68,14 +320,19 @@ pub struct SignedTx {
 }
 
 impl SignedTx {
+    /// Signs `unsigned`, whose sender must be the wallet's address under either hash
+    /// algorithm.
     pub fn sign(unsigned: UnsignedTx, wallet: &Wallet) -> Result<Self> {
-        if unsigned.from != wallet.address() {
+        if !HashAlgorithm::ALL
+            .into_iter()
+            .any(|algorithm| wallet.address_for(algorithm) == unsigned.from)
+        {
             return Err(ATokenError::InvalidSender);
         }
-        let message = unsigned.signing_bytes()?;
+        let message = unsigned.signing_bytes();
         let signature_hex = wallet.sign_hex(&message);
         let public_key_hex = wallet.public_key_hex();
-        let id = Self::compute_id(&unsigned, &public_key_hex, &signature_hex)?;
+        let id = Self::compute_id(&unsigned, &public_key_hex, &signature_hex);
         Ok(Self {
             id,
             unsigned,
@@ -84,8 +341,19 @@ impl SignedTx {
         })
     }
 
+    /// `verify_for` a SHA-256 chain.
     pub fn verify(&self) -> Result<()> {
-        let message = self.unsigned.signing_bytes()?;
+        self.verify_for(HashAlgorithm::Sha256)
+    }
+
+    /// Checks the version, the signature, that the key resolves to the sender under
+    /// `hash_algorithm` and the id.
+    pub fn verify_for(&self, hash_algorithm: HashAlgorithm) -> Result<()> {
+        if self.unsigned.version != TX_VERSION {
+            return Err(ATokenError::UnsupportedTxVersion(self.unsigned.version));
+        }
+
+        let message = self.unsigned.signing_bytes();
         verify_signature_hex(&self.public_key_hex, &self.signature_hex, &message)?;
 
         let public_key_bytes = hex::decode(&self.public_key_hex)
@@ -100,13 +368,13 @@ impl SignedTx {
             .map_err(|_| ATokenError::InvalidPublicKeyLength(public_key_len))?;
         let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(&public_key_arr)
             .map_err(|_| ATokenError::InvalidPublicKeyLength(public_key_len))?;
-        let resolved_address = address_from_public_key(&verifying_key);
+        let resolved_address = address_from_public_key_with(&verifying_key, hash_algorithm);
         if resolved_address != self.unsigned.from {
             return Err(ATokenError::InvalidSender);
         }
 
         let expected_id =
-            Self::compute_id(&self.unsigned, &self.public_key_hex, &self.signature_hex)?;
+            Self::compute_id(&self.unsigned, &self.public_key_hex, &self.signature_hex);
         if expected_id != self.id {
             return Err(ATokenError::TransactionIdMismatch);
         }
@@ -114,19 +382,24 @@ impl SignedTx {
         Ok(())
     }
 
-    fn compute_id(
-        unsigned: &UnsignedTx,
-        public_key_hex: &str,
-        signature_hex: &str,
-    ) -> Result<String> {
-        let bytes = serde_json::to_vec(&(unsigned, public_key_hex, signature_hex))
-            .map_err(|e| ATokenError::Serialization(e.to_string()))?;
-        Ok(sha256_hex(&bytes))
+    fn compute_id(unsigned: &UnsignedTx, public_key_hex: &str, signature_hex: &str) -> String {
+        sha256_hex(&canonical::tx_id_bytes(
+            unsigned,
+            public_key_hex,
+            signature_hex,
+        ))
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Version of the block format; bump on any change to how blocks are hashed or signed.
+/// Version 2 domain-separates the leaves and inner nodes of the tx Merkle tree.
+pub const BLOCK_VERSION: u16 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BlockHeader {
+    /// [`BLOCK_VERSION`]; blocks stored before the field read as `0` and are refused
+    #[serde(default)]
+    pub version: u16,
     pub chain_id: String,
     pub height: u64,
     pub previous_hash: Option<String>,
@@ -134,9 +407,14 @@ pub struct BlockHeader {
     pub proposer: Address,
     pub proposer_public_key_hex: String,
     pub timestamp_ms: u64,
+    /// Root of the Merkle tree over the block's tx ids, see [`Block::tx_merkle_root`]
+    pub tx_merkle_root: String,
+    /// The chain's, fixed by its genesis block; left out of the JSON for SHA-256
+    #[serde(default, skip_serializing_if = "HashAlgorithm::is_sha256")]
+    pub hash_algorithm: HashAlgorithm,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Block {
     pub header: BlockHeader,
     pub txs: Vec<SignedTx>,
@@ -145,24 +423,166 @@ pub struct Block {
 }
 
 impl Block {
-    pub fn previous_signature_message(header: &BlockHeader) -> Result<Vec<u8>> {
-        serde_json::to_vec(&(
-            header.chain_id.as_str(),
-            header.height,
-            header.previous_three_hashes.as_slice(),
-        ))
-        .map_err(|e| ATokenError::Serialization(e.to_string()))
+    /// What the proposer signs: the whole header, timestamp and tx root included.
+    pub fn previous_signature_message(header: &BlockHeader) -> Vec<u8> {
+        canonical::block_signing_bytes(header)
     }
 
+    /// Hash over the whole header, every tx and the proposer's signature, with the header's
+    /// hash algorithm.
     pub fn calculate_hash(
         header: &BlockHeader,
         txs: &[SignedTx],
         previous_signature_hex: &str,
-    ) -> Result<String> {
-        let payload = serde_json::to_vec(&(header, txs, previous_signature_hex))
-            .map_err(|e| ATokenError::Serialization(e.to_string()))?;
-        let mut hasher = Sha256::new();
-        hasher.update(payload);
-        Ok(hex::encode(hasher.finalize()))
+    ) -> String {
+        header.hash_algorithm.hex(&canonical::block_hash_bytes(
+            header,
+            txs,
+            previous_signature_hex,
+        ))
+    }
+
+    /// Merkle root over the tx ids in block order: leaves are the raw id digests, each
+    /// parent is `sha256(left || right)` and an odd node at the end of a level is carried up
+    /// unchanged. A block without txs gets the hash of the empty input.
+    pub fn tx_merkle_root(txs: &[SignedTx]) -> Result<String> {
+        let mut level = merkle_leaves(txs)?;
+        if level.is_empty() {
+            return Ok(sha256_hex(&[]));
+        }
+        while level.len() > 1 {
+            level = merkle_parents(&level);
+        }
+        Ok(hex::encode(&level[0]))
+    }
+
+    /// Sibling hashes from the leaf of `txs[index]` up to the root. Levels where the node is
+    /// carried up unpaired add no step.
+    pub fn tx_merkle_proof(txs: &[SignedTx], index: usize) -> Result<Vec<MerkleProofStep>> {
+        let mut level = merkle_leaves(txs)?;
+        if index >= level.len() {
+            return Err(ATokenError::UnknownTransaction(format!("index {index}")));
+        }
+
+        let mut index = index;
+        let mut proof = Vec::new();
+        while level.len() > 1 {
+            let sibling = index ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                proof.push(MerkleProofStep {
+                    side: if sibling < index {
+                        MerkleSide::Left
+                    } else {
+                        MerkleSide::Right
+                    },
+                    hash: hex::encode(hash),
+                });
+            }
+            level = merkle_parents(&level);
+            index /= 2;
+        }
+        Ok(proof)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MerkleSide {
+    Left,
+    Right,
+}
+
+/// One level of a Merkle inclusion proof: the sibling hash and which side it sits on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MerkleProofStep {
+    pub side: MerkleSide,
+    pub hash: String,
+}
+
+/// Whether `proof` leads from `tx_id` to `root`, i.e. the tx is in the block with that
+/// `tx_merkle_root`.
+pub fn verify_tx_merkle_proof(tx_id: &str, proof: &[MerkleProofStep], root: &str) -> bool {
+    let Ok(tx_id) = hex::decode(tx_id) else {
+        return false;
+    };
+    let mut node = merkle_leaf(&tx_id);
+    for step in proof {
+        let Ok(sibling) = hex::decode(&step.hash) else {
+            return false;
+        };
+        node = match step.side {
+            MerkleSide::Left => merkle_parent(&sibling, &node),
+            MerkleSide::Right => merkle_parent(&node, &sibling),
+        };
+    }
+    hex::encode(node) == root
+}
+
+fn merkle_leaves(txs: &[SignedTx]) -> Result<Vec<Vec<u8>>> {
+    txs.iter()
+        .map(|tx| {
+            let id =
+                hex::decode(&tx.id).map_err(|e| ATokenError::HexDecode(format!("tx id: {e}")))?;
+            Ok(merkle_leaf(&id))
+        })
+        .collect()
+}
+
+fn merkle_leaf(tx_id: &[u8]) -> Vec<u8> {
+    Sha256::digest(canonical::merkle_leaf_bytes(tx_id)).to_vec()
+}
+
+fn merkle_parents(level: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => merkle_parent(left, right),
+            [single] => single.clone(),
+            _ => unreachable!("chunks(2) yields one or two nodes"),
+        })
+        .collect()
+}
+
+fn merkle_parent(left: &[u8], right: &[u8]) -> Vec<u8> {
+    Sha256::digest(canonical::merkle_node_bytes(left, right)).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(symbol: usize, name: usize, description: usize, decimals: u8) -> TokenMetadata {
+        TokenMetadata {
+            name: "n".repeat(name),
+            symbol: "S".repeat(symbol),
+            description: "d".repeat(description),
+            decimals,
+            issuer: String::new(),
+        }
+    }
+
+    #[test]
+    fn metadata_bounds_are_inclusive() {
+        metadata(1, 1, 0, 0).validate().unwrap();
+        metadata(16, 64, 1024, 18).validate().unwrap();
+        // Characters, not bytes
+        let mut wide = metadata(1, 1, 0, 0);
+        wide.symbol = "Ж".repeat(16);
+        wide.validate().unwrap();
+
+        for (bad, field) in [
+            (metadata(0, 1, 0, 0), "symbol"),
+            (metadata(17, 1, 0, 0), "symbol"),
+            (metadata(1, 0, 0, 0), "name"),
+            (metadata(1, 65, 0, 0), "name"),
+            (metadata(1, 1, 1025, 0), "description"),
+            (metadata(1, 1, 0, 19), "decimals"),
+        ] {
+            let err = bad.validate().unwrap_err();
+            assert!(
+                matches!(&err, ATokenError::InvalidMetadata(reason) if reason.starts_with(field)),
+                "{err}"
+            );
+        }
     }
 }
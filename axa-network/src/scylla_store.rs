@@ -1,11 +1,30 @@
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+
 use crate::errors::{ATokenError, Result};
 use crate::model::Block;
+use crate::storage::BlockStore;
 
+use scylla::batch::{Batch, BatchType};
+use scylla::prepared_statement::PreparedStatement;
+use scylla::statement::PagingState;
 use scylla::{Session, SessionBuilder};
 
+/// Blocks per partition: block `h` lives in bucket `h / BLOCKS_PER_BUCKET`, clustered by
+/// height, so partitions stay bounded and reading buckets in turn yields blocks in order.
+const BLOCKS_PER_BUCKET: u64 = 10_000;
+const LOAD_PAGE_SIZE: i32 = 500;
+/// Blocks per batch in `save_blocks`, to stay under the server's batch size limits
+const SAVE_BATCH_SIZE: usize = 50;
+
+type BlockRow = (i64, i64, String, String);
+
 pub struct ScyllaBlockStore {
     session: Session,
-    keyspace: String,
+    blocks_per_bucket: u64,
+    insert_block: PreparedStatement,
+    select_blocks: PreparedStatement,
 }
 
 impl ScyllaBlockStore {
@@ -15,75 +34,206 @@ impl ScyllaBlockStore {
             .build()
             .await
             .map_err(|e| ATokenError::Storage(e.to_string()))?;
-        let this = Self {
+        Self::ensure_schema(&session, keyspace).await?;
+
+        let insert_block = session
+            .prepare(format!(
+                "INSERT INTO {keyspace}.chain_blocks_by_bucket (bucket, height, hash, block_json) VALUES (?, ?, ?, ?)"
+            ))
+            .await
+            .map_err(|e| ATokenError::Storage(e.to_string()))?;
+        let mut select_blocks = session
+            .prepare(format!(
+                "SELECT block_json FROM {keyspace}.chain_blocks_by_bucket WHERE bucket = ? ORDER BY height ASC"
+            ))
+            .await
+            .map_err(|e| ATokenError::Storage(e.to_string()))?;
+        select_blocks.set_page_size(LOAD_PAGE_SIZE);
+
+        Ok(Self {
             session,
-            keyspace: keyspace.to_string(),
-        };
-        this.ensure_schema().await?;
-        Ok(this)
+            blocks_per_bucket: BLOCKS_PER_BUCKET,
+            insert_block,
+            select_blocks,
+        })
     }
 
-    pub async fn ensure_schema(&self) -> Result<()> {
+    async fn ensure_schema(session: &Session, keyspace: &str) -> Result<()> {
         let create_keyspace = format!(
             "CREATE KEYSPACE IF NOT EXISTS {} \
              WITH replication = {{'class': 'SimpleStrategy', 'replication_factor': 1}}",
-            self.keyspace
+            keyspace
         );
-        self.session
+        session
             .query_unpaged(create_keyspace, &[])
             .await
             .map_err(|e| ATokenError::Storage(e.to_string()))?;
 
+        // `height` can't be the partition key: Scylla only orders rows by clustering columns
         let create_table = format!(
-            "CREATE TABLE IF NOT EXISTS {}.blocks (height bigint PRIMARY KEY, hash text, block_json text)",
-            self.keyspace
+            "CREATE TABLE IF NOT EXISTS {}.chain_blocks_by_bucket (\
+             bucket bigint, height bigint, hash text, block_json text, \
+             PRIMARY KEY ((bucket), height)) \
+             WITH CLUSTERING ORDER BY (height ASC)",
+            keyspace
         );
-        self.session
+        session
             .query_unpaged(create_table, &[])
             .await
             .map_err(|e| ATokenError::Storage(e.to_string()))?;
         Ok(())
     }
 
-    pub async fn save_block(&self, block: &Block) -> Result<()> {
-        let query = format!(
-            "INSERT INTO {}.blocks (height, hash, block_json) VALUES (?, ?, ?)",
-            self.keyspace
-        );
+    /// Writes `blocks` in batches, for reloading a long chain without a round trip per block.
+    pub async fn save_blocks(&self, blocks: &[Block]) -> Result<()> {
+        for chunk in blocks.chunks(SAVE_BATCH_SIZE) {
+            let mut batch = Batch::new(BatchType::Unlogged);
+            let mut values = Vec::with_capacity(chunk.len());
+            for block in chunk {
+                batch.append_statement(self.insert_block.clone());
+                values.push(self.block_row(block)?);
+            }
+            self.session
+                .batch(&batch, values)
+                .await
+                .map_err(|e| ATokenError::Storage(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn block_row(&self, block: &Block) -> Result<BlockRow> {
         let json = serde_json::to_string(block).map_err(|e| ATokenError::Storage(e.to_string()))?;
+        let height = block.header.height;
+        Ok((
+            (height / self.blocks_per_bucket) as i64,
+            height as i64,
+            block.hash.clone(),
+            json,
+        ))
+    }
+
+    /// Every block of `bucket`, in height order, a page at a time.
+    async fn load_bucket(&self, bucket: i64) -> Result<Vec<Block>> {
+        let mut blocks = Vec::new();
+        let mut paging_state = PagingState::start();
+        loop {
+            let (result, paging_response) = self
+                .session
+                .execute_single_page(&self.select_blocks, (bucket,), paging_state)
+                .await
+                .map_err(|e| ATokenError::Storage(e.to_string()))?;
+            let rows_result = result
+                .into_rows_result()
+                .map_err(|e| ATokenError::Storage(e.to_string()))?;
+            let rows = rows_result
+                .rows::<(String,)>()
+                .map_err(|e| ATokenError::Storage(e.to_string()))?;
+
+            for row in rows {
+                let (block_json,): (String,) =
+                    row.map_err(|e| ATokenError::Storage(e.to_string()))?;
+                let block: Block = serde_json::from_str(&block_json)
+                    .map_err(|e| ATokenError::Storage(e.to_string()))?;
+                blocks.push(block);
+            }
+
+            match paging_response.into_paging_control_flow() {
+                ControlFlow::Continue(next) => paging_state = next,
+                ControlFlow::Break(()) => break,
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+#[async_trait]
+impl BlockStore for ScyllaBlockStore {
+    async fn save_block(&self, block: &Block) -> Result<()> {
         self.session
-            .query_unpaged(
-                query,
-                (block.header.height as i64, block.hash.clone(), json),
-            )
+            .execute_unpaged(&self.insert_block, self.block_row(block)?)
             .await
             .map_err(|e| ATokenError::Storage(e.to_string()))?;
         Ok(())
     }
 
-    pub async fn load_blocks(&self) -> Result<Vec<Block>> {
-        let query = format!(
-            "SELECT block_json FROM {}.blocks ORDER BY height ASC",
-            self.keyspace
-        );
-        let rows_result = self
+    /// Reads bucket after bucket from genesis; a chain has no gaps, so the first empty
+    /// bucket is past its tip.
+    async fn load_blocks(&self) -> Result<Vec<Block>> {
+        let mut blocks = Vec::new();
+        for bucket in 0.. {
+            let bucket_blocks = self.load_bucket(bucket).await?;
+            if bucket_blocks.is_empty() {
+                break;
+            }
+            blocks.extend(bucket_blocks);
+        }
+        Ok(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::blocks;
+
+    fn test_keyspace(name: &str) -> String {
+        format!("atoken_test_{name}_{}", std::process::id())
+    }
+
+    async fn test_store(name: &str) -> ScyllaBlockStore {
+        let node = std::env::var("ATOKEN_SCYLLA_TEST_NODE")
+            .unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+        ScyllaBlockStore::connect(&node, &test_keyspace(name))
+            .await
+            .unwrap()
+    }
+
+    async fn drop_keyspace(store: ScyllaBlockStore, name: &str) {
+        store
             .session
-            .query_unpaged(query, &[])
+            .query_unpaged(format!("DROP KEYSPACE {}", test_keyspace(name)), &[])
             .await
-            .map_err(|e| ATokenError::Storage(e.to_string()))?
-            .into_rows_result()
-            .map_err(|e| ATokenError::Storage(e.to_string()))?;
-        let rows = rows_result
-            .rows::<(String,)>()
-            .map_err(|e| ATokenError::Storage(e.to_string()))?;
+            .unwrap();
+    }
 
-        let mut blocks = Vec::new();
-        for row in rows {
-            let (block_json,): (String,) = row.map_err(|e| ATokenError::Storage(e.to_string()))?;
-            let block: Block = serde_json::from_str(&block_json)
-                .map_err(|e| ATokenError::Storage(e.to_string()))?;
-            blocks.push(block);
+    async fn heights(store: &ScyllaBlockStore) -> Vec<u64> {
+        store
+            .load_blocks()
+            .await
+            .unwrap()
+            .iter()
+            .map(|block| block.header.height)
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a Scylla node at ATOKEN_SCYLLA_TEST_NODE (default 127.0.0.1:9042)"]
+    async fn blocks_load_in_height_order() {
+        let store = test_store("order").await;
+        for block in blocks(4).iter().rev() {
+            store.save_block(block).await.unwrap();
         }
-        Ok(blocks)
+        assert_eq!(heights(&store).await, vec![0, 1, 2, 3, 4]);
+        drop_keyspace(store, "order").await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a Scylla node at ATOKEN_SCYLLA_TEST_NODE (default 127.0.0.1:9042)"]
+    async fn save_blocks_writes_a_batch() {
+        let store = test_store("batch").await;
+        store.save_blocks(&blocks(4)).await.unwrap();
+        assert_eq!(heights(&store).await, vec![0, 1, 2, 3, 4]);
+        drop_keyspace(store, "batch").await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a Scylla node at ATOKEN_SCYLLA_TEST_NODE (default 127.0.0.1:9042)"]
+    async fn blocks_load_across_buckets() {
+        let mut store = test_store("buckets").await;
+        // Buckets {0, 1}, {2, 3} and {4}
+        store.blocks_per_bucket = 2;
+        store.save_blocks(&blocks(4)).await.unwrap();
+        assert_eq!(heights(&store).await, vec![0, 1, 2, 3, 4]);
+        drop_keyspace(store, "buckets").await;
     }
 }
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::chain::Asset;
+use crate::crypto::Address;
+use crate::errors::{ATokenError, Result};
+
+/// Ledger derived from the blocks up to `height`, so a restart can pick up from there
+/// instead of replaying the whole chain. See `ATokenChain::export_snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainSnapshot {
+    pub chain_id: String,
+    /// Last block the snapshot covers
+    pub height: u64,
+    pub block_hash: String,
+    /// Indexed by `AssetId`
+    pub assets: Vec<Asset>,
+    /// Last applied nonce per address
+    pub nonces: BTreeMap<Address, u64>,
+}
+
+/// Snapshot file the server checkpoints to every `every_blocks` blocks and restores from
+/// on startup. Set by `ATOKEN_SNAPSHOT_PATH`.
+#[derive(Debug, Clone)]
+pub struct SnapshotFile {
+    path: PathBuf,
+    every_blocks: u64,
+}
+
+impl SnapshotFile {
+    pub fn new(path: impl Into<PathBuf>, every_blocks: u64) -> Self {
+        Self {
+            path: path.into(),
+            every_blocks,
+        }
+    }
+
+    /// Whether the block at `height` should be followed by a checkpoint.
+    pub fn is_due(&self, height: u64) -> bool {
+        self.every_blocks > 0 && (height + 1).is_multiple_of(self.every_blocks)
+    }
+
+    /// The last snapshot written, or `None` if there is none yet.
+    pub async fn load(&self) -> Result<Option<ChainSnapshot>> {
+        let raw = match tokio::fs::read(&self.path).await {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_slice(&raw)
+            .map(Some)
+            .map_err(|e| ATokenError::InvalidSnapshot(e.to_string()))
+    }
+
+    /// Replaces the snapshot file. Written to a temporary file, synced, and renamed over it,
+    /// with the directory synced after, so a crash leaves either the old snapshot or the new
+    /// one.
+    pub async fn save(&self, snapshot: &ChainSnapshot) -> Result<()> {
+        let raw =
+            serde_json::to_vec(snapshot).map_err(|e| ATokenError::Serialization(e.to_string()))?;
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut file = tokio::fs::File::create(&tmp).await?;
+        file.write_all(&raw).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&tmp, &self.path).await?;
+        self.sync_dir().await
+    }
+
+    /// Makes the rename durable. Directories can only be opened, and so synced, on Unix.
+    #[cfg(unix)]
+    async fn sync_dir(&self) -> Result<()> {
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        tokio::fs::File::open(dir).await?.sync_all().await?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    async fn sync_dir(&self) -> Result<()> {
+        Ok(())
+    }
+}
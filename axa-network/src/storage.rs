@@ -1,32 +1,259 @@
+use std::io::{ErrorKind, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tracing::{info, warn};
+
+use crate::chain::{ATokenChain, ChainConfig};
 use crate::errors::{ATokenError, Result};
 use crate::model::Block;
+use crate::snapshot::ChainSnapshot;
+
+#[async_trait]
+pub trait BlockStore: Send + Sync {
+    async fn save_block(&self, block: &Block) -> Result<()>;
+    async fn load_blocks(&self) -> Result<Vec<Block>>;
+
+    /// First block, if any. Override when it can be read without loading the whole chain.
+    async fn load_genesis(&self) -> Result<Option<Block>> {
+        Ok(self.load_blocks().await?.into_iter().next())
+    }
+
+    /// Block at `height`, if stored.
+    async fn load_block(&self, height: u64) -> Result<Option<Block>> {
+        Ok(self
+            .load_range(height, height.saturating_add(1))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Blocks with heights in `from..to`, in height order. Override when the store can
+    /// seek to a height instead of loading the whole chain.
+    async fn load_range(&self, from: u64, to: u64) -> Result<Vec<Block>> {
+        Ok(self
+            .load_blocks()
+            .await?
+            .into_iter()
+            .filter(|block| (from..to).contains(&block.header.height))
+            .collect())
+    }
 
-pub trait BlockStore {
-    fn save_block(&mut self, block: &Block) -> Result<()>;
-    fn load_blocks(&self) -> Result<Vec<Block>>;
+    /// Makes every saved block durable; called once on shutdown. Stores that sync on each
+    /// `save_block` have nothing to do.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Default)]
 pub struct InMemoryBlockStore {
-    blocks: Vec<Block>,
+    blocks: Mutex<Vec<Block>>,
 }
 
+#[async_trait]
 impl BlockStore for InMemoryBlockStore {
-    fn save_block(&mut self, block: &Block) -> Result<()> {
-        self.blocks.push(block.clone());
+    async fn save_block(&self, block: &Block) -> Result<()> {
+        self.blocks.lock().unwrap().push(block.clone());
+        Ok(())
+    }
+
+    async fn load_blocks(&self) -> Result<Vec<Block>> {
+        Ok(self.blocks.lock().unwrap().clone())
+    }
+}
+
+/// Blocks as JSON lines in an append-only file, so the ledger survives restarts.
+/// A crash mid-append leaves a line without its newline; reads skip it and the next
+/// append cuts it off.
+#[derive(Debug, Clone)]
+pub struct FileBlockStore {
+    path: PathBuf,
+}
+
+impl FileBlockStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    async fn reader(&self) -> Result<Option<BlockReader>> {
+        match File::open(&self.path).await {
+            Ok(file) => Ok(Some(BlockReader {
+                inner: BufReader::new(file),
+                line: Vec::new(),
+            })),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Truncates a torn trailing line so the next block starts on a line of its own.
+    async fn drop_torn_tail(file: &mut File) -> Result<()> {
+        let len = file.metadata().await?.len();
+        if len == 0 {
+            return Ok(());
+        }
+        let mut last = [0u8];
+        file.seek(SeekFrom::End(-1)).await?;
+        file.read_exact(&mut last).await?;
+        if last[0] == b'\n' {
+            return Ok(());
+        }
+
+        let mut contents = Vec::with_capacity(len as usize);
+        file.seek(SeekFrom::Start(0)).await?;
+        file.read_to_end(&mut contents).await?;
+        let complete = contents
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |i| i + 1);
+        file.set_len(complete as u64).await?;
+        Ok(())
+    }
+}
+
+struct BlockReader {
+    inner: BufReader<File>,
+    line: Vec<u8>,
+}
+
+impl BlockReader {
+    /// Next complete block; `None` at the end of the file or at a torn trailing line.
+    async fn next_block(&mut self) -> Result<Option<Block>> {
+        loop {
+            self.line.clear();
+            if self.inner.read_until(b'\n', &mut self.line).await? == 0 {
+                return Ok(None);
+            }
+            if self.line.last() != Some(&b'\n') {
+                return Ok(None);
+            }
+            let line = std::str::from_utf8(&self.line)
+                .map_err(|e| ATokenError::Storage(format!("corrupt block: {e}")))?;
+            if !line.trim().is_empty() {
+                return parse_block(line).map(Some);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl BlockStore for FileBlockStore {
+    async fn save_block(&self, block: &Block) -> Result<()> {
+        let mut line =
+            serde_json::to_vec(block).map_err(|e| ATokenError::Serialization(e.to_string()))?;
+        line.push(b'\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        Self::drop_torn_tail(&mut file).await?;
+        file.write_all(&line).await?;
+        file.sync_data().await?;
         Ok(())
     }
 
-    fn load_blocks(&self) -> Result<Vec<Block>> {
-        Ok(self.blocks.clone())
+    async fn load_blocks(&self) -> Result<Vec<Block>> {
+        let Some(mut reader) = self.reader().await? else {
+            return Ok(Vec::new());
+        };
+        let mut blocks = Vec::new();
+        while let Some(block) = reader.next_block().await? {
+            blocks.push(block);
+        }
+        Ok(blocks)
+    }
+
+    async fn load_genesis(&self) -> Result<Option<Block>> {
+        match self.reader().await? {
+            Some(mut reader) => reader.next_block().await,
+            None => Ok(None),
+        }
     }
 }
 
-pub fn replay_from_store<S>(chain: &mut crate::chain::ATokenChain, store: &S) -> Result<()>
+fn parse_block(line: &str) -> Result<Block> {
+    serde_json::from_str(line).map_err(|e| ATokenError::Storage(format!("corrupt block: {e}")))
+}
+
+/// Rebuilds the chain persisted in `store`, or `None` if nothing has been issued yet.
+/// The issuer comes from the genesis block, so restarting does not require a new mint.
+pub async fn restore_chain<S>(chain_id: &str, store: &S) -> Result<Option<ATokenChain>>
+where
+    S: BlockStore + ?Sized,
+{
+    restore_chain_from(chain_id, store, None).await
+}
+
+/// `restore_chain`, starting from `snapshot` when it matches the store.
+pub async fn restore_chain_from<S>(
+    chain_id: &str,
+    store: &S,
+    snapshot: Option<ChainSnapshot>,
+) -> Result<Option<ATokenChain>>
+where
+    S: BlockStore + ?Sized,
+{
+    let Some(genesis) = store.load_genesis().await? else {
+        return Ok(None);
+    };
+    let mut chain = ATokenChain::new(ChainConfig::from_genesis(chain_id, &genesis)?);
+    replay_from_snapshot(&mut chain, store, snapshot).await?;
+    Ok(Some(chain))
+}
+
+pub async fn replay_from_store<S>(chain: &mut crate::chain::ATokenChain, store: &S) -> Result<()>
+where
+    S: BlockStore + ?Sized,
+{
+    replay_from_snapshot(chain, store, None).await
+}
+
+/// Brings an empty `chain` up to date with `store`: takes the ledger from `snapshot` and
+/// replays only the blocks after it, or replays everything when there is no snapshot or it
+/// does not match the stored blocks.
+pub async fn replay_from_snapshot<S>(
+    chain: &mut ATokenChain,
+    store: &S,
+    snapshot: Option<ChainSnapshot>,
+) -> Result<()>
 where
-    S: BlockStore,
+    S: BlockStore + ?Sized,
 {
-    let blocks = store.load_blocks()?;
+    let mut blocks = store.load_blocks().await?;
+    if let Some(snapshot) = snapshot {
+        let height = snapshot.height;
+        let covered = usize::try_from(height + 1).unwrap_or(usize::MAX);
+        if covered <= blocks.len() {
+            let tail = blocks.split_off(covered);
+            match chain.import_snapshot(snapshot, blocks) {
+                Ok(()) => {
+                    info!(
+                        height,
+                        replayed = tail.len(),
+                        "chain restored from snapshot"
+                    );
+                    blocks = tail;
+                }
+                Err(e) => {
+                    warn!("ignoring snapshot, replaying every block: {e}");
+                    blocks = store.load_blocks().await?;
+                }
+            }
+        } else {
+            warn!(
+                height,
+                stored = blocks.len(),
+                "snapshot is ahead of the store, replaying every block"
+            );
+        }
+    }
     for block in blocks {
         chain.append_block(block)?;
     }
@@ -38,3 +265,113 @@ impl From<std::io::Error> for ATokenError {
         ATokenError::Storage(value.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::SnapshotFile;
+    use crate::test_support::{CHAIN_ID, blocks};
+
+    #[tokio::test]
+    async fn torn_trailing_line_is_ignored_and_cut_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.jsonl");
+        let store = FileBlockStore::new(&path);
+        let blocks = blocks(3);
+
+        store.save_block(&blocks[0]).await.unwrap();
+        store.save_block(&blocks[1]).await.unwrap();
+        let full_line = serde_json::to_string(&blocks[2]).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).await.unwrap();
+        file.write_all(&full_line.as_bytes()[..full_line.len() / 2])
+            .await
+            .unwrap();
+
+        assert_eq!(store.load_blocks().await.unwrap().len(), 2);
+        let chain = restore_chain(CHAIN_ID, &store).await.unwrap().unwrap();
+        assert_eq!(chain.blocks.len(), 2);
+
+        store.save_block(&blocks[2]).await.unwrap();
+        store.save_block(&blocks[3]).await.unwrap();
+        let heights: Vec<u64> = store
+            .load_blocks()
+            .await
+            .unwrap()
+            .iter()
+            .map(|block| block.header.height)
+            .collect();
+        assert_eq!(heights, vec![0, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn torn_genesis_means_empty_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.jsonl");
+        tokio::fs::write(&path, b"{\"header\":{\"height\":0")
+            .await
+            .unwrap();
+
+        let store = FileBlockStore::new(&path);
+        assert!(store.load_genesis().await.unwrap().is_none());
+        assert!(restore_chain(CHAIN_ID, &store).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn snapshot_and_tail_replay_match_a_full_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = SnapshotFile::new(dir.path().join("snapshot.json"), 6);
+        let foreign_blocks = blocks(6);
+        let blocks = blocks(10);
+        let store = InMemoryBlockStore::default();
+        for block in &blocks {
+            store.save_block(block).await.unwrap();
+        }
+        let full = restore_chain(CHAIN_ID, &store).await.unwrap().unwrap();
+
+        // Checkpoint after block 5, as the server does with one every 6 blocks
+        assert!(snapshots.is_due(5));
+        let mut partial =
+            ATokenChain::new(ChainConfig::from_genesis(CHAIN_ID, &blocks[0]).unwrap());
+        for block in &blocks[..6] {
+            partial.append_block(block.clone()).unwrap();
+        }
+        snapshots
+            .save(&partial.export_snapshot().unwrap())
+            .await
+            .unwrap();
+
+        let snapshot = snapshots.load().await.unwrap().unwrap();
+        assert_eq!(snapshot.height, 5);
+        let restored = restore_chain_from(CHAIN_ID, &store, Some(snapshot))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored.export_snapshot(), full.export_snapshot());
+        let hashes = |chain: &ATokenChain| -> Vec<String> {
+            chain
+                .blocks
+                .iter()
+                .map(|block| block.hash.clone())
+                .collect()
+        };
+        assert_eq!(hashes(&restored), hashes(&full));
+        let early_tx = &blocks[2].txs[0].id;
+        assert_eq!(restored.tx_location(early_tx), full.tx_location(early_tx));
+        restored.verify_integrity().unwrap();
+
+        // A snapshot of some other history is not trusted; everything is replayed instead
+        let foreign = {
+            let mut chain =
+                ATokenChain::new(ChainConfig::from_genesis(CHAIN_ID, &foreign_blocks[0]).unwrap());
+            for block in foreign_blocks {
+                chain.append_block(block).unwrap();
+            }
+            chain.export_snapshot().unwrap()
+        };
+        let replayed = restore_chain_from(CHAIN_ID, &store, Some(foreign))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(replayed.export_snapshot(), full.export_snapshot());
+    }
+}
@@ -0,0 +1,52 @@
+//! Fixtures shared by the unit tests, all on one asset minted on `CHAIN_ID`.
+
+use crate::chain::{ATokenChain, ChainConfig};
+use crate::crypto::Wallet;
+use crate::model::{Block, SignedTx, TokenMetadata, UnsignedTx};
+
+pub(crate) const CHAIN_ID: &str = "AToken-local";
+
+pub(crate) fn metadata() -> TokenMetadata {
+    TokenMetadata {
+        name: "AToken".to_string(),
+        symbol: "ATKN".to_string(),
+        description: "Test token".to_string(),
+        decimals: 0,
+        issuer: String::new(),
+    }
+}
+
+/// A chain whose block 0 mints `amount` tokens to its issuer
+pub(crate) fn minted_chain(amount: u64) -> (Wallet, ATokenChain) {
+    let issuer = Wallet::generate();
+    let mut chain = ATokenChain::new(ChainConfig::new(CHAIN_ID, issuer.address()));
+    let mint = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, amount, metadata()),
+        &issuer,
+    )
+    .unwrap();
+    let b0 = chain.build_block(&issuer, vec![mint]).unwrap();
+    chain.append_block(b0).unwrap();
+    (issuer, chain)
+}
+
+/// A mint of `transfers` tokens followed by one block per token, each moving it away
+pub(crate) fn blocks(transfers: u64) -> Vec<Block> {
+    let (issuer, mut chain) = minted_chain(transfers);
+    let alice = Wallet::generate();
+    for token_id in 0..transfers {
+        let tx = UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            token_id + 2,
+            0,
+            alice.address(),
+            vec![token_id],
+        );
+        let block = chain
+            .build_block(&issuer, vec![SignedTx::sign(tx, &issuer).unwrap()])
+            .unwrap();
+        chain.append_block(block).unwrap();
+    }
+    chain.blocks
+}
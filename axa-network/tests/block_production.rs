@@ -0,0 +1,77 @@
+mod common;
+
+use std::time::Duration;
+
+use axa_network::api::{AppState, BlockProduction, produce_blocks};
+use axa_network::crypto::Wallet;
+use axa_network::model::{SignedTx, UnsignedTx};
+use axum::http::StatusCode;
+use common::{CHAIN_ID, call, mint};
+use serde_json::json;
+
+const INTERVAL: Duration = Duration::from_secs(2);
+
+/// Node with one minted block and a producer sealing every `INTERVAL`
+async fn producing_state(produce_empty: bool) -> (AppState, Wallet) {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    mint(&state, &issuer, 2).await;
+
+    let production = BlockProduction {
+        interval: INTERVAL,
+        max_txs: 100,
+        produce_empty,
+    };
+    tokio::spawn(produce_blocks(
+        state.clone(),
+        production,
+        std::future::pending(),
+    ));
+    (state, issuer)
+}
+
+async fn block_count(state: &AppState) -> u64 {
+    let (_, chain) = call(state, "GET", "/chain", None).await;
+    chain["blocks"].as_u64().unwrap()
+}
+
+// Paused time jumps straight to the next tick once everything else is idle
+#[tokio::test(start_paused = true)]
+async fn pending_txs_are_sealed_on_the_next_tick() {
+    let (state, issuer) = producing_state(false).await;
+    let alice = Wallet::generate().address();
+    for token_id in 0..2 {
+        let tx = UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            token_id + 2,
+            0,
+            alice.clone(),
+            vec![token_id],
+        );
+        let signed = SignedTx::sign(tx, &issuer).unwrap();
+        let (status, body) = call(&state, "POST", "/tx", Some(json!(signed))).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+    assert_eq!(block_count(&state).await, 1);
+
+    tokio::time::sleep(INTERVAL + Duration::from_millis(1)).await;
+    assert_eq!(block_count(&state).await, 2);
+    let (_, block) = call(&state, "GET", "/block/height/1", None).await;
+    assert_eq!(block["txs"].as_array().unwrap().len(), 2);
+    let (_, mempool) = call(&state, "GET", "/mempool/count", None).await;
+    assert_eq!(mempool["pending"], 0);
+
+    // Nothing pending, so the next tick adds no block
+    tokio::time::sleep(INTERVAL).await;
+    assert_eq!(block_count(&state).await, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn empty_blocks_only_when_asked_for() {
+    let (state, _) = producing_state(true).await;
+    tokio::time::sleep(2 * INTERVAL + Duration::from_millis(1)).await;
+    assert_eq!(block_count(&state).await, 3);
+    let (_, block) = call(&state, "GET", "/block/height/2", None).await;
+    assert_eq!(block["txs"], json!([]));
+}
@@ -0,0 +1,278 @@
+mod common;
+
+use axa_network::api::AppState;
+use axa_network::crypto::Wallet;
+use axa_network::model::{MerkleProofStep, SignedTx, UnsignedTx, verify_tx_merkle_proof};
+use axum::http::StatusCode;
+use common::{CHAIN_ID, call, metadata, mint};
+use serde_json::json;
+
+/// A chain of `1 + transfers` blocks: the mint, then one single-token transfer each
+async fn seeded_state(transfers: u64) -> AppState {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    let mut txs = vec![UnsignedTx::mint(
+        CHAIN_ID,
+        issuer.address(),
+        1,
+        transfers.max(1),
+        metadata(),
+    )];
+    let alice = Wallet::generate().address();
+    txs.extend((0..transfers).map(|token_id| {
+        UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            token_id + 2,
+            0,
+            alice.clone(),
+            vec![token_id],
+        )
+    }));
+    for tx in txs {
+        let signed = SignedTx::sign(tx, &issuer).unwrap();
+        let (status, body) = call(&state, "POST", "/tx/submit", Some(json!(signed))).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+    state
+}
+
+#[tokio::test]
+async fn blocks_by_height_and_hash() {
+    let state = seeded_state(2).await;
+
+    let (status, block) = call(&state, "GET", "/block/height/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(block["header"]["height"], 1);
+    assert_eq!(block["txs"].as_array().unwrap().len(), 1);
+
+    let hash = block["hash"].as_str().unwrap();
+    let (status, by_hash) = call(&state, "GET", &format!("/block/hash/{hash}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(by_hash, block);
+
+    let (_, genesis) = call(&state, "GET", "/block/height/0", None).await;
+    assert_eq!(block["header"]["previous_hash"], genesis["hash"]);
+}
+
+#[tokio::test]
+async fn absent_blocks_are_not_found() {
+    let empty = AppState::new(CHAIN_ID.to_string());
+    let (status, _) = call(&empty, "GET", "/block/height/0", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let state = seeded_state(2).await;
+    let (status, body) = call(&state, "GET", "/block/height/3", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains('3'));
+    let (status, _) = call(&state, "GET", "/block/hash/deadbeef", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(&state, "GET", &format!("/block/height/{}", u64::MAX), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn blocks_are_listed_in_pages() {
+    let state = seeded_state(4).await;
+
+    let (status, page) = call(&state, "GET", "/blocks?offset=1&limit=2", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["total"], 5);
+    let blocks = page["blocks"].as_array().unwrap();
+    let heights: Vec<u64> = blocks
+        .iter()
+        .map(|block| block["height"].as_u64().unwrap())
+        .collect();
+    assert_eq!(heights, vec![1, 2]);
+    assert_eq!(blocks[1]["previous_hash"], blocks[0]["hash"]);
+    assert_eq!(blocks[0]["tx_count"], 1);
+    assert!(blocks[0].get("txs").is_none());
+
+    // Past the end
+    let (status, page) = call(&state, "GET", "/blocks?offset=10", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["total"], 5);
+    assert!(page["blocks"].as_array().unwrap().is_empty());
+
+    let (_, page) = call(&state, "GET", "/blocks", None).await;
+    assert_eq!(page["blocks"].as_array().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn block_page_limit_is_clamped() {
+    let state = seeded_state(201).await;
+
+    let (_, page) = call(&state, "GET", "/blocks?limit=1000", None).await;
+    assert_eq!(page["limit"], 200);
+    assert_eq!(page["total"], 202);
+    assert_eq!(page["blocks"].as_array().unwrap().len(), 200);
+}
+
+#[tokio::test]
+async fn txs_are_found_by_id() {
+    let state = seeded_state(2).await;
+    let (_, block) = call(&state, "GET", "/block/height/2", None).await;
+    let tx_id = block["txs"][0]["id"].as_str().unwrap();
+
+    let (status, body) = call(&state, "GET", &format!("/tx/{tx_id}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["block_height"], 2);
+    assert_eq!(body["tx"], block["txs"][0]);
+
+    let (status, _) = call(&state, "GET", "/tx/unknown", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn intact_chain_verifies() {
+    let (status, body) = call(&AppState::new(CHAIN_ID.to_string()), "GET", "/verify", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ok"], true);
+
+    let state = seeded_state(3).await;
+    let (status, body) = call(&state, "GET", "/verify", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "ok": true, "blocks": 4 }));
+}
+
+#[tokio::test]
+async fn tx_inclusion_proofs_verify_against_the_header() {
+    // Three txs sealed into one block, so one leaf is carried up unpaired
+    let issuer = Wallet::generate();
+    let state = AppState::new(CHAIN_ID.to_string());
+    mint(&state, &issuer, 3).await;
+    let alice = Wallet::generate().address();
+    for token_id in 0..3 {
+        let tx = UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            token_id + 2,
+            0,
+            alice.clone(),
+            vec![token_id],
+        );
+        let signed = SignedTx::sign(tx, &issuer).unwrap();
+        let (status, body) = call(&state, "POST", "/tx", Some(json!(signed))).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+    let (status, _) = call(&state, "POST", "/seal", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, block) = call(&state, "GET", "/block/height/1", None).await;
+    let root = block["header"]["tx_merkle_root"].as_str().unwrap();
+    for (index, tx) in block["txs"].as_array().unwrap().iter().enumerate() {
+        let tx_id = tx["id"].as_str().unwrap();
+        let (status, body) = call(&state, "GET", &format!("/proof/tx/{tx_id}"), None).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["block_height"], 1);
+        assert_eq!(body["tx_index"], index);
+        assert_eq!(body["tx_merkle_root"], root);
+
+        let mut proof: Vec<MerkleProofStep> =
+            serde_json::from_value(body["proof"].clone()).unwrap();
+        assert!(!proof.is_empty());
+        assert!(verify_tx_merkle_proof(tx_id, &proof, root));
+
+        proof[0].hash = "00".repeat(32);
+        assert!(!verify_tx_merkle_proof(tx_id, &proof, root));
+    }
+
+    let (status, _) = call(&state, "GET", "/proof/tx/unknown", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn mempool_lists_pending_txs_until_they_are_sealed() {
+    let issuer = Wallet::generate();
+    let state = AppState::new(CHAIN_ID.to_string());
+    mint(&state, &issuer, 2).await;
+
+    let alice = Wallet::generate().address();
+    let mut tx_ids = Vec::new();
+    for token_id in 0..2 {
+        let tx = UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            token_id + 2,
+            0,
+            alice.clone(),
+            vec![token_id],
+        );
+        let signed = SignedTx::sign(tx, &issuer).unwrap();
+        tx_ids.push(signed.id.clone());
+        call(&state, "POST", "/tx", Some(json!(signed))).await;
+    }
+
+    let (status, body) = call(&state, "GET", "/mempool", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["pending"], 2);
+    let txs = body["txs"].as_array().unwrap();
+    assert_eq!(txs[0]["tx_id"], tx_ids[0]);
+    assert_eq!(txs[0]["sender"], issuer.address());
+    assert_eq!(txs[0]["nonce"], 2);
+    assert_eq!(txs[1]["tx_id"], tx_ids[1]);
+    assert_eq!(txs[1]["nonce"], 3);
+    let (_, body) = call(&state, "GET", "/mempool/count", None).await;
+    assert_eq!(body["pending"], 2);
+
+    // Sealed in the listed order, and gone from the pool
+    let (_, sealed) = call(&state, "POST", "/seal", None).await;
+    assert_eq!(sealed["tx_ids"], json!(tx_ids));
+    let (_, body) = call(&state, "GET", "/mempool", None).await;
+    assert_eq!(body, json!({"pending": 0, "txs": []}));
+    let (_, body) = call(&state, "GET", "/mempool/count", None).await;
+    assert_eq!(body["pending"], 0);
+}
+
+#[tokio::test]
+async fn a_full_mempool_answers_503() {
+    let issuer = Wallet::generate();
+    let state = AppState::new(CHAIN_ID.to_string()).with_max_pending(1);
+    mint(&state, &issuer, 2).await;
+
+    let alice = Wallet::generate().address();
+    let mut statuses = Vec::new();
+    for token_id in 0..2 {
+        let tx = UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            token_id + 2,
+            0,
+            alice.clone(),
+            vec![token_id],
+        );
+        let signed = SignedTx::sign(tx, &issuer).unwrap();
+        statuses.push(call(&state, "POST", "/tx", Some(json!(signed))).await.0);
+    }
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::SERVICE_UNAVAILABLE]);
+}
+
+#[tokio::test]
+async fn balances_can_be_read_at_past_heights() {
+    // Alice receives one token per block after the mint
+    let state = seeded_state(2).await;
+    let (_, block) = call(&state, "GET", "/block/height/1", None).await;
+    let alice = block["txs"][0]["unsigned"]["payload"]["Transfer"]["to"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let balance_at = |height: u64| {
+        let state = state.clone();
+        let uri = format!("/balance/0/{alice}?height={height}");
+        async move { call(&state, "GET", &uri, None).await }
+    };
+    let (status, before) = balance_at(0).await;
+    assert_eq!(status, StatusCode::OK, "{before}");
+    assert_eq!(before["balance"], 0);
+    assert_eq!(before["height"], 0);
+    assert_eq!(balance_at(1).await.1["balance"], 1);
+    assert_eq!(balance_at(2).await.1["balance"], 2);
+
+    let (_, now) = call(&state, "GET", &format!("/balance/0/{alice}"), None).await;
+    assert_eq!(now["balance"], 2);
+    assert!(now.get("height").is_none());
+
+    let (status, _) = balance_at(3).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use axa_network::api::AppState;
+use axa_network::crypto::Wallet;
+use axum::http::StatusCode;
+use common::call;
+use serde_json::json;
+
+#[tokio::test]
+async fn oversized_bodies_get_a_json_413() {
+    let state = AppState::new("AToken-local".to_string()).with_max_body_bytes(1024);
+    let transfer = json!({
+        "from_private_key_hex": Wallet::generate().private_key_hex(),
+        "asset_id": 0,
+        "to_address": Wallet::generate().address(),
+        "token_ids": (0..1_000).collect::<Vec<u64>>(),
+    });
+
+    let (status, body) = call(&state, "POST", "/transfer", Some(transfer.clone())).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        body,
+        json!({ "error": "request body is over the 1024-byte limit" })
+    );
+
+    // Under the limit the body is parsed and the transfer itself is what fails
+    let state = state.with_max_body_bytes(64 * 1024);
+    let (status, body) = call(&state, "POST", "/transfer", Some(transfer)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"], "token has not been issued yet");
+}
+
+#[tokio::test]
+async fn malformed_bodies_get_a_json_error() {
+    let state = AppState::new("AToken-local".to_string());
+
+    let (status, body) = call(&state, "POST", "/verify-tx", Some(json!({ "id": 1 }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to deserialize the JSON body")
+    );
+}
@@ -0,0 +1,76 @@
+use std::process::Command;
+
+use axa_network::api::{AppState, serve};
+use serde_json::Value;
+use tokio::net::TcpListener;
+
+/// Runs the `atoken` binary against `url` and parses what it prints.
+async fn atoken(url: &str, args: &[&str]) -> Value {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_atoken"));
+    command
+        .env_remove("ATOKEN_PRIVATE_KEY")
+        .arg("--url")
+        .arg(url)
+        .args(args);
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "atoken {args:?}: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[tokio::test]
+async fn cli_signs_locally_and_submits_to_the_node() {
+    // No server signing, so only locally signed txs can go through
+    let state = AppState::new("AToken-test".to_string()).with_server_signing(false);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(serve(listener, state, std::future::pending()));
+
+    let issuer = atoken(&url, &["wallet", "new"]).await;
+    let recipient = atoken(&url, &["wallet", "new"]).await;
+    let issuer_key = issuer["private_key_hex"].as_str().unwrap();
+    let recipient_address = recipient["address"].as_str().unwrap();
+    assert_eq!(issuer["mnemonic"].as_str().unwrap().split(' ').count(), 24);
+
+    let issued = atoken(
+        &url,
+        &[
+            "issue",
+            "--private-key",
+            issuer_key,
+            "--amount",
+            "4",
+            "--symbol",
+            "ATKN",
+        ],
+    )
+    .await;
+    assert_eq!(issued["block_height"], 0);
+    assert_eq!(issued["asset_id"], 0);
+
+    let sent = atoken(
+        &url,
+        &[
+            "transfer",
+            "--private-key",
+            issuer_key,
+            "--to",
+            recipient_address,
+            "--ids",
+            "1,3",
+        ],
+    )
+    .await;
+    assert_eq!(sent["block_height"], 1);
+
+    let balance = atoken(&url, &["balance", recipient_address]).await;
+    assert_eq!(balance["balance"], 2);
+    let balance = atoken(&url, &["balance", issuer["address"].as_str().unwrap()]).await;
+    assert_eq!(balance["balance"], 2);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use axa_network::api::{AppState, serve};
+use axa_network::api_types::{IssueRequest, TransferRequest};
+use axa_network::client::Client;
+use axa_network::crypto::Wallet;
+use axa_network::errors::ATokenError;
+use axa_network::model::{SignedTx, UnsignedTx};
+use common::{CHAIN_ID, metadata_input};
+use tokio::net::TcpListener;
+
+async fn spawn_node() -> Client {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, state, std::future::pending()));
+    Client::new(format!("http://{addr}/"))
+}
+
+#[tokio::test]
+async fn client_drives_a_node_end_to_end() {
+    let client = spawn_node().await;
+    let issuer = Wallet::generate();
+    let alice = Wallet::generate();
+    let bob = Wallet::generate();
+
+    let info = client.chain_info().await.unwrap();
+    assert_eq!(info.chain_id, CHAIN_ID);
+    assert!(!info.initialized);
+
+    let issued = client
+        .issue(&IssueRequest {
+            issuer_private_key_hex: issuer.private_key_hex(),
+            amount: 5,
+            metadata: metadata_input(),
+            distribution: Vec::new(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(issued.block_height, 0);
+    let asset_id = issued.asset_id.unwrap();
+
+    client
+        .transfer(&TransferRequest {
+            from_private_key_hex: issuer.private_key_hex(),
+            asset_id,
+            to_address: alice.address(),
+            token_ids: vec![0, 1],
+            memo: None,
+            locked_until_ms: None,
+        })
+        .await
+        .unwrap();
+
+    // Alice signs her own transfer to Bob
+    let nonce = client.nonce(&alice.address()).await.unwrap().next_nonce;
+    let tx = SignedTx::sign(
+        UnsignedTx::transfer(
+            CHAIN_ID,
+            alice.address(),
+            nonce,
+            asset_id,
+            bob.address(),
+            vec![1],
+        ),
+        &alice,
+    )
+    .unwrap();
+    let accepted = client.submit_tx(&tx).await.unwrap();
+    assert_eq!(accepted.tx_id, tx.id);
+    assert_eq!(accepted.block_height, 2);
+
+    assert_eq!(
+        client
+            .balance(asset_id, &issuer.address())
+            .await
+            .unwrap()
+            .balance,
+        3
+    );
+    assert_eq!(
+        client
+            .balance(asset_id, &alice.address())
+            .await
+            .unwrap()
+            .balance,
+        1
+    );
+    assert_eq!(
+        client
+            .balance(asset_id, &bob.address())
+            .await
+            .unwrap()
+            .balance,
+        1
+    );
+    let info = client.chain_info().await.unwrap();
+    assert!(info.initialized);
+    assert_eq!(info.blocks, 3);
+
+    // Replaying the tx is refused with the node's own message
+    match client.submit_tx(&tx).await {
+        Err(ATokenError::Api { status, message }) => {
+            assert_eq!(status, 400);
+            assert!(message.starts_with("duplicate transaction"), "{message}");
+        }
+        other => panic!("expected an API error, got {other:?}"),
+    }
+}
@@ -0,0 +1,278 @@
+mod common;
+
+use axa_network::api::AppState;
+use axa_network::crypto::Wallet;
+use axa_network::model::{SignedTx, TxPayload, UnsignedTx};
+use axum::http::StatusCode;
+use common::{CHAIN_ID, call, metadata};
+use serde_json::json;
+
+#[tokio::test]
+async fn client_signed_txs_are_accepted() {
+    let state = AppState::new(CHAIN_ID.to_string()).with_server_signing(false);
+    let issuer = Wallet::generate();
+    let alice = Wallet::generate();
+
+    let mint = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, 5, metadata()),
+        &issuer,
+    )
+    .unwrap();
+    let (status, body) = call(&state, "POST", "/tx/submit", Some(json!(mint))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["tx_id"], mint.id);
+    assert_eq!(body["asset_id"], 0);
+
+    let transfer = SignedTx::sign(
+        UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            2,
+            0,
+            alice.address(),
+            vec![0, 1],
+        ),
+        &issuer,
+    )
+    .unwrap();
+    let (status, body) = call(&state, "POST", "/tx/submit", Some(json!(transfer))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["block_height"], 1);
+
+    let (_, body) = call(
+        &state,
+        "GET",
+        &format!("/balance/0/{}", alice.address()),
+        None,
+    )
+    .await;
+    assert_eq!(body["balance"], 2);
+    let (_, body) = call(&state, "GET", "/metadata/0", None).await;
+    assert_eq!(body["metadata"]["issuer"], issuer.address());
+
+    // Server-side signing is off, so the private-key endpoints are gone
+    let (status, _) = call(&state, "POST", "/transfer", Some(json!({}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn tampered_tx_is_rejected() {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    let mallory = Wallet::generate();
+
+    let mint = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, 5, metadata()),
+        &issuer,
+    )
+    .unwrap();
+    call(&state, "POST", "/tx/submit", Some(json!(mint))).await;
+
+    let transfer = SignedTx::sign(
+        UnsignedTx::transfer(CHAIN_ID, issuer.address(), 2, 0, mallory.address(), vec![0]),
+        &issuer,
+    )
+    .unwrap();
+    let mut tampered = json!(transfer);
+    tampered["unsigned"]["payload"]["Transfer"]["token_ids"] = json!([0, 1, 2, 3, 4]);
+    let (status, body) = call(&state, "POST", "/tx/submit", Some(tampered)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+
+    let (_, body) = call(
+        &state,
+        "GET",
+        &format!("/balance/0/{}", mallory.address()),
+        None,
+    )
+    .await;
+    assert_eq!(body["balance"], 0);
+    let (_, body) = call(&state, "GET", "/chain", None).await;
+    assert_eq!(body["blocks"], 1);
+}
+
+#[tokio::test]
+async fn addresses_are_normalized_on_reads() {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+
+    let mint = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, 5, metadata()),
+        &issuer,
+    )
+    .unwrap();
+    call(&state, "POST", "/tx/submit", Some(json!(mint))).await;
+
+    let lowercase = issuer.address().to_ascii_lowercase();
+    for uri in [
+        format!("/balance/0/{lowercase}"),
+        format!("/nonce/{lowercase}"),
+        format!("/tokens/0/{lowercase}"),
+    ] {
+        let (status, body) = call(&state, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{uri}: {body}");
+        assert_eq!(body["address"], issuer.address(), "{uri}");
+    }
+    let (_, body) = call(&state, "GET", &format!("/balance/0/{lowercase}"), None).await;
+    assert_eq!(body["balance"], 5);
+    let (_, body) = call(&state, "GET", &format!("/nonce/{lowercase}"), None).await;
+    assert_eq!(body["next_nonce"], 2);
+
+    for uri in ["/balance/0/nobody", "/nonce/nobody", "/tokens/0/nobody"] {
+        let (status, _) = call(&state, "GET", uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+
+    // A signed transfer to a spelling nobody can sign for never reaches the chain
+    let transfer = SignedTx::sign(
+        UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            2,
+            0,
+            Wallet::generate().address().to_ascii_lowercase(),
+            vec![0],
+        ),
+        &issuer,
+    )
+    .unwrap();
+    let (status, body) = call(&state, "POST", "/tx/submit", Some(json!(transfer))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+}
+
+#[tokio::test]
+async fn next_nonce_is_exposed() {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    let nonce_uri = format!("/nonce/{}", issuer.address());
+
+    // Unseen address on an uninitialized chain
+    let (status, body) = call(&state, "GET", &nonce_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["next_nonce"], 1);
+
+    let mint = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, 5, metadata()),
+        &issuer,
+    )
+    .unwrap();
+    call(&state, "POST", "/tx/submit", Some(json!(mint))).await;
+
+    let (_, body) = call(&state, "GET", &nonce_uri, None).await;
+    let next_nonce = body["next_nonce"].as_u64().unwrap();
+    assert_eq!(next_nonce, 2);
+    let transfer = SignedTx::sign(
+        UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            next_nonce,
+            0,
+            Wallet::generate().address(),
+            vec![0],
+        ),
+        &issuer,
+    )
+    .unwrap();
+    let (status, body) = call(&state, "POST", "/tx/submit", Some(json!(transfer))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let (_, body) = call(&state, "GET", &nonce_uri, None).await;
+    assert_eq!(body["next_nonce"], 3);
+    let (_, body) = call(
+        &state,
+        "GET",
+        &format!("/nonce/{}", Wallet::generate().address()),
+        None,
+    )
+    .await;
+    assert_eq!(body["next_nonce"], 1);
+}
+
+#[tokio::test]
+async fn transfer_memo_is_signed_and_stored() {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    let alice = Wallet::generate();
+    let mint = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, 5, metadata()),
+        &issuer,
+    )
+    .unwrap();
+    let (status, _) = call(&state, "POST", "/tx/submit", Some(json!(mint))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let memo = "invoice 2024-117";
+    let transfer = SignedTx::sign(
+        UnsignedTx::transfer_with_memo(
+            CHAIN_ID,
+            issuer.address(),
+            2,
+            0,
+            alice.address(),
+            vec![0],
+            Some(memo.to_string()),
+        ),
+        &issuer,
+    )
+    .unwrap();
+    transfer.verify().unwrap();
+
+    let mut tampered = json!(transfer);
+    tampered["unsigned"]["payload"]["Transfer"]["memo"] = json!("invoice 2024-118");
+    let (status, _) = call(&state, "POST", "/tx/submit", Some(tampered)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = call(&state, "POST", "/tx/submit", Some(json!(transfer))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (status, body) = call(&state, "GET", &format!("/tx/{}", transfer.id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["tx"]["unsigned"]["payload"]["Transfer"]["memo"], memo);
+
+    let too_long = SignedTx::sign(
+        UnsignedTx::transfer_with_memo(
+            CHAIN_ID,
+            issuer.address(),
+            3,
+            0,
+            alice.address(),
+            vec![1],
+            Some("x".repeat(TxPayload::MAX_MEMO_LEN + 1)),
+        ),
+        &issuer,
+    )
+    .unwrap();
+    let (status, body) = call(&state, "POST", "/tx/submit", Some(json!(too_long))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("memo"), "{body}");
+}
+
+#[tokio::test]
+async fn verify_tx_explains_what_is_wrong() {
+    // Needs no chain at all
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    let tx = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, 5, metadata()),
+        &issuer,
+    )
+    .unwrap();
+
+    let (status, body) = call(&state, "POST", "/verify-tx", Some(json!(tx))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"tx_id": tx.id, "valid": true}));
+
+    let mut bad_signature = tx.clone();
+    bad_signature.signature_hex = Wallet::generate().sign_hex(&tx.unsigned.signing_bytes());
+    let (status, body) = call(&state, "POST", "/verify-tx", Some(json!(bad_signature))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["valid"], false);
+    assert_eq!(body["reason"], "signature verification failed");
+
+    let mut wrong_id = tx.clone();
+    wrong_id.id = "00".repeat(32);
+    let (_, body) = call(&state, "POST", "/verify-tx", Some(json!(wrong_id))).await;
+    assert_eq!(body["valid"], false);
+    assert_eq!(
+        body["reason"],
+        "integrity mismatch: transaction id does not match payload"
+    );
+}
@@ -0,0 +1,76 @@
+// Each test binary uses its own subset of these
+#![allow(dead_code)]
+
+use axa_network::api::{AppState, router};
+use axa_network::api_types::MetadataInput;
+use axa_network::crypto::Wallet;
+use axa_network::model::{SignedTx, TokenMetadata, UnsignedTx};
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+pub const CHAIN_ID: &str = "AToken-test";
+
+/// Metadata of the asset the tests mint
+pub fn metadata() -> TokenMetadata {
+    TokenMetadata {
+        name: "AToken".to_string(),
+        symbol: "ATKN".to_string(),
+        description: "Test token".to_string(),
+        decimals: 0,
+        issuer: String::new(),
+    }
+}
+
+/// `metadata()` as `/issue` takes it
+pub fn metadata_input() -> MetadataInput {
+    let TokenMetadata {
+        name,
+        symbol,
+        description,
+        decimals,
+        ..
+    } = metadata();
+    MetadataInput {
+        name,
+        symbol,
+        description,
+        decimals,
+    }
+}
+
+/// Commits a client-signed mint of `amount` tokens to `issuer`, the first tx on `CHAIN_ID`.
+pub async fn mint(state: &AppState, issuer: &Wallet, amount: u64) {
+    let mint = SignedTx::sign(
+        UnsignedTx::mint(CHAIN_ID, issuer.address(), 1, amount, metadata()),
+        issuer,
+    )
+    .unwrap();
+    let (status, body) = call(state, "POST", "/tx/submit", Some(json!(mint))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+pub async fn call(
+    state: &AppState,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+        .unwrap();
+    let response = router(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let body = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).unwrap()
+    };
+    (status, body)
+}
@@ -0,0 +1,78 @@
+use axa_network::api::{AppState, router};
+use axum::body::Body;
+use axum::http::{HeaderValue, Request, StatusCode, header};
+use tower::ServiceExt;
+
+const EXPLORER: &str = "https://explorer.example";
+
+async fn send(state: &AppState, request: Request<Body>) -> axum::response::Response {
+    router(state.clone()).oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn configured_origins_get_cors_headers() {
+    let state = AppState::new("AToken-local".to_string())
+        .with_cors_origins(vec![HeaderValue::from_static(EXPLORER)]);
+
+    let response = send(
+        &state,
+        Request::get("/health")
+            .header(header::ORIGIN, EXPLORER)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        EXPLORER
+    );
+
+    let preflight = send(
+        &state,
+        Request::options("/tx/submit")
+            .header(header::ORIGIN, EXPLORER)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(preflight.status(), StatusCode::OK);
+    assert_eq!(
+        preflight.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        EXPLORER
+    );
+
+    let other = send(
+        &state,
+        Request::get("/health")
+            .header(header::ORIGIN, "https://elsewhere.example")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert!(
+        !other
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+}
+
+#[tokio::test]
+async fn no_cors_headers_without_configured_origins() {
+    let state = AppState::new("AToken-local".to_string());
+    let response = send(
+        &state,
+        Request::get("/health")
+            .header(header::ORIGIN, EXPLORER)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert!(
+        !response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+}
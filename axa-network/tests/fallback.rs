@@ -0,0 +1,39 @@
+mod common;
+
+use axa_network::api::{AppState, router};
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use common::call;
+use serde_json::json;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn unknown_paths_get_a_json_404() {
+    let state = AppState::new("AToken-local".to_string());
+
+    let (status, body) = call(&state, "GET", "/no/such/route", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, json!({ "error": "no route for /no/such/route" }));
+
+    // Server-signing routes do not exist while it is off
+    let state = state.with_server_signing(false);
+    let (status, body) = call(&state, "POST", "/issue", Some(json!({}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "no route for /issue");
+}
+
+#[tokio::test]
+async fn wrong_methods_get_a_json_405() {
+    let state = AppState::new("AToken-local".to_string());
+
+    let (status, body) = call(&state, "GET", "/seal", None).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body, json!({ "error": "GET is not allowed on /seal" }));
+
+    let response = router(state)
+        .oneshot(Request::post("/chain").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()[header::ALLOW], "GET,HEAD");
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use axa_network::api::AppState;
+use axa_network::crypto::{HashAlgorithm, Wallet};
+use axa_network::genesis::GenesisConfig;
+use axa_network::storage::{FileBlockStore, InMemoryBlockStore};
+use axum::http::StatusCode;
+use common::{call, metadata_input};
+use serde_json::json;
+
+fn issue_body(wallet: &Wallet) -> serde_json::Value {
+    json!({
+        "issuer_private_key_hex": wallet.private_key_hex(),
+        "amount": 10,
+        "metadata": metadata_input()
+    })
+}
+
+#[tokio::test]
+async fn genesis_config_fixes_issuer_and_params() {
+    let dir = tempfile::tempdir().unwrap();
+    let issuer = Wallet::generate();
+    let node = Wallet::generate();
+    let genesis_path = dir.path().join("genesis.json");
+    std::fs::write(
+        &genesis_path,
+        json!({
+            "chain_id": "AToken-genesis",
+            "issuer": issuer.address().to_ascii_lowercase(),
+            "required_previous_blocks": 2,
+            "proposers": [node.address()],
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let genesis = GenesisConfig::load(&genesis_path).unwrap();
+    assert_eq!(genesis.issuer, issuer.address());
+    assert_eq!(genesis.chain_config().required_previous_blocks, 2);
+    assert_eq!(genesis.chain_config().proposers, vec![node.address()]);
+    // Same file, same block 0
+    assert_eq!(genesis.block().unwrap().hash, genesis.block().unwrap().hash);
+
+    let state = AppState::with_genesis(&genesis, InMemoryBlockStore::default())
+        .await
+        .unwrap()
+        .with_proposer(node);
+    let (_, chain) = call(&state, "GET", "/chain", None).await;
+    assert_eq!(chain["chain_id"], "AToken-genesis");
+    assert_eq!(chain["initialized"], true);
+    assert_eq!(chain["blocks"], 1);
+    let (_, block) = call(&state, "GET", "/block/height/0", None).await;
+    assert_eq!(block["hash"], genesis.block().unwrap().hash);
+
+    // Only the configured issuer can mint, even as the first /issue
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/issue",
+        Some(issue_body(&Wallet::generate())),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = call(&state, "POST", "/issue", Some(issue_body(&issuer))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["block_height"], 1);
+
+    for _ in 0..2 {
+        let (status, _) = call(&state, "POST", "/issue", Some(issue_body(&issuer))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (_, block) = call(&state, "GET", "/block/height/3", None).await;
+    assert_eq!(
+        block["header"]["previous_three_hashes"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn store_of_another_genesis_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("blocks.jsonl");
+    let genesis = GenesisConfig {
+        chain_id: "AToken-genesis".to_string(),
+        issuer: Wallet::generate().address(),
+        required_previous_blocks: 3,
+        timestamp_ms: 0,
+        proposers: Vec::new(),
+        hash_algorithm: HashAlgorithm::Sha256,
+    };
+
+    AppState::with_genesis(&genesis, FileBlockStore::new(&path))
+        .await
+        .unwrap();
+    let reopened = AppState::with_genesis(&genesis, FileBlockStore::new(&path))
+        .await
+        .unwrap();
+    let (_, chain) = call(&reopened, "GET", "/chain", None).await;
+    assert_eq!(chain["blocks"], 1);
+
+    let other = GenesisConfig {
+        issuer: Wallet::generate().address(),
+        ..genesis
+    };
+    assert!(
+        AppState::with_genesis(&other, FileBlockStore::new(&path))
+            .await
+            .is_err()
+    );
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use axa_network::api::AppState;
+use axa_network::crypto::Wallet;
+use axum::http::StatusCode;
+use common::{CHAIN_ID, call, metadata_input};
+use serde_json::json;
+
+#[tokio::test]
+async fn holders_are_counted_and_ranked() {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    let (alice, bob, carol) = (Wallet::generate(), Wallet::generate(), Wallet::generate());
+    let (status, body) = call(
+        &state,
+        "POST",
+        "/issue",
+        Some(json!({
+            "issuer_private_key_hex": issuer.private_key_hex(),
+            "amount": 10,
+            "metadata": metadata_input(),
+            "distribution": [
+                {"address": alice.address(), "count": 2},
+                {"address": bob.address(), "count": 4},
+                {"address": issuer.address(), "count": 4},
+            ],
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    // Alice hands everything to carol and drops out
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/transfer",
+        Some(json!({
+            "from_private_key_hex": alice.private_key_hex(),
+            "asset_id": 0,
+            "to_address": carol.address(),
+            "token_ids": [0, 1],
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = call(&state, "GET", "/holders/0/count", None).await;
+    assert_eq!(body["holders"], 3);
+    let (_, body) = call(&state, "GET", "/holders/0/count?exclude_issuer=true", None).await;
+    assert_eq!(body["holders"], 2);
+
+    let (status, body) = call(&state, "GET", "/holders/0/top", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let balances: Vec<u64> = body["holders"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|holder| holder["balance"].as_u64().unwrap())
+        .collect();
+    assert_eq!(balances, vec![4, 4, 2]);
+    assert_eq!(body["holders"][2]["address"], carol.address());
+
+    let (_, body) = call(
+        &state,
+        "GET",
+        "/holders/0/top?limit=1&exclude_issuer=true",
+        None,
+    )
+    .await;
+    assert_eq!(
+        body["holders"],
+        json!([{ "address": bob.address(), "balance": 4 }])
+    );
+
+    let (status, _) = call(&state, "GET", "/holders/1/count", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn supply_splits_issuer_held_and_circulating() {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    let alice = Wallet::generate();
+    call(
+        &state,
+        "POST",
+        "/issue",
+        Some(json!({
+            "issuer_private_key_hex": issuer.private_key_hex(),
+            "amount": 10,
+            "metadata": metadata_input(),
+        })),
+    )
+    .await;
+    let (_, body) = call(&state, "GET", "/supply/0", None).await;
+    assert_eq!(
+        body,
+        json!({ "asset_id": 0, "total": 10, "issuer_held": 10, "circulating": 0 })
+    );
+
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/transfer",
+        Some(json!({
+            "from_private_key_hex": issuer.private_key_hex(),
+            "asset_id": 0,
+            "to_address": alice.address(),
+            "token_ids": [0, 1, 2],
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/burn",
+        Some(json!({
+            "from_private_key_hex": issuer.private_key_hex(),
+            "asset_id": 0,
+            "token_ids": [9],
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = call(&state, "GET", "/supply/0", None).await;
+    assert_eq!(
+        body,
+        json!({ "asset_id": 0, "total": 9, "issuer_held": 6, "circulating": 3 })
+    );
+    let (status, _) = call(&state, "GET", "/supply/1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
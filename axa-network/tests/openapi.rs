@@ -0,0 +1,155 @@
+mod common;
+
+use axa_network::api::AppState;
+use axum::http::StatusCode;
+use common::call;
+use serde_json::Value;
+
+/// Every route of `router`, server-signing ones last
+const ROUTES: [(&str, &str); 37] = [
+    ("get", "/health"),
+    ("get", "/openapi.json"),
+    ("post", "/wallet/generate"),
+    ("post", "/wallet/from-private-key"),
+    ("post", "/wallet/from-mnemonic"),
+    ("post", "/wallet/export"),
+    ("post", "/wallet/import"),
+    ("post", "/tx/submit"),
+    ("get", "/tx/{tx_id}"),
+    ("get", "/proof/tx/{tx_id}"),
+    ("post", "/tx"),
+    ("post", "/simulate"),
+    ("post", "/seal"),
+    ("get", "/mempool"),
+    ("get", "/mempool/count"),
+    ("get", "/assets"),
+    ("get", "/metadata/{asset_id}"),
+    ("get", "/balance/{asset_id}/{address}"),
+    ("get", "/nonce/{address}"),
+    ("get", "/tokens/{asset_id}/{address}"),
+    ("get", "/owner/{asset_id}/{token_id}"),
+    ("get", "/supply/{asset_id}"),
+    ("get", "/holders/{asset_id}/count"),
+    ("get", "/holders/{asset_id}/top"),
+    ("get", "/chain"),
+    ("get", "/verify"),
+    ("post", "/verify-tx"),
+    ("post", "/verify-personal"),
+    ("get", "/blocks"),
+    ("get", "/block/height/{height}"),
+    ("get", "/block/hash/{hash}"),
+    ("get", "/ws/blocks"),
+    ("get", "/ws/address/{address}"),
+    ("post", "/issue"),
+    ("post", "/transfer"),
+    ("post", "/burn"),
+    ("post", "/metadata/{asset_id}"),
+];
+const SERVER_SIGNING_ROUTES: usize = 4;
+
+fn operations(spec: &Value) -> usize {
+    spec["paths"]
+        .as_object()
+        .unwrap()
+        .values()
+        .map(|item| item.as_object().unwrap().len())
+        .sum()
+}
+
+/// Follows a `#/components/schemas/...` reference.
+fn resolve<'a>(spec: &'a Value, schema: &'a Value) -> &'a Value {
+    match schema["$ref"].as_str() {
+        Some(reference) => {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            &spec["components"]["schemas"][name]
+        }
+        None => schema,
+    }
+}
+
+/// Every `$ref` anywhere under `value`.
+fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+    match value {
+        Value::Object(map) => {
+            found.extend(map.get("$ref").and_then(Value::as_str));
+            map.values().for_each(|v| refs(v, found));
+        }
+        Value::Array(items) => items.iter().for_each(|v| refs(v, found)),
+        _ => {}
+    }
+}
+
+#[tokio::test]
+async fn spec_covers_every_route_with_its_bodies_and_errors() {
+    let state = AppState::new("AToken-local".to_string());
+    let (status, spec) = call(&state, "GET", "/openapi.json", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(spec["openapi"], "3.1.0");
+
+    for (method, path) in ROUTES {
+        assert!(
+            spec["paths"][path][method].is_object(),
+            "{method} {path} missing"
+        );
+    }
+    assert_eq!(operations(&spec), ROUTES.len());
+
+    let mut found = Vec::new();
+    refs(&spec, &mut found);
+    assert!(!found.is_empty());
+    for reference in found {
+        assert!(
+            resolve(&spec, &serde_json::json!({ "$ref": reference })).is_object(),
+            "{reference}"
+        );
+    }
+
+    // Bodies resolve to the derived schemas
+    let transfer = &spec["paths"]["/transfer"]["post"];
+    assert_eq!(transfer["deprecated"], true);
+    let body = resolve(
+        &spec,
+        &transfer["requestBody"]["content"]["application/json"]["schema"],
+    );
+    assert!(body["properties"]["token_ids"].is_object());
+
+    // Path and query parameters
+    let balance = &spec["paths"]["/balance/{asset_id}/{address}"]["get"];
+    let parameters: Vec<(&str, &str)> = balance["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| (p["name"].as_str().unwrap(), p["in"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        parameters,
+        [
+            ("asset_id", "path"),
+            ("address", "path"),
+            ("height", "query")
+        ]
+    );
+
+    // Failures answer with `{error}` under the statuses `ApiError` maps to
+    for status in ["400", "403", "404", "409", "500", "503"] {
+        let error = resolve(
+            &spec,
+            &balance["responses"][status]["content"]["application/json"]["schema"],
+        );
+        assert_eq!(error["required"], serde_json::json!(["error"]), "{status}");
+    }
+    assert!(spec["paths"]["/health"]["get"]["responses"]["404"].is_null());
+}
+
+#[tokio::test]
+async fn server_signing_routes_are_left_out_when_disabled() {
+    let state = AppState::new("AToken-local".to_string()).with_server_signing(false);
+    let (_, spec) = call(&state, "GET", "/openapi.json", None).await;
+
+    for (method, path) in &ROUTES[ROUTES.len() - SERVER_SIGNING_ROUTES..] {
+        assert!(spec["paths"][path][method].is_null(), "{method} {path}");
+    }
+    // `GET /metadata/{asset_id}` stays
+    assert!(spec["paths"]["/metadata/{asset_id}"]["get"].is_object());
+    assert_eq!(operations(&spec), ROUTES.len() - SERVER_SIGNING_ROUTES);
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use axa_network::api::AppState;
+use axa_network::crypto::Wallet;
+use axa_network::model::{SignedTx, UnsignedTx};
+use axa_network::snapshot::SnapshotFile;
+use axa_network::storage::FileBlockStore;
+use axum::http::StatusCode;
+use common::{CHAIN_ID, call, metadata_input};
+use serde_json::{Value, json};
+
+async fn balance(state: &AppState, address: &Value) -> u64 {
+    let (_, body) = call(
+        state,
+        "GET",
+        &format!("/balance/0/{}", address.as_str().unwrap()),
+        None,
+    )
+    .await;
+    body["balance"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn ledger_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("blocks.jsonl");
+    // The node that sealed the genesis block stays a proposer, so it keeps its key
+    let node = Wallet::generate();
+
+    let state = AppState::with_store(CHAIN_ID.to_string(), FileBlockStore::new(&path))
+        .await
+        .unwrap()
+        .with_proposer(node.clone());
+    let (_, issuer) = call(&state, "POST", "/wallet/generate", None).await;
+    let (_, alice) = call(&state, "POST", "/wallet/generate", None).await;
+
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/issue",
+        Some(json!({
+            "issuer_private_key_hex": issuer["private_key_hex"],
+            "amount": 10,
+            "metadata": metadata_input()
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let transfer = |token_ids: Vec<u64>| {
+        json!({
+            "from_private_key_hex": issuer["private_key_hex"],
+            "asset_id": 0,
+            "to_address": alice["address"],
+            "token_ids": token_ids,
+        })
+    };
+    let (status, _) = call(&state, "POST", "/transfer", Some(transfer(vec![0, 1, 2]))).await;
+    assert_eq!(status, StatusCode::OK);
+    drop(state);
+
+    // Restart over the same file; a key generated at startup could not seal on it
+    let unkeyed = AppState::with_store(CHAIN_ID.to_string(), FileBlockStore::new(&path))
+        .await
+        .unwrap();
+    assert!(unkeyed.check_proposer().await.is_err());
+    let restarted = unkeyed.with_proposer(node);
+    restarted.check_proposer().await.unwrap();
+    assert_eq!(balance(&restarted, &issuer["address"]).await, 7);
+    assert_eq!(balance(&restarted, &alice["address"]).await, 3);
+
+    let (_, chain) = call(&restarted, "GET", "/chain", None).await;
+    assert_eq!(chain["assets"], 1);
+    assert_eq!(chain["blocks"], 2);
+
+    // Issuer and nonces are recovered, so the chain keeps going without a new mint
+    let (status, _) = call(&restarted, "POST", "/transfer", Some(transfer(vec![3]))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(balance(&restarted, &alice["address"]).await, 4);
+    drop(restarted);
+
+    // A node with a fresh key reads the chain but may not extend it
+    let stranger = AppState::with_store(CHAIN_ID.to_string(), FileBlockStore::new(&path))
+        .await
+        .unwrap();
+    assert_eq!(balance(&stranger, &alice["address"]).await, 4);
+    let (status, _) = call(&stranger, "POST", "/transfer", Some(transfer(vec![4]))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // A tx it queued survives the block it failed to seal
+    let issuer = Wallet::from_private_key_hex(issuer["private_key_hex"].as_str().unwrap()).unwrap();
+    let queued = SignedTx::sign(
+        UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            4,
+            0,
+            alice["address"].as_str().unwrap().to_string(),
+            vec![4],
+        ),
+        &issuer,
+    )
+    .unwrap();
+    let (status, body) = call(&stranger, "POST", "/tx", Some(json!(queued))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (status, _) = call(&stranger, "POST", "/seal", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, body) = call(&stranger, "GET", "/mempool", None).await;
+    assert_eq!(body["txs"][0]["tx_id"], queued.id);
+}
+
+#[tokio::test]
+async fn empty_store_starts_uninitialized() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = AppState::with_store(
+        CHAIN_ID.to_string(),
+        FileBlockStore::new(dir.path().join("none")),
+    )
+    .await
+    .unwrap();
+
+    let (_, chain) = call(&state, "GET", "/chain", None).await;
+    assert_eq!(chain["initialized"], false);
+}
+
+#[tokio::test]
+async fn restart_picks_up_from_the_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("blocks.jsonl");
+    let snapshot_path = dir.path().join("snapshot.json");
+    let node = Wallet::generate();
+    let open = || async {
+        AppState::with_snapshots(
+            CHAIN_ID.to_string(),
+            None,
+            FileBlockStore::new(&path),
+            SnapshotFile::new(&snapshot_path, 2),
+        )
+        .await
+        .unwrap()
+        .with_proposer(node.clone())
+    };
+
+    let state = open().await;
+    let issuer = Wallet::generate();
+    let alice = Wallet::generate();
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/issue",
+        Some(json!({
+            "issuer_private_key_hex": issuer.private_key_hex(),
+            "amount": 10,
+            "metadata": metadata_input()
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    for token_id in 0..2 {
+        let (status, _) = call(
+            &state,
+            "POST",
+            "/transfer",
+            Some(json!({
+                "from_private_key_hex": issuer.private_key_hex(),
+                "asset_id": 0,
+                "to_address": alice.address(),
+                "token_ids": [token_id],
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    drop(state);
+
+    // Written after block 1; block 2 is replayed on top of it
+    let snapshot = SnapshotFile::new(&snapshot_path, 2).load().await.unwrap();
+    assert_eq!(snapshot.unwrap().height, 1);
+    let state = open().await;
+    assert_eq!(balance(&state, &json!(alice.address())).await, 2);
+    let (_, chain) = call(&state, "GET", "/chain", None).await;
+    assert_eq!(chain["blocks"], 3);
+    let (_, verify) = call(&state, "GET", "/verify", None).await;
+    assert_eq!(verify["ok"], true);
+}
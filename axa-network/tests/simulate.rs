@@ -0,0 +1,97 @@
+mod common;
+
+use axa_network::api::AppState;
+use axa_network::crypto::Wallet;
+use axa_network::model::{SignedTx, UnsignedTx};
+use axum::http::StatusCode;
+use common::{CHAIN_ID, call, mint};
+use serde_json::json;
+
+#[tokio::test]
+async fn simulation_reports_the_outcome_without_applying_it() {
+    let state = AppState::new(CHAIN_ID.to_string());
+    let issuer = Wallet::generate();
+    let alice = Wallet::generate();
+    mint(&state, &issuer, 5).await;
+    let (_, chain_before) = call(&state, "GET", "/chain", None).await;
+
+    let transfer = SignedTx::sign(
+        UnsignedTx::transfer(
+            CHAIN_ID,
+            issuer.address(),
+            2,
+            0,
+            alice.address(),
+            vec![0, 1],
+        ),
+        &issuer,
+    )
+    .unwrap();
+    let (status, body) = call(&state, "POST", "/simulate", Some(json!(transfer))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["ok"], true);
+    assert_eq!(body["tx_id"], transfer.id);
+    assert_eq!(body["changes"].as_array().unwrap().len(), 2);
+
+    // Alice owns nothing yet
+    let stolen = SignedTx::sign(
+        UnsignedTx::transfer(CHAIN_ID, alice.address(), 1, 0, alice.address(), vec![3]),
+        &alice,
+    )
+    .unwrap();
+    let (status, body) = call(&state, "POST", "/simulate", Some(json!(stolen))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["ok"], false);
+    assert_eq!(body["error_kind"], "NotTokenOwner");
+    assert_eq!(body["error"], "sender does not own token id 3");
+
+    // The unsigned form is signed with the given key first
+    let (_, body) = call(
+        &state,
+        "POST",
+        "/simulate",
+        Some(json!({
+            "unsigned": transfer.unsigned,
+            "private_key_hex": issuer.private_key_hex(),
+        })),
+    )
+    .await;
+    assert_eq!(body["ok"], true, "{body}");
+
+    let (_, chain_after) = call(&state, "GET", "/chain", None).await;
+    assert_eq!(chain_after, chain_before);
+    let (_, body) = call(
+        &state,
+        "GET",
+        &format!("/balance/0/{}", alice.address()),
+        None,
+    )
+    .await;
+    assert_eq!(body["balance"], 0);
+    let (_, body) = call(&state, "GET", &format!("/nonce/{}", issuer.address()), None).await;
+    assert_eq!(body["next_nonce"], 2);
+
+    // The simulated tx is still good to submit for real
+    let (status, body) = call(&state, "POST", "/tx/submit", Some(json!(transfer))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+#[tokio::test]
+async fn simulating_unsigned_txs_needs_server_signing() {
+    let state = AppState::new(CHAIN_ID.to_string()).with_server_signing(false);
+    let issuer = Wallet::generate();
+    mint(&state, &issuer, 5).await;
+
+    let unsigned = UnsignedTx::burn(CHAIN_ID, issuer.address(), 2, 0, vec![0]);
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/simulate",
+        Some(json!({
+            "unsigned": unsigned,
+            "private_key_hex": issuer.private_key_hex(),
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use axa_network::api::AppState;
+use axa_network::crypto::Wallet;
+use axa_network::errors::Result;
+use axa_network::model::Block;
+use axa_network::storage::{BlockStore, InMemoryBlockStore};
+use axum::http::StatusCode;
+use common::{call, metadata_input};
+use serde_json::json;
+use tokio::sync::{Notify, Semaphore};
+
+/// In-memory store whose saves wait until the test lets them through.
+struct SlowStore {
+    blocks: InMemoryBlockStore,
+    saving: Arc<Notify>,
+    release: Arc<Semaphore>,
+}
+
+impl SlowStore {
+    fn new() -> Self {
+        Self {
+            blocks: InMemoryBlockStore::default(),
+            saving: Arc::new(Notify::new()),
+            release: Arc::new(Semaphore::new(0)),
+        }
+    }
+}
+
+#[async_trait]
+impl BlockStore for SlowStore {
+    async fn save_block(&self, block: &Block) -> Result<()> {
+        self.saving.notify_one();
+        self.release.acquire().await.unwrap().forget();
+        self.blocks.save_block(block).await
+    }
+
+    async fn load_blocks(&self) -> Result<Vec<Block>> {
+        self.blocks.load_blocks().await
+    }
+}
+
+#[tokio::test]
+async fn reads_go_on_while_a_block_is_saved() {
+    let store = SlowStore::new();
+    let saving = store.saving.clone();
+    let release = store.release.clone();
+    let state = AppState::with_store("AToken-test".to_string(), store)
+        .await
+        .unwrap();
+
+    let issuer = Wallet::generate();
+    let issue = tokio::spawn({
+        let state = state.clone();
+        async move {
+            call(
+                &state,
+                "POST",
+                "/issue",
+                Some(json!({
+                    "issuer_private_key_hex": issuer.private_key_hex(),
+                    "amount": 3,
+                    "metadata": metadata_input()
+                })),
+            )
+            .await
+        }
+    });
+    saving.notified().await;
+
+    // The block is applied and its save is stuck, yet reads answer right away
+    let (status, chain) =
+        tokio::time::timeout(Duration::from_secs(1), call(&state, "GET", "/chain", None))
+            .await
+            .expect("read blocked by the save");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(chain["blocks"], 1);
+    assert!(!issue.is_finished());
+
+    release.add_permits(1);
+    let (status, body) = issue.await.unwrap();
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
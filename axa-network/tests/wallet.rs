@@ -0,0 +1,128 @@
+mod common;
+
+use axa_network::api::AppState;
+use axa_network::crypto::Wallet;
+use axum::http::StatusCode;
+use common::call;
+use serde_json::json;
+
+#[tokio::test]
+async fn wallets_round_trip_through_an_encrypted_keystore() {
+    let state = AppState::new("AToken-test".to_string());
+    let (_, wallet) = call(&state, "POST", "/wallet/generate", None).await;
+
+    let (status, keystore) = call(
+        &state,
+        "POST",
+        "/wallet/export",
+        Some(json!({
+            "private_key_hex": wallet["private_key_hex"],
+            "password": "correct horse",
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{keystore}");
+    assert_eq!(keystore["address"], wallet["address"]);
+    assert_eq!(keystore["crypto"]["kdf"], "scrypt");
+    assert!(
+        !keystore
+            .to_string()
+            .contains(wallet["private_key_hex"].as_str().unwrap())
+    );
+
+    let (status, imported) = call(
+        &state,
+        "POST",
+        "/wallet/import",
+        Some(json!({"keystore": keystore, "password": "correct horse"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{imported}");
+    assert_eq!(imported["address"], wallet["address"]);
+    assert_eq!(imported["public_key_hex"], wallet["public_key_hex"]);
+    assert!(imported.get("private_key_hex").is_none());
+
+    let (status, body) = call(
+        &state,
+        "POST",
+        "/wallet/import",
+        Some(json!({"keystore": keystore, "password": "battery staple"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "wrong keystore password");
+}
+
+#[tokio::test]
+async fn generated_mnemonics_recover_the_same_wallet() {
+    let state = AppState::new("AToken-test".to_string());
+    let (_, wallet) = call(&state, "POST", "/wallet/generate", None).await;
+    let mnemonic = wallet["mnemonic"].as_str().unwrap();
+    assert_eq!(mnemonic.split_whitespace().count(), 24);
+
+    let (status, recovered) = call(
+        &state,
+        "POST",
+        "/wallet/from-mnemonic",
+        Some(json!({"mnemonic": mnemonic})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{recovered}");
+    assert_eq!(recovered["private_key_hex"], wallet["private_key_hex"]);
+    assert_eq!(recovered["address"], wallet["address"]);
+
+    let (status, other) = call(
+        &state,
+        "POST",
+        "/wallet/from-mnemonic",
+        Some(json!({"mnemonic": mnemonic, "passphrase": "extra"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{other}");
+    assert_ne!(other["address"], wallet["address"]);
+
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/wallet/from-mnemonic",
+        Some(json!({"mnemonic": "not a real phrase"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn verify_personal_accepts_only_the_signed_message() {
+    let state = AppState::new("AToken-test".to_string());
+    let wallet = Wallet::generate();
+    let signature = wallet.sign_personal("prove it: 7f3a");
+
+    let (status, body) = call(
+        &state,
+        "POST",
+        "/verify-personal",
+        Some(json!({
+            "address": wallet.address(),
+            "message": "prove it: 7f3a",
+            "signature_hex": signature,
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"valid": true}));
+
+    let (status, body) = call(
+        &state,
+        "POST",
+        "/verify-personal",
+        Some(json!({
+            "address": wallet.address(),
+            "message": "prove it: 7f3b",
+            "signature_hex": signature,
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["valid"], false);
+    assert_eq!(body["reason"], "signature verification failed");
+}
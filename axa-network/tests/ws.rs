@@ -0,0 +1,118 @@
+mod common;
+
+use std::time::Duration;
+
+use axa_network::api::{AppState, serve};
+use axa_network::crypto::Wallet;
+use axum::http::StatusCode;
+use common::{call, metadata_input};
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+#[tokio::test]
+async fn committed_blocks_are_pushed_to_subscribers() {
+    let state = AppState::new("AToken-test".to_string());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, state.clone(), std::future::pending()));
+
+    let (mut feed, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws/blocks"))
+        .await
+        .unwrap();
+
+    let issuer = Wallet::generate();
+    let (status, issued) = call(
+        &state,
+        "POST",
+        "/issue",
+        Some(json!({
+            "issuer_private_key_hex": issuer.private_key_hex(),
+            "amount": 3,
+            "metadata": metadata_input()
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let message = tokio::time::timeout(Duration::from_secs(5), feed.next())
+        .await
+        .expect("no block pushed")
+        .unwrap()
+        .unwrap();
+    let Message::Text(text) = message else {
+        panic!("expected a text message, got {message:?}");
+    };
+    let block: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(block["header"]["height"], 0);
+    assert_eq!(block["hash"], issued["block_hash"]);
+    assert_eq!(block["txs"][0]["id"], issued["tx_id"]);
+}
+
+#[tokio::test]
+async fn transfers_push_balance_events_to_the_recipient() {
+    let state = AppState::new("AToken-test".to_string());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, state.clone(), std::future::pending()));
+
+    let issuer = Wallet::generate();
+    let alice = Wallet::generate();
+    let (status, _) = call(
+        &state,
+        "POST",
+        "/issue",
+        Some(json!({
+            "issuer_private_key_hex": issuer.private_key_hex(),
+            "amount": 3,
+            "metadata": metadata_input()
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (mut feed, _) = tokio_tungstenite::connect_async(format!(
+        "ws://{addr}/ws/address/{}",
+        alice.address().to_ascii_lowercase()
+    ))
+    .await
+    .unwrap();
+
+    let (status, transferred) = call(
+        &state,
+        "POST",
+        "/transfer",
+        Some(json!({
+            "from_private_key_hex": issuer.private_key_hex(),
+            "asset_id": 0,
+            "to_address": alice.address(),
+            "token_ids": [0, 2]
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let message = tokio::time::timeout(Duration::from_secs(5), feed.next())
+        .await
+        .expect("no balance event pushed")
+        .unwrap()
+        .unwrap();
+    let Message::Text(text) = message else {
+        panic!("expected a text message, got {message:?}");
+    };
+    let event: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(event["address"], alice.address());
+    assert_eq!(event["block_height"], 1);
+    assert_eq!(event["block_hash"], transferred["block_hash"]);
+    assert_eq!(
+        event["changes"],
+        json!([{
+            "address": alice.address(),
+            "asset_id": 0,
+            "gained": [0, 2],
+            "lost": [],
+            "balance": 2
+        }])
+    );
+}
@@ -0,0 +1,117 @@
+//! Best bid/ask selection across exchanges: cloning full updates while holding the
+//! map guard (previous `find_arbitrage`) vs. scanning a top-of-book snapshot.
+
+use arbscanner::exchanges::PriceUpdate;
+use arbscanner::scanner::{snapshot_quotes, QuoteSnapshot};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+
+const EXCHANGES: &[&str] = &[
+    "binance", "bybit", "okx", "kraken", "kucoin", "gate", "mexc", "htx", "bitget", "coinbase",
+];
+const DEPTH: usize = 5;
+
+type Prices = DashMap<String, DashMap<String, PriceUpdate>>;
+
+fn populate(symbols: usize) -> (Prices, Vec<String>) {
+    let prices = DashMap::new();
+    let mut names = Vec::with_capacity(symbols);
+
+    for s in 0..symbols {
+        let symbol = format!("SYM{}/USDT", s);
+        let by_exchange = DashMap::new();
+        for (e, exchange) in EXCHANGES.iter().enumerate() {
+            let mid = Decimal::from(100 + (s + e * 7) % 13);
+            let level = |i: usize, side: i64| {
+                (mid + Decimal::new(side * (i as i64 + 1), 2), Decimal::from(i + 1))
+            };
+            by_exchange.insert(
+                exchange.to_string(),
+                PriceUpdate {
+                    exchange: exchange.to_string(),
+                    symbol: symbol.clone(),
+                    raw_symbol: symbol.replace('/', ""),
+                    bid: level(0, -1).0,
+                    ask: level(0, 1).0,
+                    bid_size: Decimal::ONE,
+                    ask_size: Decimal::ONE,
+                    bids: (0..DEPTH).map(|i| level(i, -1)).collect(),
+                    asks: (0..DEPTH).map(|i| level(i, 1)).collect(),
+                    timestamp: 0,
+                },
+            );
+        }
+        prices.insert(symbol.clone(), by_exchange);
+        names.push(symbol);
+    }
+
+    (prices, names)
+}
+
+/// The previous approach: outer guard held for the whole scan, full update cloned on every improvement
+fn clone_under_guard(prices: &Prices, symbol: &str) -> Option<(PriceUpdate, PriceUpdate)> {
+    let by_exchange = prices.get(symbol)?;
+    let mut best_bid: Option<PriceUpdate> = None;
+    let mut best_ask: Option<PriceUpdate> = None;
+
+    for entry in by_exchange.iter() {
+        let update = entry.value();
+        if best_bid.as_ref().is_none_or(|b| update.bid > b.bid) {
+            best_bid = Some(update.clone());
+        }
+        if best_ask.as_ref().is_none_or(|a| update.ask < a.ask) {
+            best_ask = Some(update.clone());
+        }
+    }
+
+    Some((best_bid?, best_ask?))
+}
+
+/// Best bid (highest, where we sell) and best ask (lowest, where we buy)
+fn best_bid_ask(quotes: &[QuoteSnapshot]) -> Option<(&QuoteSnapshot, &QuoteSnapshot)> {
+    let best_bid = quotes.iter().max_by_key(|quote| quote.bid)?;
+    let best_ask = quotes.iter().min_by_key(|quote| quote.ask)?;
+    Some((best_bid, best_ask))
+}
+
+/// Current approach: copy top of book, release the guard, then clone only the two winners
+fn snapshot_then_scan(prices: &Prices, symbol: &str) -> Option<(PriceUpdate, PriceUpdate)> {
+    let quotes = snapshot_quotes(prices.get(symbol)?.value());
+    let (bid, ask) = best_bid_ask(&quotes)?;
+
+    let by_exchange = prices.get(symbol)?;
+    let sell = by_exchange.get(&bid.exchange)?.clone();
+    let buy = by_exchange.get(&ask.exchange)?.clone();
+    Some((sell, buy))
+}
+
+fn bench_find_arbitrage(c: &mut Criterion) {
+    let mut group = c.benchmark_group("best_bid_ask");
+
+    for symbols in [1_000, 3_000, 5_000] {
+        let (prices, names) = populate(symbols);
+        group.throughput(Throughput::Elements(symbols as u64));
+
+        group.bench_with_input(BenchmarkId::new("clone_under_guard", symbols), &names, |b, names| {
+            b.iter(|| {
+                for symbol in names {
+                    black_box(clone_under_guard(&prices, symbol));
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("snapshot", symbols), &names, |b, names| {
+            b.iter(|| {
+                for symbol in names {
+                    black_box(snapshot_then_scan(&prices, symbol));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_find_arbitrage);
+criterion_main!(benches);
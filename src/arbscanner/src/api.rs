@@ -0,0 +1,156 @@
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Json, Router,
+};
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+use crate::exchanges::PriceUpdate;
+use crate::scanner::{ArbitrageOpportunity, ScannerHandle, SymbolOpportunity};
+
+#[derive(Debug, Serialize)]
+struct PricesResponse {
+    symbol: String,
+    prices: Vec<PriceUpdate>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+pub fn router(scanner: ScannerHandle) -> Router {
+    Router::new()
+        .route("/prices/:symbol", get(prices))
+        .route("/opportunities", get(opportunities))
+        .route("/stream/opportunities", get(stream_opportunities))
+        .with_state(scanner)
+}
+
+/// Serve the read-only API on `addr` until the process exits
+pub async fn serve(addr: String, scanner: ScannerHandle) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+
+    info!(addr = %addr, "API server listening");
+    axum::serve(listener, router(scanner)).await?;
+    Ok(())
+}
+
+/// Accept `BTC-USDT`, `btc_usdt` or an encoded `BTC%2FUSDT` for the normalized `BTC/USDT`
+fn symbol_from_path(raw: &str) -> String {
+    raw.trim().to_uppercase().replace(['-', '_'], "/")
+}
+
+async fn prices(
+    State(scanner): State<ScannerHandle>,
+    Path(symbol): Path<String>,
+) -> Result<Json<PricesResponse>, ApiError> {
+    let symbol = symbol_from_path(&symbol);
+
+    match scanner.prices(&symbol) {
+        Some(prices) => Ok(Json(PricesResponse { symbol, prices })),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!("no prices for {}", symbol),
+            }),
+        )),
+    }
+}
+
+/// Latest opportunity per symbol, each with the runner-up exchange pair when there is one
+async fn opportunities(State(scanner): State<ScannerHandle>) -> Json<Vec<SymbolOpportunity>> {
+    Json(scanner.recent_opportunities())
+}
+
+/// Server-Sent Events feed of alerted opportunities (same cooldown as the notifier).
+/// Each opportunity is an `opportunity` event with a JSON body; a subscriber that falls
+/// more than the channel capacity behind gets a `lagged` event with the number of skipped
+/// opportunities instead of unbounded buffering.
+async fn stream_opportunities(
+    State(scanner): State<ScannerHandle>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Sse::new(opportunity_events(scanner.subscribe())).keep_alive(KeepAlive::default())
+}
+
+fn opportunity_events(
+    rx: broadcast::Receiver<ArbitrageOpportunity>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream::unfold(rx, |mut rx| async move {
+        let event = match rx.recv().await {
+            Ok(opportunity) => match Event::default().event("opportunity").json_data(&opportunity) {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!(error = ?e, "Failed to encode opportunity event");
+                    return None;
+                }
+            },
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(skipped, "Opportunity stream subscriber lagged");
+                Event::default().event("lagged").data(skipped.to_string())
+            }
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(event), rx))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_from_path() {
+        assert_eq!(symbol_from_path("BTC-USDT"), "BTC/USDT");
+        assert_eq!(symbol_from_path("eth_usdc"), "ETH/USDC");
+        assert_eq!(symbol_from_path("SOL/USDT"), "SOL/USDT");
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_gets_lagged_event() {
+        use futures_util::StreamExt;
+        use rust_decimal::Decimal;
+
+        let (tx, rx) = broadcast::channel(2);
+        let opportunity = |timestamp| ArbitrageOpportunity {
+            symbol: "BTC/USDT".to_string(),
+            buy_exchange: "binance".to_string(),
+            sell_exchange: "okx".to_string(),
+            buy_symbol: "BTCUSDT".to_string(),
+            sell_symbol: "BTC-USDT".to_string(),
+            buy_price: Decimal::from(100),
+            sell_price: Decimal::from(101),
+            spread_percent: Decimal::ONE,
+            spread_usd: Decimal::ONE,
+            net_spread_percent: Decimal::ONE,
+            buy_fee: Decimal::ZERO,
+            sell_fee: Decimal::ZERO,
+            executable_quantity: Decimal::ONE,
+            available_volume: Decimal::ONE,
+            limiting_size: None,
+            timestamp,
+        };
+        for timestamp in 0..5 {
+            tx.send(opportunity(timestamp)).unwrap();
+        }
+        drop(tx);
+
+        let events: Vec<String> = opportunity_events(rx)
+            .map(|event| format!("{:?}", event.unwrap()))
+            .collect()
+            .await;
+
+        assert_eq!(events.len(), 3);
+        assert!(events[0].contains("lagged") && events[0].contains('3'));
+        assert!(events[1].contains("opportunity"));
+    }
+}
@@ -1,88 +1,735 @@
 use anyhow::{Result, Context};
 use rust_decimal::Decimal;
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::Duration;
+
+use crate::matcher::DEFAULT_QUOTE_CURRENCIES;
+
+/// Exchanges the scanner knows how to connect to
+pub const KNOWN_EXCHANGES: &[&str] = &[
+    "binance", "bybit", "okx", "kraken", "kucoin", "gate", "mexc", "htx", "bitget", "coinbase",
+    "bitfinex",
+];
+
+/// Endpoint overrides for a single exchange (testnet, regional hosts, proxies)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointOverride {
+    /// Replaces the connector's websocket URL (`<EXCHANGE>_WS_URL`)
+    pub ws_url: Option<String>,
+
+    /// Replaces the connector's REST base URL, without path (`<EXCHANGE>_REST_URL`)
+    pub rest_url: Option<String>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     /// Minimum spread % to trigger alert (e.g., 0.3 = 0.3%)
     pub min_spread_percent: Decimal,
-    
+
     /// Maximum spread % (filter anomalies)
     pub max_spread_percent: Decimal,
-    
+
+    /// Symbol -> minimum spread %, from `SPREAD_OVERRIDES=BTC/USDT:0.1,DOGE/USDT:1.5`
+    pub min_spread_overrides: HashMap<String, Decimal>,
+
+    /// Symbol -> maximum spread %, from `MAX_SPREAD_OVERRIDES` (same format)
+    pub max_spread_overrides: HashMap<String, Decimal>,
+
     /// Cooldown between alerts for same pair (ms)
     pub cooldown_ms: u64,
-    
-    /// URL to send arbitrage alerts to
+
+    /// URL to send arbitrage alerts to (empty disables the callback sink)
     pub callback_url: String,
-    
+
+    /// Shared secret for signing callback requests (`CALLBACK_SECRET`, unsigned when unset)
+    pub callback_secret: Option<String>,
+
+    /// Telegram bot token; the Telegram sink is enabled when this and the chat id are set
+    pub telegram_bot_token: Option<String>,
+
+    /// Telegram chat to post alerts to
+    pub telegram_chat_id: Option<String>,
+
+    /// Discord webhook; enables the Discord sink when set
+    pub discord_webhook_url: Option<String>,
+
+    /// Kafka bootstrap servers; enables the Kafka sink when set (needs the `kafka` feature)
+    pub kafka_brokers: Option<String>,
+
+    /// Topic opportunities are produced to
+    pub kafka_topic: String,
+
+    /// Records buffered for the Kafka producer before new ones are dropped
+    pub kafka_queue_size: usize,
+
+    /// Retries per sink after a failed send, with exponential backoff
+    pub notify_max_retries: u32,
+
+    /// Delay before the first retry; doubles on each further attempt (ms)
+    pub notify_retry_base_ms: u64,
+
+    /// File that alerts are appended to once retries are exhausted (`NOTIFY_DLQ_PATH`, disabled when unset)
+    pub notify_dlq_path: Option<String>,
+
     /// Optional: filter specific pairs (comma-separated, e.g., "BTC,ETH,SOL")
     pub filter_pairs: Vec<String>,
-    
+
     /// Optional: filter specific exchanges
     pub filter_exchanges: Vec<String>,
-    
+
     /// Enabled exchanges (comma-separated)
     pub enabled_exchanges: Vec<String>,
-    
-    /// Number of top orderbook levels to track
+
+    /// Quote currencies for splitting concatenated symbols, in priority order
+    pub quote_currencies: Vec<String>,
+
+    /// Match pairs quoted in any of `stablecoins` against each other (opt-in: ignores depeg risk)
+    pub stablecoin_equivalence: bool,
+
+    /// Quotes treated as equivalent; the first is the canonical quote in normalized symbols
+    pub stablecoins: Vec<String>,
+
+    /// Base alias -> canonical base, from `BASE_ALIASES=XBT:BTC,XDG:DOGE` (empty = no renaming)
+    pub base_aliases: HashMap<String, String>,
+
+    /// Drop leveraged-token quotes (BTC3L, ETHBULL, BTCUP…) entirely instead of only
+    /// keeping them out of cross-exchange matching
+    pub exclude_leveraged_tokens: bool,
+
+    /// Number of top orderbook levels to track (5 by default); 0 or 1 keeps to the best
+    /// bid and ask
     pub orderbook_depth: usize,
+
+    /// Notional (quote currency) to size executable prices against the order book
+    pub execution_notional: Decimal,
+
+    /// Top-of-book size both legs must show, in USD, for stablecoin-quoted pairs (0 = no filter)
+    pub min_notional_usd: Decimal,
+
+    /// Let quotes without size (Gate sends zero) through the notional filter instead of dropping them
+    pub min_notional_skip_unsized: bool,
+
+    /// Max symbols each connector subscribes to after quote filtering (0 = unlimited)
+    pub max_symbols_per_exchange: usize,
+
+    /// How long a fetched symbol list is reused across reconnects (ms)
+    pub symbol_cache_ttl_ms: u64,
+
+    /// Also look for triangular cycles within a single exchange
+    pub enable_triangular: bool,
+
+    /// Max age of each leg's quote for a triangular cycle to count (ms)
+    pub triangular_max_leg_age_ms: u64,
+
+    /// Quotes besides USDT that connectors subscribe to for triangular cycles (ETH/BTC, …)
+    pub triangular_quotes: Vec<String>,
+
+    /// Quotes older than this are ignored and evicted (ms, 0 = keep forever)
+    pub max_price_age_ms: u64,
+
+    /// Exchange -> taker fee as a fraction (0.001 = 0.1%), from `FEE_<EXCHANGE>`
+    pub fees: HashMap<String, Decimal>,
+
+    /// Taker fee for exchanges without an explicit entry (`FEE_DEFAULT`)
+    pub default_fee: Decimal,
+
+    /// SQLite database to persist opportunities to (`SQLITE_PATH`, disabled when unset)
+    pub sqlite_path: Option<String>,
+
+    /// File alert cooldowns are saved to and restored from (`COOLDOWN_STATE_PATH`, disabled when unset)
+    pub cooldown_state_path: Option<String>,
+
+    /// Address for the read-only REST API (`API_ADDR`, disabled when unset)
+    pub api_addr: Option<String>,
+
+    /// Address for the Prometheus `/metrics` server (`METRICS_ADDR`, disabled when unset)
+    pub metrics_addr: Option<String>,
+
+    /// NDJSON file every live price update is appended to (`RECORD_PATH`, disabled when unset)
+    pub record_path: Option<String>,
+
+    /// Recorded NDJSON file to play back instead of connecting to exchanges (`REPLAY_PATH`)
+    pub replay_path: Option<String>,
+
+    /// Replay as fast as the scanner keeps up instead of at the recorded pace (`REPLAY_FAST`)
+    pub replay_fast: bool,
+
+    /// Exchange -> endpoint overrides; exchanges without overrides use built-in defaults
+    pub endpoints: HashMap<String, EndpointOverride>,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self> {
         dotenvy::dotenv().ok();
-        
-        let min_spread = std::env::var("MIN_SPREAD_PERCENT")
-            .unwrap_or_else(|_| "0.8".to_string());
-        let max_spread = std::env::var("MAX_SPREAD_PERCENT")
-            .unwrap_or_else(|_| "10.0".to_string());
-        let cooldown = std::env::var("COOLDOWN_MS")
-            .unwrap_or_else(|_| "1000".to_string());
-        let callback_url = std::env::var("CALLBACK_URL")
-            .unwrap_or_else(|_| "http://192.168.1.223:82/api/datastudio/trigger".to_string());
-        
-        let filter_pairs = std::env::var("FILTER_PAIRS")
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Build config from an arbitrary variable source (env in production, a map in tests)
+    pub fn from_lookup<F>(lookup: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let min_spread = lookup("MIN_SPREAD_PERCENT")
+            .unwrap_or_else(|| "0.8".to_string());
+        let max_spread = lookup("MAX_SPREAD_PERCENT")
+            .unwrap_or_else(|| "10.0".to_string());
+        let min_spread_overrides = lookup("SPREAD_OVERRIDES")
+            .map(|raw| parse_spread_overrides(&raw).context("Invalid SPREAD_OVERRIDES"))
+            .transpose()?
+            .unwrap_or_default();
+        let max_spread_overrides = lookup("MAX_SPREAD_OVERRIDES")
+            .map(|raw| parse_spread_overrides(&raw).context("Invalid MAX_SPREAD_OVERRIDES"))
+            .transpose()?
+            .unwrap_or_default();
+        let cooldown = lookup("COOLDOWN_MS")
+            .unwrap_or_else(|| "1000".to_string());
+        let callback_url = lookup("CALLBACK_URL")
+            .unwrap_or_else(|| "http://192.168.1.223:82/api/datastudio/trigger".to_string());
+        let callback_secret = lookup("CALLBACK_SECRET")
+            .filter(|s| !s.is_empty());
+
+        let telegram_bot_token = lookup("TELEGRAM_BOT_TOKEN")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        let telegram_chat_id = lookup("TELEGRAM_CHAT_ID")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let discord_webhook_url = lookup("DISCORD_WEBHOOK_URL")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let kafka_brokers = lookup("KAFKA_BROKERS")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        let kafka_topic = lookup("KAFKA_TOPIC")
+            .unwrap_or_else(|| "arbitrage-opportunities".to_string());
+        let kafka_queue_size = lookup("KAFKA_QUEUE_SIZE")
+            .unwrap_or_else(|| "10000".to_string());
+
+        let notify_max_retries = lookup("NOTIFY_MAX_RETRIES")
+            .unwrap_or_else(|| "3".to_string());
+        let notify_retry_base = lookup("NOTIFY_RETRY_BASE_MS")
+            .unwrap_or_else(|| "500".to_string());
+        let notify_dlq_path = lookup("NOTIFY_DLQ_PATH")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let filter_pairs = lookup("FILTER_PAIRS")
             .unwrap_or_default()
             .split(',')
             .map(|s| s.trim().to_uppercase())
             .filter(|s| !s.is_empty())
             .collect();
-        
-        let filter_exchanges = std::env::var("FILTER_EXCHANGES")
+
+        let filter_exchanges = lookup("FILTER_EXCHANGES")
             .unwrap_or_default()
             .split(',')
             .map(|s| s.trim().to_lowercase())
             .filter(|s| !s.is_empty())
             .collect();
-        
-        let enabled_exchanges = std::env::var("ENABLED_EXCHANGES")
-            .unwrap_or_else(|_| "binance,bybit,okx,kraken,kucoin,gate,mexc,htx,bitget,coinbase".to_string())
+
+        let enabled_exchanges = lookup("ENABLED_EXCHANGES")
+            .unwrap_or_else(|| KNOWN_EXCHANGES.join(","))
             .split(',')
             .map(|s| s.trim().to_lowercase())
             .filter(|s| !s.is_empty())
             .collect();
-        
-        let orderbook_depth = std::env::var("ORDERBOOK_DEPTH")
-            .unwrap_or_else(|_| "5".to_string())
+
+        let quote_currencies: Vec<String> = lookup("QUOTE_CURRENCIES")
+            .map(|v| {
+                v.split(',')
+                    .map(|s| s.trim().to_uppercase())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .filter(|quotes: &Vec<String>| !quotes.is_empty())
+            .unwrap_or_else(|| DEFAULT_QUOTE_CURRENCIES.iter().map(|q| q.to_string()).collect());
+
+        let stablecoin_equivalence = lookup("STABLECOIN_EQUIVALENCE")
+            .map(|v| parse_flag(&v))
+            .unwrap_or(false);
+        let stablecoins = lookup("STABLECOINS")
+            .unwrap_or_else(|| "USDT,USDC,USD,FDUSD,BUSD,TUSD,USDP,DAI".to_string())
+            .split(',')
+            .map(|s| s.trim().to_uppercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let base_aliases = lookup("BASE_ALIASES")
+            .map(|raw| parse_base_aliases(&raw).context("Invalid BASE_ALIASES"))
+            .transpose()?
+            .unwrap_or_default();
+
+        let exclude_leveraged_tokens = lookup("EXCLUDE_LEVERAGED_TOKENS")
+            .map(|v| parse_flag(&v))
+            .unwrap_or(true);
+
+        let orderbook_depth = lookup("ORDERBOOK_DEPTH")
+            .unwrap_or_else(|| "5".to_string())
+            .trim()
             .parse()
-            .unwrap_or(5);
-        
+            .context("Invalid ORDERBOOK_DEPTH")?;
+
+        let execution_notional = lookup("EXECUTION_NOTIONAL")
+            .unwrap_or_else(|| "1000".to_string());
+        let min_notional = lookup("MIN_NOTIONAL_USD")
+            .unwrap_or_else(|| "0".to_string());
+        let min_notional_skip_unsized = lookup("MIN_NOTIONAL_SKIP_UNSIZED")
+            .map(|v| parse_flag(&v))
+            .unwrap_or(true);
+        let max_symbols = lookup("MAX_SYMBOLS_PER_EXCHANGE")
+            .unwrap_or_else(|| "100".to_string());
+        let symbol_cache_ttl = lookup("SYMBOL_CACHE_TTL_MS")
+            .unwrap_or_else(|| "3600000".to_string());
+
+        let enable_triangular = lookup("ENABLE_TRIANGULAR")
+            .map(|v| parse_flag(&v))
+            .unwrap_or(false);
+        let triangular_max_leg_age = lookup("TRIANGULAR_MAX_LEG_AGE_MS")
+            .unwrap_or_else(|| "2000".to_string());
+        let triangular_quotes = lookup("TRIANGULAR_QUOTES")
+            .unwrap_or_else(|| "BTC,ETH".to_string())
+            .split(',')
+            .map(|s| s.trim().to_uppercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let max_price_age = lookup("MAX_PRICE_AGE_MS")
+            .unwrap_or_else(|| "5000".to_string());
+
+        let default_fee = lookup("FEE_DEFAULT")
+            .unwrap_or_else(|| "0.001".to_string());
+        let mut fees = HashMap::new();
+        for exchange in KNOWN_EXCHANGES {
+            let key = format!("FEE_{}", exchange.to_uppercase());
+            if let Some(fee) = lookup(&key) {
+                let fee = Decimal::from_str(fee.trim())
+                    .with_context(|| format!("Invalid {}", key))?;
+                fees.insert(exchange.to_string(), fee);
+            }
+        }
+
+        let sqlite_path = lookup("SQLITE_PATH")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let cooldown_state_path = lookup("COOLDOWN_STATE_PATH")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let api_addr = lookup("API_ADDR")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let metrics_addr = lookup("METRICS_ADDR")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let record_path = lookup("RECORD_PATH")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        let replay_path = lookup("REPLAY_PATH")
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        let replay_fast = lookup("REPLAY_FAST")
+            .map(|v| parse_flag(&v))
+            .unwrap_or(false);
+
+        let mut endpoints = HashMap::new();
+        for exchange in KNOWN_EXCHANGES {
+            let prefix = exchange.to_uppercase();
+            let endpoint = EndpointOverride {
+                ws_url: lookup(&format!("{}_WS_URL", prefix)).filter(|s| !s.trim().is_empty()),
+                rest_url: lookup(&format!("{}_REST_URL", prefix)).filter(|s| !s.trim().is_empty()),
+            };
+            if endpoint != EndpointOverride::default() {
+                endpoints.insert(exchange.to_string(), endpoint);
+            }
+        }
+
         Ok(Self {
-            min_spread_percent: Decimal::from_str(&min_spread)
+            min_spread_percent: Decimal::from_str(min_spread.trim())
                 .context("Invalid MIN_SPREAD_PERCENT")?,
-            max_spread_percent: Decimal::from_str(&max_spread)
+            max_spread_percent: Decimal::from_str(max_spread.trim())
                 .context("Invalid MAX_SPREAD_PERCENT")?,
-            cooldown_ms: cooldown.parse().context("Invalid COOLDOWN_MS")?,
+            min_spread_overrides,
+            max_spread_overrides,
+            cooldown_ms: cooldown.trim().parse().context("Invalid COOLDOWN_MS")?,
             callback_url,
+            callback_secret,
+            telegram_bot_token,
+            telegram_chat_id,
+            discord_webhook_url,
+            kafka_brokers,
+            kafka_topic,
+            kafka_queue_size: kafka_queue_size
+                .trim()
+                .parse()
+                .context("Invalid KAFKA_QUEUE_SIZE")?,
+            notify_max_retries: notify_max_retries
+                .trim()
+                .parse()
+                .context("Invalid NOTIFY_MAX_RETRIES")?,
+            notify_retry_base_ms: notify_retry_base
+                .trim()
+                .parse()
+                .context("Invalid NOTIFY_RETRY_BASE_MS")?,
+            notify_dlq_path,
             filter_pairs,
             filter_exchanges,
             enabled_exchanges,
+            quote_currencies,
+            stablecoin_equivalence,
+            stablecoins,
+            base_aliases,
+            exclude_leveraged_tokens,
             orderbook_depth,
+            execution_notional: Decimal::from_str(execution_notional.trim())
+                .context("Invalid EXECUTION_NOTIONAL")?,
+            min_notional_usd: Decimal::from_str(min_notional.trim())
+                .context("Invalid MIN_NOTIONAL_USD")?,
+            min_notional_skip_unsized,
+            max_symbols_per_exchange: max_symbols
+                .trim()
+                .parse()
+                .context("Invalid MAX_SYMBOLS_PER_EXCHANGE")?,
+            symbol_cache_ttl_ms: symbol_cache_ttl
+                .trim()
+                .parse()
+                .context("Invalid SYMBOL_CACHE_TTL_MS")?,
+            enable_triangular,
+            triangular_max_leg_age_ms: triangular_max_leg_age
+                .trim()
+                .parse()
+                .context("Invalid TRIANGULAR_MAX_LEG_AGE_MS")?,
+            triangular_quotes,
+            max_price_age_ms: max_price_age
+                .trim()
+                .parse()
+                .context("Invalid MAX_PRICE_AGE_MS")?,
+            fees,
+            default_fee: Decimal::from_str(default_fee.trim())
+                .context("Invalid FEE_DEFAULT")?,
+            sqlite_path,
+            cooldown_state_path,
+            api_addr,
+            metrics_addr,
+            record_path,
+            replay_path,
+            replay_fast,
+            endpoints,
         })
     }
-    
+
     pub fn is_exchange_enabled(&self, exchange: &str) -> bool {
         self.enabled_exchanges.contains(&exchange.to_lowercase())
     }
+
+    /// Symbol cap suitable for `Iterator::take` (`usize::MAX` when unlimited)
+    pub fn symbol_limit(&self) -> usize {
+        if self.max_symbols_per_exchange == 0 {
+            usize::MAX
+        } else {
+            self.max_symbols_per_exchange
+        }
+    }
+
+    /// Whether connectors should subscribe to pairs quoted in `quote`: USDT always, the
+    /// `STABLECOINS` with stablecoin equivalence on, and the cross quotes of
+    /// `TRIANGULAR_QUOTES` while triangular scanning is on
+    pub fn subscribes_to_quote(&self, quote: &str) -> bool {
+        let listed = |quotes: &[String]| quotes.iter().any(|q| q.eq_ignore_ascii_case(quote));
+        quote.eq_ignore_ascii_case("USDT")
+            || (self.stablecoin_equivalence && listed(&self.stablecoins))
+            || (self.enable_triangular && listed(&self.triangular_quotes))
+    }
+
+    /// `symbol_cache_ttl_ms` as a `Duration`
+    pub fn symbol_cache_ttl(&self) -> Duration {
+        Duration::from_millis(self.symbol_cache_ttl_ms)
+    }
+
+    /// Minimum net spread % for `symbol`, falling back to `MIN_SPREAD_PERCENT`
+    pub fn min_spread_for(&self, symbol: &str) -> Decimal {
+        self.min_spread_overrides
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.min_spread_percent)
+    }
+
+    /// Maximum gross spread % for `symbol`, falling back to `MAX_SPREAD_PERCENT`
+    pub fn max_spread_for(&self, symbol: &str) -> Decimal {
+        self.max_spread_overrides
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.max_spread_percent)
+    }
+
+    /// Taker fee fraction charged by an exchange
+    pub fn fee_for(&self, exchange: &str) -> Decimal {
+        self.fees
+            .get(exchange)
+            .copied()
+            .unwrap_or(self.default_fee)
+    }
+
+    /// Websocket URL for an exchange, falling back to the connector's default
+    pub fn ws_url(&self, exchange: &str, default: &str) -> String {
+        self.endpoints
+            .get(exchange)
+            .and_then(|e| e.ws_url.clone())
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    /// REST base URL for an exchange (no trailing slash), falling back to the connector's default
+    pub fn rest_url(&self, exchange: &str, default: &str) -> String {
+        self.endpoints
+            .get(exchange)
+            .and_then(|e| e.rest_url.as_deref())
+            .unwrap_or(default)
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+/// Parse `BTC/USDT:0.1,DOGE/USDT:1.5` into symbol -> percent; blank entries are skipped
+fn parse_spread_overrides(raw: &str) -> Result<HashMap<String, Decimal>> {
+    let mut overrides = HashMap::new();
+    for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (symbol, percent) = entry
+            .split_once(':')
+            .with_context(|| format!("Expected SYMBOL:PERCENT, got {:?}", entry))?;
+        let symbol = symbol.trim().to_uppercase();
+        let valid = symbol.split_once('/').is_some_and(|(base, quote)| {
+            [base, quote]
+                .iter()
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+        });
+        if !valid {
+            anyhow::bail!("Expected a BASE/QUOTE symbol, got {:?}", symbol);
+        }
+        let percent = Decimal::from_str(percent.trim())
+            .with_context(|| format!("Invalid percent for {}", symbol))?;
+        overrides.insert(symbol, percent);
+    }
+    Ok(overrides)
+}
+
+/// Parse `ALIAS:CANONICAL,...` base renames, e.g. `XBT:BTC`
+fn parse_base_aliases(raw: &str) -> Result<HashMap<String, String>> {
+    let mut aliases = HashMap::new();
+    for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (alias, canonical) = entry
+            .split_once(':')
+            .with_context(|| format!("Expected ALIAS:CANONICAL, got {:?}", entry))?;
+        let (alias, canonical) = (alias.trim().to_uppercase(), canonical.trim().to_uppercase());
+        let valid = [&alias, &canonical]
+            .iter()
+            .all(|ticker| !ticker.is_empty() && ticker.chars().all(|c| c.is_ascii_alphanumeric()));
+        if !valid {
+            anyhow::bail!("Expected alphanumeric tickers, got {:?}", entry);
+        }
+        aliases.insert(alias, canonical);
+    }
+    Ok(aliases)
+}
+
+/// Parse an on/off env flag ("1", "true", "yes", "on" are truthy)
+fn parse_flag(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_from(vars: &[(&str, &str)]) -> Config {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::from_lookup(|key| vars.get(key).cloned()).unwrap()
+    }
+
+    #[test]
+    fn test_endpoint_defaults() {
+        let config = config_from(&[]);
+
+        assert!(config.endpoints.is_empty());
+        assert_eq!(
+            config.ws_url("binance", "wss://stream.binance.com:9443"),
+            "wss://stream.binance.com:9443"
+        );
+        assert_eq!(
+            config.rest_url("binance", "https://api.binance.com"),
+            "https://api.binance.com"
+        );
+    }
+
+    #[test]
+    fn test_endpoint_overrides() {
+        let config = config_from(&[
+            ("BINANCE_WS_URL", "wss://stream.binance.us:9443"),
+            ("BINANCE_REST_URL", "https://api.binance.us/"),
+            ("BYBIT_WS_URL", "wss://stream-testnet.bybit.com/v5/public/spot"),
+        ]);
+
+        assert_eq!(
+            config.ws_url("binance", "wss://stream.binance.com:9443"),
+            "wss://stream.binance.us:9443"
+        );
+        assert_eq!(
+            config.rest_url("binance", "https://api.binance.com"),
+            "https://api.binance.us"
+        );
+        assert_eq!(
+            config.ws_url("bybit", "wss://stream.bybit.com/v5/public/spot"),
+            "wss://stream-testnet.bybit.com/v5/public/spot"
+        );
+        // Only the WS URL was overridden for Bybit
+        assert_eq!(
+            config.rest_url("bybit", "https://api.bybit.com"),
+            "https://api.bybit.com"
+        );
+        assert_eq!(config.ws_url("okx", "wss://ws.okx.com:8443/ws/v5/public"), "wss://ws.okx.com:8443/ws/v5/public");
+    }
+
+    #[test]
+    fn test_symbol_limit() {
+        assert_eq!(config_from(&[]).symbol_limit(), 100);
+        assert_eq!(config_from(&[("MAX_SYMBOLS_PER_EXCHANGE", "250")]).symbol_limit(), 250);
+        assert_eq!(config_from(&[("MAX_SYMBOLS_PER_EXCHANGE", "0")]).symbol_limit(), usize::MAX);
+
+        let vars: HashMap<String, String> =
+            [("MAX_SYMBOLS_PER_EXCHANGE".to_string(), "lots".to_string())].into();
+        assert!(Config::from_lookup(|key| vars.get(key).cloned()).is_err());
+    }
+
+    #[test]
+    fn test_orderbook_depth() {
+        assert_eq!(config_from(&[]).orderbook_depth, 5);
+        assert_eq!(config_from(&[("ORDERBOOK_DEPTH", " 10 ")]).orderbook_depth, 10);
+
+        let vars: HashMap<String, String> =
+            [("ORDERBOOK_DEPTH".to_string(), "deep".to_string())].into();
+        assert!(Config::from_lookup(|key| vars.get(key).cloned()).is_err());
+    }
+
+    #[test]
+    fn test_symbol_cache_ttl() {
+        assert_eq!(config_from(&[]).symbol_cache_ttl(), Duration::from_secs(3600));
+        assert_eq!(config_from(&[("SYMBOL_CACHE_TTL_MS", "0")]).symbol_cache_ttl(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_triangular_flag() {
+        assert!(!config_from(&[]).enable_triangular);
+        assert!(config_from(&[("ENABLE_TRIANGULAR", "true")]).enable_triangular);
+        assert!(config_from(&[("ENABLE_TRIANGULAR", "1")]).enable_triangular);
+        assert!(!config_from(&[("ENABLE_TRIANGULAR", "off")]).enable_triangular);
+    }
+
+    #[test]
+    fn test_subscribed_quotes() {
+        let config = config_from(&[]);
+        assert!(config.subscribes_to_quote("USDT"));
+        assert!(config.subscribes_to_quote("usdt"));
+        assert!(!config.subscribes_to_quote("BTC"));
+
+        // Cross pairs only once triangular scanning needs them
+        let config = config_from(&[("ENABLE_TRIANGULAR", "true")]);
+        assert!(config.subscribes_to_quote("BTC"));
+        assert!(config.subscribes_to_quote("eth"));
+        assert!(!config.subscribes_to_quote("BNB"));
+
+        let config = config_from(&[("ENABLE_TRIANGULAR", "true"), ("TRIANGULAR_QUOTES", "bnb, ")]);
+        assert!(config.subscribes_to_quote("BNB"));
+        assert!(!config.subscribes_to_quote("BTC"));
+
+        // Every equivalent stablecoin once they are matched against each other
+        assert!(!config_from(&[]).subscribes_to_quote("USDC"));
+        let config = config_from(&[("STABLECOIN_EQUIVALENCE", "true"), ("STABLECOINS", "USDT,USDC,FDUSD")]);
+        assert!(config.subscribes_to_quote("USDC"));
+        assert!(config.subscribes_to_quote("fdusd"));
+        assert!(!config.subscribes_to_quote("DAI"));
+    }
+
+    #[test]
+    fn test_fee_table() {
+        let config = config_from(&[("FEE_BINANCE", "0.00075"), ("FEE_DEFAULT", "0.002")]);
+
+        assert_eq!(config.fee_for("binance"), Decimal::from_str("0.00075").unwrap());
+        assert_eq!(config.fee_for("okx"), Decimal::from_str("0.002").unwrap());
+        assert_eq!(config_from(&[]).fee_for("kraken"), Decimal::from_str("0.001").unwrap());
+        assert_eq!(config_from(&[("FEE_DEFAULT", " 0.002\n")]).fee_for("okx"), Decimal::from_str("0.002").unwrap());
+
+        let padded = config_from(&[("COOLDOWN_MS", " 500\n"), ("TRIANGULAR_MAX_LEG_AGE_MS", "1500 ")]);
+        assert_eq!(padded.cooldown_ms, 500);
+        assert_eq!(padded.triangular_max_leg_age_ms, 1500);
+
+        let vars: HashMap<String, String> = [("FEE_OKX".to_string(), "cheap".to_string())].into();
+        assert!(Config::from_lookup(|key| vars.get(key).cloned()).is_err());
+    }
+
+    #[test]
+    fn test_quote_currencies() {
+        assert_eq!(config_from(&[]).quote_currencies.len(), DEFAULT_QUOTE_CURRENCIES.len());
+        assert_eq!(
+            config_from(&[("QUOTE_CURRENCIES", "fdusd, USDT,,USD")]).quote_currencies,
+            vec!["FDUSD", "USDT", "USD"]
+        );
+        assert_eq!(
+            config_from(&[("QUOTE_CURRENCIES", " , ")]).quote_currencies.len(),
+            DEFAULT_QUOTE_CURRENCIES.len()
+        );
+    }
+
+    #[test]
+    fn test_spread_overrides() {
+        let config = config_from(&[
+            ("MIN_SPREAD_PERCENT", "0.3"),
+            ("SPREAD_OVERRIDES", "btc/usdt:0.1, DOGE/USDT : 1.5,"),
+            ("MAX_SPREAD_OVERRIDES", "DOGE/USDT:20"),
+        ]);
+
+        assert_eq!(config.min_spread_for("BTC/USDT"), Decimal::from_str("0.1").unwrap());
+        assert_eq!(config.min_spread_for("DOGE/USDT"), Decimal::from_str("1.5").unwrap());
+        assert_eq!(config.min_spread_for("ETH/USDT"), Decimal::from_str("0.3").unwrap());
+        assert_eq!(config.max_spread_for("DOGE/USDT"), Decimal::from(20));
+        assert_eq!(config.max_spread_for("BTC/USDT"), config.max_spread_percent);
+    }
+
+    #[test]
+    fn test_malformed_spread_overrides() {
+        for raw in ["BTC/USDT", "BTC/USDT:", "BTC/USDT:tight", ":0.5", "BTCUSDT:0.5", "BTC/USDT:0.1;ETH/USDT:0.2"] {
+            assert!(parse_spread_overrides(raw).is_err(), "{:?} should not parse", raw);
+        }
+        assert!(parse_spread_overrides(" , ").unwrap().is_empty());
+
+        let vars: HashMap<String, String> =
+            [("MAX_SPREAD_OVERRIDES".to_string(), "ETH/USDT=5".to_string())].into();
+        assert!(Config::from_lookup(|key| vars.get(key).cloned()).is_err());
+    }
+
+    #[test]
+    fn test_base_aliases() {
+        let config = config_from(&[("BASE_ALIASES", "xbt:btc, XDG:DOGE")]);
+        assert_eq!(config.base_aliases.len(), 2);
+        assert_eq!(config.base_aliases["XBT"], "BTC");
+        assert_eq!(config.base_aliases["XDG"], "DOGE");
+        assert!(config_from(&[]).base_aliases.is_empty());
+
+        for raw in ["XBT", "XBT:", ":BTC", "XBT/USD:BTC/USD"] {
+            assert!(parse_base_aliases(raw).is_err(), "{:?} should not parse", raw);
+        }
+    }
+
+    #[test]
+    fn test_empty_override_is_ignored() {
+        let config = config_from(&[("OKX_WS_URL", "  ")]);
+
+        assert!(config.endpoints.is_empty());
+    }
 }
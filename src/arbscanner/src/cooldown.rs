@@ -0,0 +1,79 @@
+// Alert cooldowns persisted across restarts (`COOLDOWN_STATE_PATH`), so a deploy
+// doesn't re-alert every opportunity that was already sent.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Read saved cooldowns, dropping entries whose window has already passed at `now`.
+/// A missing file is an empty state (first run).
+pub fn load(path: impl AsRef<Path>, cooldown_ms: u64, now: i64) -> Result<HashMap<String, i64>> {
+    let path = path.as_ref();
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read cooldown state {}", path.display())),
+    };
+
+    let mut entries: HashMap<String, i64> = serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid cooldown state {}", path.display()))?;
+    entries.retain(|_, last| is_active(*last, cooldown_ms, now));
+    Ok(entries)
+}
+
+/// Write cooldowns still active at `now`; goes through a temp file so a crash mid-write
+/// never leaves a truncated state behind
+pub async fn save(path: impl AsRef<Path>, entries: HashMap<String, i64>, cooldown_ms: u64, now: i64) -> Result<usize> {
+    let path = path.as_ref();
+    let active: HashMap<String, i64> = entries
+        .into_iter()
+        .filter(|(_, last)| is_active(*last, cooldown_ms, now))
+        .collect();
+
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(&active)?)
+        .await
+        .with_context(|| format!("Failed to write cooldown state {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .with_context(|| format!("Failed to replace cooldown state {}", path.display()))?;
+    Ok(active.len())
+}
+
+fn is_active(last_alert: i64, cooldown_ms: u64, now: i64) -> bool {
+    now - last_alert < cooldown_ms as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip_prunes_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cooldowns.json");
+        assert!(load(&path, 60_000, 0).unwrap().is_empty());
+
+        let entries = HashMap::from([
+            ("BTC/USDT-binance-okx".to_string(), 100_000),
+            ("ETH/USDT-bybit-okx".to_string(), 30_000),
+        ]);
+        assert_eq!(save(&path, entries, 60_000, 100_000).await.unwrap(), 1);
+
+        let loaded = load(&path, 60_000, 120_000).unwrap();
+        assert_eq!(loaded.get("BTC/USDT-binance-okx"), Some(&100_000));
+        assert_eq!(loaded.len(), 1);
+
+        // Window over by the time we restart
+        assert!(load(&path, 60_000, 160_000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_state_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cooldowns.json");
+        std::fs::write(&path, "{not json").unwrap();
+
+        assert!(load(&path, 60_000, 0).is_err());
+    }
+}
@@ -1,19 +1,23 @@
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::Deserialize;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::symbols::cached_symbols;
+use super::ws_util::{reconnect_loop, Ping, Session};
+use super::{parse_levels, publish, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
-const WS_URL: &str = "wss://stream.binance.com:9443/ws";
-const REST_URL: &str = "https://api.binance.com/api/v3/exchangeInfo";
+const WS_URL: &str = "wss://stream.binance.com:9443";
+const REST_URL: &str = "https://api.binance.com";
+const EXCHANGE_INFO_PATH: &str = "/api/v3/exchangeInfo";
 
 #[derive(Debug, Deserialize)]
 struct ExchangeInfo {
@@ -42,119 +46,208 @@ struct BookTickerEvent {
     ask_qty: String,
 }
 
+/// Partial book depth snapshot (`<symbol>@depth<levels>@100ms`)
+#[derive(Debug, Deserialize)]
+struct DepthEvent {
+    bids: Vec<Vec<String>>,
+    asks: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StreamData {
+    BookTicker(BookTickerEvent),
+    Depth(DepthEvent),
+}
+
 #[derive(Debug, Deserialize)]
 struct StreamWrapper {
     stream: String,
-    data: BookTickerEvent,
+    data: StreamData,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "Binance connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+/// Binance partial depth streams only come in 5, 10 and 20 levels
+fn depth_stream_levels(depth: usize) -> usize {
+    match depth {
+        0..=5 => 5,
+        6..=10 => 10,
+        _ => 20,
+    }
+}
+
+pub struct Binance;
+
+#[async_trait]
+impl Exchange for Binance {
+    fn name(&self) -> &'static str {
+        "binance"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("binance", "Binance", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
 async fn run_connection(
-    _config: &Config,
+    config: &Config,
     matcher: &TickerMatcher,
     price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
-    // Fetch available symbols
-    let symbols = fetch_symbols().await?;
-    info!(count = symbols.len(), "Binance: fetched symbols");
+    // Symbols survive reconnects; only the websocket is re-established
+    let subscribed_symbols = cached_symbols("binance", config.symbol_cache_ttl(), || load_symbols(config, matcher)).await?;
     
-    // Filter USDT pairs (most liquid)
-    let usdt_symbols: Vec<_> = symbols
+    // Build subscription streams: partial depth when tracking more than the top level
+    let depth = config.orderbook_depth;
+    let streams: Vec<String> = subscribed_symbols
         .iter()
-        .filter(|s| s.status == "TRADING" && s.quote_asset == "USDT")
-        .take(100) // Top 100 pairs
-        .collect();
-    
-    // Register symbols with matcher
-    for sym in &usdt_symbols {
-        matcher.register("binance", &sym.symbol);
-    }
-    
-    // Build subscription streams
-    let streams: Vec<String> = usdt_symbols
-        .iter()
-        .map(|s| format!("{}@bookTicker", s.symbol.to_lowercase()))
+        .map(|s| {
+            if depth > 1 {
+                format!("{}@depth{}@100ms", s.to_lowercase(), depth_stream_levels(depth))
+            } else {
+                format!("{}@bookTicker", s.to_lowercase())
+            }
+        })
         .collect();
     
-    let ws_url = format!("{}/stream?streams={}", WS_URL.replace("/ws", ""), streams.join("/"));
+    // Streams are part of the URL, so there is nothing to subscribe to after connecting
+    let ws_url = format!(
+        "{}/stream?streams={}",
+        config.ws_url("binance", WS_URL).trim_end_matches('/'),
+        streams.join("/")
+    );
     
     info!(streams = streams.len(), "Binance: connecting to websocket");
     
-    let (ws_stream, _) = connect_async(&ws_url).await?;
-    let (mut write, mut read) = ws_stream.split();
+    Session::new("binance", "Binance", ws_url)
+        .ping(Ping::Frame(Duration::from_secs(30)))
+        .run(shutdown, |text| {
+            if let Some(update) = parse_message(text, matcher, depth) {
+                publish(price_tx, update);
+            }
+            None
+        })
+        .await
+}
+
+/// Trading pairs in a subscribed quote (see `Config::subscribes_to_quote`), registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let symbols = fetch_symbols(&config.rest_url("binance", REST_URL)).await?;
+    info!(count = symbols.len(), "Binance: fetched symbols");
+    
+    // USDT pairs (most liquid), plus equivalent stablecoins and cross pairs when enabled
+    let subscribed_symbols: Vec<String> = symbols
+        .into_iter()
+        .filter(|s| s.status == "TRADING" && config.subscribes_to_quote(&s.quote_asset))
+        .take(config.symbol_limit())
+        .map(|s| s.symbol)
+        .collect();
     
-    info!("Binance: connected");
+    // Register symbols with matcher
+    for symbol in &subscribed_symbols {
+        matcher.register("binance", symbol);
+    }
     
-    // Ping task
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-            if write.send(Message::Ping(vec![])).await.is_err() {
-                break;
+    Ok(subscribed_symbols)
+}
+
+/// Combined-stream frame -> update, for both `bookTicker` and partial depth payloads
+fn parse_message(text: &str, matcher: &TickerMatcher, depth: usize) -> Option<PriceUpdate> {
+    let wrapper = serde_json::from_str::<StreamWrapper>(text).ok()?;
+    let update = match wrapper.data {
+        StreamData::BookTicker(data) => {
+            let bid = Decimal::from_str(&data.bid_price).unwrap_or_default();
+            let ask = Decimal::from_str(&data.ask_price).unwrap_or_default();
+            
+            PriceUpdate {
+                exchange: "binance".to_string(),
+                symbol: matcher.register("binance", &data.symbol),
+                raw_symbol: data.symbol,
+                bid,
+                ask,
+                bid_size: Decimal::from_str(&data.bid_qty).unwrap_or_default(),
+                ask_size: Decimal::from_str(&data.ask_qty).unwrap_or_default(),
+                bids: Vec::new(),
+                asks: Vec::new(),
+                timestamp: chrono::Utc::now().timestamp_millis(),
             }
         }
-    });
-    
-    // Read messages
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if let Ok(wrapper) = serde_json::from_str::<StreamWrapper>(&text) {
-                    let data = wrapper.data;
-                    
-                    let bid = Decimal::from_str(&data.bid_price).unwrap_or_default();
-                    let ask = Decimal::from_str(&data.ask_price).unwrap_or_default();
-                    
-                    if bid.is_zero() || ask.is_zero() {
-                        continue;
-                    }
-                    
-                    let normalized = matcher.register("binance", &data.symbol);
-                    
-                    let update = PriceUpdate {
-                        exchange: "binance".to_string(),
-                        symbol: normalized,
-                        raw_symbol: data.symbol,
-                        bid,
-                        ask,
-                        bid_size: Decimal::from_str(&data.bid_qty).unwrap_or_default(),
-                        ask_size: Decimal::from_str(&data.ask_qty).unwrap_or_default(),
-                        timestamp: chrono::Utc::now().timestamp_millis(),
-                    };
-                    
-                    let _ = price_tx.send(update);
-                }
-            }
-            Ok(Message::Pong(_)) => {}
-            Ok(Message::Close(_)) => {
-                warn!("Binance: connection closed");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "Binance: websocket error");
-                break;
+        StreamData::Depth(data) => {
+            // Depth payloads carry no symbol; it's in the stream name: btcusdt@depth5@100ms
+            let symbol = wrapper.stream
+                .split('@')
+                .next()
+                .unwrap_or_default()
+                .to_uppercase();
+            let bids = parse_levels(&data.bids, depth);
+            let asks = parse_levels(&data.asks, depth);
+            let (&(bid, bid_size), &(ask, ask_size)) = (bids.first()?, asks.first()?);
+            
+            PriceUpdate {
+                exchange: "binance".to_string(),
+                symbol: matcher.register("binance", &symbol),
+                raw_symbol: symbol,
+                bid,
+                ask,
+                bid_size,
+                ask_size,
+                bids,
+                asks,
+                timestamp: chrono::Utc::now().timestamp_millis(),
             }
-            _ => {}
         }
-    }
+    };
     
-    ping_handle.abort();
-    Ok(())
+    if update.bid.is_zero() || update.ask.is_zero() {
+        return None;
+    }
+    Some(update)
 }
 
-async fn fetch_symbols() -> Result<Vec<SymbolInfo>> {
-    let resp: ExchangeInfo = reqwest::get(REST_URL).await?.json().await?;
+async fn fetch_symbols(rest_url: &str) -> Result<Vec<SymbolInfo>> {
+    let url = format!("{}{}", rest_url, EXCHANGE_INFO_PATH);
+    let resp: ExchangeInfo = reqwest::get(&url).await?.json().await?;
     Ok(resp.symbols)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_book_ticker() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"stream":"btcusdt@bookTicker","data":{"u":400900217,"s":"BTCUSDT","b":"43250.10","B":"1.5","a":"43250.20","A":"0.4"}}"#;
+
+        let update = parse_message(text, &matcher, 1).unwrap();
+        assert_eq!(update.symbol, "BTC/USDT");
+        assert_eq!(update.raw_symbol, "BTCUSDT");
+        assert_eq!(update.bid, Decimal::from_str("43250.10").unwrap());
+        assert_eq!(update.ask_size, Decimal::from_str("0.4").unwrap());
+        assert!(update.bids.is_empty());
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"stream":"ethusdt@depth5@100ms","data":{"lastUpdateId":1,"bids":[["2000.5","3"],["2000.4","1"]],"asks":[["2000.6","2"],["2000.7","5"]]}}"#;
+
+        let update = parse_message(text, &matcher, 5).unwrap();
+        assert_eq!(update.symbol, "ETH/USDT");
+        assert_eq!(update.raw_symbol, "ETHUSDT");
+        assert_eq!(update.bid, Decimal::from_str("2000.5").unwrap());
+        assert_eq!(update.asks.len(), 2);
+
+        let empty = r#"{"stream":"ethusdt@depth5@100ms","data":{"lastUpdateId":1,"bids":[],"asks":[]}}"#;
+        assert!(parse_message(empty, &matcher, 5).is_none());
+    }
+}
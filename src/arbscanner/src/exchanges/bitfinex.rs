@@ -0,0 +1,312 @@
+// Bitfinex WebSocket connector
+// Docs: https://docs.bitfinex.com/reference/ws-public-ticker
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::symbols::cached_symbols;
+use super::ws_util::{reconnect_loop, Ping, Session};
+use super::{publish, Exchange, PriceUpdate};
+use crate::config::Config;
+use crate::matcher::TickerMatcher;
+
+const WS_URL: &str = "wss://api-pub.bitfinex.com/ws/2";
+const REST_URL: &str = "https://api-pub.bitfinex.com";
+const TICKERS_PATH: &str = "/v2/tickers?symbols=ALL";
+/// Public channels allowed per connection
+const MAX_SUBSCRIPTIONS: usize = 25;
+
+/// Bitfinex currency codes that differ from everyone else's
+const CURRENCY_ALIASES: &[(&str, &str)] = &[
+    ("UST", "USDT"),
+    ("UDC", "USDC"),
+    ("DSH", "DASH"),
+    ("IOT", "IOTA"),
+    ("QTM", "QTUM"),
+];
+
+#[derive(Debug, Serialize)]
+struct SubscribeMessage<'a> {
+    event: &'a str,
+    channel: &'a str,
+    symbol: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventMessage {
+    event: String,
+    #[serde(rename = "chanId")]
+    chan_id: Option<u64>,
+    symbol: Option<String>,
+    msg: Option<String>,
+}
+
+/// Fields of a ticker array we use: `[BID, BID_SIZE, ASK, ASK_SIZE, ...]`
+#[derive(Debug, PartialEq)]
+struct Ticker {
+    bid: Decimal,
+    bid_size: Decimal,
+    ask: Decimal,
+    ask_size: Decimal,
+}
+
+pub struct Bitfinex;
+
+#[async_trait]
+impl Exchange for Bitfinex {
+    fn name(&self) -> &'static str {
+        "bitfinex"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("bitfinex", "Bitfinex", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
+    }
+}
+
+async fn run_connection(
+    config: &Config,
+    matcher: &TickerMatcher,
+    price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let symbols = cached_symbols("bitfinex", config.symbol_cache_ttl(), || load_symbols(config, matcher)).await?;
+
+    let subscriptions = symbols
+        .iter()
+        .map(|symbol| {
+            serde_json::to_string(&SubscribeMessage {
+                event: "subscribe",
+                channel: "ticker",
+                symbol,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Updates only carry the channel id; subscribe acks tell us which symbol it belongs to
+    let mut channels: HashMap<u64, String> = HashMap::new();
+
+    // Bitfinex answers {"event":"ping"} with a pong event
+    Session::new("bitfinex", "Bitfinex", config.ws_url("bitfinex", WS_URL))
+        .subscribe(subscriptions)
+        .ping(Ping::Json(
+            || serde_json::json!({"event": "ping", "cid": chrono::Utc::now().timestamp_millis()}),
+            Duration::from_secs(30),
+        ))
+        .run(shutdown, |text| {
+            if let Some(update) = parse_message(text, &mut channels, matcher) {
+                publish(price_tx, update);
+            }
+            None
+        })
+        .await
+}
+
+/// Most liquid USD pairs and those in a subscribed quote, within the per-connection channel
+/// limit, registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let symbols = fetch_liquid_symbols(
+        &config.rest_url("bitfinex", REST_URL),
+        config.symbol_limit().min(MAX_SUBSCRIPTIONS),
+        |quote| quote == "USD" || config.subscribes_to_quote(quote),
+    )
+    .await?;
+    info!(count = symbols.len(), "Bitfinex: fetched symbols");
+
+    for symbol in &symbols {
+        if let Some((base, quote)) = split_pair(symbol) {
+            matcher.register_pair("bitfinex", symbol, &base, &quote);
+        }
+    }
+
+    Ok(symbols)
+}
+
+/// Track subscribe acks in `channels` and turn ticker arrays into updates
+fn parse_message(text: &str, channels: &mut HashMap<u64, String>, matcher: &TickerMatcher) -> Option<PriceUpdate> {
+    if text.starts_with('{') {
+        let event = serde_json::from_str::<EventMessage>(text).ok()?;
+        match event.event.as_str() {
+            "subscribed" => {
+                if let (Some(chan_id), Some(symbol)) = (event.chan_id, event.symbol) {
+                    channels.insert(chan_id, symbol);
+                }
+            }
+            "error" => warn!(msg = ?event.msg, "Bitfinex: subscription error"),
+            _ => debug!(event = %event.event, "Bitfinex: event"),
+        }
+        return None;
+    }
+
+    let (chan_id, ticker) = parse_ticker(text)?;
+    let symbol = channels.get(&chan_id)?;
+    if ticker.bid.is_zero() || ticker.ask.is_zero() {
+        return None;
+    }
+
+    Some(PriceUpdate {
+        exchange: "bitfinex".to_string(),
+        symbol: matcher.get_normalized("bitfinex", symbol)?,
+        raw_symbol: symbol.clone(),
+        bid: ticker.bid,
+        ask: ticker.ask,
+        bid_size: ticker.bid_size,
+        ask_size: ticker.ask_size,
+        bids: Vec::new(),
+        asks: Vec::new(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    })
+}
+
+/// Trading pairs whose quote passes `subscribed`, by 24h quote volume, best first
+async fn fetch_liquid_symbols(
+    rest_url: &str,
+    limit: usize,
+    subscribed: impl Fn(&str) -> bool,
+) -> Result<Vec<String>> {
+    let client = reqwest::Client::new();
+    let tickers: Vec<Vec<Value>> = client
+        .get(format!("{}{}", rest_url, TICKERS_PATH))
+        .header("User-Agent", "arbscanner/1.0")
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    // Trading tickers: [SYMBOL, BID, BID_SIZE, ASK, ASK_SIZE, DAILY_CHANGE, DAILY_CHANGE_RELATIVE, LAST_PRICE, VOLUME, HIGH, LOW]
+    let mut pairs: Vec<(String, f64)> = tickers
+        .iter()
+        .filter_map(|t| {
+            let symbol = t.first()?.as_str()?;
+            let (_, quote) = split_pair(symbol)?;
+            if !subscribed(&quote) {
+                return None;
+            }
+            let last = t.get(7)?.as_f64()?;
+            let volume = t.get(8)?.as_f64()?;
+            Some((symbol.to_string(), last * volume))
+        })
+        .collect();
+
+    pairs.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(pairs.into_iter().take(limit).map(|(symbol, _)| symbol).collect())
+}
+
+/// Split a trading symbol into (base, quote): `tBTCUSD` -> (BTC, USD), `tAVAX:USD` -> (AVAX, USD),
+/// `tBTCUST` -> (BTC, USDT). Funding symbols (`fUSD`) and malformed names yield `None`.
+fn split_pair(symbol: &str) -> Option<(String, String)> {
+    let pair = symbol.strip_prefix('t')?;
+
+    let (base, quote) = match pair.split_once(':') {
+        Some(split) => split,
+        // Without a delimiter both currencies are 3 letters
+        None if pair.len() == 6 && pair.is_ascii() => pair.split_at(3),
+        None => return None,
+    };
+    if base.is_empty() || quote.is_empty() {
+        return None;
+    }
+
+    Some((currency(base), currency(quote)))
+}
+
+fn currency(code: &str) -> String {
+    let code = code.to_uppercase();
+    CURRENCY_ALIASES
+        .iter()
+        .find(|(bitfinex, _)| *bitfinex == code)
+        .map(|(_, common)| common.to_string())
+        .unwrap_or(code)
+}
+
+/// Parse `[CHANNEL_ID, [BID, BID_SIZE, ASK, ASK_SIZE, ...]]`; heartbeats (`[CHANNEL_ID, "hb"]`) yield `None`
+fn parse_ticker(text: &str) -> Option<(u64, Ticker)> {
+    let message: Value = serde_json::from_str(text).ok()?;
+    let message = message.as_array()?;
+    let chan_id = message.first()?.as_u64()?;
+    let fields = message.get(1)?.as_array()?;
+
+    let field = |i: usize| -> Option<Decimal> {
+        let number = fields.get(i)?.as_number()?.to_string();
+        Decimal::from_str(&number)
+            .or_else(|_| Decimal::from_scientific(&number))
+            .ok()
+    };
+
+    Some((
+        chan_id,
+        Ticker {
+            bid: field(0)?,
+            bid_size: field(1)?,
+            ask: field(2)?,
+            ask_size: field(3)?,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_pair() {
+        let pair = |b: &str, q: &str| Some((b.to_string(), q.to_string()));
+
+        assert_eq!(split_pair("tBTCUSD"), pair("BTC", "USD"));
+        assert_eq!(split_pair("tETHUST"), pair("ETH", "USDT"));
+        assert_eq!(split_pair("tAVAX:USD"), pair("AVAX", "USD"));
+        assert_eq!(split_pair("tTESTBTC:TESTUSD"), pair("TESTBTC", "TESTUSD"));
+        assert_eq!(split_pair("fUSD"), None);
+        assert_eq!(split_pair("tBTCUSDT"), None);
+    }
+
+    #[test]
+    fn test_parse_ticker() {
+        let text = "[17082,[43250.5,1.2,43251,0.75,-120,-0.0028,43250,1520.3,43800,42900]]";
+        let (chan_id, ticker) = parse_ticker(text).unwrap();
+
+        assert_eq!(chan_id, 17082);
+        assert_eq!(ticker.bid, Decimal::from_str("43250.5").unwrap());
+        assert_eq!(ticker.bid_size, Decimal::from_str("1.2").unwrap());
+        assert_eq!(ticker.ask, Decimal::from(43251));
+        assert_eq!(ticker.ask_size, Decimal::from_str("0.75").unwrap());
+
+        assert_eq!(parse_ticker(r#"[17082,"hb"]"#), None);
+    }
+
+    #[test]
+    fn test_parse_message_maps_channels() {
+        let matcher = TickerMatcher::new();
+        matcher.register_pair("bitfinex", "tBTCUST", "BTC", "USDT");
+        let mut channels = HashMap::new();
+        let ticker = "[17082,[43250.5,1.2,43251,0.75,-120,-0.0028,43250,1520.3,43800,42900]]";
+
+        // Unknown channel until the subscribe ack arrives
+        assert!(parse_message(ticker, &mut channels, &matcher).is_none());
+
+        let ack = r#"{"event":"subscribed","channel":"ticker","chanId":17082,"symbol":"tBTCUST","pair":"BTCUST"}"#;
+        assert!(parse_message(ack, &mut channels, &matcher).is_none());
+
+        let update = parse_message(ticker, &mut channels, &matcher).unwrap();
+        assert_eq!(update.symbol, "BTC/USDT");
+        assert_eq!(update.raw_symbol, "tBTCUST");
+    }
+}
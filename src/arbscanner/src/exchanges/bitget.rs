@@ -2,21 +2,29 @@
 // Docs: https://www.bitget.com/api-doc/spot/websocket/public/Tickers-Channel
 
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::ws_util::{classify_text, fetch_with_retry, run_shards, Frame, Ping, Session};
+use super::{parse_timestamp_ms, publish_stamped, subscription_batches, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
 const WS_URL: &str = "wss://ws.bitget.com/v2/ws/public";
-const REST_URL: &str = "https://api.bitget.com/api/v2/spot/public/symbols";
+const REST_URL: &str = "https://api.bitget.com";
+const SYMBOLS_PATH: &str = "/api/v2/spot/public/symbols";
+/// Max args per subscribe message
+const SUBSCRIBE_BATCH_SIZE: usize = 30;
+/// Channels per connection; Bitget allows more but recommends staying under 50 for a stable feed
+const MAX_CHANNELS_PER_CONNECTION: usize = 50;
 
 #[derive(Debug, Deserialize)]
 struct SymbolsResponse {
@@ -26,8 +34,6 @@ struct SymbolsResponse {
 #[derive(Debug, Deserialize)]
 struct SymbolInfo {
     symbol: String,
-    #[serde(rename = "baseCoin")]
-    base_coin: String,
     #[serde(rename = "quoteCoin")]
     quote_coin: String,
     status: String,
@@ -50,18 +56,9 @@ struct SubscribeArg {
 
 #[derive(Debug, Deserialize)]
 struct WsMessage {
-    action: Option<String>,
-    arg: Option<WsArg>,
     data: Option<Vec<TickerData>>,
 }
 
-#[derive(Debug, Deserialize)]
-struct WsArg {
-    channel: String,
-    #[serde(rename = "instId")]
-    inst_id: String,
-}
-
 #[derive(Debug, Deserialize)]
 struct TickerData {
     #[serde(rename = "instId")]
@@ -77,140 +74,149 @@ struct TickerData {
     ts: String,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "Bitget connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Bitget;
+
+#[async_trait]
+impl Exchange for Bitget {
+    fn name(&self) -> &'static str {
+        "bitget"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        // Symbols are fetched once; each shard then reconnects on its own
+        let Some(symbols) = fetch_with_retry("Bitget", &shutdown, || load_symbols(&config, &matcher)).await else {
+            return Ok(());
+        };
+        let shards: Vec<Vec<String>> = subscription_batches(&symbols, MAX_CHANNELS_PER_CONNECTION)
+            .map(<[String]>::to_vec)
+            .collect();
+
+        run_shards("bitget", "Bitget", &shutdown, &shards, |label, symbols| {
+            run_shard(label, symbols, &config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
-async fn run_connection(
-    _config: &Config,
-    matcher: &TickerMatcher,
-    price_tx: &broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    // Fetch available symbols
-    let symbols = fetch_symbols().await?;
+/// Online pairs in a subscribed quote (see `Config::subscribes_to_quote`), registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let symbols = fetch_symbols(&config.rest_url("bitget", REST_URL)).await?;
     info!(count = symbols.len(), "Bitget: fetched symbols");
 
-    // Filter USDT pairs
-    let usdt_symbols: Vec<_> = symbols
-        .iter()
-        .filter(|s| s.status == "online" && s.quote_coin == "USDT")
-        .take(100)
+    // USDT pairs, plus equivalent stablecoins and cross pairs when enabled
+    let subscribed_symbols: Vec<String> = symbols
+        .into_iter()
+        .filter(|s| s.status == "online" && config.subscribes_to_quote(&s.quote_coin))
+        .take(config.symbol_limit())
+        .map(|s| s.symbol)
         .collect();
 
     // Register symbols with matcher
-    for sym in &usdt_symbols {
+    for symbol in &subscribed_symbols {
         // Bitget uses BTCUSDT format
-        matcher.register("bitget", &sym.symbol);
+        matcher.register("bitget", symbol);
     }
 
-    info!(symbols = usdt_symbols.len(), "Bitget: connecting to websocket");
+    Ok(subscribed_symbols)
+}
 
-    let (ws_stream, _) = connect_async(WS_URL).await?;
-    let (mut write, mut read) = ws_stream.split();
+/// One connection carrying `symbols` (at most `MAX_CHANNELS_PER_CONNECTION`)
+async fn run_shard(
+    label: String,
+    symbols: &[String],
+    config: &Config,
+    matcher: &TickerMatcher,
+    price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    info!(symbols = symbols.len(), "{}: connecting to websocket", label);
 
     // Subscribe to ticker channels (max 30 per message)
-    for chunk in usdt_symbols.chunks(30) {
-        let args: Vec<SubscribeArg> = chunk
-            .iter()
-            .map(|s| SubscribeArg {
-                inst_type: "SPOT".to_string(),
-                channel: "ticker".to_string(),
-                inst_id: s.symbol.clone(),
+    let subscriptions = subscription_batches(symbols, SUBSCRIBE_BATCH_SIZE)
+        .map(|chunk| {
+            let args: Vec<SubscribeArg> = chunk
+                .iter()
+                .map(|symbol| SubscribeArg {
+                    inst_type: "SPOT".to_string(),
+                    channel: "ticker".to_string(),
+                    inst_id: symbol.clone(),
+                })
+                .collect();
+
+            serde_json::to_string(&SubscribeMessage {
+                op: "subscribe".to_string(),
+                args,
             })
-            .collect();
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Symbols that actually streamed on this connection, to catch silently dropped subscriptions
+    let mut live = HashSet::new();
+    // Bitget requires "ping" string
+    let result = Session::new("bitget", label.as_str(), config.ws_url("bitget", WS_URL))
+        .subscribe(subscriptions)
+        .ping(Ping::Text("ping", Duration::from_secs(25)))
+        .run(shutdown, |text| match classify_text(text) {
+            Frame::Ping => Some("pong".to_string()),
+            Frame::Pong => None,
+            Frame::Data(text) => {
+                for update in parse_message(text, matcher) {
+                    if live.insert(update.raw_symbol.clone()) && live.len() == symbols.len() {
+                        info!(symbols = live.len(), "{}: every symbol is streaming", label);
+                    }
+                    publish_stamped(price_tx, update);
+                }
+                None
+            }
+        })
+        .await;
 
-        let subscribe = SubscribeMessage {
-            op: "subscribe".to_string(),
-            args,
-        };
+    info!(live = live.len(), subscribed = symbols.len(), "{}: symbols that produced updates", label);
+    result
+}
 
-        let sub_msg = serde_json::to_string(&subscribe)?;
-        write.send(Message::Text(sub_msg)).await?;
-    }
+/// `ticker` push -> updates for symbols registered at startup, stamped with the exchange `ts`
+fn parse_message(text: &str, matcher: &TickerMatcher) -> Vec<PriceUpdate> {
+    let Some(data) = serde_json::from_str::<WsMessage>(text).ok().and_then(|m| m.data) else {
+        return Vec::new();
+    };
 
-    info!("Bitget: subscribed to ticker channels");
-
-    // Ping task - Bitget requires "ping" string
-    let ping_write = Arc::new(tokio::sync::Mutex::new(write));
-    let ping_write_clone = Arc::clone(&ping_write);
-    
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(25)).await;
-            let mut writer = ping_write_clone.lock().await;
-            if writer.send(Message::Text("ping".to_string())).await.is_err() {
-                break;
-            }
-        }
-    });
-
-    // Read messages
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                // Handle pong response
-                if text == "pong" {
-                    continue;
-                }
+    data.into_iter()
+        .filter_map(|ticker| {
+            let bid = Decimal::from_str(&ticker.best_bid).unwrap_or_default();
+            let ask = Decimal::from_str(&ticker.best_ask).unwrap_or_default();
 
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    if let Some(data) = ws_msg.data {
-                        for ticker in data {
-                            let bid = Decimal::from_str(&ticker.best_bid).unwrap_or_default();
-                            let ask = Decimal::from_str(&ticker.best_ask).unwrap_or_default();
-
-                            if bid.is_zero() || ask.is_zero() {
-                                continue;
-                            }
-
-                            if let Some(normalized) = matcher.get_normalized("bitget", &ticker.inst_id) {
-                                let update = PriceUpdate {
-                                    exchange: "bitget".to_string(),
-                                    symbol: normalized,
-                                    raw_symbol: ticker.inst_id.clone(),
-                                    bid,
-                                    ask,
-                                    bid_size: Decimal::from_str(&ticker.bid_sz).unwrap_or_default(),
-                                    ask_size: Decimal::from_str(&ticker.ask_sz).unwrap_or_default(),
-                                    timestamp: ticker.ts.parse().unwrap_or(0),
-                                };
-
-                                let _ = price_tx.send(update);
-                            }
-                        }
-                    }
-                }
+            if bid.is_zero() || ask.is_zero() {
+                return None;
             }
-            Ok(Message::Pong(_)) => {}
-            Ok(Message::Close(_)) => {
-                warn!("Bitget: connection closed by server");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "Bitget: websocket error");
-                break;
-            }
-            _ => {}
-        }
-    }
 
-    ping_handle.abort();
-    Ok(())
+            Some(PriceUpdate {
+                exchange: "bitget".to_string(),
+                symbol: matcher.get_normalized("bitget", &ticker.inst_id)?,
+                raw_symbol: ticker.inst_id,
+                bid,
+                ask,
+                bid_size: Decimal::from_str(&ticker.bid_sz).unwrap_or_default(),
+                ask_size: Decimal::from_str(&ticker.ask_sz).unwrap_or_default(),
+                bids: Vec::new(),
+                asks: Vec::new(),
+                timestamp: parse_timestamp_ms(&ticker.ts)
+                    .unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+            })
+        })
+        .collect()
 }
 
-async fn fetch_symbols() -> Result<Vec<SymbolInfo>> {
+async fn fetch_symbols(rest_url: &str) -> Result<Vec<SymbolInfo>> {
     let client = reqwest::Client::new();
     let response = client
-        .get(REST_URL)
+        .get(format!("{}{}", rest_url, SYMBOLS_PATH))
         .header("User-Agent", "arbscanner/1.0")
         .send()
         .await?;
@@ -218,3 +224,23 @@ async fn fetch_symbols() -> Result<Vec<SymbolInfo>> {
     let symbols_resp: SymbolsResponse = response.json().await?;
     Ok(symbols_resp.data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ticker() {
+        let matcher = TickerMatcher::new();
+        matcher.register("bitget", "BTCUSDT");
+        let text = r#"{"action":"snapshot","arg":{"instType":"SPOT","channel":"ticker","instId":"BTCUSDT"},"data":[{"instId":"BTCUSDT","lastPr":"43250","bidPr":"43250","askPr":"43250.1","bestBid":"43250","bestAsk":"43250.1","bidSz":"2.1","askSz":"0.3","ts":"1700000000123"}],"ts":1700000000125}"#;
+
+        let updates = parse_message(text, &matcher);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].symbol, "BTC/USDT");
+        assert_eq!(updates[0].ask, Decimal::from_str("43250.1").unwrap());
+        assert_eq!(updates[0].timestamp, 1_700_000_000_123);
+
+        assert!(parse_message("pong", &matcher).is_empty());
+    }
+}
@@ -1,19 +1,25 @@
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::symbols::cached_symbols;
+use super::ws_util::{reconnect_loop, Frame, Ping, Session};
+use super::{publish, subscription_batches, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
 const WS_URL: &str = "wss://stream.bybit.com/v5/public/spot";
-const REST_URL: &str = "https://api.bybit.com/v5/market/instruments-info?category=spot";
+const REST_URL: &str = "https://api.bybit.com";
+const INSTRUMENTS_PATH: &str = "/v5/market/instruments-info?category=spot";
+/// Bybit limits subscriptions per message
+const SUBSCRIBE_BATCH_SIZE: usize = 10;
 
 #[derive(Debug, Deserialize)]
 struct InstrumentsResponse {
@@ -45,6 +51,13 @@ struct WsMessage {
     data: Option<TickerData>,
 }
 
+/// Keepalive fields: our ping is answered with `op: "ping", ret_msg: "pong"`
+#[derive(Debug, Deserialize)]
+struct OpMessage {
+    op: Option<String>,
+    ret_msg: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct TickerData {
     symbol: String,
@@ -58,122 +71,165 @@ struct TickerData {
     ask_size: String,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "Bybit connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Bybit;
+
+#[async_trait]
+impl Exchange for Bybit {
+    fn name(&self) -> &'static str {
+        "bybit"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("bybit", "Bybit", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
 async fn run_connection(
-    _config: &Config,
+    config: &Config,
     matcher: &TickerMatcher,
     price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
-    // Fetch available symbols
-    let symbols = fetch_symbols().await?;
-    info!(count = symbols.len(), "Bybit: fetched symbols");
+    let subscribed_symbols = cached_symbols("bybit", config.symbol_cache_ttl(), || load_symbols(config, matcher)).await?;
     
-    // Filter USDT pairs
-    let usdt_symbols: Vec<_> = symbols
+    // Subscribe to tickers
+    let args: Vec<String> = subscribed_symbols
         .iter()
-        .filter(|s| s.status == "Trading" && s.quote_coin == "USDT")
-        .take(100)
+        .map(|s| format!("tickers.{}", s))
         .collect();
     
-    // Register with matcher
-    for sym in &usdt_symbols {
-        matcher.register("bybit", &sym.symbol);
-    }
+    let subscriptions = subscription_batches(&args, SUBSCRIBE_BATCH_SIZE)
+        .map(|chunk| {
+            serde_json::to_string(&SubscribeRequest {
+                op: "subscribe".to_string(),
+                args: chunk.to_vec(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
     
-    let (ws_stream, _) = connect_async(WS_URL).await?;
-    let (mut write, mut read) = ws_stream.split();
-    
-    info!("Bybit: connected");
+    Session::new("bybit", "Bybit", config.ws_url("bybit", WS_URL))
+        .subscribe(subscriptions)
+        .ping(Ping::Json(|| serde_json::json!({"op": "ping"}), Duration::from_secs(20)))
+        .run(shutdown, |text| match classify(text) {
+            Frame::Ping => Some(serde_json::json!({"op": "pong"}).to_string()),
+            Frame::Pong => None,
+            Frame::Data(text) => {
+                if let Some(update) = parse_message(text, matcher) {
+                    publish(price_tx, update);
+                }
+                None
+            }
+        })
+        .await
+}
+
+/// Trading pairs in a subscribed quote (see `Config::subscribes_to_quote`), registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let symbols = fetch_symbols(&config.rest_url("bybit", REST_URL)).await?;
+    info!(count = symbols.len(), "Bybit: fetched symbols");
     
-    // Subscribe to tickers
-    let args: Vec<String> = usdt_symbols
-        .iter()
-        .map(|s| format!("tickers.{}", s.symbol))
+    // USDT pairs, plus equivalent stablecoins and cross pairs when enabled
+    let subscribed_symbols: Vec<String> = symbols
+        .into_iter()
+        .filter(|s| s.status == "Trading" && config.subscribes_to_quote(&s.quote_coin))
+        .take(config.symbol_limit())
+        .map(|s| s.symbol)
         .collect();
     
-    // Bybit limits subscriptions per message
-    for chunk in args.chunks(10) {
-        let sub = SubscribeRequest {
-            op: "subscribe".to_string(),
-            args: chunk.to_vec(),
-        };
-        write.send(Message::Text(serde_json::to_string(&sub)?)).await?;
+    // Register with matcher
+    for symbol in &subscribed_symbols {
+        matcher.register("bybit", symbol);
     }
     
-    // Ping task
-    let write = Arc::new(tokio::sync::Mutex::new(write));
-    let write_clone = write.clone();
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(20)).await;
-            let ping = serde_json::json!({"op": "ping"});
-            if write_clone.lock().await.send(Message::Text(ping.to_string())).await.is_err() {
-                break;
-            }
-        }
-    });
+    Ok(subscribed_symbols)
+}
+
+/// Pong to our `{"op":"ping"}`, a server-initiated ping, or anything else as data
+fn classify(text: &str) -> Frame<'_> {
+    // Ticker pushes carry no `op`; skip parsing them twice
+    if !text.contains("\"op\"") {
+        return Frame::Data(text);
+    }
+    let Ok(msg) = serde_json::from_str::<OpMessage>(text) else {
+        return Frame::Data(text);
+    };
+    match (msg.op.as_deref(), msg.ret_msg.as_deref()) {
+        (Some("ping"), Some("pong")) | (Some("pong"), _) => Frame::Pong,
+        (Some("ping"), _) => Frame::Ping,
+        _ => Frame::Data(text),
+    }
+}
+
+/// `tickers.<symbol>` push -> update
+fn parse_message(text: &str, matcher: &TickerMatcher) -> Option<PriceUpdate> {
+    let ws_msg = serde_json::from_str::<WsMessage>(text).ok()?;
+    let (topic, data) = (ws_msg.topic?, ws_msg.data?);
+    if !topic.starts_with("tickers.") {
+        return None;
+    }
     
-    // Read messages
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    if let (Some(topic), Some(data)) = (ws_msg.topic, ws_msg.data) {
-                        if topic.starts_with("tickers.") {
-                            let bid = Decimal::from_str(&data.bid_price).unwrap_or_default();
-                            let ask = Decimal::from_str(&data.ask_price).unwrap_or_default();
-                            
-                            if bid.is_zero() || ask.is_zero() {
-                                continue;
-                            }
-                            
-                            let normalized = matcher.register("bybit", &data.symbol);
-                            
-                            let update = PriceUpdate {
-                                exchange: "bybit".to_string(),
-                                symbol: normalized,
-                                raw_symbol: data.symbol,
-                                bid,
-                                ask,
-                                bid_size: Decimal::from_str(&data.bid_size).unwrap_or_default(),
-                                ask_size: Decimal::from_str(&data.ask_size).unwrap_or_default(),
-                                timestamp: chrono::Utc::now().timestamp_millis(),
-                            };
-                            
-                            let _ = price_tx.send(update);
-                        }
-                    }
-                }
-            }
-            Ok(Message::Close(_)) => {
-                warn!("Bybit: connection closed");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "Bybit: websocket error");
-                break;
-            }
-            _ => {}
-        }
+    let bid = Decimal::from_str(&data.bid_price).unwrap_or_default();
+    let ask = Decimal::from_str(&data.ask_price).unwrap_or_default();
+    
+    if bid.is_zero() || ask.is_zero() {
+        return None;
     }
     
-    ping_handle.abort();
-    Ok(())
+    Some(PriceUpdate {
+        exchange: "bybit".to_string(),
+        symbol: matcher.register("bybit", &data.symbol),
+        raw_symbol: data.symbol,
+        bid,
+        ask,
+        bid_size: Decimal::from_str(&data.bid_size).unwrap_or_default(),
+        ask_size: Decimal::from_str(&data.ask_size).unwrap_or_default(),
+        bids: Vec::new(),
+        asks: Vec::new(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    })
 }
 
-async fn fetch_symbols() -> Result<Vec<Instrument>> {
-    let resp: InstrumentsResponse = reqwest::get(REST_URL).await?.json().await?;
+async fn fetch_symbols(rest_url: &str) -> Result<Vec<Instrument>> {
+    let url = format!("{}{}", rest_url, INSTRUMENTS_PATH);
+    let resp: InstrumentsResponse = reqwest::get(&url).await?.json().await?;
     Ok(resp.result.list)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ticker() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"topic":"tickers.SOLUSDT","ts":1700000000000,"type":"snapshot","data":{"symbol":"SOLUSDT","bid1Price":"61.25","bid1Size":"120","ask1Price":"61.26","ask1Size":"80.5","lastPrice":"61.25"}}"#;
+
+        let update = parse_message(text, &matcher).unwrap();
+        assert_eq!(update.symbol, "SOL/USDT");
+        assert_eq!(update.bid, Decimal::from_str("61.25").unwrap());
+        assert_eq!(update.ask_size, Decimal::from_str("80.5").unwrap());
+
+        assert!(parse_message(r#"{"success":true,"ret_msg":"pong","op":"ping"}"#, &matcher).is_none());
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(r#"{"success":true,"ret_msg":"pong","conn_id":"abc","op":"ping"}"#), Frame::Pong);
+        assert_eq!(classify(r#"{"op":"pong","args":["1700000000000"],"conn_id":"abc"}"#), Frame::Pong);
+        assert_eq!(classify(r#"{"op":"ping","req_id":"1"}"#), Frame::Ping);
+
+        let ack = r#"{"success":true,"ret_msg":"","conn_id":"abc","op":"subscribe"}"#;
+        assert_eq!(classify(ack), Frame::Data(ack));
+        let ticker = r#"{"topic":"tickers.SOLUSDT","ts":1700000000000,"type":"snapshot","data":{"symbol":"SOLUSDT"}}"#;
+        assert_eq!(classify(ticker), Frame::Data(ticker));
+    }
+}
@@ -2,26 +2,29 @@
 // Docs: https://docs.cdp.coinbase.com/advanced-trade/docs/ws-overview
 
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::symbols::cached_symbols;
+use super::ws_util::{reconnect_loop, Ping, Session};
+use super::{publish, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
 const WS_URL: &str = "wss://advanced-trade-ws.coinbase.com";
-const REST_URL: &str = "https://api.exchange.coinbase.com/products";
+const REST_URL: &str = "https://api.exchange.coinbase.com";
+const PRODUCTS_PATH: &str = "/products";
 
 #[derive(Debug, Deserialize)]
 struct Product {
     id: String,
-    base_currency: String,
     quote_currency: String,
     status: String,
 }
@@ -44,158 +47,138 @@ struct WsMessage {
 
 #[derive(Debug, Deserialize)]
 struct TickerEvent {
-    #[serde(rename = "type")]
-    event_type: Option<String>,
     tickers: Option<Vec<TickerData>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct TickerData {
     product_id: String,
-    price: Option<String>,
     best_bid: Option<String>,
     best_ask: Option<String>,
     best_bid_quantity: Option<String>,
     best_ask_quantity: Option<String>,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "Coinbase connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Coinbase;
+
+#[async_trait]
+impl Exchange for Coinbase {
+    fn name(&self) -> &'static str {
+        "coinbase"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("coinbase", "Coinbase", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
 async fn run_connection(
-    _config: &Config,
+    config: &Config,
     matcher: &TickerMatcher,
     price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
-    // Fetch available products
-    let products = fetch_products().await?;
+    let product_ids = cached_symbols("coinbase", config.symbol_cache_ttl(), || load_products(config, matcher)).await?;
+
+    info!(products = product_ids.len(), "Coinbase: connecting to websocket");
+
+    // Subscribe to ticker channel
+    let subscribe = SubscribeMessage {
+        msg_type: "subscribe".to_string(),
+        product_ids,
+        channel: "ticker".to_string(),
+    };
+
+    Session::new("coinbase", "Coinbase", config.ws_url("coinbase", WS_URL))
+        .subscribe(vec![serde_json::to_string(&subscribe)?])
+        .ping(Ping::Frame(Duration::from_secs(30)))
+        .run(shutdown, |text| {
+            for update in parse_message(text, matcher) {
+                publish(price_tx, update);
+            }
+            None
+        })
+        .await
+}
+
+/// Online USD products and those in a subscribed quote, registered with the matcher
+async fn load_products(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let products = fetch_products(&config.rest_url("coinbase", REST_URL)).await?;
     info!(count = products.len(), "Coinbase: fetched products");
 
     // Filter USD pairs (most liquid on Coinbase)
-    let usd_products: Vec<_> = products
-        .iter()
-        .filter(|p| p.status == "online" && (p.quote_currency == "USD" || p.quote_currency == "USDT"))
-        .take(50)
+    let product_ids: Vec<String> = products
+        .into_iter()
+        .filter(|p| p.status == "online" && (p.quote_currency == "USD" || config.subscribes_to_quote(&p.quote_currency)))
+        .take(config.symbol_limit())
+        .map(|p| p.id)
         .collect();
 
     // Register symbols with matcher
-    for product in &usd_products {
+    for product_id in &product_ids {
         // Coinbase uses BTC-USD format
-        matcher.register("coinbase", &product.id);
+        matcher.register("coinbase", product_id);
     }
 
-    let product_ids: Vec<String> = usd_products.iter().map(|p| p.id.clone()).collect();
-
-    info!(products = product_ids.len(), "Coinbase: connecting to websocket");
-
-    let (ws_stream, _) = connect_async(WS_URL).await?;
-    let (mut write, mut read) = ws_stream.split();
+    Ok(product_ids)
+}
 
-    // Subscribe to ticker channel
-    let subscribe = SubscribeMessage {
-        msg_type: "subscribe".to_string(),
-        product_ids: product_ids.clone(),
-        channel: "ticker".to_string(),
+/// `ticker` channel message -> updates for products registered at startup
+fn parse_message(text: &str, matcher: &TickerMatcher) -> Vec<PriceUpdate> {
+    let Ok(ws_msg) = serde_json::from_str::<WsMessage>(text) else {
+        return Vec::new();
     };
+    if ws_msg.msg_type != "ticker" {
+        return Vec::new();
+    }
 
-    let sub_msg = serde_json::to_string(&subscribe)?;
-    write.send(Message::Text(sub_msg)).await?;
+    let decimal = |raw: &Option<String>| {
+        raw.as_ref()
+            .and_then(|v| Decimal::from_str(v).ok())
+            .unwrap_or_default()
+    };
 
-    info!("Coinbase: subscribed to ticker channel");
+    ws_msg
+        .events
+        .into_iter()
+        .flat_map(|event| event.tickers.unwrap_or_default())
+        .filter_map(|ticker| {
+            let bid = decimal(&ticker.best_bid);
+            let ask = decimal(&ticker.best_ask);
 
-    // Ping task
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-            if write.send(Message::Ping(vec![])).await.is_err() {
-                break;
-            }
-        }
-    });
-
-    // Read messages
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    if ws_msg.msg_type == "ticker" {
-                        for event in ws_msg.events {
-                            if let Some(tickers) = event.tickers {
-                                for ticker in tickers {
-                                    let bid = ticker
-                                        .best_bid
-                                        .as_ref()
-                                        .and_then(|b| Decimal::from_str(b).ok())
-                                        .unwrap_or_default();
-                                    let ask = ticker
-                                        .best_ask
-                                        .as_ref()
-                                        .and_then(|a| Decimal::from_str(a).ok())
-                                        .unwrap_or_default();
-
-                                    if bid.is_zero() || ask.is_zero() {
-                                        continue;
-                                    }
-
-                                    if let Some(normalized) = matcher.get_normalized("coinbase", &ticker.product_id) {
-                                        let update = PriceUpdate {
-                                            exchange: "coinbase".to_string(),
-                                            symbol: normalized,
-                                            raw_symbol: ticker.product_id.clone(),
-                                            bid,
-                                            ask,
-                                            bid_size: ticker
-                                                .best_bid_quantity
-                                                .as_ref()
-                                                .and_then(|q| Decimal::from_str(q).ok())
-                                                .unwrap_or_default(),
-                                            ask_size: ticker
-                                                .best_ask_quantity
-                                                .as_ref()
-                                                .and_then(|q| Decimal::from_str(q).ok())
-                                                .unwrap_or_default(),
-                                            timestamp: chrono::Utc::now().timestamp_millis(),
-                                        };
-
-                                        let _ = price_tx.send(update);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(Message::Pong(_)) => {}
-            Ok(Message::Close(_)) => {
-                warn!("Coinbase: connection closed by server");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "Coinbase: websocket error");
-                break;
+            if bid.is_zero() || ask.is_zero() {
+                return None;
             }
-            _ => {}
-        }
-    }
 
-    ping_handle.abort();
-    Ok(())
+            Some(PriceUpdate {
+                exchange: "coinbase".to_string(),
+                symbol: matcher.get_normalized("coinbase", &ticker.product_id)?,
+                raw_symbol: ticker.product_id,
+                bid,
+                ask,
+                bid_size: decimal(&ticker.best_bid_quantity),
+                ask_size: decimal(&ticker.best_ask_quantity),
+                bids: Vec::new(),
+                asks: Vec::new(),
+                timestamp: chrono::Utc::now().timestamp_millis(),
+            })
+        })
+        .collect()
 }
 
-async fn fetch_products() -> Result<Vec<Product>> {
+async fn fetch_products(rest_url: &str) -> Result<Vec<Product>> {
     let client = reqwest::Client::new();
     let response = client
-        .get(REST_URL)
+        .get(format!("{}{}", rest_url, PRODUCTS_PATH))
         .header("User-Agent", "arbscanner/1.0")
         .send()
         .await?;
@@ -203,3 +186,22 @@ async fn fetch_products() -> Result<Vec<Product>> {
     let products: Vec<Product> = response.json().await?;
     Ok(products)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ticker() {
+        let matcher = TickerMatcher::new();
+        matcher.register("coinbase", "ETH-USD");
+        let text = r#"{"channel":"ticker","timestamp":"2023-11-14T22:13:20Z","sequence_num":0,"type":"ticker","events":[{"type":"update","tickers":[{"type":"ticker","product_id":"ETH-USD","price":"2000","best_bid":"1999.99","best_bid_quantity":"3.2","best_ask":"2000.01","best_ask_quantity":"1.1"},{"type":"ticker","product_id":"DOGE-USD","price":"0.07","best_bid":"0.07","best_ask":"0.0701"}]}]}"#;
+
+        // DOGE-USD was never registered, so only ETH-USD comes through
+        let updates = parse_message(text, &matcher);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].symbol, "ETH/USD");
+        assert_eq!(updates[0].bid, Decimal::from_str("1999.99").unwrap());
+        assert_eq!(updates[0].ask_size, Decimal::from_str("1.1").unwrap());
+    }
+}
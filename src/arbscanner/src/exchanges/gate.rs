@@ -2,21 +2,26 @@
 // Docs: https://www.gate.io/docs/developers/apiv4/ws/en/
 
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::symbols::cached_symbols;
+use super::ws_util::{reconnect_loop, Ping, Session};
+use super::{normalize_timestamp_ms, publish_stamped, subscription_batches, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
 const WS_URL: &str = "wss://api.gateio.ws/ws/v4/";
-const REST_URL: &str = "https://api.gateio.ws/api/v4/spot/currency_pairs";
+const REST_URL: &str = "https://api.gateio.ws";
+const CURRENCY_PAIRS_PATH: &str = "/api/v4/spot/currency_pairs";
+const SUBSCRIBE_BATCH_SIZE: usize = 20;
 
 #[derive(Debug, Deserialize)]
 struct CurrencyPair {
@@ -37,124 +42,158 @@ struct SubscribeRequest {
 struct WsMessage {
     channel: Option<String>,
     event: Option<String>,
-    result: Option<TickerResult>,
+    result: Option<BookTickerResult>,
 }
 
+/// Best bid/ask with their sizes, unlike `spot.tickers` which only carries prices
 #[derive(Debug, Deserialize)]
-struct TickerResult {
+struct BookTickerResult {
+    /// Book update time
+    #[serde(rename = "t")]
+    update_time: Option<i64>,
+    #[serde(rename = "s")]
     currency_pair: String,
-    highest_bid: String,
-    lowest_ask: String,
+    #[serde(rename = "b")]
+    bid_price: String,
+    #[serde(rename = "B")]
+    bid_size: String,
+    #[serde(rename = "a")]
+    ask_price: String,
+    #[serde(rename = "A")]
+    ask_size: String,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "Gate.io connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Gate;
+
+#[async_trait]
+impl Exchange for Gate {
+    fn name(&self) -> &'static str {
+        "gate"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("gate", "Gate.io", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
 async fn run_connection(
-    _config: &Config,
+    config: &Config,
     matcher: &TickerMatcher,
     price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
-    let pairs: Vec<CurrencyPair> = reqwest::get(REST_URL).await?.json().await?;
-    let usdt_pairs: Vec<_> = pairs
-        .iter()
-        .filter(|p| p.trade_status == "tradable" && p.quote == "USDT")
-        .take(100)
-        .collect();
+    let subscribed_pairs = cached_symbols("gate", config.symbol_cache_ttl(), || load_symbols(config, matcher)).await?;
     
-    info!(count = usdt_pairs.len(), "Gate.io: fetched symbols");
+    // Subscribe in batches
+    let subscriptions = subscription_batches(&subscribed_pairs, SUBSCRIBE_BATCH_SIZE)
+        .map(|chunk| {
+            serde_json::to_string(&SubscribeRequest {
+                time: chrono::Utc::now().timestamp(),
+                channel: "spot.book_ticker".to_string(),
+                event: "subscribe".to_string(),
+                payload: chunk.to_vec(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
     
-    for pair in &usdt_pairs {
-        matcher.register("gate", &pair.id);
-    }
+    Session::new("gate", "Gate.io", config.ws_url("gate", WS_URL))
+        .subscribe(subscriptions)
+        .ping(Ping::Json(
+            || serde_json::json!({
+                "time": chrono::Utc::now().timestamp(),
+                "channel": "spot.ping"
+            }),
+            Duration::from_secs(15),
+        ))
+        .run(shutdown, |text| {
+            if let Some(update) = parse_message(text, matcher) {
+                publish_stamped(price_tx, update);
+            }
+            None
+        })
+        .await
+}
+
+/// Tradable pairs in a subscribed quote (see `Config::subscribes_to_quote`), registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let pairs_url = format!("{}{}", config.rest_url("gate", REST_URL), CURRENCY_PAIRS_PATH);
+    let pairs: Vec<CurrencyPair> = reqwest::get(&pairs_url).await?.json().await?;
+    let subscribed_pairs: Vec<String> = pairs
+        .into_iter()
+        .filter(|p| p.trade_status == "tradable" && config.subscribes_to_quote(&p.quote))
+        .take(config.symbol_limit())
+        .map(|p| p.id)
+        .collect();
     
-    let (ws_stream, _) = connect_async(WS_URL).await?;
-    let (mut write, mut read) = ws_stream.split();
+    info!(count = subscribed_pairs.len(), "Gate.io: fetched symbols");
     
-    info!("Gate.io: connected");
+    for pair in &subscribed_pairs {
+        matcher.register("gate", pair);
+    }
     
-    // Subscribe in batches
-    for chunk in usdt_pairs.chunks(20) {
-        let sub = SubscribeRequest {
-            time: chrono::Utc::now().timestamp(),
-            channel: "spot.tickers".to_string(),
-            event: "subscribe".to_string(),
-            payload: chunk.iter().map(|p| p.id.clone()).collect(),
-        };
-        write.send(Message::Text(serde_json::to_string(&sub)?)).await?;
+    Ok(subscribed_pairs)
+}
+
+/// `spot.book_ticker` update -> update
+fn parse_message(text: &str, matcher: &TickerMatcher) -> Option<PriceUpdate> {
+    let ws_msg = serde_json::from_str::<WsMessage>(text).ok()?;
+    let (channel, event, result) = (ws_msg.channel?, ws_msg.event?, ws_msg.result?);
+    if channel != "spot.book_ticker" || event != "update" {
+        return None;
     }
     
-    // Ping task
-    let write = Arc::new(tokio::sync::Mutex::new(write));
-    let write_clone = write.clone();
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
-            let ping = serde_json::json!({
-                "time": chrono::Utc::now().timestamp(),
-                "channel": "spot.ping"
-            });
-            if write_clone.lock().await.send(Message::Text(ping.to_string())).await.is_err() {
-                break;
-            }
-        }
-    });
+    let bid = Decimal::from_str(&result.bid_price).unwrap_or_default();
+    let ask = Decimal::from_str(&result.ask_price).unwrap_or_default();
     
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    if let (Some(channel), Some(event), Some(result)) = 
-                        (ws_msg.channel, ws_msg.event, ws_msg.result) 
-                    {
-                        if channel == "spot.tickers" && event == "update" {
-                            let bid = Decimal::from_str(&result.highest_bid).unwrap_or_default();
-                            let ask = Decimal::from_str(&result.lowest_ask).unwrap_or_default();
-                            
-                            if bid.is_zero() || ask.is_zero() {
-                                continue;
-                            }
-                            
-                            let normalized = matcher.register("gate", &result.currency_pair);
-                            
-                            let update = PriceUpdate {
-                                exchange: "gate".to_string(),
-                                symbol: normalized,
-                                raw_symbol: result.currency_pair,
-                                bid,
-                                ask,
-                                bid_size: Decimal::ZERO, // Gate doesn't send size in ticker
-                                ask_size: Decimal::ZERO,
-                                timestamp: chrono::Utc::now().timestamp_millis(),
-                            };
-                            
-                            let _ = price_tx.send(update);
-                        }
-                    }
-                }
-            }
-            Ok(Message::Close(_)) => {
-                warn!("Gate.io: connection closed");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "Gate.io: websocket error");
-                break;
-            }
-            _ => {}
-        }
+    if bid.is_zero() || ask.is_zero() {
+        return None;
     }
     
-    ping_handle.abort();
-    Ok(())
+    Some(PriceUpdate {
+        exchange: "gate".to_string(),
+        symbol: matcher.register("gate", &result.currency_pair),
+        raw_symbol: result.currency_pair,
+        bid,
+        ask,
+        bid_size: Decimal::from_str(&result.bid_size).unwrap_or_default(),
+        ask_size: Decimal::from_str(&result.ask_size).unwrap_or_default(),
+        bids: Vec::new(),
+        asks: Vec::new(),
+        timestamp: result
+            .update_time
+            .map(normalize_timestamp_ms)
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ticker_update() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"time":1700000000,"time_ms":1700000000123,"channel":"spot.book_ticker","event":"update","result":{"t":1700000000120,"u":48733182,"s":"BTC_USDT","b":"43250.1","B":"0.8231","a":"43250.5","A":"1.2"}}"#;
+
+        let update = parse_message(text, &matcher).unwrap();
+        assert_eq!(update.symbol, "BTC/USDT");
+        assert_eq!(update.raw_symbol, "BTC_USDT");
+        assert_eq!(update.ask, Decimal::from_str("43250.5").unwrap());
+        assert_eq!(update.bid_size, Decimal::from_str("0.8231").unwrap());
+        assert_eq!(update.ask_size, Decimal::from_str("1.2").unwrap());
+        assert_eq!(update.timestamp, 1_700_000_000_120);
+
+        let ack = r#"{"time":1700000000,"channel":"spot.book_ticker","event":"subscribe","result":{"status":"success"}}"#;
+        assert!(parse_message(ack, &matcher).is_none());
+    }
 }
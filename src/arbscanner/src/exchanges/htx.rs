@@ -2,23 +2,24 @@
 // Docs: https://huobiapi.github.io/docs/spot/v1/en/
 
 use anyhow::Result;
-use flate2::read::GzDecoder;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::io::Read;
-use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::symbols::cached_symbols;
+use super::ws_util::{reconnect_loop, Session};
+use super::{normalize_timestamp_ms, publish_stamped, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
 const WS_URL: &str = "wss://api.huobi.pro/ws";
-const REST_URL: &str = "https://api.huobi.pro/v1/common/symbols";
+const REST_URL: &str = "https://api.huobi.pro";
+const SYMBOLS_PATH: &str = "/v1/common/symbols";
 
 #[derive(Debug, Deserialize)]
 struct SymbolsResponse {
@@ -48,6 +49,8 @@ struct PongMessage {
 struct WsMessage {
     ping: Option<u64>,
     ch: Option<String>,
+    /// Server time the message was sent
+    ts: Option<i64>,
     tick: Option<TickerData>,
 }
 
@@ -61,135 +64,135 @@ struct TickerData {
     ask_size: Option<Decimal>,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "HTX connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Htx;
+
+#[async_trait]
+impl Exchange for Htx {
+    fn name(&self) -> &'static str {
+        "htx"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("htx", "HTX", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
 async fn run_connection(
-    _config: &Config,
+    config: &Config,
     matcher: &TickerMatcher,
     price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
-    // Fetch available symbols
-    let symbols = fetch_symbols().await?;
-    info!(count = symbols.len(), "HTX: fetched symbols");
+    // Reconnects during an outage reuse the list instead of hitting the REST API again
+    let subscribed_symbols = cached_symbols("htx", config.symbol_cache_ttl(), || load_symbols(config, matcher)).await?;
+
+    info!(symbols = subscribed_symbols.len(), "HTX: connecting to websocket");
 
-    // Filter USDT pairs
-    let usdt_symbols: Vec<_> = symbols
+    // Subscribe to BBO (best bid/offer) channels
+    let subscriptions = subscribed_symbols
         .iter()
-        .filter(|s| s.state == "online" && s.quote_currency == "usdt")
-        .take(100)
+        .enumerate()
+        .map(|(i, sym)| {
+            serde_json::to_string(&SubscribeMessage {
+                sub: format!("market.{}.bbo", sym),
+                id: format!("sub_{}", i),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // HTX sends gzip compressed binary frames and pings us, expecting a pong
+    Session::new("htx", "HTX", config.ws_url("htx", WS_URL))
+        .subscribe(subscriptions)
+        // Small delay to avoid rate limiting
+        .pace(10, Duration::from_millis(100))
+        .gzip()
+        .run(shutdown, |text| match parse_message(text, matcher)? {
+            Incoming::Ping(ping) => serde_json::to_string(&PongMessage { pong: ping }).ok(),
+            Incoming::Update(update) => {
+                publish_stamped(price_tx, update);
+                None
+            }
+        })
+        .await
+}
+
+/// Online pairs in a subscribed quote (lowercase, as HTX names them), registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let symbols = fetch_symbols(&config.rest_url("htx", REST_URL)).await?;
+    info!(count = symbols.len(), "HTX: fetched symbols");
+
+    // USDT pairs, plus equivalent stablecoins and cross pairs when enabled
+    let subscribed_symbols: Vec<String> = symbols
+        .into_iter()
+        .filter(|s| s.state == "online" && config.subscribes_to_quote(&s.quote_currency))
+        .take(config.symbol_limit())
+        .map(|s| s.symbol)
         .collect();
 
     // Register symbols with matcher
-    for sym in &usdt_symbols {
+    for symbol in &subscribed_symbols {
         // HTX uses lowercase: btcusdt
-        matcher.register("htx", &sym.symbol.to_uppercase());
+        matcher.register("htx", &symbol.to_uppercase());
     }
 
-    info!(symbols = usdt_symbols.len(), "HTX: connecting to websocket");
-
-    let (ws_stream, _) = connect_async(WS_URL).await?;
-    let (mut write, mut read) = ws_stream.split();
+    Ok(subscribed_symbols)
+}
 
-    // Subscribe to BBO (best bid/offer) channels
-    for (i, sym) in usdt_symbols.iter().enumerate() {
-        let subscribe = SubscribeMessage {
-            sub: format!("market.{}.bbo", sym.symbol),
-            id: format!("sub_{}", i),
-        };
+/// A decompressed HTX frame we act on
+#[derive(Debug)]
+enum Incoming {
+    Ping(u64),
+    Update(PriceUpdate),
+}
 
-        let sub_msg = serde_json::to_string(&subscribe)?;
-        write.send(Message::Text(sub_msg)).await?;
-        
-        // Small delay to avoid rate limiting
-        if i % 10 == 9 {
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        }
+/// Pings and `bbo` ticks, stamped with the server `ts`
+fn parse_message(text: &str, matcher: &TickerMatcher) -> Option<Incoming> {
+    let ws_msg = serde_json::from_str::<WsMessage>(text).ok()?;
+    if let Some(ping) = ws_msg.ping {
+        return Some(Incoming::Ping(ping));
     }
 
-    info!("HTX: subscribed to BBO channels");
-
-    // HTX sends binary gzip compressed messages
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Binary(data)) => {
-                // Decompress gzip data
-                let mut decoder = GzDecoder::new(&data[..]);
-                let mut text = String::new();
-                if decoder.read_to_string(&mut text).is_err() {
-                    continue;
-                }
-
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    // Handle ping/pong (HTX requires pong response)
-                    if let Some(ping) = ws_msg.ping {
-                        let pong = PongMessage { pong: ping };
-                        let pong_msg = serde_json::to_string(&pong)?;
-                        write.send(Message::Text(pong_msg)).await?;
-                        continue;
-                    }
-
-                    // Handle ticker data
-                    if let (Some(channel), Some(tick)) = (ws_msg.ch, ws_msg.tick) {
-                        // Extract symbol from channel: market.btcusdt.bbo
-                        let parts: Vec<&str> = channel.split('.').collect();
-                        if parts.len() >= 2 {
-                            let symbol = parts[1].to_uppercase();
-
-                            let bid = tick.bid.unwrap_or_default();
-                            let ask = tick.ask.unwrap_or_default();
-
-                            if bid.is_zero() || ask.is_zero() {
-                                continue;
-                            }
-
-                            if let Some(normalized) = matcher.get_normalized("htx", &symbol) {
-                                let update = PriceUpdate {
-                                    exchange: "htx".to_string(),
-                                    symbol: normalized,
-                                    raw_symbol: symbol,
-                                    bid,
-                                    ask,
-                                    bid_size: tick.bid_size.unwrap_or_default(),
-                                    ask_size: tick.ask_size.unwrap_or_default(),
-                                    timestamp: chrono::Utc::now().timestamp_millis(),
-                                };
-
-                                let _ = price_tx.send(update);
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(Message::Close(_)) => {
-                warn!("HTX: connection closed by server");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "HTX: websocket error");
-                break;
-            }
-            _ => {}
-        }
+    let (channel, tick) = (ws_msg.ch?, ws_msg.tick?);
+    // Extract symbol from channel: market.btcusdt.bbo
+    let symbol = channel.split('.').nth(1)?.to_uppercase();
+
+    let bid = tick.bid.unwrap_or_default();
+    let ask = tick.ask.unwrap_or_default();
+
+    if bid.is_zero() || ask.is_zero() {
+        return None;
     }
 
-    Ok(())
+    Some(Incoming::Update(PriceUpdate {
+        exchange: "htx".to_string(),
+        symbol: matcher.get_normalized("htx", &symbol)?,
+        raw_symbol: symbol,
+        bid,
+        ask,
+        bid_size: tick.bid_size.unwrap_or_default(),
+        ask_size: tick.ask_size.unwrap_or_default(),
+        bids: Vec::new(),
+        asks: Vec::new(),
+        timestamp: ws_msg
+            .ts
+            .map(normalize_timestamp_ms)
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+    }))
 }
 
-async fn fetch_symbols() -> Result<Vec<SymbolInfo>> {
+async fn fetch_symbols(rest_url: &str) -> Result<Vec<SymbolInfo>> {
     let client = reqwest::Client::new();
     let response = client
-        .get(REST_URL)
+        .get(format!("{}{}", rest_url, SYMBOLS_PATH))
         .header("User-Agent", "arbscanner/1.0")
         .send()
         .await?;
@@ -197,3 +200,34 @@ async fn fetch_symbols() -> Result<Vec<SymbolInfo>> {
     let symbols_resp: SymbolsResponse = response.json().await?;
     Ok(symbols_resp.data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_ping() {
+        let matcher = TickerMatcher::new();
+        assert!(matches!(
+            parse_message(r#"{"ping":1700000000000}"#, &matcher),
+            Some(Incoming::Ping(1_700_000_000_000))
+        ));
+    }
+
+    #[test]
+    fn test_parse_bbo() {
+        let matcher = TickerMatcher::new();
+        matcher.register("htx", "BTCUSDT");
+        let text = r#"{"ch":"market.btcusdt.bbo","ts":1700000000000,"tick":{"seqId":1,"ask":43250.2,"askSize":0.4,"bid":43250.1,"bidSize":1.5,"symbol":"btcusdt"}}"#;
+
+        let Some(Incoming::Update(update)) = parse_message(text, &matcher) else {
+            panic!("expected an update");
+        };
+        assert_eq!(update.symbol, "BTC/USDT");
+        assert_eq!(update.raw_symbol, "BTCUSDT");
+        assert_eq!(update.bid, Decimal::from_str("43250.1").unwrap());
+        assert_eq!(update.bid_size, Decimal::from_str("1.5").unwrap());
+        assert_eq!(update.timestamp, 1_700_000_000_000);
+    }
+}
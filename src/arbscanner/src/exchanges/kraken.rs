@@ -1,20 +1,47 @@
 // Kraken exchange connector
 // WebSocket docs: https://docs.kraken.com/websockets-v2/
+// REST docs: https://docs.kraken.com/api/docs/rest-api/get-tradable-asset-pairs
 
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::symbols::cached_symbols;
+use super::ws_util::{reconnect_loop, Ping, Session};
+use super::{publish, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
 const WS_URL: &str = "wss://ws.kraken.com/v2";
+const REST_URL: &str = "https://api.kraken.com";
+const ASSET_PAIRS_PATH: &str = "/0/public/AssetPairs";
+
+/// Legacy Kraken codes still used in REST `wsname`s; websocket v2 uses the common tickers
+const CURRENCY_ALIASES: &[(&str, &str)] = &[("XBT", "BTC"), ("XDG", "DOGE")];
+
+#[derive(Debug, Deserialize)]
+struct AssetPairsResponse {
+    #[serde(default)]
+    error: Vec<String>,
+    #[serde(default)]
+    result: HashMap<String, AssetPair>,
+}
+
+/// An `AssetPairs` entry, keyed by its REST name (`XXBTZUSD`)
+#[derive(Debug, Deserialize)]
+struct AssetPair {
+    /// v1 websocket name (`XBT/USD`); missing on dark pool pairs
+    wsname: Option<String>,
+    /// Absent on older responses, which only listed tradable pairs
+    status: Option<String>,
+}
 
 #[derive(Debug, Serialize)]
 struct SubscribeRequest {
@@ -28,13 +55,6 @@ struct SubscribeParams {
     symbol: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-enum WsMessage {
-    Ticker(TickerMessage),
-    Other(serde_json::Value),
-}
-
 #[derive(Debug, Deserialize)]
 struct TickerMessage {
     channel: Option<String>,
@@ -50,105 +70,202 @@ struct TickerData {
     ask_qty: Decimal,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "Kraken connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Kraken;
+
+#[async_trait]
+impl Exchange for Kraken {
+    fn name(&self) -> &'static str {
+        "kraken"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("kraken", "Kraken", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
 async fn run_connection(
-    _config: &Config,
+    config: &Config,
     matcher: &TickerMatcher,
     price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
-    let (ws_stream, _) = connect_async(WS_URL).await?;
-    let (mut write, mut read) = ws_stream.split();
-    
-    info!("Kraken: connected");
-    
-    // Popular USDT pairs on Kraken
-    let symbols = vec![
-        "BTC/USD", "ETH/USD", "SOL/USD", "XRP/USD", "DOGE/USD",
-        "ADA/USD", "AVAX/USD", "DOT/USD", "LINK/USD", "MATIC/USD",
-        "BTC/USDT", "ETH/USDT", "SOL/USDT", "XRP/USDT",
-    ];
-    
-    for sym in &symbols {
-        matcher.register("kraken", sym);
-    }
+    let symbols = cached_symbols("kraken", config.symbol_cache_ttl(), || load_symbols(config, matcher)).await?;
+    info!(symbols = symbols.len(), "Kraken: connecting to websocket");
     
     let sub = SubscribeRequest {
         method: "subscribe".to_string(),
         params: SubscribeParams {
             channel: "ticker".to_string(),
-            symbol: symbols.iter().map(|s| s.to_string()).collect(),
+            symbol: symbols,
         },
     };
     
-    write.send(Message::Text(serde_json::to_string(&sub)?)).await?;
-    
-    // Ping task
-    let write = Arc::new(tokio::sync::Mutex::new(write));
-    let write_clone = write.clone();
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-            let ping = serde_json::json!({"method": "ping"});
-            if write_clone.lock().await.send(Message::Text(ping.to_string())).await.is_err() {
-                break;
+    Session::new("kraken", "Kraken", config.ws_url("kraken", WS_URL))
+        .subscribe(vec![serde_json::to_string(&sub)?])
+        .ping(Ping::Json(|| serde_json::json!({"method": "ping"}), Duration::from_secs(30)))
+        .run(shutdown, |text| {
+            for update in parse_message(text, matcher) {
+                publish(price_tx, update);
             }
-        }
+            None
+        })
+        .await
+}
+
+/// Online USD pairs and those in a subscribed quote, as websocket v2 symbols (`BTC/USD`),
+/// registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let pairs = fetch_asset_pairs(&config.rest_url("kraken", REST_URL)).await?;
+    info!(count = pairs.len(), "Kraken: fetched symbols");
+    
+    let symbols = subscribed_symbols(&pairs, config.symbol_limit(), |quote| {
+        quote == "USD" || config.subscribes_to_quote(quote)
     });
+    for symbol in &symbols {
+        matcher.register("kraken", symbol);
+    }
     
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if let Ok(WsMessage::Ticker(ticker)) = serde_json::from_str(&text) {
-                    if let (Some(channel), Some(data_vec)) = (ticker.channel, ticker.data) {
-                        if channel == "ticker" {
-                            for data in data_vec {
-                                if data.bid.is_zero() || data.ask.is_zero() {
-                                    continue;
-                                }
-                                
-                                let normalized = matcher.register("kraken", &data.symbol);
-                                
-                                let update = PriceUpdate {
-                                    exchange: "kraken".to_string(),
-                                    symbol: normalized,
-                                    raw_symbol: data.symbol,
-                                    bid: data.bid,
-                                    ask: data.ask,
-                                    bid_size: data.bid_qty,
-                                    ask_size: data.ask_qty,
-                                    timestamp: chrono::Utc::now().timestamp_millis(),
-                                };
-                                
-                                let _ = price_tx.send(update);
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(Message::Close(_)) => {
-                warn!("Kraken: connection closed");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "Kraken: websocket error");
-                break;
-            }
-            _ => {}
-        }
+    Ok(symbols)
+}
+
+/// Online pairs whose quote passes `subscribed`, at most `limit`
+fn subscribed_symbols(
+    pairs: &HashMap<String, AssetPair>,
+    limit: usize,
+    subscribed: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut symbols: Vec<String> = pairs
+        .values()
+        .filter(|pair| pair.status.as_deref().is_none_or(|status| status == "online"))
+        .filter_map(|pair| ws_symbol(pair.wsname.as_deref()?))
+        .filter(|symbol| symbol.split_once('/').is_some_and(|(_, quote)| subscribed(quote)))
+        .collect();
+    // The response is a map, so sort to keep the capped selection stable across restarts
+    symbols.sort();
+    symbols.truncate(limit);
+    symbols
+}
+
+async fn fetch_asset_pairs(rest_url: &str) -> Result<HashMap<String, AssetPair>> {
+    let response: AssetPairsResponse = reqwest::Client::new()
+        .get(format!("{}{}", rest_url, ASSET_PAIRS_PATH))
+        .header("User-Agent", "arbscanner/1.0")
+        .send()
+        .await?
+        .json()
+        .await?;
+    
+    if !response.error.is_empty() {
+        anyhow::bail!("Kraken AssetPairs error: {}", response.error.join(", "));
+    }
+    Ok(response.result)
+}
+
+/// REST `wsname` -> websocket v2 symbol: `XBT/USD` -> `BTC/USD`, `XDG/USDT` -> `DOGE/USDT`
+fn ws_symbol(wsname: &str) -> Option<String> {
+    let (base, quote) = wsname.split_once('/')?;
+    if base.is_empty() || quote.is_empty() {
+        return None;
+    }
+    Some(format!("{}/{}", currency(base), currency(quote)))
+}
+
+fn currency(code: &str) -> &str {
+    CURRENCY_ALIASES
+        .iter()
+        .find(|(kraken, _)| *kraken == code)
+        .map_or(code, |(_, common)| common)
+}
+
+/// `ticker` channel message -> updates
+fn parse_message(text: &str, matcher: &TickerMatcher) -> Vec<PriceUpdate> {
+    let Ok(ticker) = serde_json::from_str::<TickerMessage>(text) else {
+        return Vec::new();
+    };
+    let (Some(channel), Some(data_vec)) = (ticker.channel, ticker.data) else {
+        return Vec::new();
+    };
+    if channel != "ticker" {
+        return Vec::new();
     }
     
-    ping_handle.abort();
-    Ok(())
+    data_vec
+        .into_iter()
+        .filter(|data| !data.bid.is_zero() && !data.ask.is_zero())
+        .map(|data| PriceUpdate {
+            exchange: "kraken".to_string(),
+            symbol: matcher.register("kraken", &data.symbol),
+            raw_symbol: data.symbol,
+            bid: data.bid,
+            ask: data.ask,
+            bid_size: data.bid_qty,
+            ask_size: data.ask_qty,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_ticker() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"channel":"ticker","type":"update","data":[{"symbol":"BTC/USD","bid":43250.1,"bid_qty":0.5,"ask":43250.2,"ask_qty":1.25,"last":43250.1}]}"#;
+
+        let updates = parse_message(text, &matcher);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].symbol, "BTC/USD");
+        assert_eq!(updates[0].ask, Decimal::from_str("43250.2").unwrap());
+        assert_eq!(updates[0].bid_size, Decimal::from_str("0.5").unwrap());
+
+        assert!(parse_message(r#"{"channel":"heartbeat"}"#, &matcher).is_empty());
+    }
+
+    #[test]
+    fn test_ws_symbol() {
+        assert_eq!(ws_symbol("XBT/USD").as_deref(), Some("BTC/USD"));
+        assert_eq!(ws_symbol("XDG/USDT").as_deref(), Some("DOGE/USDT"));
+        assert_eq!(ws_symbol("ETH/XBT").as_deref(), Some("ETH/BTC"));
+        assert_eq!(ws_symbol("SOL/USD").as_deref(), Some("SOL/USD"));
+        assert_eq!(ws_symbol("XBTUSD"), None);
+    }
+
+    #[test]
+    fn test_subscribed_symbols_from_asset_pairs() {
+        let text = r#"{"error":[],"result":{
+            "XXBTZUSD":{"altname":"XBTUSD","wsname":"XBT/USD","base":"XXBT","quote":"ZUSD","status":"online"},
+            "XXBTZUSD.d":{"altname":"XBTUSD.d","base":"XXBT","quote":"ZUSD"},
+            "XDGUSDT":{"altname":"XDGUSDT","wsname":"XDG/USDT","base":"XXDG","quote":"USDT","status":"online"},
+            "XETHXXBT":{"altname":"ETHXBT","wsname":"ETH/XBT","base":"XETH","quote":"XXBT","status":"online"},
+            "XETHZEUR":{"altname":"ETHEUR","wsname":"ETH/EUR","base":"XETH","quote":"ZEUR","status":"online"},
+            "LUNAUSD":{"altname":"LUNAUSD","wsname":"LUNA/USD","base":"LUNA","quote":"ZUSD","status":"delisted"}
+        }}"#;
+        let response: AssetPairsResponse = serde_json::from_str(text).unwrap();
+
+        let usd = |quote: &str| quote == "USD" || quote == "USDT";
+        assert_eq!(subscribed_symbols(&response.result, usize::MAX, usd), ["BTC/USD", "DOGE/USDT"]);
+        assert_eq!(subscribed_symbols(&response.result, 1, usd), ["BTC/USD"]);
+
+        // Cross pairs once triangular scanning subscribes to BTC quotes
+        let with_btc = |quote: &str| usd(quote) || quote == "BTC";
+        assert_eq!(
+            subscribed_symbols(&response.result, usize::MAX, with_btc),
+            ["BTC/USD", "DOGE/USDT", "ETH/BTC"]
+        );
+    }
 }
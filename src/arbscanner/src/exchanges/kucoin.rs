@@ -1,22 +1,34 @@
 // KuCoin exchange connector
 // Docs: https://docs.kucoin.com/
 
-use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::symbols::cached_symbols;
+use super::ws_util::{reconnect_loop, Ping, Session};
+use super::{publish, subscription_batches, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
-const REST_URL: &str = "https://api.kucoin.com/api/v1/bullet-public";
-const SYMBOLS_URL: &str = "https://api.kucoin.com/api/v2/symbols";
+const REST_URL: &str = "https://api.kucoin.com";
+const BULLET_PATH: &str = "/api/v1/bullet-public";
+const SYMBOLS_PATH: &str = "/api/v2/symbols";
+const PAGE_SIZE: usize = 100;
+/// KuCoin accepts at most 100 symbols per ticker topic
+const SUBSCRIBE_BATCH_SIZE: usize = 100;
+/// Validity of a public bullet token; KuCoin documents it but doesn't return it
+const TOKEN_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+/// Reconnect with a fresh token this long before the old one expires
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(30 * 60);
 
 #[derive(Debug, Deserialize)]
 struct BulletResponse {
@@ -35,11 +47,51 @@ struct InstanceServer {
     endpoint: String,
     #[serde(rename = "pingInterval")]
     ping_interval: u64,
+    /// Also the handshake deadline: the server drops clients silent for this long
+    #[serde(rename = "pingTimeout")]
+    ping_timeout: u64,
+}
+
+/// Connection settings derived from a bullet response
+#[derive(Debug, PartialEq)]
+struct BulletSchedule {
+    ping_interval: Duration,
+    connect_timeout: Duration,
+    /// When to drop the connection and fetch a new token
+    refresh_at: Instant,
+}
+
+impl InstanceServer {
+    /// Settings for a connection using a token fetched at `fetched_at`
+    fn schedule(&self, fetched_at: Instant) -> BulletSchedule {
+        BulletSchedule {
+            ping_interval: Duration::from_millis(self.ping_interval),
+            connect_timeout: Duration::from_millis(self.ping_timeout),
+            refresh_at: fetched_at + TOKEN_LIFETIME - TOKEN_REFRESH_MARGIN,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct SymbolsResponse {
-    data: Vec<Symbol>,
+    data: SymbolsData,
+}
+
+/// KuCoin returns either a plain list or its paginated envelope depending on the endpoint version
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SymbolsData {
+    Page(SymbolsPage),
+    List(Vec<Symbol>),
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolsPage {
+    #[serde(rename = "currentPage")]
+    current_page: u32,
+    #[serde(rename = "totalPage")]
+    total_page: u32,
+    items: Vec<Symbol>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -64,8 +116,6 @@ struct SubscribeRequest {
 
 #[derive(Debug, Deserialize)]
 struct WsMessage {
-    #[serde(rename = "type")]
-    msg_type: Option<String>,
     topic: Option<String>,
     data: Option<TickerData>,
 }
@@ -82,128 +132,279 @@ struct TickerData {
     best_ask_size: String,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "KuCoin connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Kucoin;
+
+#[async_trait]
+impl Exchange for Kucoin {
+    fn name(&self) -> &'static str {
+        "kucoin"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("kucoin", "KuCoin", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
 async fn run_connection(
-    _config: &Config,
+    config: &Config,
     matcher: &TickerMatcher,
     price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
+    let rest_url = config.rest_url("kucoin", REST_URL);
+
     // Get WebSocket token
+    let fetched_at = Instant::now();
     let bullet: BulletResponse = reqwest::Client::new()
-        .post(REST_URL)
+        .post(format!("{}{}", rest_url, BULLET_PATH))
         .send()
         .await?
         .json()
         .await?;
     
-    let server = &bullet.data.instance_servers[0];
-    let ws_url = format!("{}?token={}", server.endpoint, bullet.data.token);
-    let ping_interval = server.ping_interval;
+    let server = bullet
+        .data
+        .instance_servers
+        .first()
+        .ok_or_else(|| anyhow!("KuCoin: bullet response lists no instance servers"))?;
+    // KUCOIN_WS_URL replaces the endpoint handed out by the bullet response; the token still applies
+    let ws_url = format!(
+        "{}?token={}",
+        config.ws_url("kucoin", &server.endpoint),
+        bullet.data.token
+    );
+    let schedule = server.schedule(fetched_at);
+    
+    // The token is per connection, the symbol list is not
+    let subscribed_symbols = cached_symbols("kucoin", config.symbol_cache_ttl(), || load_symbols(&rest_url, config, matcher)).await?;
     
-    // Fetch symbols
-    let symbols_resp: SymbolsResponse = reqwest::get(SYMBOLS_URL).await?.json().await?;
-    let usdt_symbols: Vec<_> = symbols_resp.data
-        .iter()
-        .filter(|s| s.enable_trading && s.quote_currency == "USDT")
-        .take(100)
+    // Subscribe to ticker
+    let subscriptions = subscription_batches(&subscribed_symbols, SUBSCRIBE_BATCH_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let topic = format!(
+                "/market/ticker:{}",
+                chunk.join(",")
+            );
+            
+            serde_json::to_string(&SubscribeRequest {
+                id: format!("arbscanner-{}", i),
+                msg_type: "subscribe".to_string(),
+                topic,
+                private_channel: false,
+                response: false,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    // The token is only checked at connect, so reconnect with a new one before it lapses
+    // instead of waiting for the server to drop us
+    Session::new("kucoin", "KuCoin", ws_url)
+        .subscribe(subscriptions)
+        .ping(Ping::Json(|| serde_json::json!({"id": "ping", "type": "ping"}), schedule.ping_interval))
+        .connect_timeout(schedule.connect_timeout)
+        .close_at(schedule.refresh_at)
+        .run(shutdown, |text| {
+            if let Some(update) = parse_message(text, matcher) {
+                publish(price_tx, update);
+            }
+            None
+        })
+        .await
+}
+
+/// Tradable pairs in a subscribed quote (see `Config::subscribes_to_quote`), registered with the matcher
+async fn load_symbols(rest_url: &str, config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let subscribed_symbols: Vec<String> = fetch_symbols(rest_url)
+        .await?
+        .into_iter()
+        .filter(|s| s.enable_trading && config.subscribes_to_quote(&s.quote_currency))
+        .take(config.symbol_limit())
+        .map(|s| s.symbol)
         .collect();
     
-    info!(count = usdt_symbols.len(), "KuCoin: fetched symbols");
+    info!(count = subscribed_symbols.len(), "KuCoin: fetched symbols");
     
-    for sym in &usdt_symbols {
-        matcher.register("kucoin", &sym.symbol);
+    for symbol in &subscribed_symbols {
+        matcher.register("kucoin", symbol);
     }
     
-    let (ws_stream, _) = connect_async(&ws_url).await?;
-    let (mut write, mut read) = ws_stream.split();
-    
-    info!("KuCoin: connected");
+    Ok(subscribed_symbols)
+}
+
+/// `/market/ticker:<symbol>` push -> update
+fn parse_message(text: &str, matcher: &TickerMatcher) -> Option<PriceUpdate> {
+    let ws_msg = serde_json::from_str::<WsMessage>(text).ok()?;
+    let (topic, data) = (ws_msg.topic?, ws_msg.data?);
     
-    // Subscribe to ticker
-    let topic = format!(
-        "/market/ticker:{}",
-        usdt_symbols.iter().map(|s| s.symbol.as_str()).collect::<Vec<_>>().join(",")
-    );
+    // Topic format: /market/ticker:BTC-USDT
+    let symbol = topic.split(':').next_back().unwrap_or_default();
     
-    let sub = SubscribeRequest {
-        id: "arbscanner".to_string(),
-        msg_type: "subscribe".to_string(),
-        topic,
-        private_channel: false,
-        response: false,
-    };
+    let bid = Decimal::from_str(&data.best_bid).unwrap_or_default();
+    let ask = Decimal::from_str(&data.best_ask).unwrap_or_default();
     
-    write.send(Message::Text(serde_json::to_string(&sub)?)).await?;
+    if bid.is_zero() || ask.is_zero() {
+        return None;
+    }
     
-    // Ping task
-    let write = Arc::new(tokio::sync::Mutex::new(write));
-    let write_clone = write.clone();
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_millis(ping_interval)).await;
-            let ping = serde_json::json!({"id": "ping", "type": "ping"});
-            if write_clone.lock().await.send(Message::Text(ping.to_string())).await.is_err() {
+    Some(PriceUpdate {
+        exchange: "kucoin".to_string(),
+        symbol: matcher.register("kucoin", symbol),
+        raw_symbol: symbol.to_string(),
+        bid,
+        ask,
+        bid_size: Decimal::from_str(&data.best_bid_size).unwrap_or_default(),
+        ask_size: Decimal::from_str(&data.best_ask_size).unwrap_or_default(),
+        bids: Vec::new(),
+        asks: Vec::new(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    })
+}
+
+async fn fetch_symbols(rest_url: &str) -> Result<Vec<Symbol>> {
+    let client = reqwest::Client::new();
+    let mut symbols = Vec::new();
+    let mut page = 1;
+
+    loop {
+        // Without both parameters KuCoin falls back to its own page size, first page included
+        let url = format!(
+            "{}{}?currentPage={}&pageSize={}",
+            rest_url, SYMBOLS_PATH, page, PAGE_SIZE
+        );
+
+        let resp: SymbolsResponse = client.get(&url).send().await?.json().await?;
+        match resp.data {
+            SymbolsData::List(list) => {
+                symbols.extend(list);
                 break;
             }
-        }
-    });
-    
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    if let (Some(topic), Some(data)) = (ws_msg.topic, ws_msg.data) {
-                        // Topic format: /market/ticker:BTC-USDT
-                        let symbol = topic.split(':').last().unwrap_or_default();
-                        
-                        let bid = Decimal::from_str(&data.best_bid).unwrap_or_default();
-                        let ask = Decimal::from_str(&data.best_ask).unwrap_or_default();
-                        
-                        if bid.is_zero() || ask.is_zero() {
-                            continue;
-                        }
-                        
-                        let normalized = matcher.register("kucoin", symbol);
-                        
-                        let update = PriceUpdate {
-                            exchange: "kucoin".to_string(),
-                            symbol: normalized,
-                            raw_symbol: symbol.to_string(),
-                            bid,
-                            ask,
-                            bid_size: Decimal::from_str(&data.best_bid_size).unwrap_or_default(),
-                            ask_size: Decimal::from_str(&data.best_ask_size).unwrap_or_default(),
-                            timestamp: chrono::Utc::now().timestamp_millis(),
-                        };
-                        
-                        let _ = price_tx.send(update);
-                    }
+            SymbolsData::Page(p) => {
+                let done = p.items.is_empty() || p.current_page >= p.total_page;
+                symbols.extend(p.items);
+                if done {
+                    break;
                 }
+                page = p.current_page + 1;
             }
-            Ok(Message::Close(_)) => {
-                warn!("KuCoin: connection closed");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "KuCoin: websocket error");
-                break;
-            }
-            _ => {}
         }
     }
-    
-    ping_handle.abort();
-    Ok(())
+
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_from_bullet() {
+        let text = r#"{"code":"200000","data":{"token":"2neAiuYvAU61ZDXANAGAsiL4-iAExhsBXZxftpOeh_55i3Ysy2q2LEsEWU64mdzUOPusi34M_wGoSf7iNyEWJ4aBZXpWhrmY9jKtqkdWoFa75w3istPvPtiYB9J6i9GjsxUuhPw3BlrzazF6ghq4L_6PpNd44jxOiddxKL4CNSU=.VFTJ4VBCCFdyBpqV9VrZKw==","instanceServers":[{"endpoint":"wss://ws-api-spot.kucoin.com/","encrypt":true,"protocol":"websocket","pingInterval":18000,"pingTimeout":10000}]}}"#;
+        let bullet: BulletResponse = serde_json::from_str(text).unwrap();
+        let fetched_at = Instant::now();
+
+        let schedule = bullet.data.instance_servers[0].schedule(fetched_at);
+        assert_eq!(schedule.ping_interval, Duration::from_secs(18));
+        assert_eq!(schedule.connect_timeout, Duration::from_secs(10));
+        // 23.5h after the token was issued
+        assert_eq!(schedule.refresh_at - fetched_at, Duration::from_secs(84_600));
+    }
+
+    #[test]
+    fn test_parse_ticker() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"type":"message","topic":"/market/ticker:BTC-USDT","subject":"trade.ticker","data":{"sequence":"1545896668986","price":"43250","size":"0.01","bestAsk":"43250.1","bestAskSize":"0.8","bestBid":"43250","bestBidSize":"2.5"}}"#;
+
+        let update = parse_message(text, &matcher).unwrap();
+        assert_eq!(update.symbol, "BTC/USDT");
+        assert_eq!(update.raw_symbol, "BTC-USDT");
+        assert_eq!(update.ask, Decimal::from_str("43250.1").unwrap());
+        assert_eq!(update.bid_size, Decimal::from_str("2.5").unwrap());
+
+        assert!(parse_message(r#"{"id":"ping","type":"pong"}"#, &matcher).is_none());
+    }
+
+    /// `/api/v2/symbols` serving `total` USDT pairs `page_size` at a time, recording every query
+    async fn mock_symbols(total: usize, page_size: usize) -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        use axum::extract::RawQuery;
+        use axum::routing::get;
+
+        let queries = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = queries.clone();
+        let app = axum::Router::new().route(
+            SYMBOLS_PATH,
+            get(move |RawQuery(query): RawQuery| async move {
+                let query = query.unwrap_or_default();
+                seen.lock().unwrap().push(query.clone());
+                let page: usize = query
+                    .split('&')
+                    .find_map(|p| p.strip_prefix("currentPage="))
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or(1);
+                let items: Vec<_> = (0..total)
+                    .skip((page - 1) * page_size)
+                    .take(page_size)
+                    .map(|i| {
+                        serde_json::json!({
+                            "symbol": format!("SYM{}-USDT", i),
+                            "quoteCurrency": "USDT",
+                            "enableTrading": true
+                        })
+                    })
+                    .collect();
+                axum::Json(serde_json::json!({
+                    "code": "200000",
+                    "data": {
+                        "currentPage": page,
+                        "pageSize": page_size,
+                        "totalNum": total,
+                        "totalPage": total.div_ceil(page_size),
+                        "items": items
+                    }
+                }))
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, queries)
+    }
+
+    #[tokio::test]
+    async fn test_symbols_follow_every_page() {
+        let (url, queries) = mock_symbols(250, PAGE_SIZE).await;
+
+        let symbols = fetch_symbols(&url).await.unwrap();
+        assert_eq!(symbols.len(), 250);
+        assert_eq!(symbols[249].symbol, "SYM249-USDT");
+
+        // Paging parameters go out on every request, the first one included
+        assert_eq!(
+            *queries.lock().unwrap(),
+            [
+                "currentPage=1&pageSize=100",
+                "currentPage=2&pageSize=100",
+                "currentPage=3&pageSize=100",
+            ]
+        );
+
+        // The symbol cap applies to the combined pages
+        let vars = [("MAX_SYMBOLS_PER_EXCHANGE", "150")];
+        let config = Config::from_lookup(|key| {
+            vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        })
+        .unwrap();
+        let loaded = load_symbols(&url, &config, &TickerMatcher::new()).await.unwrap();
+        assert_eq!(loaded.len(), 150);
+    }
 }
@@ -2,21 +2,27 @@
 // Docs: https://mexcdevelop.github.io/apidocs/spot_v3_en/
 
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::ws_util::{fetch_with_retry, run_shards, Ping, Session};
+use super::{publish, subscription_batches, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
 const WS_URL: &str = "wss://wbs.mexc.com/ws";
-const REST_URL: &str = "https://api.mexc.com/api/v3/exchangeInfo";
+const REST_URL: &str = "https://api.mexc.com";
+const EXCHANGE_INFO_PATH: &str = "/api/v3/exchangeInfo";
+/// MEXC serves at most 30 streams per connection and ignores the rest
+const MAX_STREAMS_PER_CONNECTION: usize = 30;
 
 #[derive(Debug, Deserialize)]
 struct ExchangeInfo {
@@ -41,7 +47,6 @@ struct SubscribeMessage {
 struct WsMessage {
     c: Option<String>,  // channel
     d: Option<TickerData>,
-    s: Option<String>,  // symbol (for some message types)
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,49 +61,72 @@ struct TickerData {
     bid_qty: Option<String>,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "MEXC connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Mexc;
+
+#[async_trait]
+impl Exchange for Mexc {
+    fn name(&self) -> &'static str {
+        "mexc"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        // Symbols are fetched once; each shard then reconnects on its own
+        let Some(symbols) = fetch_with_retry("MEXC", &shutdown, || load_symbols(&config, &matcher)).await else {
+            return Ok(());
+        };
+        let shards: Vec<Vec<String>> = subscription_batches(&symbols, MAX_STREAMS_PER_CONNECTION)
+            .map(<[String]>::to_vec)
+            .collect();
+
+        run_shards("mexc", "MEXC", &shutdown, &shards, |label, symbols| {
+            run_shard(label, symbols, &config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
-async fn run_connection(
-    _config: &Config,
-    matcher: &TickerMatcher,
-    price_tx: &broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    // Fetch available symbols
-    let symbols = fetch_symbols().await?;
+/// Enabled pairs in a subscribed quote (see `Config::subscribes_to_quote`), registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let symbols = fetch_symbols(&config.rest_url("mexc", REST_URL)).await?;
     info!(count = symbols.len(), "MEXC: fetched symbols");
 
-    // Filter USDT pairs
-    let usdt_symbols: Vec<_> = symbols
-        .iter()
-        .filter(|s| s.status == "ENABLED" && s.quote_asset == "USDT")
-        .take(100)
+    // USDT pairs, plus equivalent stablecoins and cross pairs when enabled
+    let subscribed_symbols: Vec<String> = symbols
+        .into_iter()
+        .filter(|s| s.status == "ENABLED" && config.subscribes_to_quote(&s.quote_asset))
+        .take(config.symbol_limit())
+        .map(|s| s.symbol)
         .collect();
 
     // Register symbols with matcher
-    for sym in &usdt_symbols {
-        matcher.register("mexc", &sym.symbol);
+    for symbol in &subscribed_symbols {
+        matcher.register("mexc", symbol);
     }
 
-    info!(symbols = usdt_symbols.len(), "MEXC: connecting to websocket");
+    Ok(subscribed_symbols)
+}
 
-    let (ws_stream, _) = connect_async(WS_URL).await?;
-    let (mut write, mut read) = ws_stream.split();
+/// One connection carrying `symbols` (at most `MAX_STREAMS_PER_CONNECTION`)
+async fn run_shard(
+    label: String,
+    symbols: &[String],
+    config: &Config,
+    matcher: &TickerMatcher,
+    price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    info!(symbols = symbols.len(), "{}: connecting to websocket", label);
 
     // Subscribe to book ticker streams
-    let params: Vec<String> = usdt_symbols
+    let params: Vec<String> = symbols
         .iter()
-        .map(|s| format!("spot@public.bookTicker.v3.api@{}", s.symbol))
+        .map(|symbol| format!("spot@public.bookTicker.v3.api@{}", symbol))
         .collect();
 
     let subscribe = SubscribeMessage {
@@ -106,91 +134,64 @@ async fn run_connection(
         params,
     };
 
-    let sub_msg = serde_json::to_string(&subscribe)?;
-    write.send(Message::Text(sub_msg)).await?;
-
-    info!("MEXC: subscribed to book ticker channels");
-
-    // Ping task
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(20)).await;
-            if write.send(Message::Ping(vec![])).await.is_err() {
-                break;
-            }
-        }
-    });
-
-    // Read messages
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    if let (Some(channel), Some(data)) = (ws_msg.c, ws_msg.d) {
-                        // Extract symbol from channel: spot@public.bookTicker.v3.api@BTCUSDT
-                        if let Some(symbol) = channel.split('@').last() {
-                            let bid = data
-                                .bid_price
-                                .as_ref()
-                                .and_then(|b| Decimal::from_str(b).ok())
-                                .unwrap_or_default();
-                            let ask = data
-                                .ask_price
-                                .as_ref()
-                                .and_then(|a| Decimal::from_str(a).ok())
-                                .unwrap_or_default();
-
-                            if bid.is_zero() || ask.is_zero() {
-                                continue;
-                            }
-
-                            if let Some(normalized) = matcher.get_normalized("mexc", symbol) {
-                                let update = PriceUpdate {
-                                    exchange: "mexc".to_string(),
-                                    symbol: normalized,
-                                    raw_symbol: symbol.to_string(),
-                                    bid,
-                                    ask,
-                                    bid_size: data
-                                        .bid_qty
-                                        .as_ref()
-                                        .and_then(|q| Decimal::from_str(q).ok())
-                                        .unwrap_or_default(),
-                                    ask_size: data
-                                        .ask_qty
-                                        .as_ref()
-                                        .and_then(|q| Decimal::from_str(q).ok())
-                                        .unwrap_or_default(),
-                                    timestamp: chrono::Utc::now().timestamp_millis(),
-                                };
-
-                                let _ = price_tx.send(update);
-                            }
-                        }
-                    }
+    // Symbols that actually streamed on this connection, to catch silently dropped subscriptions
+    let mut live = HashSet::new();
+    let result = Session::new("mexc", label.as_str(), config.ws_url("mexc", WS_URL))
+        .subscribe(vec![serde_json::to_string(&subscribe)?])
+        .ping(Ping::Frame(Duration::from_secs(20)))
+        .run(shutdown, |text| {
+            if let Some(update) = parse_message(text, matcher) {
+                if live.insert(update.raw_symbol.clone()) && live.len() == symbols.len() {
+                    info!(symbols = live.len(), "{}: every symbol is streaming", label);
                 }
+                publish(price_tx, update);
             }
-            Ok(Message::Pong(_)) => {}
-            Ok(Message::Close(_)) => {
-                warn!("MEXC: connection closed by server");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "MEXC: websocket error");
-                break;
-            }
-            _ => {}
-        }
+            None
+        })
+        .await;
+
+    info!(live = live.len(), subscribed = symbols.len(), "{}: symbols that produced updates", label);
+    result
+}
+
+/// `bookTicker` push -> update, for symbols registered at startup
+fn parse_message(text: &str, matcher: &TickerMatcher) -> Option<PriceUpdate> {
+    let ws_msg = serde_json::from_str::<WsMessage>(text).ok()?;
+    let (channel, data) = (ws_msg.c?, ws_msg.d?);
+
+    // Extract symbol from channel: spot@public.bookTicker.v3.api@BTCUSDT
+    let symbol = channel.split('@').next_back()?;
+    let decimal = |raw: &Option<String>| {
+        raw.as_ref()
+            .and_then(|v| Decimal::from_str(v).ok())
+            .unwrap_or_default()
+    };
+
+    let bid = decimal(&data.bid_price);
+    let ask = decimal(&data.ask_price);
+
+    if bid.is_zero() || ask.is_zero() {
+        return None;
     }
 
-    ping_handle.abort();
-    Ok(())
+    Some(PriceUpdate {
+        exchange: "mexc".to_string(),
+        symbol: matcher.get_normalized("mexc", symbol)?,
+        raw_symbol: symbol.to_string(),
+        bid,
+        ask,
+        bid_size: decimal(&data.bid_qty),
+        ask_size: decimal(&data.ask_qty),
+        bids: Vec::new(),
+        asks: Vec::new(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    })
 }
 
-async fn fetch_symbols() -> Result<Vec<SymbolInfo>> {
+async fn fetch_symbols(rest_url: &str) -> Result<Vec<SymbolInfo>> {
     let client = reqwest::Client::new();
     let response = client
-        .get(REST_URL)
+        .get(format!("{}{}", rest_url, EXCHANGE_INFO_PATH))
         .header("User-Agent", "arbscanner/1.0")
         .send()
         .await?;
@@ -198,3 +199,34 @@ async fn fetch_symbols() -> Result<Vec<SymbolInfo>> {
     let exchange_info: ExchangeInfo = response.json().await?;
     Ok(exchange_info.symbols)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shards_stay_under_stream_limit() {
+        let symbols: Vec<String> = (0..95).map(|i| format!("SYM{}USDT", i)).collect();
+        let shards: Vec<&[String]> = subscription_batches(&symbols, MAX_STREAMS_PER_CONNECTION).collect();
+
+        assert_eq!(shards.len(), 4);
+        assert!(shards.iter().all(|shard| shard.len() <= MAX_STREAMS_PER_CONNECTION));
+        let distinct: HashSet<&String> = shards.iter().flat_map(|shard| shard.iter()).collect();
+        assert_eq!(distinct.len(), symbols.len());
+    }
+
+    #[test]
+    fn test_parse_book_ticker() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"c":"spot@public.bookTicker.v3.api@BTCUSDT","d":{"A":"0.4","B":"1.5","a":"43250.2","b":"43250.1"},"s":"BTCUSDT","t":1700000000000}"#;
+
+        // Only symbols registered at startup are published
+        assert!(parse_message(text, &matcher).is_none());
+
+        matcher.register("mexc", "BTCUSDT");
+        let update = parse_message(text, &matcher).unwrap();
+        assert_eq!(update.symbol, "BTC/USDT");
+        assert_eq!(update.bid, Decimal::from_str("43250.1").unwrap());
+        assert_eq!(update.ask_size, Decimal::from_str("0.4").unwrap());
+    }
+}
@@ -8,16 +8,26 @@ mod mexc;
 mod htx;
 mod bitget;
 mod coinbase;
+mod bitfinex;
+mod symbols;
+mod ws_util;
 
 use anyhow::Result;
+use async_trait::async_trait;
+use rand::Rng;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
+use crate::metrics::metrics;
+use crate::replay::Recorder;
 
 /// Price update from any exchange
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,13 +39,152 @@ pub struct PriceUpdate {
     pub ask: Decimal,             // Best ask
     pub bid_size: Decimal,
     pub ask_size: Decimal,
+    /// Bid levels (price, size), best first; empty when the exchange only sends best bid/ask
+    #[serde(default)]
+    pub bids: Vec<(Decimal, Decimal)>,
+    /// Ask levels (price, size), best first; empty when the exchange only sends best bid/ask
+    #[serde(default)]
+    pub asks: Vec<(Decimal, Decimal)>,
     pub timestamp: i64,
 }
 
 impl PriceUpdate {
+    #[allow(dead_code)]
     pub fn mid_price(&self) -> Decimal {
         (self.bid + self.ask) / Decimal::from(2)
     }
+    
+    /// Bid depth, falling back to the best bid when the exchange sent no levels
+    pub fn bid_levels(&self) -> Vec<(Decimal, Decimal)> {
+        if self.bids.is_empty() {
+            vec![(self.bid, self.bid_size)]
+        } else {
+            self.bids.clone()
+        }
+    }
+    
+    /// Ask depth, falling back to the best ask when the exchange sent no levels
+    pub fn ask_levels(&self) -> Vec<(Decimal, Decimal)> {
+        if self.asks.is_empty() {
+            vec![(self.ask, self.ask_size)]
+        } else {
+            self.asks.clone()
+        }
+    }
+}
+
+/// Parse `[price, size, ...]` string levels (Binance/OKX book format), keeping the best `depth`
+pub fn parse_levels(raw: &[Vec<String>], depth: usize) -> Vec<(Decimal, Decimal)> {
+    raw.iter()
+        .filter_map(|level| {
+            let price = Decimal::from_str(level.first()?).ok()?;
+            let size = Decimal::from_str(level.get(1)?).ok()?;
+            Some((price, size))
+        })
+        .take(depth.max(1))
+        .collect()
+}
+
+/// Split subscription args into batches of at most `batch_size` (exchanges cap args per message).
+/// Every item lands in exactly one batch, in order, no matter how long the list grows.
+pub fn subscription_batches<T>(items: &[T], batch_size: usize) -> std::slice::Chunks<'_, T> {
+    items.chunks(batch_size.max(1))
+}
+
+/// Normalize an exchange timestamp to epoch milliseconds.
+/// Exchanges disagree on units, so the unit is inferred from magnitude (s, ms, us or ns).
+/// `unsigned_abs` rather than `abs`, which overflows on `i64::MIN`.
+pub fn normalize_timestamp_ms(ts: i64) -> i64 {
+    match ts.unsigned_abs() {
+        0..100_000_000_000 => ts * 1_000,
+        100_000_000_000..100_000_000_000_000 => ts,
+        100_000_000_000_000..100_000_000_000_000_000 => ts / 1_000,
+        _ => ts / 1_000_000,
+    }
+}
+
+/// Parse a string timestamp (integer or fractional seconds) into epoch milliseconds
+pub fn parse_timestamp_ms(raw: &str) -> Option<i64> {
+    let raw = raw.trim();
+    if let Ok(ts) = raw.parse::<i64>() {
+        return Some(normalize_timestamp_ms(ts));
+    }
+
+    // Fractional values are seconds ("1700000000.123")
+    let seconds = Decimal::from_str(raw).ok()?;
+    seconds.checked_mul(Decimal::from(1_000))?.trunc().to_string().parse().ok()
+}
+
+/// First reconnect delay; doubles on every consecutive failure
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+/// Upper bound for the reconnect delay
+const BACKOFF_CAP: Duration = Duration::from_secs(60);
+/// Fraction of the delay that may be shaved off at random so connectors don't retry in lockstep
+const BACKOFF_JITTER: f64 = 0.2;
+/// A connection that lived at least this long resets the reconnect attempt counter
+pub const STABLE_CONNECTION: Duration = Duration::from_secs(30);
+
+/// Exponential reconnect delay for the `attempt`-th consecutive failure, before jitter
+fn backoff_base(attempt: u32) -> Duration {
+    BACKOFF_BASE
+        .checked_mul(1 << attempt.min(16))
+        .map_or(BACKOFF_CAP, |delay| delay.min(BACKOFF_CAP))
+}
+
+/// Delay before reconnect `attempt` (0-based): 1s, 2s, 4s… capped at 60s, minus up to 20% jitter
+pub fn reconnect_backoff(attempt: u32) -> Duration {
+    let jitter = rand::thread_rng().gen_range(0.0..=BACKOFF_JITTER);
+    backoff_base(attempt).mul_f64(1.0 - jitter)
+}
+
+/// Hand a connector's update to the scanner, counting it per exchange
+pub fn publish(price_tx: &broadcast::Sender<PriceUpdate>, update: PriceUpdate) {
+    let now = chrono::Utc::now().timestamp_millis();
+    metrics().record_feed_timing(&update.exchange, now, None);
+    metrics().record_message(&update.exchange);
+    let _ = price_tx.send(update);
+}
+
+/// Like `publish`, for updates stamped with the exchange's own timestamp,
+/// so the delay between the exchange and us is tracked as well
+pub fn publish_stamped(price_tx: &broadcast::Sender<PriceUpdate>, update: PriceUpdate) {
+    let now = chrono::Utc::now().timestamp_millis();
+    metrics().record_feed_timing(&update.exchange, now, Some(update.timestamp));
+    metrics().record_message(&update.exchange);
+    let _ = price_tx.send(update);
+}
+
+/// A venue the manager can connect to
+#[async_trait]
+pub trait Exchange: Send + Sync {
+    /// Exchange id used in config, metrics and `PriceUpdate::exchange`
+    fn name(&self) -> &'static str;
+    
+    /// Stream prices into `price_tx`, reconnecting as needed, until `shutdown` fires
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()>;
+}
+
+/// Every supported connector; adding an exchange means adding it here and to `KNOWN_EXCHANGES`
+fn all_exchanges() -> Vec<Box<dyn Exchange>> {
+    vec![
+        Box::new(binance::Binance),
+        Box::new(bybit::Bybit),
+        Box::new(okx::Okx),
+        Box::new(kraken::Kraken),
+        Box::new(kucoin::Kucoin),
+        Box::new(gate::Gate),
+        Box::new(mexc::Mexc),
+        Box::new(htx::Htx),
+        Box::new(bitget::Bitget),
+        Box::new(coinbase::Coinbase),
+        Box::new(bitfinex::Bitfinex),
+    ]
 }
 
 /// Manages all exchange connections
@@ -43,6 +192,8 @@ pub struct ExchangeManager {
     config: Arc<Config>,
     matcher: Arc<TickerMatcher>,
     price_tx: broadcast::Sender<PriceUpdate>,
+    exchanges: Vec<Box<dyn Exchange>>,
+    recorder: Option<Recorder>,
 }
 
 impl ExchangeManager {
@@ -51,110 +202,54 @@ impl ExchangeManager {
         matcher: Arc<TickerMatcher>,
         price_tx: broadcast::Sender<PriceUpdate>,
     ) -> Self {
+        let exchanges = all_exchanges()
+            .into_iter()
+            .filter(|exchange| config.is_exchange_enabled(exchange.name()))
+            .collect();
+        
         Self {
             config,
             matcher,
             price_tx,
+            exchanges,
+            recorder: None,
         }
     }
     
-    pub async fn run(self) -> Result<()> {
+    /// Tee every update the connectors publish into `recorder`
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+    
+    /// Run every enabled connector until `shutdown` fires and they have all closed their sockets
+    pub async fn run(self, shutdown: CancellationToken) -> Result<()> {
         let mut handles = Vec::new();
         
-        // Spawn each enabled exchange
-        if self.config.is_exchange_enabled("binance") {
-            let h = tokio::spawn(binance::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("binance", h));
-        }
+        // Subscribe before any connector starts so the recording misses nothing
+        let recording = shutdown.child_token();
+        let recorder = self.recorder.map(|recorder| {
+            tokio::spawn(recorder.run(self.price_tx.subscribe(), recording.clone()))
+        });
         
-        if self.config.is_exchange_enabled("bybit") {
-            let h = tokio::spawn(bybit::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("bybit", h));
+        // Every enabled exchange reports a connection state, even before its first connect
+        for exchange in &self.exchanges {
+            metrics().set_connected(exchange.name(), false);
         }
         
-        if self.config.is_exchange_enabled("okx") {
-            let h = tokio::spawn(okx::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("okx", h));
-        }
-        
-        if self.config.is_exchange_enabled("kraken") {
-            let h = tokio::spawn(kraken::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("kraken", h));
-        }
-        
-        if self.config.is_exchange_enabled("kucoin") {
-            let h = tokio::spawn(kucoin::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("kucoin", h));
-        }
-        
-        if self.config.is_exchange_enabled("gate") {
-            let h = tokio::spawn(gate::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("gate", h));
-        }
-        
-        if self.config.is_exchange_enabled("mexc") {
-            let h = tokio::spawn(mexc::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("mexc", h));
-        }
-        
-        if self.config.is_exchange_enabled("htx") {
-            let h = tokio::spawn(htx::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("htx", h));
-        }
-        
-        if self.config.is_exchange_enabled("bitget") {
-            let h = tokio::spawn(bitget::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("bitget", h));
-        }
-        
-        if self.config.is_exchange_enabled("coinbase") {
-            let h = tokio::spawn(coinbase::connect(
-                self.config.clone(),
-                self.matcher.clone(),
-                self.price_tx.clone(),
-            ));
-            handles.push(("coinbase", h));
+        for exchange in self.exchanges {
+            let name = exchange.name();
+            let config = self.config.clone();
+            let matcher = self.matcher.clone();
+            let price_tx = self.price_tx.clone();
+            let shutdown = shutdown.clone();
+            let h = tokio::spawn(async move { exchange.connect(config, matcher, price_tx, shutdown).await });
+            handles.push((name, h));
         }
         
         info!(count = handles.len(), "Started exchange connections");
         
-        // Wait for all to complete (they shouldn't unless error)
+        // Wait for all to complete (they only return on shutdown or a fatal error)
         for (name, handle) in handles {
             match handle.await {
                 Ok(Ok(())) => info!(exchange = name, "Exchange stopped gracefully"),
@@ -163,12 +258,132 @@ impl ExchangeManager {
             }
         }
         
+        recording.cancel();
+        if let Some(recorder) = recorder {
+            match recorder.await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!(error = ?e, "Recorder error"),
+                Err(e) => warn!(error = ?e, "Recorder task panicked"),
+            }
+        }
+        
         Ok(())
     }
 }
 
-/// Common trait for exchange implementations
-pub trait Exchange {
-    fn name(&self) -> &'static str;
-    fn ws_url(&self) -> &str;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_subscription_batches_cover_whole_list() {
+        let symbols: Vec<String> = (0..2503).map(|i| format!("SYM{}USDT", i)).collect();
+
+        // OKX, Bybit, Bitget, Gate and KuCoin batch sizes
+        for batch_size in [50, 10, 30, 20, 100] {
+            let batches: Vec<_> = subscription_batches(&symbols, batch_size).collect();
+
+            assert!(batches.iter().all(|b| !b.is_empty() && b.len() <= batch_size));
+            assert_eq!(batches.len(), symbols.len().div_ceil(batch_size));
+
+            let flattened: Vec<String> = batches.concat();
+            assert_eq!(flattened, symbols);
+        }
+    }
+
+    #[test]
+    fn test_parse_levels() {
+        let raw = vec![
+            vec!["100.5".to_string(), "2".to_string()],
+            vec!["100.4".to_string(), "3.5".to_string(), "0".to_string(), "4".to_string()],
+            vec!["bad".to_string(), "1".to_string()],
+            vec!["100.2".to_string()],
+            vec!["100.1".to_string(), "1".to_string()],
+        ];
+
+        let levels = parse_levels(&raw, 5);
+        assert_eq!(levels.len(), 3);
+        assert_eq!(levels[0], (Decimal::from_str("100.5").unwrap(), Decimal::from(2)));
+        assert_eq!(levels[1], (Decimal::from_str("100.4").unwrap(), Decimal::from_str("3.5").unwrap()));
+
+        assert_eq!(parse_levels(&raw, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_normalize_timestamp_units() {
+        let ms = 1_700_000_000_123;
+
+        assert_eq!(normalize_timestamp_ms(1_700_000_000), 1_700_000_000_000);
+        assert_eq!(normalize_timestamp_ms(ms), ms);
+        assert_eq!(normalize_timestamp_ms(ms * 1_000), ms);
+        assert_eq!(normalize_timestamp_ms(ms * 1_000_000), ms);
+
+        // Garbage from the wire must not panic
+        assert_eq!(normalize_timestamp_ms(i64::MIN), i64::MIN / 1_000_000);
+        assert_eq!(normalize_timestamp_ms(i64::MAX), i64::MAX / 1_000_000);
+        assert_eq!(normalize_timestamp_ms(-1_700_000_000), -1_700_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_timestamp_strings() {
+        // Bitget sends milliseconds as a string
+        assert_eq!(parse_timestamp_ms("1700000000123"), Some(1_700_000_000_123));
+        assert_eq!(parse_timestamp_ms("1700000000"), Some(1_700_000_000_000));
+        assert_eq!(parse_timestamp_ms("1700000000.123456"), Some(1_700_000_000_123));
+        assert_eq!(parse_timestamp_ms(""), None);
+        assert_eq!(parse_timestamp_ms("soon"), None);
+        assert_eq!(parse_timestamp_ms("-9223372036854775808"), Some(i64::MIN / 1_000_000));
+        assert_eq!(parse_timestamp_ms("79228162514264337593543950335"), None);
+    }
+
+    #[test]
+    fn test_backoff_is_monotonic_up_to_cap() {
+        let delays: Vec<Duration> = (0..20).map(backoff_base).collect();
+
+        assert_eq!(delays[0], Duration::from_secs(1));
+        assert_eq!(delays[1], Duration::from_secs(2));
+        assert_eq!(delays[2], Duration::from_secs(4));
+        assert!(delays.windows(2).all(|w| w[0] <= w[1]));
+        assert!(delays.iter().all(|d| *d <= BACKOFF_CAP));
+        assert_eq!(delays[6], BACKOFF_CAP);
+        assert_eq!(backoff_base(u32::MAX), BACKOFF_CAP);
+    }
+
+    #[test]
+    fn test_backoff_jitter_within_bounds() {
+        for attempt in 0..10 {
+            let base = backoff_base(attempt);
+            for _ in 0..100 {
+                let delay = reconnect_backoff(attempt);
+                assert!(delay <= base);
+                assert!(delay >= base.mul_f64(1.0 - BACKOFF_JITTER));
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_known_exchange_has_a_connector() {
+        let names: Vec<&str> = all_exchanges().iter().map(|e| e.name()).collect();
+        assert_eq!(names, crate::config::KNOWN_EXCHANGES);
+    }
+
+    #[test]
+    fn test_manager_only_keeps_enabled_exchanges() {
+        let config = Config::from_lookup(|key| {
+            (key == "ENABLED_EXCHANGES").then(|| "okx, Kraken".to_string())
+        })
+        .unwrap();
+        let (price_tx, _) = broadcast::channel(1);
+        let manager = ExchangeManager::new(Arc::new(config), Arc::new(TickerMatcher::new()), price_tx);
+
+        let names: Vec<&str> = manager.exchanges.iter().map(|e| e.name()).collect();
+        assert_eq!(names, ["okx", "kraken"]);
+    }
+
+    #[test]
+    fn test_subscription_batches_zero_size() {
+        let symbols = vec!["BTCUSDT", "ETHUSDT"];
+        assert_eq!(subscription_batches(&symbols, 0).count(), 2);
+    }
 }
@@ -1,19 +1,28 @@
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
-use super::PriceUpdate;
+use super::symbols::cached_symbols;
+use super::ws_util::{classify_text, reconnect_loop, Frame, Ping, Session};
+use super::{parse_levels, publish, subscription_batches, Exchange, PriceUpdate};
 use crate::config::Config;
 use crate::matcher::TickerMatcher;
 
 const WS_URL: &str = "wss://ws.okx.com:8443/ws/v5/public";
-const REST_URL: &str = "https://www.okx.com/api/v5/public/instruments?instType=SPOT";
+const REST_URL: &str = "https://www.okx.com";
+const INSTRUMENTS_PATH: &str = "/api/v5/public/instruments?instType=SPOT";
+/// OKX list endpoints page at this many rows; a full page means there may be more
+const PAGE_LIMIT: usize = 100;
+/// Max args per subscribe message
+const SUBSCRIBE_BATCH_SIZE: usize = 50;
 
 #[derive(Debug, Deserialize)]
 struct InstrumentsResponse {
@@ -44,15 +53,23 @@ struct SubscribeArg {
 
 #[derive(Debug, Deserialize)]
 struct WsMessage {
-    arg: Option<WsArg>,
-    data: Option<Vec<TickerData>>,
+    data: Option<Vec<PushData>>,
 }
 
 #[derive(Debug, Deserialize)]
-struct WsArg {
-    channel: String,
+#[serde(untagged)]
+enum PushData {
+    Ticker(TickerData),
+    Book(BookData),
+}
+
+/// `books5` snapshot: levels are `[price, size, deprecated, order_count]`
+#[derive(Debug, Deserialize)]
+struct BookData {
     #[serde(rename = "instId")]
     inst_id: String,
+    bids: Vec<Vec<String>>,
+    asks: Vec<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -69,123 +86,197 @@ struct TickerData {
     ask_size: String,
 }
 
-pub async fn connect(
-    config: Arc<Config>,
-    matcher: Arc<TickerMatcher>,
-    price_tx: broadcast::Sender<PriceUpdate>,
-) -> Result<()> {
-    loop {
-        if let Err(e) = run_connection(&config, &matcher, &price_tx).await {
-            error!(error = ?e, "OKX connection error, reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+pub struct Okx;
+
+#[async_trait]
+impl Exchange for Okx {
+    fn name(&self) -> &'static str {
+        "okx"
+    }
+
+    async fn connect(
+        &self,
+        config: Arc<Config>,
+        matcher: Arc<TickerMatcher>,
+        price_tx: broadcast::Sender<PriceUpdate>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        reconnect_loop("okx", "OKX", &shutdown, || {
+            run_connection(&config, &matcher, &price_tx, &shutdown)
+        })
+        .await
     }
 }
 
 async fn run_connection(
-    _config: &Config,
+    config: &Config,
     matcher: &TickerMatcher,
     price_tx: &broadcast::Sender<PriceUpdate>,
+    shutdown: &CancellationToken,
 ) -> Result<()> {
-    let symbols = fetch_symbols().await?;
-    info!(count = symbols.len(), "OKX: fetched symbols");
+    let inst_ids = cached_symbols("okx", config.symbol_cache_ttl(), || load_symbols(config, matcher)).await?;
     
-    let usdt_symbols: Vec<_> = symbols
-        .iter()
-        .filter(|s| s.state == "live" && s.quote_ccy == "USDT")
-        .take(100)
+    // Subscribe in batches: 5-level books when tracking depth, otherwise plain tickers
+    let depth = config.orderbook_depth;
+    let channel = if depth > 1 { "books5" } else { "tickers" };
+    let args: Vec<SubscribeArg> = inst_ids
+        .into_iter()
+        .map(|inst_id| SubscribeArg {
+            channel: channel.to_string(),
+            inst_id,
+        })
         .collect();
     
-    for sym in &usdt_symbols {
-        matcher.register("okx", &sym.inst_id);
-    }
-    
-    let (ws_stream, _) = connect_async(WS_URL).await?;
-    let (mut write, mut read) = ws_stream.split();
-    
-    info!("OKX: connected");
+    let subscriptions = subscription_batches(&args, SUBSCRIBE_BATCH_SIZE)
+        .map(|chunk| {
+            serde_json::to_string(&SubscribeRequest {
+                op: "subscribe".to_string(),
+                args: chunk.to_vec(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
     
-    // Subscribe in batches
-    let args: Vec<SubscribeArg> = usdt_symbols
-        .iter()
-        .map(|s| SubscribeArg {
-            channel: "tickers".to_string(),
-            inst_id: s.inst_id.clone(),
+    Session::new("okx", "OKX", config.ws_url("okx", WS_URL))
+        .subscribe(subscriptions)
+        // OKX drops a connection idle for 30s, so we ping inside that window
+        .ping(Ping::Text("ping", Duration::from_secs(25)))
+        .run(shutdown, |text| match classify_text(text) {
+            Frame::Ping => Some("pong".to_string()),
+            Frame::Pong => None,
+            Frame::Data(text) => {
+                for update in parse_message(text, matcher, depth) {
+                    publish(price_tx, update);
+                }
+                None
+            }
         })
+        .await
+}
+
+/// Live instruments in a subscribed quote (see `Config::subscribes_to_quote`), registered with the matcher
+async fn load_symbols(config: &Config, matcher: &TickerMatcher) -> Result<Vec<String>> {
+    let symbols = fetch_symbols(&config.rest_url("okx", REST_URL)).await?;
+    info!(count = symbols.len(), "OKX: fetched symbols");
+    
+    let inst_ids: Vec<String> = symbols
+        .into_iter()
+        .filter(|s| s.state == "live" && config.subscribes_to_quote(&s.quote_ccy))
+        .take(config.symbol_limit())
+        .map(|s| s.inst_id)
         .collect();
     
-    for chunk in args.chunks(50) {
-        let sub = SubscribeRequest {
-            op: "subscribe".to_string(),
-            args: chunk.to_vec(),
-        };
-        write.send(Message::Text(serde_json::to_string(&sub)?)).await?;
+    for inst_id in &inst_ids {
+        matcher.register("okx", inst_id);
     }
     
-    // Ping task
-    let write = Arc::new(tokio::sync::Mutex::new(write));
-    let write_clone = write.clone();
-    let ping_handle = tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(25)).await;
-            if write_clone.lock().await.send(Message::Text("ping".to_string())).await.is_err() {
-                break;
-            }
-        }
-    });
+    Ok(inst_ids)
+}
+
+/// `tickers` / `books5` push -> updates (one per instrument in `data`)
+fn parse_message(text: &str, matcher: &TickerMatcher, depth: usize) -> Vec<PriceUpdate> {
+    let Some(data_vec) = serde_json::from_str::<WsMessage>(text).ok().and_then(|m| m.data) else {
+        return Vec::new();
+    };
     
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if text == "pong" {
-                    continue;
-                }
-                
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    if let Some(data_vec) = ws_msg.data {
-                        for data in data_vec {
-                            let bid = Decimal::from_str(&data.bid_price).unwrap_or_default();
-                            let ask = Decimal::from_str(&data.ask_price).unwrap_or_default();
-                            
-                            if bid.is_zero() || ask.is_zero() {
-                                continue;
-                            }
-                            
-                            let normalized = matcher.register("okx", &data.inst_id);
-                            
-                            let update = PriceUpdate {
-                                exchange: "okx".to_string(),
-                                symbol: normalized,
-                                raw_symbol: data.inst_id,
-                                bid,
-                                ask,
-                                bid_size: Decimal::from_str(&data.bid_size).unwrap_or_default(),
-                                ask_size: Decimal::from_str(&data.ask_size).unwrap_or_default(),
-                                timestamp: chrono::Utc::now().timestamp_millis(),
-                            };
-                            
-                            let _ = price_tx.send(update);
-                        }
+    data_vec
+        .into_iter()
+        .filter_map(|data| {
+            let update = match data {
+                PushData::Ticker(data) => PriceUpdate {
+                    exchange: "okx".to_string(),
+                    symbol: matcher.register("okx", &data.inst_id),
+                    raw_symbol: data.inst_id,
+                    bid: Decimal::from_str(&data.bid_price).unwrap_or_default(),
+                    ask: Decimal::from_str(&data.ask_price).unwrap_or_default(),
+                    bid_size: Decimal::from_str(&data.bid_size).unwrap_or_default(),
+                    ask_size: Decimal::from_str(&data.ask_size).unwrap_or_default(),
+                    bids: Vec::new(),
+                    asks: Vec::new(),
+                    timestamp: chrono::Utc::now().timestamp_millis(),
+                },
+                PushData::Book(data) => {
+                    let bids = parse_levels(&data.bids, depth);
+                    let asks = parse_levels(&data.asks, depth);
+                    let (&(bid, bid_size), &(ask, ask_size)) = (bids.first()?, asks.first()?);
+                    
+                    PriceUpdate {
+                        exchange: "okx".to_string(),
+                        symbol: matcher.register("okx", &data.inst_id),
+                        raw_symbol: data.inst_id,
+                        bid,
+                        ask,
+                        bid_size,
+                        ask_size,
+                        bids,
+                        asks,
+                        timestamp: chrono::Utc::now().timestamp_millis(),
                     }
                 }
+            };
+            
+            (!update.bid.is_zero() && !update.ask.is_zero()).then_some(update)
+        })
+        .collect()
+}
+
+async fn fetch_symbols(rest_url: &str) -> Result<Vec<Instrument>> {
+    let client = reqwest::Client::new();
+    let mut instruments: Vec<Instrument> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut after: Option<String> = None;
+
+    loop {
+        let mut url = format!("{}{}", rest_url, INSTRUMENTS_PATH);
+        if let Some(cursor) = &after {
+            url.push_str(&format!("&after={}&limit={}", cursor, PAGE_LIMIT));
+        }
+
+        let resp: InstrumentsResponse = client.get(&url).send().await?.json().await?;
+        let page_len = resp.data.len();
+        let mut new_rows = 0;
+        for instrument in resp.data {
+            if seen.insert(instrument.inst_id.clone()) {
+                instruments.push(instrument);
+                new_rows += 1;
             }
-            Ok(Message::Close(_)) => {
-                warn!("OKX: connection closed");
-                break;
-            }
-            Err(e) => {
-                error!(error = ?e, "OKX: websocket error");
-                break;
-            }
-            _ => {}
         }
+
+        // Anything but a full page is the last one; no new rows means the cursor was ignored
+        if page_len != PAGE_LIMIT || new_rows == 0 {
+            break;
+        }
+        after = instruments.last().map(|i| i.inst_id.clone());
     }
-    
-    ping_handle.abort();
-    Ok(())
+
+    Ok(instruments)
 }
 
-async fn fetch_symbols() -> Result<Vec<Instrument>> {
-    let resp: InstrumentsResponse = reqwest::get(REST_URL).await?.json().await?;
-    Ok(resp.data)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tickers() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"arg":{"channel":"tickers","instId":"BTC-USDT"},"data":[{"instType":"SPOT","instId":"BTC-USDT","last":"43250","bidPx":"43249.9","bidSz":"0.5","askPx":"43250","askSz":"1.2","ts":"1700000000000"}]}"#;
+
+        let updates = parse_message(text, &matcher, 1);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].symbol, "BTC/USDT");
+        assert_eq!(updates[0].raw_symbol, "BTC-USDT");
+        assert_eq!(updates[0].bid, Decimal::from_str("43249.9").unwrap());
+
+        assert!(parse_message("pong", &matcher, 1).is_empty());
+    }
+
+    #[test]
+    fn test_parse_books5() {
+        let matcher = TickerMatcher::new();
+        let text = r#"{"arg":{"channel":"books5","instId":"ETH-USDT"},"data":[{"instId":"ETH-USDT","bids":[["2000.1","4","0","2"]],"asks":[["2000.2","1.5","0","1"],["2000.3","2","0","1"]],"ts":"1700000000000"}]}"#;
+
+        let updates = parse_message(text, &matcher, 5);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].ask, Decimal::from_str("2000.2").unwrap());
+        assert_eq!(updates[0].asks.len(), 2);
+    }
 }
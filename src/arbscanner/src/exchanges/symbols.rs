@@ -0,0 +1,136 @@
+// Symbol lists fetched over REST, cached per exchange so reconnects reuse them
+// instead of hitting the API on every retry during an outage.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+struct CachedSymbols {
+    symbols: Vec<String>,
+    fetched_at: Instant,
+}
+
+/// Exchange -> last fetched symbol list
+#[derive(Default)]
+pub struct SymbolCache {
+    entries: Mutex<HashMap<&'static str, CachedSymbols>>,
+}
+
+static SYMBOL_CACHE: OnceLock<SymbolCache> = OnceLock::new();
+
+/// Process-wide cache shared by the connectors
+pub fn symbol_cache() -> &'static SymbolCache {
+    SYMBOL_CACHE.get_or_init(SymbolCache::default)
+}
+
+impl SymbolCache {
+    /// Symbols for `exchange`: the cached list while it is younger than `ttl`, otherwise a fresh
+    /// one from `fetch`. If the refresh fails, the expired list is reused rather than failing.
+    pub async fn get_or_fetch<F, Fut>(&self, exchange: &'static str, ttl: Duration, fetch: F) -> Result<Vec<String>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<String>>>,
+    {
+        if let Some(symbols) = self.lookup(exchange, |entry| entry.fetched_at.elapsed() < ttl) {
+            debug!(exchange, count = symbols.len(), "Reusing cached symbols");
+            return Ok(symbols);
+        }
+
+        match fetch().await {
+            Ok(symbols) => {
+                self.entries.lock().unwrap().insert(
+                    exchange,
+                    CachedSymbols {
+                        symbols: symbols.clone(),
+                        fetched_at: Instant::now(),
+                    },
+                );
+                Ok(symbols)
+            }
+            Err(e) => match self.lookup(exchange, |_| true) {
+                Some(symbols) => {
+                    warn!(exchange, error = ?e, "Symbol refresh failed, reusing the expired list");
+                    Ok(symbols)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    fn lookup(&self, exchange: &str, usable: impl Fn(&CachedSymbols) -> bool) -> Option<Vec<String>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(exchange)
+            .filter(|entry| usable(entry))
+            .map(|entry| entry.symbols.clone())
+    }
+}
+
+/// `symbol_cache().get_or_fetch`
+pub async fn cached_symbols<F, Fut>(exchange: &'static str, ttl: Duration, fetch: F) -> Result<Vec<String>>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Vec<String>>>,
+{
+    symbol_cache().get_or_fetch(exchange, ttl, fetch).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn symbols(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_reuse_cached_symbols() {
+        let cache = SymbolCache::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(symbols(&["BTCUSDT", "ETHUSDT"]))
+        };
+
+        let ttl = Duration::from_secs(3600);
+        assert_eq!(cache.get_or_fetch("binance", ttl, fetch).await.unwrap().len(), 2);
+        assert_eq!(cache.get_or_fetch("binance", ttl, fetch).await.unwrap().len(), 2);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        // Keyed by exchange
+        cache.get_or_fetch("bybit", ttl, fetch).await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        // Expired entries are fetched again
+        cache.get_or_fetch("binance", Duration::ZERO, fetch).await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_falls_back_to_expired_list() {
+        let cache = SymbolCache::default();
+        cache
+            .get_or_fetch("okx", Duration::ZERO, || async { Ok(symbols(&["BTC-USDT"])) })
+            .await
+            .unwrap();
+
+        let fallback = cache
+            .get_or_fetch("okx", Duration::ZERO, || async { Err(anyhow!("rate limited")) })
+            .await
+            .unwrap();
+        assert_eq!(fallback, symbols(&["BTC-USDT"]));
+
+        // Nothing to fall back on
+        assert!(cache
+            .get_or_fetch("gate", Duration::ZERO, || async { Err(anyhow!("rate limited")) })
+            .await
+            .is_err());
+    }
+}
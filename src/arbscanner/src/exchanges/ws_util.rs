@@ -0,0 +1,411 @@
+// Shared websocket plumbing for the connectors: reconnect loop, subscribe, keepalive and read loop.
+// Each exchange only builds its subscribe messages and parses incoming frames.
+
+use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
+use futures_util::future::join_all;
+use futures_util::{SinkExt, StreamExt};
+use std::future::Future;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use super::{reconnect_backoff, STABLE_CONNECTION};
+use crate::metrics::metrics;
+
+/// Client keepalive sent on a fixed interval
+#[derive(Debug, Clone, Copy)]
+pub enum Ping {
+    /// Nothing to send; the server pings us (HTX)
+    None,
+    /// Websocket ping frame
+    Frame(Duration),
+    /// Plain text message, e.g. `"ping"`
+    Text(&'static str, Duration),
+    /// JSON message, built on every tick so it can carry a timestamp
+    Json(fn() -> serde_json::Value, Duration),
+}
+
+impl Ping {
+    fn interval(&self) -> Option<Duration> {
+        match self {
+            Ping::None => None,
+            Ping::Frame(interval) | Ping::Text(_, interval) | Ping::Json(_, interval) => Some(*interval),
+        }
+    }
+
+    fn message(&self) -> Option<Message> {
+        match self {
+            Ping::None => None,
+            Ping::Frame(_) => Some(Message::Ping(Vec::new())),
+            Ping::Text(text, _) => Some(Message::Text(text.to_string())),
+            Ping::Json(build, _) => Some(Message::Text(build().to_string())),
+        }
+    }
+}
+
+/// Incoming text payload, keepalive told apart from market data
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame<'a> {
+    /// The server checking on us; left unanswered, it drops the connection
+    Ping,
+    /// Answer to our own `Ping`
+    Pong,
+    Data(&'a str),
+}
+
+/// Plain-text keepalive, `"ping"` / `"pong"` (OKX, Bitget)
+pub fn classify_text(text: &str) -> Frame<'_> {
+    match text.trim() {
+        "ping" => Frame::Ping,
+        "pong" => Frame::Pong,
+        _ => Frame::Data(text),
+    }
+}
+
+/// One websocket connection: where to connect, what to subscribe to, how to stay alive
+pub struct Session {
+    exchange: &'static str,
+    label: String,
+    url: String,
+    subscriptions: Vec<String>,
+    ping: Ping,
+    gzip: bool,
+    pace: Option<(usize, Duration)>,
+    connect_timeout: Option<Duration>,
+    close_at: Option<Instant>,
+}
+
+impl Session {
+    /// `exchange` is the id used for metrics, `label` the display name used in logs
+    pub fn new(exchange: &'static str, label: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            exchange,
+            label: label.into(),
+            url: url.into(),
+            subscriptions: Vec::new(),
+            ping: Ping::None,
+            gzip: false,
+            pace: None,
+            connect_timeout: None,
+            close_at: None,
+        }
+    }
+
+    /// Text messages sent right after connecting
+    pub fn subscribe(mut self, messages: Vec<String>) -> Self {
+        self.subscriptions = messages;
+        self
+    }
+
+    pub fn ping(mut self, ping: Ping) -> Self {
+        self.ping = ping;
+        self
+    }
+
+    /// Binary frames are gzip-compressed text (HTX)
+    pub fn gzip(mut self) -> Self {
+        self.gzip = true;
+        self
+    }
+
+    /// Pause for `delay` after every `burst` subscribe messages
+    pub fn pace(mut self, burst: usize, delay: Duration) -> Self {
+        self.pace = Some((burst.max(1), delay));
+        self
+    }
+
+    /// Give up on the handshake after `timeout`
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Close the connection cleanly at `deadline` and return, so the caller reconnects
+    /// (e.g. when the URL carries a token that expires)
+    pub fn close_at(mut self, deadline: Instant) -> Self {
+        self.close_at = Some(deadline);
+        self
+    }
+
+    /// Connect, subscribe and feed every text payload to `on_message` until the socket closes,
+    /// `shutdown` fires or the `close_at` deadline passes; the last two send a close frame first.
+    /// `on_message` may return a reply to send back (application-level pongs).
+    pub async fn run<F>(self, shutdown: &CancellationToken, mut on_message: F) -> Result<()>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let (ws_stream, _) = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect_async(&self.url))
+                .await
+                .map_err(|_| anyhow!("{}: connect timed out after {:?}", self.label, timeout))??,
+            None => connect_async(&self.url).await?,
+        };
+        metrics().set_connected(self.exchange, true);
+        let (mut write, mut read) = ws_stream.split();
+
+        info!("{}: connected", self.label);
+
+        for (i, message) in self.subscriptions.into_iter().enumerate() {
+            write.send(Message::Text(message)).await?;
+
+            if let Some((burst, delay)) = self.pace {
+                if i % burst == burst - 1 {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+
+        // Ping task
+        let write = Arc::new(Mutex::new(write));
+        let ping_handle = self.ping.interval().map(|interval| {
+            let write = write.clone();
+            let ping = self.ping;
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let Some(message) = ping.message() else { break };
+                    if write.lock().await.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            })
+        });
+
+        let expired = async {
+            match self.close_at {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(expired);
+
+        loop {
+            let msg = tokio::select! {
+                msg = read.next() => msg,
+                _ = shutdown.cancelled() => {
+                    info!("{}: closing connection", self.label);
+                    close(&write, ping_handle.as_ref(), &self.label).await;
+                    break;
+                }
+                _ = &mut expired => {
+                    info!("{}: connection reached its deadline, reconnecting", self.label);
+                    close(&write, ping_handle.as_ref(), &self.label).await;
+                    break;
+                }
+            };
+            let Some(msg) = msg else { break };
+
+            let text = match msg {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Binary(data)) if self.gzip => {
+                    let mut text = String::new();
+                    if GzDecoder::new(&data[..]).read_to_string(&mut text).is_err() {
+                        continue;
+                    }
+                    text
+                }
+                Ok(Message::Close(_)) => {
+                    warn!("{}: connection closed", self.label);
+                    break;
+                }
+                Err(e) => {
+                    error!(error = ?e, "{}: websocket error", self.label);
+                    break;
+                }
+                // Ping frames are answered by tungstenite itself on the next read
+                _ => continue,
+            };
+
+            if let Some(reply) = on_message(&text) {
+                write.lock().await.send(Message::Text(reply)).await?;
+            }
+        }
+
+        if let Some(handle) = ping_handle {
+            handle.abort();
+        }
+        Ok(())
+    }
+}
+
+/// Stop the keepalive and send a close frame
+async fn close<S>(write: &Mutex<S>, ping_handle: Option<&tokio::task::JoinHandle<()>>, label: &str)
+where
+    S: SinkExt<Message> + Unpin,
+    S::Error: std::fmt::Debug,
+{
+    if let Some(handle) = ping_handle {
+        handle.abort();
+    }
+    if let Err(e) = write.lock().await.close().await {
+        warn!(error = ?e, "{}: failed to close connection", label);
+    }
+}
+
+/// Run `connect_once` until `shutdown` fires, backing off between attempts.
+/// A connection that stayed up for `STABLE_CONNECTION` starts the backoff over.
+pub async fn reconnect_loop<F, Fut>(
+    exchange: &'static str,
+    label: &str,
+    shutdown: &CancellationToken,
+    mut connect_once: F,
+) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        let result = connect_once().await;
+        metrics().set_connected(exchange, false);
+        if shutdown.is_cancelled() {
+            info!("{}: stopped", label);
+            return Ok(());
+        }
+
+        if started.elapsed() >= STABLE_CONNECTION {
+            attempt = 0;
+        }
+        let delay = reconnect_backoff(attempt);
+        attempt = attempt.saturating_add(1);
+
+        match result {
+            Err(e) => error!(error = ?e, delay_ms = delay.as_millis() as u64, "{} connection error, reconnecting...", label),
+            Ok(()) => info!(delay_ms = delay.as_millis() as u64, "{}: reconnecting...", label),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.cancelled() => {
+                info!("{}: stopped", label);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Retry `fetch` (symbol lists and the like) with the reconnect backoff until it succeeds;
+/// `None` if `shutdown` fires first
+pub async fn fetch_with_retry<T, F, Fut>(label: &str, shutdown: &CancellationToken, mut fetch: F) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        let result = tokio::select! {
+            result = fetch() => result,
+            _ = shutdown.cancelled() => return None,
+        };
+        let e = match result {
+            Ok(value) => return Some(value),
+            Err(e) => e,
+        };
+
+        let delay = reconnect_backoff(attempt);
+        attempt = attempt.saturating_add(1);
+        error!(error = ?e, delay_ms = delay.as_millis() as u64, "{}: startup request failed, retrying...", label);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.cancelled() => return None,
+        }
+    }
+}
+
+/// Run one independently reconnecting connection per shard until `shutdown` fires.
+/// Shards show up in logs as `{label}#1`, `{label}#2`, ...
+pub async fn run_shards<'a, S, F, Fut>(
+    exchange: &'static str,
+    label: &str,
+    shutdown: &CancellationToken,
+    shards: &'a [S],
+    run_shard: F,
+) -> Result<()>
+where
+    F: Fn(String, &'a S) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    info!(shards = shards.len(), "{}: opening connections", label);
+
+    let run_shard = &run_shard;
+    let connections = shards.iter().enumerate().map(|(i, shard)| {
+        let shard_label = format!("{}#{}", label, i + 1);
+        async move {
+            reconnect_loop(exchange, &shard_label, shutdown, || run_shard(shard_label.clone(), shard)).await
+        }
+    });
+
+    for result in join_all(connections).await {
+        result?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping_messages() {
+        let interval = Duration::from_secs(20);
+
+        assert!(Ping::None.interval().is_none());
+        assert_eq!(Ping::Frame(interval).message(), Some(Message::Ping(Vec::new())));
+        assert_eq!(Ping::Text("ping", interval).message(), Some(Message::Text("ping".to_string())));
+        assert_eq!(
+            Ping::Json(|| serde_json::json!({"op": "ping"}), interval).message(),
+            Some(Message::Text(r#"{"op":"ping"}"#.to_string()))
+        );
+        assert_eq!(Ping::Json(|| serde_json::json!({}), interval).interval(), Some(interval));
+    }
+
+    #[test]
+    fn test_classify_text() {
+        assert_eq!(classify_text("ping"), Frame::Ping);
+        assert_eq!(classify_text("pong"), Frame::Pong);
+        assert_eq!(classify_text("pong\n"), Frame::Pong);
+
+        let data = r#"{"arg":{"channel":"tickers","instId":"BTC-USDT"},"data":[]}"#;
+        assert_eq!(classify_text(data), Frame::Data(data));
+        assert_eq!(classify_text("pingpong"), Frame::Data("pingpong"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_sends_close_frame() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut received = Vec::new();
+            while let Some(Ok(msg)) = ws.next().await {
+                received.push(msg);
+            }
+            received
+        });
+
+        let shutdown = CancellationToken::new();
+        let session = Session::new("test", "Test", url)
+            .subscribe(vec!["sub".to_string()])
+            .ping(Ping::Text("ping", Duration::from_secs(3600)));
+        let client = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { session.run(&shutdown, |_| None).await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown.cancel();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), client).await;
+        assert!(result.unwrap().unwrap().is_ok());
+        let received = server.await.unwrap();
+        assert_eq!(received[0], Message::Text("sub".to_string()));
+        assert!(matches!(received.last(), Some(Message::Close(_))));
+    }
+}
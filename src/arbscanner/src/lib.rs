@@ -0,0 +1,10 @@
+pub mod api;
+pub mod config;
+pub mod cooldown;
+pub mod exchanges;
+pub mod matcher;
+pub mod scanner;
+pub mod notifier;
+pub mod metrics;
+pub mod store;
+pub mod replay;
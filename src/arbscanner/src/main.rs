@@ -1,20 +1,53 @@
-mod config;
-mod exchanges;
-mod matcher;
-mod scanner;
-mod notifier;
-
 use anyhow::Result;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tracing::{info, error, Level};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, error, warn, Level};
 use tracing_subscriber::EnvFilter;
 
-use config::Config;
-use exchanges::ExchangeManager;
-use matcher::TickerMatcher;
-use scanner::ArbitrageScanner;
-use notifier::Notifier;
+use arbscanner::api;
+use arbscanner::config::Config;
+use arbscanner::exchanges::ExchangeManager;
+use arbscanner::matcher::TickerMatcher;
+use arbscanner::metrics;
+use arbscanner::scanner::ArbitrageScanner;
+use arbscanner::notifier::Notifier;
+use arbscanner::replay::{self, Recorder};
+use arbscanner::store::OpportunityStore;
+
+/// How long connectors, the scanner and pending notifications get to finish after a stop signal
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(15);
+
+/// Resolves on Ctrl-C, or SIGTERM where supported (systemd, docker stop)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!(error = ?e, "Failed to listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+    
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!(error = ?e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -35,41 +68,147 @@ async fn main() -> Result<()> {
     info!(
         min_spread = %config.min_spread_percent,
         max_spread = %config.max_spread_percent,
+        spread_overrides = config.min_spread_overrides.len() + config.max_spread_overrides.len(),
         cooldown_ms = config.cooldown_ms,
+        orderbook_depth = config.orderbook_depth,
+        quote_currencies = %config.quote_currencies.join(","),
+        stablecoin_equivalence = config.stablecoin_equivalence,
+        base_aliases = config.base_aliases.len(),
+        exclude_leveraged_tokens = config.exclude_leveraged_tokens,
+        min_notional_usd = %config.min_notional_usd,
+        max_symbols = config.max_symbols_per_exchange,
+        symbol_cache_ttl_ms = config.symbol_cache_ttl_ms,
+        max_price_age_ms = config.max_price_age_ms,
+        triangular = config.enable_triangular,
+        default_fee = %config.default_fee,
+        fee_overrides = config.fees.len(),
+        endpoint_overrides = config.endpoints.len(),
+        metrics_addr = ?config.metrics_addr,
+        record_path = ?config.record_path,
+        replay_path = ?config.replay_path,
+        replay_fast = config.replay_fast,
+        api_addr = ?config.api_addr,
+        sqlite_path = ?config.sqlite_path,
+        cooldown_state_path = ?config.cooldown_state_path,
+        kafka_brokers = ?config.kafka_brokers,
+        kafka_topic = %config.kafka_topic,
+        kafka_queue_size = config.kafka_queue_size,
         callback_url = %config.callback_url,
+        callback_signed = config.callback_secret.is_some(),
+        notify_max_retries = config.notify_max_retries,
+        notify_dlq_path = ?config.notify_dlq_path,
         "Configuration loaded"
     );
 
+    if let Some(addr) = config.metrics_addr.clone() {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr).await {
+                error!(error = ?e, "Metrics server stopped");
+            }
+        });
+    }
+
     // Create shared state
-    let matcher = Arc::new(TickerMatcher::new());
-    let notifier = Arc::new(Notifier::new(config.clone()));
+    let mut matcher = TickerMatcher::with_quotes(config.quote_currencies.clone());
+    if config.stablecoin_equivalence {
+        matcher = matcher.with_equivalent_quotes(config.stablecoins.clone());
+    }
+    if !config.base_aliases.is_empty() {
+        matcher = matcher.with_base_aliases(config.base_aliases.clone());
+    }
+    let matcher = Arc::new(matcher);
+    let notifier = Arc::new(Notifier::new(config.clone())?);
+    info!(sinks = ?notifier.sink_names(), "Notification sinks configured");
     
     // Broadcast channel for price updates
     let (price_tx, _) = broadcast::channel(10000);
     
-    // Start exchange connections
-    let exchange_manager = ExchangeManager::new(
-        config.clone(),
-        matcher.clone(),
-        price_tx.clone(),
-    );
-    
     // Start scanner
-    let scanner = ArbitrageScanner::new(
+    let mut scanner = ArbitrageScanner::new(
         config.clone(),
         matcher.clone(),
         notifier.clone(),
         price_tx.subscribe(),
     );
+    let store = match &config.sqlite_path {
+        Some(path) => Some(Arc::new(OpportunityStore::open(path)?)),
+        None => None,
+    };
+    if let Some(store) = &store {
+        scanner = scanner.with_store(store.clone());
+    }
+    if let Some(path) = &config.cooldown_state_path {
+        scanner = scanner.with_cooldown_state(path);
+    }
+    
+    if let Some(addr) = config.api_addr.clone() {
+        let handle = scanner.handle();
+        tokio::spawn(async move {
+            if let Err(e) = api::serve(addr, handle).await {
+                error!(error = ?e, "API server stopped");
+            }
+        });
+    }
+
+    // Run everything until one side stops or we're asked to
+    let shutdown = CancellationToken::new();
+    let replaying = config.replay_path.is_some();
+    
+    // Prices come from the exchanges, or from a recording; the scanner can't tell the difference
+    let mut feed_task = match config.replay_path.clone() {
+        Some(path) => tokio::spawn(replay::replay(path, price_tx, config.replay_fast, shutdown.clone())),
+        None => {
+            let mut exchange_manager = ExchangeManager::new(
+                config.clone(),
+                matcher.clone(),
+                price_tx,
+            );
+            if let Some(path) = &config.record_path {
+                exchange_manager = exchange_manager.with_recorder(Recorder::open(path)?);
+            }
+            tokio::spawn(exchange_manager.run(shutdown.clone()))
+        }
+    };
+    let mut scanner_task = tokio::spawn(scanner.run(shutdown.clone()));
     
-    // Run everything
     tokio::select! {
-        res = exchange_manager.run() => {
-            error!(?res, "Exchange manager stopped");
+        res = &mut feed_task => {
+            if replaying {
+                // The scanner drains what is left and stops once the replay's sender is gone
+                info!(?res, "Replay finished, waiting for the scanner");
+                tokio::select! {
+                    res = &mut scanner_task => info!(?res, "Scanner stopped"),
+                    _ = shutdown_signal() => info!("Shutting down..."),
+                }
+            } else {
+                error!(?res, "Exchange manager stopped");
+            }
         }
-        res = scanner.run() => {
+        res = &mut scanner_task => {
             error!(?res, "Scanner stopped");
         }
+        _ = shutdown_signal() => {
+            info!("Shutting down...");
+        }
+    }
+    shutdown.cancel();
+    
+    // Connectors close their sockets, the scanner finishes its current update, then queued alerts and rows go out
+    let drain = async {
+        if !feed_task.is_finished() {
+            let _ = feed_task.await;
+        }
+        if !scanner_task.is_finished() {
+            let _ = scanner_task.await;
+        }
+        notifier.flush().await;
+        if let Some(store) = &store {
+            store.flush().await;
+        }
+    };
+    match tokio::time::timeout(SHUTDOWN_GRACE, drain).await {
+        Ok(()) => info!("Shutdown complete"),
+        Err(_) => warn!(grace_secs = SHUTDOWN_GRACE.as_secs(), "Shutdown timed out, exiting anyway"),
     }
 
     Ok(())
@@ -8,6 +8,51 @@ pub type NormalizedSymbol = String;
 /// Exchange-specific symbol format
 pub type ExchangeSymbol = String;
 
+/// Default quote currencies, in match priority order
+pub const DEFAULT_QUOTE_CURRENCIES: &[&str] = &[
+    "USDT", "USDC", "USD", "BUSD", "TUSD", "USDP", "DAI", "FDUSD",
+    "EUR", "GBP", "JPY", "AUD", "CAD",
+    "BTC", "ETH", "BNB", "SOL", "XRP",
+];
+
+/// Suffixes marking leveraged/ETF tokens regardless of the underlying (ETHBULL, XRPBEAR)
+const LEVERAGED_SUFFIXES: &[&str] = &["BULL", "BEAR"];
+
+/// UP/DOWN are common word endings (JUP, SYRUP), so they only mark a leveraged token
+/// on underlyings that actually had UP/DOWN tokens listed
+const UP_DOWN_UNDERLYINGS: &[&str] = &[
+    "BTC", "ETH", "BNB", "XRP", "ADA", "DOT", "LINK", "TRX", "EOS", "LTC",
+    "XTZ", "FIL", "SXP", "YFI", "AAVE", "SUSHI", "UNI", "1INCH", "XLM", "BCH",
+];
+
+/// Whether a normalized symbol is a leveraged token (BTC3L/USDT, ETHBULL/USDT, BTCUP/USDT).
+/// Different exchanges' leveraged tokens track different baskets, so their prices are never comparable.
+pub fn is_leveraged_token(symbol: &str) -> bool {
+    let base = symbol.split('/').next().unwrap_or(symbol);
+    
+    // <underlying><N>L / <underlying><N>S, e.g. BTC3L, ETH5S
+    let bytes = base.as_bytes();
+    if bytes.len() >= 3
+        && matches!(bytes[bytes.len() - 1], b'L' | b'S')
+        && bytes[bytes.len() - 2].is_ascii_digit()
+        && bytes[..bytes.len() - 2].iter().any(|b| b.is_ascii_alphabetic())
+    {
+        return true;
+    }
+    
+    if LEVERAGED_SUFFIXES
+        .iter()
+        .any(|suffix| base.len() > suffix.len() && base.ends_with(suffix))
+    {
+        return true;
+    }
+    
+    ["UP", "DOWN"].iter().any(|suffix| {
+        base.strip_suffix(suffix)
+            .is_some_and(|underlying| UP_DOWN_UNDERLYINGS.contains(&underlying))
+    })
+}
+
 /// Maps exchange symbols to normalized format and vice versa
 pub struct TickerMatcher {
     /// Exchange -> ExchangeSymbol -> NormalizedSymbol
@@ -17,36 +62,108 @@ pub struct TickerMatcher {
     to_exchange: DashMap<NormalizedSymbol, HashMap<String, ExchangeSymbol>>,
     
     /// Known quote currencies (ordered by priority)
-    quote_currencies: Vec<&'static str>,
+    quote_currencies: Vec<String>,
+    
+    /// Quote -> canonical quote it is matched as (empty unless equivalence is enabled)
+    quote_aliases: HashMap<String, String>,
+    
+    /// Equivalent quotes in preference order, the canonical one first
+    equivalent_quotes: Vec<String>,
+    
+    /// Base -> canonical base it is matched as, e.g. XBT -> BTC (opt-in, empty by default)
+    base_aliases: HashMap<String, String>,
+}
+
+impl Default for TickerMatcher {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TickerMatcher {
     pub fn new() -> Self {
+        Self::with_quotes(DEFAULT_QUOTE_CURRENCIES.iter().map(|q| q.to_string()).collect())
+    }
+    
+    /// Matcher splitting concatenated symbols on `quotes`, tried in order.
+    /// Put a quote before any shorter quote it ends with (e.g. "TUSD" before "USD").
+    pub fn with_quotes(quotes: Vec<String>) -> Self {
         Self {
             to_normalized: DashMap::new(),
             to_exchange: DashMap::new(),
-            quote_currencies: vec![
-                "USDT", "USDC", "USD", "BUSD", "TUSD", "USDP", "DAI", "FDUSD",
-                "EUR", "GBP", "JPY", "AUD", "CAD",
-                "BTC", "ETH", "BNB", "SOL", "XRP",
-            ],
+            quote_currencies: quotes
+                .into_iter()
+                .map(|q| q.trim().to_uppercase())
+                .filter(|q| !q.is_empty())
+                .collect(),
+            quote_aliases: HashMap::new(),
+            equivalent_quotes: Vec::new(),
+            base_aliases: HashMap::new(),
         }
     }
     
+    /// Treat every quote in `equivalent` as the first one when matching, so e.g. BTC/USDC
+    /// and BTC/USD pair with BTC/USDT. Exchange symbols keep the real pair.
+    pub fn with_equivalent_quotes(mut self, equivalent: Vec<String>) -> Self {
+        let quotes: Vec<String> = equivalent
+            .into_iter()
+            .map(|q| q.trim().to_uppercase())
+            .filter(|q| !q.is_empty())
+            .collect();
+        
+        if let Some(canonical) = quotes.first() {
+            for quote in &quotes {
+                self.quote_aliases.insert(quote.clone(), canonical.clone());
+            }
+        }
+        self.equivalent_quotes = quotes;
+        self
+    }
+    
+    /// Position of the quote `exchange_symbol` really trades in among the equivalent quotes
+    /// (0 = canonical), for picking one of several pairs an exchange lists under the same
+    /// normalized symbol. `None` for other quotes and when equivalence is off.
+    pub fn equivalent_quote_rank(&self, exchange_symbol: &str) -> Option<usize> {
+        let split = self.split_symbol(exchange_symbol);
+        let (_, quote) = split.split_once('/')?;
+        self.equivalent_quotes.iter().position(|q| q == quote)
+    }
+    
+    /// Rename bases per `aliases` (alias -> canonical) when normalizing, so e.g. Kraken's XBT/USD
+    /// matches BTC/USD. Only listed tickers are merged: wrapped or bridged assets (WBTC) stay distinct.
+    pub fn with_base_aliases(mut self, aliases: HashMap<String, String>) -> Self {
+        self.base_aliases = aliases
+            .into_iter()
+            .map(|(alias, canonical)| (alias.trim().to_uppercase(), canonical.trim().to_uppercase()))
+            .filter(|(alias, canonical)| !alias.is_empty() && !canonical.is_empty())
+            .collect();
+        self
+    }
+    
     /// Register a symbol from an exchange and get normalized version
     pub fn register(&self, exchange: &str, exchange_symbol: &str) -> NormalizedSymbol {
         let normalized = self.normalize_symbol(exchange_symbol);
-        
+        self.insert(exchange, exchange_symbol, normalized)
+    }
+    
+    /// Register an exchange symbol whose base and quote the connector already split
+    /// (for formats the generic splitter can't parse, like Bitfinex `tBTCUSD` / `tAVAX:USD`)
+    pub fn register_pair(&self, exchange: &str, exchange_symbol: &str, base: &str, quote: &str) -> NormalizedSymbol {
+        let normalized = self.normalize_symbol(&format!("{}/{}", base, quote));
+        self.insert(exchange, exchange_symbol, normalized)
+    }
+    
+    fn insert(&self, exchange: &str, exchange_symbol: &str, normalized: NormalizedSymbol) -> NormalizedSymbol {
         // Update to_normalized map
         self.to_normalized
             .entry(exchange.to_string())
-            .or_insert_with(HashMap::new)
+            .or_default()
             .insert(exchange_symbol.to_string(), normalized.clone());
         
         // Update to_exchange map
         self.to_exchange
             .entry(normalized.clone())
-            .or_insert_with(HashMap::new)
+            .or_default()
             .insert(exchange.to_string(), exchange_symbol.to_string());
         
         normalized
@@ -60,6 +177,7 @@ impl TickerMatcher {
     }
     
     /// Get all exchanges that have this normalized symbol
+    #[allow(dead_code)]
     pub fn get_exchanges_for_symbol(&self, normalized: &str) -> Vec<String> {
         self.to_exchange
             .get(normalized)
@@ -67,17 +185,30 @@ impl TickerMatcher {
             .unwrap_or_default()
     }
     
-    /// Get all normalized symbols that exist on multiple exchanges
+    /// Get all normalized symbols that exist on multiple exchanges (leveraged tokens excluded)
     pub fn get_arbitrageable_symbols(&self) -> Vec<NormalizedSymbol> {
         self.to_exchange
             .iter()
-            .filter(|entry| entry.value().len() >= 2)
+            .filter(|entry| entry.value().len() >= 2 && !is_leveraged_token(entry.key()))
             .map(|entry| entry.key().clone())
             .collect()
     }
     
-    /// Normalize symbol to standard format: "BTC/USDT"
+    /// Normalize symbol to standard format: "BTC/USDT", with aliased bases and equivalent quotes canonicalized
     fn normalize_symbol(&self, raw: &str) -> NormalizedSymbol {
+        let normalized = self.split_symbol(raw);
+        
+        match normalized.split_once('/') {
+            Some((base, quote)) => {
+                let base = self.base_aliases.get(base).map_or(base, String::as_str);
+                let quote = self.quote_aliases.get(quote).map_or(quote, String::as_str);
+                format!("{}/{}", base, quote)
+            }
+            None => normalized,
+        }
+    }
+    
+    fn split_symbol(&self, raw: &str) -> NormalizedSymbol {
         let raw = raw.to_uppercase();
         
         // Already normalized (contains /)
@@ -97,7 +228,7 @@ impl TickerMatcher {
         
         // Try to split by known quote currencies
         for quote in &self.quote_currencies {
-            if raw.ends_with(quote) {
+            if raw.ends_with(quote.as_str()) {
                 let base = &raw[..raw.len() - quote.len()];
                 if !base.is_empty() {
                     return format!("{}/{}", base, quote);
@@ -139,4 +270,107 @@ mod tests {
         assert_eq!(matcher.normalize_symbol("ETHBTC"), "ETH/BTC");
         assert_eq!(matcher.normalize_symbol("SOLUSDC"), "SOL/USDC");
     }
+    
+    #[test]
+    fn test_register_pair_keeps_exchange_symbol() {
+        let matcher = TickerMatcher::new();
+        
+        assert_eq!(matcher.register_pair("bitfinex", "tAVAX:USD", "AVAX", "USD"), "AVAX/USD");
+        assert_eq!(matcher.get_normalized("bitfinex", "tAVAX:USD").as_deref(), Some("AVAX/USD"));
+        assert_eq!(matcher.get_exchanges_for_symbol("AVAX/USD"), ["bitfinex"]);
+    }
+    
+    #[test]
+    fn test_equivalent_quotes_pair_across_stablecoins() {
+        let stablecoins = vec!["USDT".to_string(), "USDC".to_string(), "USD".to_string(), "FDUSD".to_string()];
+        let matcher = TickerMatcher::new().with_equivalent_quotes(stablecoins);
+        
+        assert_eq!(matcher.register("binance", "BTCUSDC"), "BTC/USDT");
+        assert_eq!(matcher.register("coinbase", "BTC-USD"), "BTC/USDT");
+        assert_eq!(matcher.register("kraken", "BTC/USDC"), "BTC/USDT");
+        assert_eq!(matcher.normalize_symbol("ETHBTC"), "ETH/BTC");
+        
+        assert_eq!(matcher.get_arbitrageable_symbols(), vec!["BTC/USDT"]);
+        assert_eq!(matcher.get_normalized("coinbase", "BTC-USD").as_deref(), Some("BTC/USDT"));
+        
+        // Ranked by the real quote, in STABLECOINS order
+        assert_eq!(matcher.equivalent_quote_rank("BTCUSDT"), Some(0));
+        assert_eq!(matcher.equivalent_quote_rank("BTC_USDC"), Some(1));
+        assert_eq!(matcher.equivalent_quote_rank("BTC-FDUSD"), Some(3));
+        assert_eq!(matcher.equivalent_quote_rank("ETHBTC"), None);
+        
+        // Without equivalence the quotes stay distinct
+        let plain = TickerMatcher::new();
+        plain.register("binance", "BTCUSDC");
+        plain.register("coinbase", "BTC-USD");
+        assert!(plain.get_arbitrageable_symbols().is_empty());
+        assert_eq!(plain.equivalent_quote_rank("BTCUSDT"), None);
+    }
+    
+    #[test]
+    fn test_base_aliases_merge_kraken_xbt() {
+        let aliases = HashMap::from([("xbt".to_string(), "BTC".to_string())]);
+        let matcher = TickerMatcher::new().with_base_aliases(aliases);
+        
+        assert_eq!(matcher.register("kraken", "XBT/USD"), "BTC/USD");
+        assert_eq!(matcher.normalize_symbol("XBTUSD"), "BTC/USD");
+        assert_eq!(matcher.register("coinbase", "BTC-USD"), "BTC/USD");
+        assert_eq!(matcher.get_arbitrageable_symbols(), vec!["BTC/USD"]);
+        // Updates still look up the exchange's own symbol
+        assert_eq!(matcher.get_normalized("kraken", "XBT/USD").as_deref(), Some("BTC/USD"));
+        
+        // Unlisted tickers are left alone
+        assert_eq!(matcher.normalize_symbol("WBTCUSDT"), "WBTC/USDT");
+        assert_eq!(TickerMatcher::new().normalize_symbol("XBT/USD"), "XBT/USD");
+    }
+    
+    #[test]
+    fn test_leveraged_tokens() {
+        let matcher = TickerMatcher::new();
+        let leveraged = |raw: &str| is_leveraged_token(&matcher.normalize_symbol(raw));
+        
+        assert!(leveraged("BTC3LUSDT"));
+        assert!(leveraged("ETH5S_USDT"));
+        assert!(leveraged("ETHBULLUSDT"));
+        assert!(leveraged("XRPBEAR-USDT"));
+        assert!(leveraged("BTCUPUSDT"));
+        assert!(leveraged("LINKDOWNUSDT"));
+        
+        // Real coins that merely contain those letters
+        assert!(!leveraged("BTCUSDT"));
+        assert!(!leveraged("JUPUSDT"));
+        assert!(!leveraged("SYRUPUSDT"));
+        assert!(!leveraged("SUPERUSDT"));
+        assert!(!leveraged("UPUSDT"));
+        assert!(!leveraged("BULLUSDT"));
+        assert!(!leveraged("BEARUSDT"));
+        assert!(!leveraged("DOWNUSDT"));
+        assert!(!leveraged("FLUXUSDT"));
+        assert!(!leveraged("1000SATSUSDT"));
+        assert!(!leveraged("C98USDT"));
+    }
+    
+    #[test]
+    fn test_leveraged_tokens_not_arbitrageable() {
+        let matcher = TickerMatcher::new();
+        matcher.register("gate", "BTC3L_USDT");
+        matcher.register("mexc", "BTC3L_USDT");
+        matcher.register("gate", "BTC_USDT");
+        matcher.register("mexc", "BTC_USDT");
+        
+        assert_eq!(matcher.get_arbitrageable_symbols(), vec!["BTC/USDT"]);
+    }
+    
+    #[test]
+    fn test_custom_quotes_change_split() {
+        // Defaults try USD before TUSD
+        assert_eq!(TickerMatcher::new().normalize_symbol("BTCTUSD"), "BTCT/USD");
+        
+        let matcher = TickerMatcher::with_quotes(vec!["tusd".to_string(), " USD".to_string(), String::new()]);
+        assert_eq!(matcher.normalize_symbol("BTCTUSD"), "BTC/TUSD");
+        assert_eq!(matcher.normalize_symbol("BTCUSD"), "BTC/USD");
+        
+        // USDT is no longer a known quote
+        assert_eq!(matcher.normalize_symbol("ETHUSDT"), "ETHUSDT/USD");
+    }
 }
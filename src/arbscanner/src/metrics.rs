@@ -0,0 +1,322 @@
+use anyhow::Result;
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use dashmap::DashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tracing::info;
+
+/// Weight of the newest sample in the smoothed feed timings
+const FEED_SMOOTHING: f64 = 0.1;
+
+/// Smoothed timing of one exchange's feed. Averages are f64 bits so updates stay lock-free.
+#[derive(Default)]
+struct FeedTiming {
+    last_received_ms: AtomicI64,
+    gap_ms: AtomicU64,
+    has_gap: AtomicBool,
+    latency_ms: AtomicU64,
+    has_latency: AtomicBool,
+}
+
+/// Per-exchange feed timing as of now
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedStats {
+    pub exchange: String,
+    /// Average time between two updates from this exchange
+    pub gap_ms: Option<f64>,
+    /// Average `received - exchange timestamp`; only for exchanges that send one
+    pub latency_ms: Option<f64>,
+}
+
+/// Process-wide counters and gauges. The hot path only touches atomics;
+/// per-exchange entries are created once and then looked up without a write lock.
+#[derive(Default)]
+pub struct Metrics {
+    /// Exchange -> price updates published by its connector
+    messages_received: DashMap<String, AtomicU64>,
+
+    /// Exchange -> updates dropped because bid >= ask
+    crossed_books: DashMap<String, AtomicU64>,
+
+    /// Exchange -> whether its websocket is currently up
+    connected: DashMap<String, AtomicBool>,
+
+    /// Exchange -> inter-message gap and server-to-us latency
+    feed_timing: DashMap<String, FeedTiming>,
+
+    opportunities_found: AtomicU64,
+    triangular_found: AtomicU64,
+    notifications_sent: AtomicU64,
+    notifications_failed: AtomicU64,
+    kafka_dropped: AtomicU64,
+    tracked_symbols: AtomicU64,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Global metrics registry
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::default)
+}
+
+impl Metrics {
+    pub fn record_message(&self, exchange: &str) {
+        increment(&self.messages_received, exchange);
+    }
+
+    /// Note an update received at `received_ms`; `exchange_ts` is the exchange's own
+    /// timestamp for it, when the feed carries one
+    pub fn record_feed_timing(&self, exchange: &str, received_ms: i64, exchange_ts: Option<i64>) {
+        if !self.feed_timing.contains_key(exchange) {
+            self.feed_timing.entry(exchange.to_string()).or_default();
+        }
+        let Some(timing) = self.feed_timing.get(exchange) else {
+            return;
+        };
+
+        let previous = timing.last_received_ms.swap(received_ms, Ordering::Relaxed);
+        if previous > 0 {
+            smooth(&timing.gap_ms, &timing.has_gap, (received_ms - previous).max(0) as f64);
+        }
+        if let Some(exchange_ts) = exchange_ts {
+            smooth(&timing.latency_ms, &timing.has_latency, (received_ms - exchange_ts) as f64);
+        }
+    }
+
+    /// Smoothed feed timings, sorted by exchange
+    pub fn feed_stats(&self) -> Vec<FeedStats> {
+        let mut stats: Vec<FeedStats> = self
+            .feed_timing
+            .iter()
+            .map(|e| {
+                let timing = e.value();
+                FeedStats {
+                    exchange: e.key().clone(),
+                    gap_ms: load_smoothed(&timing.gap_ms, &timing.has_gap),
+                    latency_ms: load_smoothed(&timing.latency_ms, &timing.has_latency),
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.exchange.cmp(&b.exchange));
+        stats
+    }
+
+    pub fn record_crossed_book(&self, exchange: &str) {
+        increment(&self.crossed_books, exchange);
+    }
+
+    pub fn set_connected(&self, exchange: &str, up: bool) {
+        if let Some(state) = self.connected.get(exchange) {
+            state.store(up, Ordering::Relaxed);
+            return;
+        }
+        self.connected
+            .entry(exchange.to_string())
+            .or_default()
+            .store(up, Ordering::Relaxed);
+    }
+
+    pub fn record_opportunity(&self) {
+        self.opportunities_found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_triangular(&self) {
+        self.triangular_found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_notification(&self, ok: bool) {
+        let counter = if ok {
+            &self.notifications_sent
+        } else {
+            &self.notifications_failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+    pub fn record_kafka_dropped(&self) {
+        self.kafka_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_tracked_symbols(&self, count: usize) {
+        self.tracked_symbols.store(count as u64, Ordering::Relaxed);
+    }
+
+    /// Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        render_per_exchange(
+            &mut out,
+            "arbscanner_messages_received_total",
+            "Price updates received per exchange",
+            &self.messages_received,
+        );
+        render_per_exchange(
+            &mut out,
+            "arbscanner_crossed_books_total",
+            "Price updates dropped because bid >= ask",
+            &self.crossed_books,
+        );
+
+        let mut connected: Vec<_> = self
+            .connected
+            .iter()
+            .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+            .collect();
+        connected.sort();
+        out.push_str("# HELP arbscanner_exchange_connected Whether the exchange websocket is connected (1) or not (0)\n");
+        out.push_str("# TYPE arbscanner_exchange_connected gauge\n");
+        for (exchange, up) in connected {
+            let _ = writeln!(out, "arbscanner_exchange_connected{{exchange=\"{}\"}} {}", exchange, up as u8);
+        }
+
+        let feeds = self.feed_stats();
+        out.push_str("# HELP arbscanner_feed_gap_ms Smoothed time between updates per exchange\n");
+        out.push_str("# TYPE arbscanner_feed_gap_ms gauge\n");
+        for feed in &feeds {
+            if let Some(gap) = feed.gap_ms {
+                let _ = writeln!(out, "arbscanner_feed_gap_ms{{exchange=\"{}\"}} {:.1}", feed.exchange, gap);
+            }
+        }
+        out.push_str("# HELP arbscanner_feed_latency_ms Smoothed delay between the exchange timestamp and receipt\n");
+        out.push_str("# TYPE arbscanner_feed_latency_ms gauge\n");
+        for feed in &feeds {
+            if let Some(latency) = feed.latency_ms {
+                let _ = writeln!(out, "arbscanner_feed_latency_ms{{exchange=\"{}\"}} {:.1}", feed.exchange, latency);
+            }
+        }
+
+        let scalars = [
+            ("arbscanner_opportunities_found_total", "counter", "Cross-exchange opportunities found", &self.opportunities_found),
+            ("arbscanner_triangular_found_total", "counter", "Triangular opportunities found", &self.triangular_found),
+            ("arbscanner_notifications_sent_total", "counter", "Notifications delivered", &self.notifications_sent),
+            ("arbscanner_notifications_failed_total", "counter", "Notifications that failed to deliver", &self.notifications_failed),
+            ("arbscanner_kafka_dropped_total", "counter", "Kafka records dropped because the queue was full", &self.kafka_dropped),
+            ("arbscanner_tracked_symbols", "gauge", "Symbols with at least one stored price", &self.tracked_symbols),
+        ];
+        for (name, kind, help, value) in scalars {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        out
+    }
+}
+
+/// Bump a per-exchange counter, creating it on first use
+fn increment(counters: &DashMap<String, AtomicU64>, exchange: &str) {
+    if let Some(counter) = counters.get(exchange) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    counters
+        .entry(exchange.to_string())
+        .or_default()
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// Fold `sample` into an exponential moving average; the first sample seeds it
+fn smooth(average: &AtomicU64, seeded: &AtomicBool, sample: f64) {
+    if !seeded.swap(true, Ordering::Relaxed) {
+        average.store(sample.to_bits(), Ordering::Relaxed);
+        return;
+    }
+    let _ = average.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        let current = f64::from_bits(bits);
+        Some((current + FEED_SMOOTHING * (sample - current)).to_bits())
+    });
+}
+
+fn load_smoothed(average: &AtomicU64, seeded: &AtomicBool) -> Option<f64> {
+    seeded
+        .load(Ordering::Relaxed)
+        .then(|| f64::from_bits(average.load(Ordering::Relaxed)))
+}
+
+fn render_per_exchange(out: &mut String, name: &str, help: &str, counters: &DashMap<String, AtomicU64>) {
+    let mut values: Vec<_> = counters
+        .iter()
+        .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+        .collect();
+    values.sort();
+
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (exchange, count) in values {
+        let _ = writeln!(out, "{}{{exchange=\"{}\"}} {}", name, exchange, count);
+    }
+}
+
+/// Serve `/metrics` on `addr` until the process exits
+pub async fn serve(addr: String) -> Result<()> {
+    let app = Router::new().route("/metrics", get(metrics_handler));
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+
+    info!(addr = %addr, "Metrics server listening");
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.record_message("binance");
+        metrics.record_message("binance");
+        metrics.record_message("okx");
+        metrics.set_connected("okx", true);
+        metrics.set_connected("kraken", false);
+        metrics.record_opportunity();
+        metrics.record_notification(true);
+        metrics.record_notification(false);
+        metrics.record_notification(false);
+        metrics.set_tracked_symbols(42);
+        metrics.record_crossed_book("mexc");
+
+        let text = metrics.render();
+        assert!(text.contains("arbscanner_messages_received_total{exchange=\"binance\"} 2\n"));
+        assert!(text.contains("arbscanner_messages_received_total{exchange=\"okx\"} 1\n"));
+        assert!(text.contains("arbscanner_exchange_connected{exchange=\"okx\"} 1\n"));
+        assert!(text.contains("arbscanner_exchange_connected{exchange=\"kraken\"} 0\n"));
+        assert!(text.contains("arbscanner_opportunities_found_total 1\n"));
+        assert!(text.contains("arbscanner_notifications_sent_total 1\n"));
+        assert!(text.contains("arbscanner_notifications_failed_total 2\n"));
+        assert!(text.contains("arbscanner_tracked_symbols 42\n"));
+        assert!(text.contains("arbscanner_crossed_books_total{exchange=\"mexc\"} 1\n"));
+    }
+
+    #[test]
+    fn test_feed_timing() {
+        let metrics = Metrics::default();
+        metrics.record_feed_timing("binance", 1_000, None);
+        metrics.record_feed_timing("binance", 1_100, None);
+        metrics.record_feed_timing("binance", 1_300, None);
+        metrics.record_feed_timing("bitget", 5_000, Some(4_950));
+
+        let stats = metrics.feed_stats();
+        assert_eq!(stats[0].exchange, "binance");
+        // 100ms seeds the average, then 10% of the way towards 200ms
+        assert_eq!(stats[0].gap_ms, Some(110.0));
+        assert_eq!(stats[0].latency_ms, None);
+        assert_eq!(stats[1].gap_ms, None);
+        assert_eq!(stats[1].latency_ms, Some(50.0));
+
+        let text = metrics.render();
+        assert!(text.contains("arbscanner_feed_gap_ms{exchange=\"binance\"} 110.0\n"));
+        assert!(text.contains("arbscanner_feed_latency_ms{exchange=\"bitget\"} 50.0\n"));
+        assert!(!text.contains("arbscanner_feed_latency_ms{exchange=\"binance\"}"));
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use super::capitalize;
+use crate::scanner::{ArbitrageOpportunity, TriangularOpportunity};
+
+/// Wrapper for callback request
+#[derive(Debug, Serialize)]
+pub struct CallbackRequest<T: Serialize> {
+    pub key: String,
+    pub payload: T,
+}
+
+/// Payload sent to callback URL
+#[derive(Debug, Serialize)]
+pub struct ArbitragePayload {
+    pub pair: String,
+    #[serde(rename = "exchangeBuy")]
+    pub exchange_buy: String,
+    #[serde(rename = "exchangeSell")]
+    pub exchange_sell: String,
+    #[serde(rename = "symbolBuy")]
+    pub symbol_buy: String,
+    #[serde(rename = "symbolSell")]
+    pub symbol_sell: String,
+    #[serde(rename = "priceBuy")]
+    pub price_buy: f64,
+    #[serde(rename = "priceSell")]
+    pub price_sell: f64,
+    #[serde(rename = "spreadPercent")]
+    pub spread_percent: f64,
+    #[serde(rename = "spreadUsd")]
+    pub spread_usd: f64,
+    #[serde(rename = "netSpreadPercent")]
+    pub net_spread_percent: f64,
+    #[serde(rename = "feeBuy")]
+    pub fee_buy: f64,
+    #[serde(rename = "feeSell")]
+    pub fee_sell: f64,
+    #[serde(rename = "executableQuantity")]
+    pub executable_quantity: f64,
+    #[serde(rename = "availableVolume")]
+    pub available_volume: f64,
+    #[serde(rename = "limitingSize")]
+    pub limiting_size: Option<f64>,
+    pub timestamp: i64,
+}
+
+impl From<ArbitrageOpportunity> for ArbitragePayload {
+    fn from(opp: ArbitrageOpportunity) -> Self {
+        use rust_decimal::prelude::ToPrimitive;
+        
+        Self {
+            pair: opp.symbol,
+            exchange_buy: capitalize(&opp.buy_exchange),
+            exchange_sell: capitalize(&opp.sell_exchange),
+            symbol_buy: opp.buy_symbol,
+            symbol_sell: opp.sell_symbol,
+            price_buy: opp.buy_price.to_f64().unwrap_or(0.0),
+            price_sell: opp.sell_price.to_f64().unwrap_or(0.0),
+            spread_percent: opp.spread_percent.to_f64().unwrap_or(0.0),
+            spread_usd: opp.spread_usd.to_f64().unwrap_or(0.0),
+            net_spread_percent: opp.net_spread_percent.to_f64().unwrap_or(0.0),
+            fee_buy: opp.buy_fee.to_f64().unwrap_or(0.0),
+            fee_sell: opp.sell_fee.to_f64().unwrap_or(0.0),
+            executable_quantity: opp.executable_quantity.to_f64().unwrap_or(0.0),
+            available_volume: opp.available_volume.to_f64().unwrap_or(0.0),
+            limiting_size: opp.limiting_size.and_then(|size| size.to_f64()),
+            timestamp: opp.timestamp,
+        }
+    }
+}
+
+/// One leg of a triangular cycle in the callback payload
+#[derive(Debug, Serialize)]
+pub struct TriangularLegPayload {
+    pub pair: String,
+    pub side: String,
+    pub price: f64,
+}
+
+/// Payload sent to callback URL for triangular opportunities
+#[derive(Debug, Serialize)]
+pub struct TriangularPayload {
+    pub exchange: String,
+    pub path: Vec<String>,
+    pub legs: Vec<TriangularLegPayload>,
+    #[serde(rename = "profitPercent")]
+    pub profit_percent: f64,
+    pub timestamp: i64,
+}
+
+impl From<TriangularOpportunity> for TriangularPayload {
+    fn from(opp: TriangularOpportunity) -> Self {
+        use rust_decimal::prelude::ToPrimitive;
+        
+        Self {
+            exchange: capitalize(&opp.exchange),
+            path: opp.path.to_vec(),
+            legs: opp.legs
+                .into_iter()
+                .map(|leg| TriangularLegPayload {
+                    pair: leg.symbol,
+                    side: leg.side.to_string(),
+                    price: leg.price.to_f64().unwrap_or(0.0),
+                })
+                .collect(),
+            profit_percent: opp.profit_percent.to_f64().unwrap_or(0.0),
+            timestamp: opp.timestamp,
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of the request, prefixed with `sha256=`
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+/// Unix time in milliseconds at which the request was signed
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+
+/// Posts opportunities as JSON to the datastudio trigger endpoint (`CALLBACK_URL`)
+pub struct CallbackNotifier {
+    url: String,
+    client: reqwest::Client,
+    secret: Option<String>,
+}
+
+impl CallbackNotifier {
+    pub fn new(url: String, client: reqwest::Client) -> Self {
+        Self {
+            url,
+            client,
+            secret: None,
+        }
+    }
+
+    /// Sign every request with `secret` (`CALLBACK_SECRET`), see [`sign`]
+    pub fn with_secret(mut self, secret: String) -> Self {
+        self.secret = Some(secret);
+        self
+    }
+    
+    pub async fn send_arbitrage(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        let payload = ArbitragePayload::from(opportunity.clone());
+        self.send_callback("act:arbitrage-spread", &payload).await
+    }
+    
+    pub async fn send_triangular(&self, opportunity: &TriangularOpportunity) -> Result<()> {
+        let payload = TriangularPayload::from(opportunity.clone());
+        self.send_callback("act:triangular-arbitrage", &payload).await
+    }
+    
+    async fn send_callback<T: Serialize>(&self, key: &str, payload: &T) -> Result<()> {
+        let request = CallbackRequest {
+            key: key.to_string(),
+            payload,
+        };
+
+        let body = serde_json::to_vec(&request)?;
+        let mut builder = self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.secret {
+            let timestamp = chrono::Utc::now().timestamp_millis();
+            builder = builder
+                .header(SIGNATURE_HEADER, sign(secret, timestamp, &body))
+                .header(TIMESTAMP_HEADER, timestamp.to_string());
+        }
+
+        let response = builder.body(body).send().await?;
+        
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Callback failed: {} - {}", status, body);
+        }
+        
+        Ok(())
+    }
+}
+
+/// Signature for a callback request.
+///
+/// The signed bytes are the decimal `X-Signature-Timestamp` value, a single `.`, then the
+/// exact request body as sent (compact JSON, no trailing newline):
+///
+/// ```text
+/// 1700000000000.{"key":"act:arbitrage-spread","payload":{...}}
+/// ```
+///
+/// The header value is `sha256=` followed by the lowercase hex HMAC-SHA256 of those bytes
+/// keyed with `CALLBACK_SECRET`. Receivers should recompute it over the raw body before
+/// parsing, compare in constant time and reject stale timestamps to prevent replay.
+pub fn sign(secret: &str, timestamp_ms: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp_ms.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        let body = br#"{"key":"act:arbitrage-spread","payload":{}}"#;
+
+        assert_eq!(
+            sign("topsecret", 1_700_000_000_000, body),
+            "sha256=cbdd08a6f925aa02173cb8fe2f63d01fbe4b4c5bcce1294c99c245c5297cffff"
+        );
+        assert_ne!(sign("topsecret", 1_700_000_000_001, body), sign("topsecret", 1_700_000_000_000, body));
+    }
+}
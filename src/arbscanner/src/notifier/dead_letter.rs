@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use super::Alert;
+
+/// One undeliverable alert, written as a single JSON line
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a> {
+    sink: &'a str,
+    error: String,
+    #[serde(rename = "failedAt")]
+    failed_at: i64,
+    #[serde(flatten)]
+    alert: &'a Alert,
+}
+
+/// Appends alerts that exhausted their retries to `NOTIFY_DLQ_PATH` as JSON lines,
+/// so they can be inspected or replayed instead of being lost
+pub struct DeadLetterLog {
+    file: Mutex<tokio::fs::File>,
+}
+
+impl DeadLetterLog {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open dead-letter log {}", path.display()))?;
+
+        Ok(Self {
+            file: Mutex::new(tokio::fs::File::from_std(file)),
+        })
+    }
+
+    pub async fn append(&self, sink: &str, alert: &Alert, error: &anyhow::Error) -> Result<()> {
+        let record = DeadLetter {
+            sink,
+            error: format!("{:#}", error),
+            failed_at: chrono::Utc::now().timestamp_millis(),
+            alert,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::warn;
+
+use super::capitalize;
+use crate::scanner::{ArbitrageOpportunity, TriangularOpportunity};
+
+/// Give up on an alert after this many 429 responses in a row
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Embed colors, from thin to fat spreads
+const COLOR_LOW: u32 = 0xF1C40F;
+const COLOR_MEDIUM: u32 = 0xE67E22;
+const COLOR_HIGH: u32 = 0x2ECC71;
+
+#[derive(Debug, Serialize)]
+struct WebhookRequest {
+    embeds: Vec<Embed>,
+}
+
+#[derive(Debug, Serialize)]
+struct Embed {
+    title: String,
+    color: u32,
+    fields: Vec<EmbedField>,
+    timestamp: String,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbedField {
+    name: String,
+    value: String,
+    inline: bool,
+}
+
+/// Body of a 429 response; `retry_after` is in seconds
+#[derive(Debug, Deserialize)]
+struct RateLimitResponse {
+    retry_after: f64,
+}
+
+/// Posts rich embeds to a Discord webhook (`DISCORD_WEBHOOK_URL`)
+pub struct DiscordNotifier {
+    webhook_url: String,
+    client: reqwest::Client,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: String, client: reqwest::Client) -> Self {
+        Self {
+            webhook_url,
+            client,
+        }
+    }
+
+    pub async fn send_arbitrage(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.send_embed(arbitrage_embed(opportunity)).await
+    }
+
+    pub async fn send_triangular(&self, opportunity: &TriangularOpportunity) -> Result<()> {
+        self.send_embed(triangular_embed(opportunity)).await
+    }
+
+    async fn send_embed(&self, embed: Embed) -> Result<()> {
+        let request = WebhookRequest { embeds: vec![embed] };
+
+        for _ in 0..=MAX_RATE_LIMIT_RETRIES {
+            let response = self.client
+                .post(&self.webhook_url)
+                .json(&request)
+                .send()
+                .await?;
+
+            let status = response.status();
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                let body = response.text().await.unwrap_or_default();
+                let delay = retry_after(&body).unwrap_or(Duration::from_secs(1));
+                warn!(delay_ms = delay.as_millis() as u64, "Discord rate limited, retrying");
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_default();
+                anyhow::bail!("Discord webhook failed: {} - {}", status, body);
+            }
+
+            return Ok(());
+        }
+
+        anyhow::bail!("Discord webhook still rate limited after {} retries", MAX_RATE_LIMIT_RETRIES)
+    }
+}
+
+/// Delay requested by a 429 body
+fn retry_after(body: &str) -> Option<Duration> {
+    let response: RateLimitResponse = serde_json::from_str(body).ok()?;
+    Duration::try_from_secs_f64(response.retry_after).ok()
+}
+
+/// Color-code by spread: under 1% yellow, under 3% orange, otherwise green
+fn spread_color(spread_percent: Decimal) -> u32 {
+    if spread_percent < Decimal::ONE {
+        COLOR_LOW
+    } else if spread_percent < Decimal::from(3) {
+        COLOR_MEDIUM
+    } else {
+        COLOR_HIGH
+    }
+}
+
+fn field(name: &str, value: String, inline: bool) -> EmbedField {
+    EmbedField {
+        name: name.to_string(),
+        value,
+        inline,
+    }
+}
+
+fn embed_timestamp(timestamp_ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(timestamp_ms)
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+fn arbitrage_embed(opp: &ArbitrageOpportunity) -> Embed {
+    Embed {
+        title: format!("{} arbitrage", opp.symbol),
+        color: spread_color(opp.net_spread_percent),
+        fields: vec![
+            field("Buy", capitalize(&opp.buy_exchange), true),
+            field("Buy price", opp.buy_price.normalize().to_string(), true),
+            field("\u{200b}", "\u{200b}".to_string(), true),
+            field("Sell", capitalize(&opp.sell_exchange), true),
+            field("Sell price", opp.sell_price.normalize().to_string(), true),
+            field("\u{200b}", "\u{200b}".to_string(), true),
+            field("Spread", format!("{}%", opp.spread_percent.round_dp(3).normalize()), true),
+            field("Net spread", format!("{}%", opp.net_spread_percent.round_dp(3).normalize()), true),
+            field("Volume", opp.available_volume.normalize().to_string(), true),
+        ],
+        timestamp: embed_timestamp(opp.timestamp),
+    }
+}
+
+fn triangular_embed(opp: &TriangularOpportunity) -> Embed {
+    let legs: Vec<String> = opp
+        .legs
+        .iter()
+        .map(|leg| format!("{} {} at {}", leg.side, leg.symbol, leg.price.normalize()))
+        .collect();
+
+    Embed {
+        title: format!("{} triangular arbitrage", opp.path.join(" -> ")),
+        color: spread_color(opp.profit_percent),
+        fields: vec![
+            field("Exchange", capitalize(&opp.exchange), true),
+            field("Profit", format!("{}%", opp.profit_percent.round_dp(3).normalize()), true),
+            field("Legs", legs.join("\n"), false),
+        ],
+        timestamp: embed_timestamp(opp.timestamp),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_spread_color() {
+        assert_eq!(spread_color(dec("0.4")), COLOR_LOW);
+        assert_eq!(spread_color(dec("1.5")), COLOR_MEDIUM);
+        assert_eq!(spread_color(dec("3")), COLOR_HIGH);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let body = r#"{"message": "You are being rate limited.", "retry_after": 0.75, "global": false}"#;
+
+        assert_eq!(retry_after(body), Some(Duration::from_millis(750)));
+        assert_eq!(retry_after("rate limited"), None);
+        assert_eq!(retry_after(r#"{"retry_after": -1}"#), None);
+    }
+
+    #[test]
+    fn test_arbitrage_embed() {
+        let opp = ArbitrageOpportunity {
+            symbol: "ETH/USDT".to_string(),
+            buy_exchange: "kucoin".to_string(),
+            sell_exchange: "binance".to_string(),
+            buy_symbol: "ETH-USDT".to_string(),
+            sell_symbol: "ETHUSDT".to_string(),
+            buy_price: dec("2000"),
+            sell_price: dec("2040"),
+            spread_percent: dec("2"),
+            spread_usd: dec("40"),
+            net_spread_percent: dec("1.798"),
+            buy_fee: dec("0.001"),
+            sell_fee: dec("0.001"),
+            executable_quantity: dec("0.5"),
+            available_volume: dec("1.25"),
+            limiting_size: None,
+            timestamp: 1_700_000_000_000,
+        };
+
+        let embed = arbitrage_embed(&opp);
+        assert_eq!(embed.title, "ETH/USDT arbitrage");
+        assert_eq!(embed.color, COLOR_MEDIUM);
+        assert_eq!(embed.timestamp, "2023-11-14T22:13:20+00:00");
+
+        let value = |name: &str| embed.fields.iter().find(|f| f.name == name).unwrap().value.clone();
+        assert_eq!(value("Buy"), "KuCoin");
+        assert_eq!(value("Sell price"), "2040");
+        assert_eq!(value("Net spread"), "1.798%");
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::metrics::metrics;
+use crate::scanner::{ArbitrageOpportunity, TriangularOpportunity};
+
+/// How long the producer may wait for room in librdkafka's own queue
+const ENQUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Keyed JSON record waiting to be produced
+struct KafkaMessage {
+    key: String,
+    payload: String,
+}
+
+/// Produces opportunities as JSON to a topic (`KAFKA_BROKERS`, `KAFKA_TOPIC`).
+/// Records go through a bounded queue to a background producer task, so a slow
+/// broker never blocks the scanner; when the queue is full the record is dropped.
+pub struct KafkaNotifier {
+    tx: mpsc::Sender<KafkaMessage>,
+    /// Tells the producer task to drain the queue and stop
+    closing: CancellationToken,
+    producer_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl KafkaNotifier {
+    pub fn new(brokers: &str, topic: String, queue_size: usize) -> Result<Self> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("message.timeout.ms", "5000")
+            .create()
+            .context("Failed to create Kafka producer")?;
+
+        let (tx, mut rx) = mpsc::channel::<KafkaMessage>(queue_size.max(1));
+
+        let closing = CancellationToken::new();
+        let producer_task = tokio::spawn({
+            let closing = closing.clone();
+            async move {
+                loop {
+                    let message = tokio::select! {
+                        message = rx.recv() => message,
+                        // Stop accepting records but still produce what is already queued
+                        _ = closing.cancelled(), if !closing.is_cancelled() => {
+                            rx.close();
+                            continue;
+                        }
+                    };
+                    let Some(message) = message else { break };
+
+                    let record = FutureRecord::to(&topic)
+                        .key(&message.key)
+                        .payload(&message.payload);
+
+                    if let Err((e, _)) = producer.send(record, Timeout::After(ENQUEUE_TIMEOUT)).await {
+                        error!(error = ?e, key = %message.key, "Failed to produce Kafka message");
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            tx,
+            closing,
+            producer_task: Mutex::new(Some(producer_task)),
+        })
+    }
+
+    /// Keyed by normalized symbol so a pair always lands on the same partition
+    pub async fn send_arbitrage(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.enqueue(opportunity.symbol.clone(), serde_json::to_string(opportunity)?)
+    }
+
+    pub async fn send_triangular(&self, opportunity: &TriangularOpportunity) -> Result<()> {
+        self.enqueue(opportunity.cycle_key(), serde_json::to_string(opportunity)?)
+    }
+
+    /// Produce everything still queued and stop the producer task
+    pub async fn flush(&self) {
+        self.closing.cancel();
+        if let Some(task) = self.producer_task.lock().await.take() {
+            info!(queued = self.tx.max_capacity() - self.tx.capacity(), "Draining Kafka queue");
+            if let Err(e) = task.await {
+                error!(error = ?e, "Kafka producer task failed");
+            }
+        }
+    }
+
+    /// A full queue drops the record and counts it, but is not an error: retrying would only
+    /// delay the alert and dead-letter a copy of it
+    fn enqueue(&self, key: String, payload: String) -> Result<()> {
+        match self.tx.try_send(KafkaMessage { key, payload }) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(message)) => {
+                metrics().record_kafka_dropped();
+                warn!(key = %message.key, "Kafka queue full, dropping message");
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => anyhow::bail!("Kafka producer task stopped"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_full_queue_drops_without_error() {
+        let notifier = KafkaNotifier::new("127.0.0.1:1", "arbs".to_string(), 1).unwrap();
+
+        // The producer task does not get to run in between, so the queue holds only the first
+        for i in 0..3 {
+            assert!(notifier.enqueue(i.to_string(), "{}".to_string()).is_ok());
+        }
+        assert!(metrics().render().contains("arbscanner_kafka_dropped_total 2"));
+    }
+}
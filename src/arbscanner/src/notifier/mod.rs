@@ -0,0 +1,436 @@
+mod callback;
+mod dead_letter;
+mod discord;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod telegram;
+
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::metrics::metrics;
+use crate::scanner::{ArbitrageOpportunity, TriangularOpportunity};
+
+pub use callback::CallbackNotifier;
+pub use dead_letter::DeadLetterLog;
+pub use discord::DiscordNotifier;
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaNotifier;
+pub use telegram::TelegramNotifier;
+
+/// Longest wait between two delivery attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// An alert on its way to the sinks
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "opportunity", rename_all = "lowercase")]
+pub enum Alert {
+    Arbitrage(ArbitrageOpportunity),
+    Triangular(TriangularOpportunity),
+}
+
+/// How often a failed send is retried, doubling the delay each time
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            base_delay: Duration::ZERO,
+        }
+    }
+
+    /// Delay before retry number `attempt` (0-based)
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+/// A destination for alerts
+pub enum Sink {
+    Callback(CallbackNotifier),
+    Telegram(TelegramNotifier),
+    Discord(DiscordNotifier),
+    #[cfg(feature = "kafka")]
+    Kafka(KafkaNotifier),
+}
+
+impl Sink {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sink::Callback(_) => "callback",
+            Sink::Telegram(_) => "telegram",
+            Sink::Discord(_) => "discord",
+            #[cfg(feature = "kafka")]
+            Sink::Kafka(_) => "kafka",
+        }
+    }
+    
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        match alert {
+            Alert::Arbitrage(opportunity) => self.send_arbitrage(opportunity).await,
+            Alert::Triangular(opportunity) => self.send_triangular(opportunity).await,
+        }
+    }
+    
+    async fn send_arbitrage(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        match self {
+            Sink::Callback(sink) => sink.send_arbitrage(opportunity).await,
+            Sink::Telegram(sink) => sink.send_arbitrage(opportunity).await,
+            Sink::Discord(sink) => sink.send_arbitrage(opportunity).await,
+            #[cfg(feature = "kafka")]
+            Sink::Kafka(sink) => sink.send_arbitrage(opportunity).await,
+        }
+    }
+    
+    async fn send_triangular(&self, opportunity: &TriangularOpportunity) -> Result<()> {
+        match self {
+            Sink::Callback(sink) => sink.send_triangular(opportunity).await,
+            Sink::Telegram(sink) => sink.send_triangular(opportunity).await,
+            Sink::Discord(sink) => sink.send_triangular(opportunity).await,
+            #[cfg(feature = "kafka")]
+            Sink::Kafka(sink) => sink.send_triangular(opportunity).await,
+        }
+    }
+    
+    /// Push out anything the sink buffers internally
+    async fn flush(&self) {
+        #[cfg(feature = "kafka")]
+        if let Sink::Kafka(sink) = self {
+            sink.flush().await;
+        }
+    }
+}
+
+/// Fans opportunities out to every configured sink.
+/// Cooldowns are applied by the scanner before anything reaches here, so all sinks share them.
+/// Each send runs on its own task with retries, so a slow or failing sink never stalls the scanner;
+/// alerts that still fail go to the dead-letter log when one is configured.
+pub struct Notifier {
+    sinks: Vec<Arc<Sink>>,
+    retry: RetryPolicy,
+    dead_letter: Option<Arc<DeadLetterLog>>,
+    /// Deliveries still running, so shutdown can wait for them
+    in_flight: TaskTracker,
+}
+
+impl Notifier {
+    /// Build the sinks enabled in `config`
+    pub fn new(config: Arc<Config>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap();
+        
+        let mut sinks = Vec::new();
+        if !config.callback_url.is_empty() {
+            let mut callback = CallbackNotifier::new(config.callback_url.clone(), client.clone());
+            if let Some(secret) = &config.callback_secret {
+                callback = callback.with_secret(secret.clone());
+            }
+            sinks.push(Sink::Callback(callback));
+        }
+        if let (Some(token), Some(chat_id)) = (&config.telegram_bot_token, &config.telegram_chat_id) {
+            sinks.push(Sink::Telegram(TelegramNotifier::new(
+                token.clone(),
+                chat_id.clone(),
+                client.clone(),
+            )));
+        }
+        if let Some(webhook_url) = &config.discord_webhook_url {
+            sinks.push(Sink::Discord(DiscordNotifier::new(
+                webhook_url.clone(),
+                client.clone(),
+            )));
+        }
+        if let Some(brokers) = &config.kafka_brokers {
+            #[cfg(feature = "kafka")]
+            sinks.push(Sink::Kafka(KafkaNotifier::new(
+                brokers,
+                config.kafka_topic.clone(),
+                config.kafka_queue_size,
+            )?));
+            #[cfg(not(feature = "kafka"))]
+            tracing::warn!(brokers = %brokers, "KAFKA_BROKERS is set but arbscanner was built without the `kafka` feature");
+        }
+        
+        let mut notifier = Self::with_sinks(sinks).with_retry(RetryPolicy {
+            max_retries: config.notify_max_retries,
+            base_delay: Duration::from_millis(config.notify_retry_base_ms),
+        });
+        if let Some(path) = &config.notify_dlq_path {
+            notifier = notifier.with_dead_letter(Arc::new(DeadLetterLog::open(path)?));
+        }
+        
+        Ok(notifier)
+    }
+    
+    pub fn with_sinks(sinks: Vec<Sink>) -> Self {
+        Self {
+            sinks: sinks.into_iter().map(Arc::new).collect(),
+            retry: RetryPolicy::none(),
+            dead_letter: None,
+            in_flight: TaskTracker::new(),
+        }
+    }
+    
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    
+    pub fn with_dead_letter(mut self, dead_letter: Arc<DeadLetterLog>) -> Self {
+        self.dead_letter = Some(dead_letter);
+        self
+    }
+    
+    pub fn sink_names(&self) -> Vec<&'static str> {
+        self.sinks.iter().map(|sink| sink.name()).collect()
+    }
+    
+    pub fn notify(&self, opportunity: ArbitrageOpportunity) {
+        info!(
+            pair = %opportunity.symbol,
+            spread = %opportunity.spread_percent,
+            sinks = self.sinks.len(),
+            "Sending notification"
+        );
+        
+        self.dispatch(Alert::Arbitrage(opportunity));
+    }
+    
+    pub fn notify_triangular(&self, opportunity: TriangularOpportunity) {
+        info!(
+            exchange = %opportunity.exchange,
+            path = %opportunity.path.join(" -> "),
+            profit = %opportunity.profit_percent,
+            sinks = self.sinks.len(),
+            "Sending triangular notification"
+        );
+        
+        self.dispatch(Alert::Triangular(opportunity));
+    }
+    
+    /// Wait for in-flight deliveries (retries included) and flush buffering sinks.
+    /// Alerts dispatched after this still go out, but nothing waits for them.
+    pub async fn flush(&self) {
+        self.in_flight.close();
+        info!(pending = self.in_flight.len(), "Flushing notifications");
+        self.in_flight.wait().await;
+        
+        for sink in &self.sinks {
+            sink.flush().await;
+        }
+    }
+    
+    fn dispatch(&self, alert: Alert) {
+        for sink in &self.sinks {
+            self.in_flight.spawn(deliver(
+                sink.clone(),
+                alert.clone(),
+                self.retry,
+                self.dead_letter.clone(),
+            ));
+        }
+    }
+}
+
+/// Send `alert` to one sink, dead-lettering it if every attempt fails
+async fn deliver(
+    sink: Arc<Sink>,
+    alert: Alert,
+    retry: RetryPolicy,
+    dead_letter: Option<Arc<DeadLetterLog>>,
+) {
+    match send_with_retry(&sink, &alert, retry).await {
+        Ok(()) => {
+            metrics().record_notification(true);
+            info!(sink = sink.name(), "Notification sent successfully");
+        }
+        Err(e) => {
+            metrics().record_notification(false);
+            error!(sink = sink.name(), error = ?e, "Failed to send notification");
+
+            if let Some(dead_letter) = dead_letter {
+                if let Err(e) = dead_letter.append(sink.name(), &alert, &e).await {
+                    error!(sink = sink.name(), error = ?e, "Failed to write dead letter");
+                }
+            }
+        }
+    }
+}
+
+async fn send_with_retry(sink: &Sink, alert: &Alert, retry: RetryPolicy) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        match sink.send(alert).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < retry.max_retries => {
+                let delay = retry.delay(attempt);
+                warn!(
+                    sink = sink.name(),
+                    attempt = attempt + 1,
+                    delay_ms = delay.as_millis() as u64,
+                    error = ?e,
+                    "Notification failed, retrying"
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Display name for an exchange id
+pub(crate) fn capitalize(s: &str) -> String {
+    let mapping = [
+        ("binance", "Binance"),
+        ("bybit", "Bybit"),
+        ("okx", "OKX"),
+        ("kraken", "Kraken"),
+        ("kucoin", "KuCoin"),
+        ("gate", "Gate.io"),
+        ("mexc", "MEXC"),
+        ("htx", "HTX"),
+        ("bitget", "Bitget"),
+        ("coinbase", "Coinbase"),
+        ("bitfinex", "Bitfinex"),
+    ];
+    
+    for (key, val) in mapping {
+        if s.to_lowercase() == key {
+            return val.to_string();
+        }
+    }
+    
+    let mut chars = s.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::StatusCode, routing::post, Router};
+    use rust_decimal::Decimal;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn opportunity() -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            symbol: "BTC/USDT".to_string(),
+            buy_exchange: "binance".to_string(),
+            sell_exchange: "okx".to_string(),
+            buy_symbol: "BTCUSDT".to_string(),
+            sell_symbol: "BTC-USDT".to_string(),
+            buy_price: Decimal::from(100),
+            sell_price: Decimal::from(101),
+            spread_percent: Decimal::ONE,
+            spread_usd: Decimal::ONE,
+            net_spread_percent: Decimal::ONE,
+            buy_fee: Decimal::ZERO,
+            sell_fee: Decimal::ZERO,
+            executable_quantity: Decimal::ONE,
+            available_volume: Decimal::ONE,
+            limiting_size: None,
+            timestamp: 0,
+        }
+    }
+
+    /// Callback endpoint that answers 500 to the first `failures` requests, then 200
+    async fn mock_callback(failures: u32) -> (String, Arc<AtomicU32>) {
+        let hits = Arc::new(AtomicU32::new(0));
+        let app = Router::new()
+            .route(
+                "/",
+                post(move |State(hits): State<Arc<AtomicU32>>| async move {
+                    if hits.fetch_add(1, Ordering::SeqCst) < failures {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    } else {
+                        StatusCode::OK
+                    }
+                }),
+            )
+            .with_state(hits.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, hits)
+    }
+
+    fn retry(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_and_caps() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(500),
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(500));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(20), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_callback_succeeds() {
+        let (url, hits) = mock_callback(2).await;
+        let sink = Sink::Callback(CallbackNotifier::new(url, reqwest::Client::new()));
+
+        let result = send_with_retry(&sink, &Alert::Arbitrage(opportunity()), retry(3)).await;
+
+        assert!(result.is_ok());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_flush_waits_for_retries() {
+        let (url, hits) = mock_callback(2).await;
+        let notifier = Notifier::with_sinks(vec![Sink::Callback(CallbackNotifier::new(
+            url,
+            reqwest::Client::new(),
+        ))])
+        .with_retry(retry(3));
+
+        notifier.notify(opportunity());
+        notifier.flush().await;
+
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_retries_are_dead_lettered() {
+        let (url, hits) = mock_callback(u32::MAX).await;
+        let sink = Arc::new(Sink::Callback(CallbackNotifier::new(url, reqwest::Client::new())));
+        let dlq = tempfile::NamedTempFile::new().unwrap();
+        let dead_letter = Arc::new(DeadLetterLog::open(dlq.path()).unwrap());
+
+        deliver(sink, Alert::Arbitrage(opportunity()), retry(1), Some(dead_letter)).await;
+
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        let contents = std::fs::read_to_string(dlq.path()).unwrap();
+        let record: serde_json::Value = serde_json::from_str(contents.trim()).unwrap();
+        assert_eq!(record["sink"], "callback");
+        assert_eq!(record["kind"], "arbitrage");
+        assert_eq!(record["opportunity"]["symbol"], "BTC/USDT");
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::capitalize;
+use crate::scanner::{ArbitrageOpportunity, TriangularOpportunity};
+
+const API_URL: &str = "https://api.telegram.org";
+
+#[derive(Debug, Serialize)]
+struct SendMessageRequest<'a> {
+    chat_id: &'a str,
+    text: String,
+    disable_web_page_preview: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendMessageResponse {
+    ok: bool,
+    description: Option<String>,
+}
+
+/// Posts human-readable alerts to a chat via the Bot API (`TELEGRAM_BOT_TOKEN`, `TELEGRAM_CHAT_ID`)
+pub struct TelegramNotifier {
+    bot_token: String,
+    chat_id: String,
+    client: reqwest::Client,
+}
+
+impl TelegramNotifier {
+    pub fn new(bot_token: String, chat_id: String, client: reqwest::Client) -> Self {
+        Self {
+            bot_token,
+            chat_id,
+            client,
+        }
+    }
+
+    pub async fn send_arbitrage(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.send_message(format_arbitrage(opportunity)).await
+    }
+
+    pub async fn send_triangular(&self, opportunity: &TriangularOpportunity) -> Result<()> {
+        self.send_message(format_triangular(opportunity)).await
+    }
+
+    async fn send_message(&self, text: String) -> Result<()> {
+        let request = SendMessageRequest {
+            chat_id: &self.chat_id,
+            text,
+            disable_web_page_preview: true,
+        };
+
+        let response: SendMessageResponse = self
+            .client
+            .post(format!("{}/bot{}/sendMessage", API_URL, self.bot_token))
+            .json(&request)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        if !response.ok {
+            anyhow::bail!(
+                "Telegram sendMessage failed: {}",
+                response.description.unwrap_or_default()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn format_arbitrage(opp: &ArbitrageOpportunity) -> String {
+    format!(
+        "{} arbitrage\n\
+         Buy on {} at {}\n\
+         Sell on {} at {}\n\
+         Spread: {}% (net {}%)\n\
+         Volume: {}",
+        opp.symbol,
+        capitalize(&opp.buy_exchange),
+        opp.buy_price.normalize(),
+        capitalize(&opp.sell_exchange),
+        opp.sell_price.normalize(),
+        opp.spread_percent.round_dp(3).normalize(),
+        opp.net_spread_percent.round_dp(3).normalize(),
+        opp.available_volume.normalize(),
+    )
+}
+
+fn format_triangular(opp: &TriangularOpportunity) -> String {
+    let legs: Vec<String> = opp
+        .legs
+        .iter()
+        .map(|leg| format!("{} {} at {}", leg.side, leg.symbol, leg.price.normalize()))
+        .collect();
+
+    format!(
+        "{} triangular arbitrage on {}\n{}\nProfit: {}%",
+        opp.path.join(" -> "),
+        capitalize(&opp.exchange),
+        legs.join("\n"),
+        opp.profit_percent.round_dp(3).normalize(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_format_arbitrage() {
+        let opp = ArbitrageOpportunity {
+            symbol: "BTC/USDT".to_string(),
+            buy_exchange: "okx".to_string(),
+            sell_exchange: "gate".to_string(),
+            buy_symbol: "BTC-USDT".to_string(),
+            sell_symbol: "BTC_USDT".to_string(),
+            buy_price: dec("100.50"),
+            sell_price: dec("101.40"),
+            spread_percent: dec("0.89552238"),
+            spread_usd: dec("0.90"),
+            net_spread_percent: dec("0.69402985"),
+            buy_fee: dec("0.001"),
+            sell_fee: dec("0.001"),
+            executable_quantity: dec("2"),
+            available_volume: dec("3.5"),
+            limiting_size: None,
+            timestamp: 0,
+        };
+
+        assert_eq!(
+            format_arbitrage(&opp),
+            "BTC/USDT arbitrage\n\
+             Buy on OKX at 100.5\n\
+             Sell on Gate.io at 101.4\n\
+             Spread: 0.896% (net 0.694%)\n\
+             Volume: 3.5"
+        );
+    }
+}
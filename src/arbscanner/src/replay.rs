@@ -0,0 +1,235 @@
+//! Record live price updates to NDJSON and play them back into the scanner,
+//! so a reported opportunity can be reproduced offline.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::exchanges::{publish, PriceUpdate};
+
+/// Updates a fast replay lets queue up unread before it waits for the scanner. Kept well
+/// under the price channel's capacity: a broadcast channel drops the oldest updates of a
+/// receiver that falls behind, so a replay must never outrun the scanner by that much.
+const FAST_REPLAY_BACKLOG: usize = 1_000;
+const FAST_REPLAY_POLL: Duration = Duration::from_millis(1);
+
+/// Appends every update seen on the price channel to `RECORD_PATH`, one JSON object per line
+pub struct Recorder {
+    writer: BufWriter<tokio::fs::File>,
+}
+
+impl Recorder {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open recording {}", path.display()))?;
+
+        info!(path = %path.display(), "Recording price updates");
+        Ok(Self {
+            writer: BufWriter::new(tokio::fs::File::from_std(file)),
+        })
+    }
+
+    /// Write updates from `price_rx` until `shutdown` fires or the channel closes, then flush
+    pub async fn run(mut self, mut price_rx: broadcast::Receiver<PriceUpdate>, shutdown: CancellationToken) -> Result<()> {
+        let mut recorded = 0u64;
+        loop {
+            let update = tokio::select! {
+                result = price_rx.recv() => match result {
+                    Ok(update) => update,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Recorder lagged, recording has gaps");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = shutdown.cancelled() => break,
+            };
+
+            let mut line = serde_json::to_vec(&update)?;
+            line.push(b'\n');
+            self.writer.write_all(&line).await?;
+            recorded += 1;
+        }
+
+        self.writer.flush().await?;
+        info!(recorded, "Recording closed");
+        Ok(())
+    }
+}
+
+/// Maps recorded timestamps onto the wall clock, so replayed quotes look as fresh
+/// to the scanner as they did live
+#[derive(Debug, Default)]
+struct ReplayClock {
+    first: Option<i64>,
+    latest: i64,
+}
+
+impl ReplayClock {
+    /// Advance to the update stamped `timestamp`; returns how far into the recording it is.
+    /// Updates are recorded in arrival order, so an older stamp never moves the clock back.
+    fn advance(&mut self, timestamp: i64) -> Duration {
+        let first = *self.first.get_or_insert(timestamp);
+        self.latest = self.latest.max(timestamp);
+        Duration::from_millis((self.latest - first).max(0) as u64)
+    }
+
+    /// Restamp relative to `now_ms`, keeping the update's age against the newest one replayed
+    fn rebase(&self, timestamp: i64, now_ms: i64) -> i64 {
+        now_ms - (self.latest - timestamp)
+    }
+}
+
+/// Feed a recording into `price_tx` at its original pace, or as fast as the scanner keeps up
+/// when `fast`. Returns once the file is exhausted or `shutdown` fires.
+pub async fn replay(
+    path: String,
+    price_tx: broadcast::Sender<PriceUpdate>,
+    fast: bool,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let file = tokio::fs::File::open(&path)
+        .await
+        .with_context(|| format!("Failed to open recording {}", path))?;
+    let mut lines = BufReader::new(file).lines();
+
+    info!(path = %path, fast, "Replaying price updates");
+
+    let started = tokio::time::Instant::now();
+    let mut clock = ReplayClock::default();
+    let mut line_no = 0;
+    let mut replayed = 0u64;
+
+    while let Some(line) = lines.next_line().await? {
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut update: PriceUpdate = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid price update on line {} of {}", line_no, path))?;
+
+        let offset = clock.advance(update.timestamp);
+        if !fast {
+            tokio::select! {
+                _ = tokio::time::sleep_until(started + offset) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        } else if !wait_for_backlog(&price_tx, &shutdown).await {
+            break;
+        }
+
+        update.timestamp = clock.rebase(update.timestamp, chrono::Utc::now().timestamp_millis());
+        publish(&price_tx, update);
+        replayed += 1;
+    }
+
+    info!(replayed, "Replay finished");
+    Ok(())
+}
+
+/// Wait until fewer than `FAST_REPLAY_BACKLOG` updates are unread; false once `shutdown` fires
+async fn wait_for_backlog(price_tx: &broadcast::Sender<PriceUpdate>, shutdown: &CancellationToken) -> bool {
+    while price_tx.len() >= FAST_REPLAY_BACKLOG {
+        tokio::select! {
+            _ = tokio::time::sleep(FAST_REPLAY_POLL) => {}
+            _ = shutdown.cancelled() => return false,
+        }
+    }
+    !shutdown.is_cancelled()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn update(exchange: &str, timestamp: i64) -> PriceUpdate {
+        PriceUpdate {
+            exchange: exchange.to_string(),
+            symbol: "BTC/USDT".to_string(),
+            raw_symbol: "BTCUSDT".to_string(),
+            bid: Decimal::from(100),
+            ask: Decimal::from(101),
+            bid_size: Decimal::ONE,
+            ask_size: Decimal::ONE,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_replay_clock() {
+        let mut clock = ReplayClock::default();
+
+        assert_eq!(clock.advance(1_000), Duration::ZERO);
+        assert_eq!(clock.advance(1_250), Duration::from_millis(250));
+        // Out-of-order stamp: released right away, but keeps its age
+        assert_eq!(clock.advance(1_200), Duration::from_millis(250));
+        assert_eq!(clock.rebase(1_200, 50_000), 49_950);
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.ndjson");
+
+        let (live_tx, live_rx) = broadcast::channel(16);
+        let shutdown = CancellationToken::new();
+        let recorder = tokio::spawn(Recorder::open(&path).unwrap().run(live_rx, shutdown.clone()));
+        live_tx.send(update("binance", 1_000)).unwrap();
+        live_tx.send(update("okx", 1_040)).unwrap();
+        drop(live_tx);
+        recorder.await.unwrap().unwrap();
+
+        let (replay_tx, mut replay_rx) = broadcast::channel(16);
+        replay(path.display().to_string(), replay_tx, false, shutdown).await.unwrap();
+
+        let first = replay_rx.recv().await.unwrap();
+        let second = replay_rx.recv().await.unwrap();
+        assert_eq!(first.exchange, "binance");
+        assert_eq!(second.exchange, "okx");
+        // Restamped to the present, released about 40ms apart as recorded
+        assert!((30..500).contains(&(second.timestamp - first.timestamp)));
+        assert!(chrono::Utc::now().timestamp_millis() - second.timestamp < 1_000);
+        assert!(replay_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fast_replay_waits_for_the_scanner() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.ndjson");
+        let total = 5 * FAST_REPLAY_BACKLOG as i64;
+        let lines: Vec<String> = (0..total)
+            .map(|i| serde_json::to_string(&update("binance", i)).unwrap())
+            .collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        // Much smaller than the recording: without backpressure the reader would lag
+        let (replay_tx, mut replay_rx) = broadcast::channel(2 * FAST_REPLAY_BACKLOG);
+        let replaying = tokio::spawn(replay(path.display().to_string(), replay_tx, true, CancellationToken::new()));
+
+        let mut received = 0;
+        loop {
+            match replay_rx.recv().await {
+                Ok(_) => received += 1,
+                Err(RecvError::Lagged(skipped)) => panic!("scanner lagged by {skipped}"),
+                Err(RecvError::Closed) => break,
+            }
+            // A scanner slower than the file
+            if received % 100 == 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+        replaying.await.unwrap().unwrap();
+        assert_eq!(received, total);
+    }
+}
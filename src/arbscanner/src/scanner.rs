@@ -1,28 +1,139 @@
 use anyhow::Result;
 use dashmap::DashMap;
 use rust_decimal::Decimal;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use tracing::{debug, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 
 use crate::config::Config;
+use crate::cooldown;
 use crate::exchanges::PriceUpdate;
-use crate::matcher::TickerMatcher;
+use crate::matcher::{is_leveraged_token, TickerMatcher};
+use crate::metrics::metrics;
 use crate::notifier::Notifier;
+use crate::store::OpportunityStore;
+
+/// Alerted opportunities buffered per stream subscriber before it starts lagging
+const STREAM_CAPACITY: usize = 1024;
+
+/// How often cooldowns are written to `COOLDOWN_STATE_PATH` (they are also written on shutdown)
+const COOLDOWN_SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
 
 /// Arbitrage opportunity
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ArbitrageOpportunity {
     pub symbol: String,
     pub buy_exchange: String,
     pub sell_exchange: String,
+    /// Exchange pair actually bought (differs from `symbol` with stablecoin equivalence)
+    pub buy_symbol: String,
+    /// Exchange pair actually sold
+    pub sell_symbol: String,
     pub buy_price: Decimal,
     pub sell_price: Decimal,
     pub spread_percent: Decimal,
     pub spread_usd: Decimal,
+    /// Spread after paying taker fees on both legs
+    pub net_spread_percent: Decimal,
+    /// Taker fee fraction assumed on the buy exchange
+    pub buy_fee: Decimal,
+    /// Taker fee fraction assumed on the sell exchange
+    pub sell_fee: Decimal,
+    /// Base quantity the prices above are volume-weighted over (zero = top of book only)
+    pub executable_quantity: Decimal,
+    /// Base quantity available where the buy venue's asks are below the sell venue's bids
+    pub available_volume: Decimal,
+    /// Smaller of the best ask size bought and best bid size sold; `None` when an exchange sends no size
+    pub limiting_size: Option<Decimal>,
+    pub timestamp: i64,
+}
+
+impl ArbitrageOpportunity {
+    /// Direction-independent key: buying on A to sell on B shares a cooldown with the reverse
+    pub fn pair_key(&self) -> String {
+        let (a, b) = if self.buy_exchange <= self.sell_exchange {
+            (&self.buy_exchange, &self.sell_exchange)
+        } else {
+            (&self.sell_exchange, &self.buy_exchange)
+        };
+        format!("{}-{}-{}", self.symbol, a, b)
+    }
+}
+
+/// Best opportunity on a symbol, plus the next best exchange pair for depth
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolOpportunity {
+    #[serde(flatten)]
+    pub best: ArbitrageOpportunity,
+    /// Next pairing in line after `best` that also clears the thresholds and filters
+    pub runner_up: Option<ArbitrageOpportunity>,
+}
+
+/// One trade in a triangular cycle
+#[derive(Debug, Clone, Serialize)]
+pub struct TriangularLeg {
+    pub symbol: String,
+    /// "buy" (pay the ask) or "sell" (hit the bid)
+    pub side: &'static str,
+    pub price: Decimal,
+}
+
+/// Triangular arbitrage within a single exchange, e.g. USDT -> BTC -> ETH -> USDT
+#[derive(Debug, Clone, Serialize)]
+pub struct TriangularOpportunity {
+    pub exchange: String,
+    /// Currencies in trade order; the cycle returns to `path[0]`
+    pub path: [String; 3],
+    pub legs: [TriangularLeg; 3],
+    pub profit_percent: Decimal,
     pub timestamp: i64,
 }
 
+impl TriangularOpportunity {
+    /// Rotation-independent key: A->B->C, B->C->A and C->A->B are the same cycle
+    pub fn cycle_key(&self) -> String {
+        let start = (0..3).min_by_key(|&i| &self.path[i]).unwrap_or(0);
+        let rotated: Vec<&str> = (0..3).map(|i| self.path[(start + i) % 3].as_str()).collect();
+        format!("tri-{}-{}", self.exchange, rotated.join(">"))
+    }
+}
+
+/// Shared read-only access to the scanner's latest prices and opportunities
+#[derive(Clone)]
+pub struct ScannerHandle {
+    prices: Arc<DashMap<String, DashMap<String, PriceUpdate>>>,
+    recent: Arc<DashMap<String, SymbolOpportunity>>,
+    alerts: broadcast::Sender<ArbitrageOpportunity>,
+}
+
+impl ScannerHandle {
+    /// Latest update from every exchange quoting `symbol`, sorted by exchange
+    pub fn prices(&self, symbol: &str) -> Option<Vec<PriceUpdate>> {
+        let by_exchange = self.prices.get(symbol)?;
+        let mut updates: Vec<PriceUpdate> = by_exchange.iter().map(|e| e.value().clone()).collect();
+        updates.sort_by(|a, b| a.exchange.cmp(&b.exchange));
+        Some(updates)
+    }
+
+    /// Most recent opportunity per symbol with its runner-up, newest first
+    pub fn recent_opportunities(&self) -> Vec<SymbolOpportunity> {
+        let mut opportunities: Vec<SymbolOpportunity> =
+            self.recent.iter().map(|e| e.value().clone()).collect();
+        opportunities.sort_by_key(|o| std::cmp::Reverse(o.best.timestamp));
+        opportunities
+    }
+
+    /// Live feed of opportunities that passed the alert cooldown
+    pub fn subscribe(&self) -> broadcast::Receiver<ArbitrageOpportunity> {
+        self.alerts.subscribe()
+    }
+}
+
 /// Scans for arbitrage opportunities across exchanges
 pub struct ArbitrageScanner {
     config: Arc<Config>,
@@ -31,10 +142,25 @@ pub struct ArbitrageScanner {
     price_rx: broadcast::Receiver<PriceUpdate>,
     
     /// Latest prices: Symbol -> Exchange -> PriceUpdate
-    prices: DashMap<String, DashMap<String, PriceUpdate>>,
+    prices: Arc<DashMap<String, DashMap<String, PriceUpdate>>>,
+
+    /// Most recent opportunity found per symbol
+    recent: Arc<DashMap<String, SymbolOpportunity>>,
+
+    /// Opportunities that passed the cooldown, for stream subscribers
+    alerts: broadcast::Sender<ArbitrageOpportunity>,
     
     /// Last alert time per opportunity key
     last_alert: DashMap<String, i64>,
+
+    /// Currency -> normalized symbols containing it (for triangular cycle lookup)
+    symbols_by_currency: DashMap<String, HashSet<String>>,
+
+    /// Optional history of detected opportunities
+    store: Option<Arc<OpportunityStore>>,
+
+    /// Where `last_alert` is persisted between runs
+    cooldown_state: Option<PathBuf>,
 }
 
 impl ArbitrageScanner {
@@ -49,15 +175,56 @@ impl ArbitrageScanner {
             matcher,
             notifier,
             price_rx,
-            prices: DashMap::new(),
+            prices: Arc::new(DashMap::new()),
+            recent: Arc::new(DashMap::new()),
+            alerts: broadcast::channel(STREAM_CAPACITY).0,
             last_alert: DashMap::new(),
+            symbols_by_currency: DashMap::new(),
+            store: None,
+            cooldown_state: None,
+        }
+    }
+    
+    /// Read-only view of the scanner's state for the API; stays valid after `run` consumes the scanner
+    pub fn handle(&self) -> ScannerHandle {
+        ScannerHandle {
+            prices: self.prices.clone(),
+            recent: self.recent.clone(),
+            alerts: self.alerts.clone(),
+        }
+    }
+
+    /// Persist every detected opportunity to `store`, including those the cooldown silences
+    pub fn with_store(mut self, store: Arc<OpportunityStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+    
+    /// Restore cooldowns saved at `path` and keep saving them there while running.
+    /// An unreadable state is logged and ignored rather than blocking startup.
+    pub fn with_cooldown_state(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let now = chrono::Utc::now().timestamp_millis();
+        match cooldown::load(&path, self.config.cooldown_ms, now) {
+            Ok(entries) => {
+                info!(path = %path.display(), restored = entries.len(), "Cooldown state loaded");
+                self.last_alert.extend(entries);
+            }
+            Err(e) => warn!(error = ?e, "Ignoring cooldown state"),
         }
+        self.cooldown_state = Some(path);
+        self
     }
     
-    pub async fn run(mut self) -> Result<()> {
+    /// Scan price updates until `shutdown` fires or every price sender is gone
+    pub async fn run(mut self, shutdown: CancellationToken) -> Result<()> {
         info!("ArbitrageScanner started");
         
+        let mut cooldown_interval = tokio::time::interval(COOLDOWN_SAVE_INTERVAL);
         let mut stats_interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        let mut eviction_interval = tokio::time::interval(tokio::time::Duration::from_millis(
+            self.config.max_price_age_ms.max(1000),
+        ));
         
         loop {
             tokio::select! {
@@ -75,102 +242,310 @@ impl ArbitrageScanner {
                 _ = stats_interval.tick() => {
                     self.log_stats();
                 }
+                _ = eviction_interval.tick() => {
+                    self.evict_stale_prices();
+                }
+                _ = cooldown_interval.tick(), if self.cooldown_state.is_some() => {
+                    self.save_cooldowns().await;
+                }
+                _ = shutdown.cancelled() => {
+                    info!("ArbitrageScanner stopping");
+                    break;
+                }
             }
         }
         
+        self.save_cooldowns().await;
+        self.log_stats();
         Ok(())
     }
     
     async fn handle_price_update(&self, update: PriceUpdate) {
-        // Store latest price
-        self.prices
-            .entry(update.symbol.clone())
-            .or_insert_with(DashMap::new)
-            .insert(update.exchange.clone(), update.clone());
-        
-        // Check for arbitrage on this symbol
-        if let Some(opportunity) = self.find_arbitrage(&update.symbol) {
-            // Check cooldown
-            let key = format!(
-                "{}-{}-{}",
-                opportunity.symbol, opportunity.buy_exchange, opportunity.sell_exchange
+        // Crossed/locked books show up briefly during fast moves and would read as huge spreads
+        if update.bid >= update.ask {
+            metrics().record_crossed_book(&update.exchange);
+            debug!(
+                exchange = %update.exchange,
+                symbol = %update.symbol,
+                bid = %update.bid,
+                ask = %update.ask,
+                "Dropping crossed book"
             );
+            return;
+        }
+        
+        let leveraged = is_leveraged_token(&update.symbol);
+        if leveraged && self.config.exclude_leveraged_tokens {
+            return;
+        }
+        
+        // Store latest price
+        {
+            let by_exchange = self.prices.entry(update.symbol.clone()).or_default();
+            if by_exchange
+                .get(&update.exchange)
+                .is_some_and(|current| self.outranks(&current, &update))
+            {
+                return;
+            }
+            by_exchange.insert(update.exchange.clone(), update.clone());
+        }
+        metrics().set_tracked_symbols(self.prices.len());
+        
+        if self.config.enable_triangular {
+            if let Some((base, quote)) = update.symbol.split_once('/') {
+                for currency in [base, quote] {
+                    if !self.symbols_by_currency
+                        .get(currency)
+                        .is_some_and(|set| set.contains(&update.symbol))
+                    {
+                        self.symbols_by_currency
+                            .entry(currency.to_string())
+                            .or_default()
+                            .insert(update.symbol.clone());
+                    }
+                }
+            }
+        }
+        
+        // Check for arbitrage on this symbol; leveraged tokens from different issuers never match
+        let found = if leveraged { None } else { self.find_arbitrage(&update.symbol) };
+        if let Some(found) = found {
+            let opportunity = found.best.clone();
+            self.recent.insert(opportunity.symbol.clone(), found);
             
-            let now = chrono::Utc::now().timestamp_millis();
-            let last = self.last_alert.get(&key).map(|v| *v).unwrap_or(0);
+            metrics().record_opportunity();
             
-            if now - last >= self.config.cooldown_ms as i64 {
-                self.last_alert.insert(key, now);
-                
+            // The history keeps every detection; the cooldown only throttles alerts
+            if let Some(store) = &self.store {
+                store.record(&opportunity);
+            }
+            
+            // One cooldown per exchange pair, so a spread flipping direction doesn't re-alert
+            if self.acquire_cooldown(opportunity.pair_key()) {
                 info!(
                     symbol = %opportunity.symbol,
                     buy = %opportunity.buy_exchange,
                     sell = %opportunity.sell_exchange,
                     spread = %opportunity.spread_percent,
+                    net_spread = %opportunity.net_spread_percent,
+                    volume = %opportunity.available_volume,
                     "Arbitrage opportunity found!"
                 );
+
+                // No subscribers is not an error
+                let _ = self.alerts.send(opportunity.clone());
                 
                 // Send notification
-                self.notifier.notify(opportunity).await;
+                self.notifier.notify(opportunity);
+            }
+        }
+        
+        if self.config.enable_triangular {
+            for opportunity in self.find_triangular(&update.exchange, &update.symbol) {
+                metrics().record_triangular();
+                
+                if self.acquire_cooldown(opportunity.cycle_key()) {
+                    info!(
+                        exchange = %opportunity.exchange,
+                        path = %opportunity.path.join(" -> "),
+                        profit = %opportunity.profit_percent,
+                        "Triangular arbitrage opportunity found!"
+                    );
+                    
+                    self.notifier.notify_triangular(opportunity);
+                }
             }
         }
     }
     
-    fn find_arbitrage(&self, symbol: &str) -> Option<ArbitrageOpportunity> {
-        let prices = self.prices.get(symbol)?;
+    /// Returns true (and starts a new cooldown) if `key` hasn't alerted within `cooldown_ms`
+    fn acquire_cooldown(&self, key: String) -> bool {
+        let now = chrono::Utc::now().timestamp_millis();
+        let last = self.last_alert.get(&key).map(|v| *v).unwrap_or(0);
         
-        if prices.len() < 2 {
-            return None;
+        if now - last >= self.config.cooldown_ms as i64 {
+            self.last_alert.insert(key, now);
+            true
+        } else {
+            false
         }
+    }
+    
+    /// Write `last_alert` to the cooldown state file, if one is configured
+    async fn save_cooldowns(&self) {
+        let Some(path) = &self.cooldown_state else {
+            return;
+        };
+        let entries = self
+            .last_alert
+            .iter()
+            .map(|e| (e.key().clone(), *e.value()))
+            .collect();
+        let now = chrono::Utc::now().timestamp_millis();
         
-        // Find best bid (highest) and best ask (lowest) across exchanges
-        let mut best_bid: Option<(String, Decimal)> = None;
-        let mut best_ask: Option<(String, Decimal)> = None;
+        match cooldown::save(path, entries, self.config.cooldown_ms, now).await {
+            Ok(saved) => debug!(saved, "Cooldown state saved"),
+            Err(e) => warn!(error = ?e, "Failed to save cooldown state"),
+        }
+    }
+    
+    /// Whether a stored quote is recent enough to trade against
+    fn is_fresh(&self, timestamp: i64, now: i64) -> bool {
+        self.config.max_price_age_ms == 0
+            || now - timestamp <= self.config.max_price_age_ms as i64
+    }
+    
+    /// Whether the stored `current` quote should stay over `update` from the same exchange.
+    /// With stablecoin equivalence an exchange can list several pairs under one symbol
+    /// (BTCUSDT and BTCUSDC); the one in the earlier `STABLECOINS` quote wins, ties going to
+    /// the lower exchange symbol, and a stale quote gives way to any fresh one.
+    fn outranks(&self, current: &PriceUpdate, update: &PriceUpdate) -> bool {
+        if current.raw_symbol == update.raw_symbol || !self.is_fresh(current.timestamp, update.timestamp) {
+            return false;
+        }
+        let rank = |u: &PriceUpdate| {
+            let quote_rank = self.matcher.equivalent_quote_rank(&u.raw_symbol).unwrap_or(usize::MAX);
+            (quote_rank, u.raw_symbol.clone())
+        };
+        rank(current) < rank(update)
+    }
+    
+    /// Drop quotes from exchanges that stopped updating, and symbols left with none
+    fn evict_stale_prices(&self) {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut evicted = 0;
         
-        for entry in prices.iter() {
-            let exchange = entry.key().clone();
-            let update = entry.value();
-            
-            // Check filter
-            if !self.config.filter_exchanges.is_empty() 
-                && !self.config.filter_exchanges.contains(&exchange.to_lowercase()) 
-            {
-                continue;
-            }
-            
-            // Best bid = highest bid (where we can sell)
-            if best_bid.is_none() || update.bid > best_bid.as_ref().unwrap().1 {
-                best_bid = Some((exchange.clone(), update.bid));
+        self.prices.retain(|_, by_exchange| {
+            by_exchange.retain(|_, update| {
+                let fresh = self.is_fresh(update.timestamp, now);
+                if !fresh {
+                    evicted += 1;
+                }
+                fresh
+            });
+            !by_exchange.is_empty()
+        });
+        
+        metrics().set_tracked_symbols(self.prices.len());
+        
+        if evicted > 0 {
+            debug!(evicted, "Evicted stale prices");
+        }
+    }
+    
+    fn find_arbitrage(&self, symbol: &str) -> Option<SymbolOpportunity> {
+        // Copy out top of book and release the map guard before scanning
+        let mut quotes = {
+            let prices = self.prices.get(symbol)?;
+            if prices.len() < 2 {
+                return None;
             }
+            snapshot_quotes(&prices)
+        };
+        
+        let now = chrono::Utc::now().timestamp_millis();
+        quotes.retain(|quote| {
+            // Check filter
+            let allowed = self.config.filter_exchanges.is_empty()
+                || self.config.filter_exchanges.contains(&quote.exchange.to_lowercase());
             
-            // Best ask = lowest ask (where we can buy)
-            if best_ask.is_none() || update.ask < best_ask.as_ref().unwrap().1 {
-                best_ask = Some((exchange, update.ask));
-            }
-        }
+            // A stalled feed would pair a frozen price against live ones
+            allowed && self.is_fresh(quote.timestamp, now)
+        });
         
-        let (sell_exchange, sell_price) = best_bid?;
-        let (buy_exchange, buy_price) = best_ask?;
+        // The first pair to clear every filter is the best, so one venue failing a filter
+        // (a glitch above MAX_SPREAD_PERCENT, a thin book) doesn't hide the pairs behind it
+        let min_spread = self.config.min_spread_for(symbol);
+        let mut found = ranked_pairs(&quotes, self.ratio_floor(&quotes, min_spread))
+            .filter(|(buy, sell)| self.top_of_book_net_spread(buy, sell) >= min_spread)
+            .filter_map(|(buy, sell)| self.evaluate_pair(symbol, &buy.exchange, &sell.exchange));
+        let best = found.next()?;
+        let runner_up = found.next();
         
-        // No arbitrage if same exchange
-        if sell_exchange == buy_exchange {
-            return None;
+        Some(SymbolOpportunity { best, runner_up })
+    }
+    
+    /// Lowest `bid / ask` that could still net `min_spread` % after fees, charging both legs
+    /// the cheapest fee among `quotes`; every pair below it fails `top_of_book_net_spread`.
+    fn ratio_floor(&self, quotes: &[QuoteSnapshot], min_spread: Decimal) -> Decimal {
+        let fee = quotes
+            .iter()
+            .map(|quote| self.config.fee_for(&quote.exchange))
+            .min()
+            .unwrap_or_default();
+        if fee >= Decimal::ONE {
+            return Decimal::ZERO;
         }
+        (Decimal::ONE + fee + min_spread / Decimal::from(100)) / (Decimal::ONE - fee)
+    }
+    
+    /// Net spread % buying at `buy`'s best ask and selling at `sell`'s best bid. Walking the
+    /// books only worsens both prices, so a pair below the threshold here can't clear it in
+    /// `evaluate_pair`, and is skipped without copying its books.
+    fn top_of_book_net_spread(&self, buy: &QuoteSnapshot, sell: &QuoteSnapshot) -> Decimal {
+        let net = sell.bid * (Decimal::ONE - self.config.fee_for(&sell.exchange))
+            - buy.ask * (Decimal::ONE + self.config.fee_for(&buy.exchange));
+        (net / buy.ask) * Decimal::from(100)
+    }
+    
+    /// Price buying `symbol` on `buy_exchange` and selling on `sell_exchange` against both books;
+    /// `None` unless it clears the spread thresholds and filters
+    fn evaluate_pair(&self, symbol: &str, buy_exchange: &str, sell_exchange: &str) -> Option<ArbitrageOpportunity> {
+        // Only the two chosen updates are cloned, depth included
+        let (sell, buy) = {
+            let prices = self.prices.get(symbol)?;
+            let sell = prices.get(sell_exchange)?.clone();
+            let buy = prices.get(buy_exchange)?.clone();
+            (sell, buy)
+        };
         
         // Calculate spread: (sell_price - buy_price) / buy_price * 100
-        if buy_price.is_zero() {
+        if buy.ask.is_zero() {
             return None;
         }
         
+        // A few dollars at the top of book isn't executable
+        if !self.has_min_notional(symbol, &buy, &sell) {
+            return None;
+        }
+        let limiting_size = (!buy.ask_size.is_zero() && !sell.bid_size.is_zero())
+            .then(|| buy.ask_size.min(sell.bid_size));
+        
+        // Size both legs against the books for the configured notional
+        let asks = buy.ask_levels();
+        let bids = sell.bid_levels();
+        let available_volume = crossed_volume(&asks, &bids);
+        let (buy_price, sell_price, executable_quantity) =
+            executable_prices(&asks, &bids, self.config.execution_notional)
+                .unwrap_or((buy.ask, sell.bid, Decimal::ZERO));
+        let (buy_exchange, sell_exchange) = (buy.exchange, sell.exchange);
+        let (buy_symbol, sell_symbol) = (buy.raw_symbol, sell.raw_symbol);
+        
         let spread_usd = sell_price - buy_price;
         let spread_percent = (spread_usd / buy_price) * Decimal::from(100);
         
+        // Net of taker fees: sell * (1 - fee_sell) - buy * (1 + fee_buy)
+        let buy_fee = self.config.fee_for(&buy_exchange);
+        let sell_fee = self.config.fee_for(&sell_exchange);
+        let net = sell_price * (Decimal::ONE - sell_fee) - buy_price * (Decimal::ONE + buy_fee);
+        let net_spread_percent = (net / buy_price) * Decimal::from(100);
+        
         // Check thresholds
-        if spread_percent < self.config.min_spread_percent {
+        if net_spread_percent < self.config.min_spread_for(symbol) {
             return None;
         }
         
-        if spread_percent > self.config.max_spread_percent {
+        if spread_percent > self.config.max_spread_for(symbol) {
+            debug!(
+                symbol,
+                buy = %buy_exchange,
+                sell = %sell_exchange,
+                buy_price = %buy_price,
+                sell_price = %sell_price,
+                spread = %spread_percent,
+                "Spread above MAX_SPREAD_PERCENT, ignoring as anomaly"
+            );
             return None;
         }
         
@@ -186,14 +561,111 @@ impl ArbitrageScanner {
             symbol: symbol.to_string(),
             buy_exchange,
             sell_exchange,
+            buy_symbol,
+            sell_symbol,
             buy_price,
             sell_price,
             spread_percent,
             spread_usd,
+            net_spread_percent,
+            buy_fee,
+            sell_fee,
+            executable_quantity,
+            available_volume,
+            limiting_size,
             timestamp: chrono::Utc::now().timestamp_millis(),
         })
     }
     
+    /// Whether the best ask bought and the best bid sold are each worth `MIN_NOTIONAL_USD`.
+    /// Only stablecoin-quoted pairs are checked, since the notional is in the quote currency;
+    /// a zero size means the exchange doesn't report it and passes per `MIN_NOTIONAL_SKIP_UNSIZED`.
+    fn has_min_notional(&self, symbol: &str, buy: &PriceUpdate, sell: &PriceUpdate) -> bool {
+        let min = self.config.min_notional_usd;
+        let quote = symbol.split('/').nth(1).unwrap_or_default();
+        if min <= Decimal::ZERO || !self.config.stablecoins.iter().any(|s| s == quote) {
+            return true;
+        }
+        
+        [(buy.ask, buy.ask_size), (sell.bid, sell.bid_size)]
+            .iter()
+            .all(|&(price, size)| {
+                if size.is_zero() {
+                    self.config.min_notional_skip_unsized
+                } else {
+                    price * size >= min
+                }
+            })
+    }
+    
+    /// Find profitable 3-currency cycles on `exchange` that include `symbol`.
+    /// At most one opportunity (the better direction) is returned per cycle.
+    fn find_triangular(&self, exchange: &str, symbol: &str) -> Vec<TriangularOpportunity> {
+        let Some((x, y)) = symbol.split_once('/') else {
+            return Vec::new();
+        };
+        
+        let now = chrono::Utc::now().timestamp_millis();
+        let min_timestamp = now - self.config.triangular_max_leg_age_ms as i64;
+        
+        // Latest fresh quote for a currency pair on this exchange, in either orientation
+        let quote = |a: &str, b: &str| -> Option<PriceUpdate> {
+            [format!("{}/{}", a, b), format!("{}/{}", b, a)]
+                .iter()
+                .find_map(|s| {
+                    self.prices
+                        .get(s)
+                        .and_then(|by_exchange| by_exchange.get(exchange).map(|u| u.clone()))
+                })
+                .filter(|u| u.timestamp >= min_timestamp && !u.bid.is_zero() && !u.ask.is_zero())
+        };
+        
+        // Third currencies that trade against `x` on any exchange
+        let candidates: Vec<String> = self.symbols_by_currency
+            .get(x)
+            .map(|set| {
+                set.iter()
+                    .filter_map(|s| s.split_once('/'))
+                    .map(|(b, q)| if b == x { q.to_string() } else { b.to_string() })
+                    .filter(|z| z != x && z != y)
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .collect()
+            })
+            .unwrap_or_default();
+        
+        let mut opportunities = Vec::new();
+        for z in candidates {
+            let (Some(xy), Some(xz), Some(yz)) = (quote(x, y), quote(x, z.as_str()), quote(y, z.as_str())) else {
+                continue;
+            };
+            let quotes = [xy, xz, yz];
+            
+            let fee = self.config.fee_for(exchange);
+            let best = [[x, y, z.as_str()], [x, z.as_str(), y]]
+                .into_iter()
+                .filter_map(|path| triangular_cycle(exchange, path, &quotes, fee))
+                .max_by(|a, b| a.profit_percent.cmp(&b.profit_percent));
+            
+            // Every leg's overrides apply, so the strictest bound wins
+            if let Some(opp) = best {
+                let min_spread = opp.legs.iter()
+                    .map(|leg| self.config.min_spread_for(&leg.symbol))
+                    .max()
+                    .unwrap_or(self.config.min_spread_percent);
+                let max_spread = opp.legs.iter()
+                    .map(|leg| self.config.max_spread_for(&leg.symbol))
+                    .min()
+                    .unwrap_or(self.config.max_spread_percent);
+                if opp.profit_percent >= min_spread && opp.profit_percent <= max_spread {
+                    opportunities.push(opp);
+                }
+            }
+        }
+        
+        opportunities
+    }
+    
     fn log_stats(&self) {
         let symbols = self.prices.len();
         let total_prices: usize = self.prices.iter().map(|e| e.value().len()).sum();
@@ -206,6 +678,773 @@ impl ArbitrageScanner {
             "Scanner stats"
         );
         
+        for feed in metrics().feed_stats() {
+            info!(
+                exchange = %feed.exchange,
+                gap_ms = ?feed.gap_ms.map(|ms| ms.round() as i64),
+                latency_ms = ?feed.latency_ms.map(|ms| ms.round() as i64),
+                "Feed timing"
+            );
+        }
+        
         self.matcher.log_stats();
     }
 }
+
+/// Top of book for one exchange, copied out of the price map so no guard is held while scanning
+#[derive(Debug, Clone)]
+pub struct QuoteSnapshot {
+    pub exchange: String,
+    pub bid: Decimal,
+    pub ask: Decimal,
+    pub timestamp: i64,
+}
+
+/// Snapshot every exchange's quote for one symbol
+pub fn snapshot_quotes(by_exchange: &DashMap<String, PriceUpdate>) -> Vec<QuoteSnapshot> {
+    by_exchange
+        .iter()
+        .map(|entry| {
+            let update = entry.value();
+            QuoteSnapshot {
+                exchange: entry.key().clone(),
+                bid: update.bid,
+                ask: update.ask,
+                timestamp: update.timestamp,
+            }
+        })
+        .collect()
+}
+
+/// (buy, sell) pairings of two exchanges, most profitable at the top of book first, down to a
+/// `sell.bid / buy.ask` of `floor`. Bids are sorted once and each buy's pairings merged
+/// best-first, so pairs past the first few a caller takes are never priced.
+fn ranked_pairs(
+    quotes: &[QuoteSnapshot],
+    floor: Decimal,
+) -> impl Iterator<Item = (&QuoteSnapshot, &QuoteSnapshot)> {
+    // Stable, so equal bids keep their order and ties rank as if every pair were sorted
+    let mut sells: Vec<usize> = (0..quotes.len()).collect();
+    sells.sort_by_key(|&j| Reverse(quotes[j].bid));
+    
+    // Per buy, its next sell in `sells`; ties go to the earlier buy
+    let mut heap: BinaryHeap<(Decimal, Reverse<usize>, Reverse<usize>)> = quotes
+        .iter()
+        .enumerate()
+        .filter(|(_, buy)| !buy.ask.is_zero())
+        .map(|(i, buy)| (quotes[sells[0]].bid / buy.ask, Reverse(i), Reverse(0)))
+        .collect();
+    
+    std::iter::from_fn(move || loop {
+        let (ratio, Reverse(i), Reverse(k)) = heap.pop()?;
+        if ratio < floor {
+            return None;
+        }
+        if let Some(&next) = sells.get(k + 1) {
+            heap.push((quotes[next].bid / quotes[i].ask, Reverse(i), Reverse(k + 1)));
+        }
+        let (buy, sell) = (&quotes[i], &quotes[sells[k]]);
+        if buy.exchange != sell.exchange {
+            return Some((buy, sell));
+        }
+    })
+}
+
+/// Volume-weighted price to fill `quantity` from `levels` (best first); `None` if the book is too thin
+fn vwap(levels: &[(Decimal, Decimal)], quantity: Decimal) -> Option<Decimal> {
+    if quantity <= Decimal::ZERO {
+        return None;
+    }
+    
+    let mut remaining = quantity;
+    let mut cost = Decimal::ZERO;
+    for &(price, size) in levels {
+        let take = remaining.min(size);
+        cost += take * price;
+        remaining -= take;
+        if remaining.is_zero() {
+            return Some(cost / quantity);
+        }
+    }
+    None
+}
+
+/// Executable (buy, sell, quantity) for buying `notional` worth on `asks` and selling it into `bids`.
+/// The quantity shrinks to whatever both books can absorb; `None` if either side has no size.
+fn executable_prices(
+    asks: &[(Decimal, Decimal)],
+    bids: &[(Decimal, Decimal)],
+    notional: Decimal,
+) -> Option<(Decimal, Decimal, Decimal)> {
+    let best_ask = asks.first()?.0;
+    if best_ask.is_zero() {
+        return None;
+    }
+    
+    let ask_depth: Decimal = asks.iter().map(|(_, size)| *size).sum();
+    let bid_depth: Decimal = bids.iter().map(|(_, size)| *size).sum();
+    let quantity = (notional / best_ask).min(ask_depth).min(bid_depth);
+    
+    Some((vwap(asks, quantity)?, vwap(bids, quantity)?, quantity))
+}
+
+/// Base quantity that can be bought on `asks` and sold on `bids` at a profit, level by level
+fn crossed_volume(asks: &[(Decimal, Decimal)], bids: &[(Decimal, Decimal)]) -> Decimal {
+    let (mut i, mut j) = (0, 0);
+    let (mut ask_left, mut bid_left) = (
+        asks.first().map(|l| l.1).unwrap_or_default(),
+        bids.first().map(|l| l.1).unwrap_or_default(),
+    );
+    let mut volume = Decimal::ZERO;
+    
+    while i < asks.len() && j < bids.len() && asks[i].0 < bids[j].0 {
+        let take = ask_left.min(bid_left);
+        volume += take;
+        ask_left -= take;
+        bid_left -= take;
+        
+        if ask_left.is_zero() {
+            i += 1;
+            ask_left = asks.get(i).map(|l| l.1).unwrap_or_default();
+        }
+        if bid_left.is_zero() {
+            j += 1;
+            bid_left = bids.get(j).map(|l| l.1).unwrap_or_default();
+        }
+    }
+    
+    volume
+}
+
+/// Evaluate trading around `path` (c0 -> c1 -> c2 -> c0) using the quotes for its three pairs,
+/// paying the taker `fee` on every leg
+fn triangular_cycle(
+    exchange: &str,
+    path: [&str; 3],
+    quotes: &[PriceUpdate],
+    fee: Decimal,
+) -> Option<TriangularOpportunity> {
+    let mut rate = Decimal::ONE;
+    let mut legs = Vec::with_capacity(3);
+    
+    for i in 0..3 {
+        let (from, to) = (path[i], path[(i + 1) % 3]);
+        let sell = format!("{}/{}", from, to);
+        let buy = format!("{}/{}", to, from);
+        
+        // Selling `from` for `to` hits the bid; buying `to` with `from` pays the ask
+        let leg = if let Some(q) = quotes.iter().find(|q| q.symbol == sell) {
+            rate *= q.bid;
+            TriangularLeg { symbol: sell, side: "sell", price: q.bid }
+        } else if let Some(q) = quotes.iter().find(|q| q.symbol == buy) {
+            rate = rate.checked_div(q.ask)?;
+            TriangularLeg { symbol: buy, side: "buy", price: q.ask }
+        } else {
+            return None;
+        };
+        rate *= Decimal::ONE - fee;
+        legs.push(leg);
+    }
+    
+    let legs: [TriangularLeg; 3] = legs.try_into().ok()?;
+    Some(TriangularOpportunity {
+        exchange: exchange.to_string(),
+        path: path.map(str::to_string),
+        legs,
+        profit_percent: (rate - Decimal::ONE) * Decimal::from(100),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    fn scanner(vars: &[(&str, &str)]) -> ArbitrageScanner {
+        // No callback unless a test asks for one, so alerts never leave the machine
+        let vars: HashMap<String, String> = [("CALLBACK_URL", "")]
+            .iter()
+            .chain(vars)
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let config = Arc::new(Config::from_lookup(|key| vars.get(key).cloned()).unwrap());
+        let mut matcher = TickerMatcher::new();
+        if config.stablecoin_equivalence {
+            matcher = matcher.with_equivalent_quotes(config.stablecoins.clone());
+        }
+        let (_, price_rx) = broadcast::channel(16);
+        ArbitrageScanner::new(
+            config.clone(),
+            Arc::new(matcher),
+            Arc::new(Notifier::new(config).unwrap()),
+            price_rx,
+        )
+    }
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn quote(exchange: &str, symbol: &str, bid: &str, ask: &str, age_ms: i64) -> PriceUpdate {
+        PriceUpdate {
+            exchange: exchange.to_string(),
+            symbol: symbol.to_string(),
+            raw_symbol: symbol.replace('/', ""),
+            bid: dec(bid),
+            ask: dec(ask),
+            bid_size: Decimal::ONE,
+            ask_size: Decimal::ONE,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            timestamp: chrono::Utc::now().timestamp_millis() - age_ms,
+        }
+    }
+
+    fn levels(raw: &[(&str, &str)]) -> Vec<(Decimal, Decimal)> {
+        raw.iter().map(|(p, s)| (dec(p), dec(s))).collect()
+    }
+
+    fn snapshot(exchange: &str, bid: &str, ask: &str) -> QuoteSnapshot {
+        QuoteSnapshot {
+            exchange: exchange.to_string(),
+            bid: dec(bid),
+            ask: dec(ask),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_ranked_pairs() {
+        let quotes = [
+            snapshot("binance", "100", "101"),
+            snapshot("okx", "102", "103"),
+            snapshot("kraken", "99", "100.5"),
+            snapshot("bybit", "102", "0"),
+        ];
+        let names = |floor: &str| -> Vec<(String, String)> {
+            ranked_pairs(&quotes, dec(floor))
+                .map(|(buy, sell)| (buy.exchange.clone(), sell.exchange.clone()))
+                .collect()
+        };
+
+        // Same order as sorting every pair by bid / ask; a zero ask is never bought
+        let mut expected: Vec<(&QuoteSnapshot, &QuoteSnapshot)> = Vec::new();
+        for buy in quotes.iter().filter(|q| !q.ask.is_zero()) {
+            for sell in quotes.iter().filter(|q| q.exchange != buy.exchange) {
+                expected.push((buy, sell));
+            }
+        }
+        expected.sort_by_key(|(buy, sell)| Reverse(sell.bid / buy.ask));
+        let expected: Vec<(String, String)> = expected
+            .into_iter()
+            .map(|(buy, sell)| (buy.exchange.clone(), sell.exchange.clone()))
+            .collect();
+        assert_eq!(names("0"), expected);
+        assert_eq!(expected[..2], [
+            ("kraken".to_string(), "okx".to_string()),
+            ("kraken".to_string(), "bybit".to_string()),
+        ]);
+
+        // Stops at the floor
+        assert_eq!(names("1"), expected[..4]);
+        assert!(ranked_pairs(&[], Decimal::ZERO).next().is_none());
+    }
+
+    #[test]
+    fn test_snapshot_quotes() {
+        let by_exchange = DashMap::new();
+        let update = quote("binance", "BTC/USDT", "99", "100", 0);
+        by_exchange.insert("binance".to_string(), update.clone());
+
+        let snapshot = snapshot_quotes(&by_exchange);
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].exchange, "binance");
+        assert_eq!(snapshot[0].ask, dec("100"));
+        assert_eq!(snapshot[0].timestamp, update.timestamp);
+    }
+
+    #[test]
+    fn test_vwap_walks_levels() {
+        let asks = levels(&[("100", "1"), ("101", "1"), ("103", "2")]);
+
+        assert_eq!(vwap(&asks, dec("1")), Some(dec("100")));
+        assert_eq!(vwap(&asks, dec("2")), Some(dec("100.5")));
+        assert_eq!(vwap(&asks, dec("4")), Some(dec("101.75")));
+        assert_eq!(vwap(&asks, dec("5")), None);
+    }
+
+    #[test]
+    fn test_executable_prices_limited_by_thinner_book() {
+        let asks = levels(&[("100", "1"), ("102", "5")]);
+        let bids = levels(&[("105", "0.5"), ("101", "1")]);
+
+        // 1000 notional wants 10 units; the bid side only holds 1.5
+        let (buy, sell, qty) = executable_prices(&asks, &bids, dec("1000")).unwrap();
+        assert_eq!(qty, dec("1.5"));
+        assert_eq!(buy, (dec("100") + dec("0.5") * dec("102")) / dec("1.5"));
+        assert_eq!(sell, (dec("0.5") * dec("105") + dec("101")) / dec("1.5"));
+
+        // No size information at all: caller falls back to top of book
+        let empty = levels(&[("100", "0")]);
+        assert!(executable_prices(&empty, &bids, dec("1000")).is_none());
+    }
+
+    #[test]
+    fn test_crossed_volume() {
+        let asks = levels(&[("100", "1"), ("101", "2"), ("106", "5")]);
+        let bids = levels(&[("105", "0.5"), ("102", "2"), ("100", "10")]);
+
+        // 0.5 @100/105, 0.5 @100/102, 1.5 @101/102; 106 > 100 stops the walk
+        assert_eq!(crossed_volume(&asks, &bids), dec("2.5"));
+        assert_eq!(crossed_volume(&bids, &asks), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_find_arbitrage_uses_depth() {
+        let scanner = scanner(&[
+            ("MIN_SPREAD_PERCENT", "0.1"),
+            ("EXECUTION_NOTIONAL", "300"),
+            ("FEE_DEFAULT", "0"),
+        ]);
+
+        let mut buy = quote("binance", "BTC/USDT", "99", "100", 0);
+        buy.asks = levels(&[("100", "1"), ("102", "10")]);
+        let mut sell = quote("okx", "BTC/USDT", "105", "106", 0);
+        sell.bids = levels(&[("105", "10")]);
+        store(&scanner, buy);
+        store(&scanner, sell);
+
+        let opp = scanner.find_arbitrage("BTC/USDT").unwrap().best;
+        assert_eq!(opp.buy_exchange, "binance");
+        assert_eq!(opp.sell_exchange, "okx");
+        // 3 units: 1 @100 + 2 @102
+        assert_eq!(opp.executable_quantity, dec("3"));
+        assert_eq!(opp.buy_price, dec("304") / dec("3"));
+        assert_eq!(opp.sell_price, dec("105"));
+        assert_eq!(opp.available_volume, dec("10"));
+    }
+
+    #[test]
+    fn test_fees_filter_thin_spread() {
+        let with_fees = scanner(&[("MIN_SPREAD_PERCENT", "0.1"), ("FEE_DEFAULT", "0.001")]);
+        store(&with_fees, quote("binance", "BTC/USDT", "99", "100", 0));
+        store(&with_fees, quote("okx", "BTC/USDT", "100.15", "101", 0));
+
+        // 0.15% gross, 0.1% taker fee per side leaves a net loss
+        assert!(with_fees.find_arbitrage("BTC/USDT").is_none());
+
+        let fee_free = scanner(&[("MIN_SPREAD_PERCENT", "0.1"), ("FEE_DEFAULT", "0")]);
+        store(&fee_free, quote("binance", "BTC/USDT", "99", "100", 0));
+        store(&fee_free, quote("okx", "BTC/USDT", "100.15", "101", 0));
+
+        let opp = fee_free.find_arbitrage("BTC/USDT").unwrap().best;
+        assert_eq!(opp.spread_percent, dec("0.15"));
+        assert_eq!(opp.net_spread_percent, dec("0.15"));
+    }
+
+    #[test]
+    fn test_per_pair_spread_threshold() {
+        let scanner = scanner(&[
+            ("MIN_SPREAD_PERCENT", "0.5"),
+            ("SPREAD_OVERRIDES", "BTC/USDT:0.1,DOGE/USDT:5"),
+            ("FEE_DEFAULT", "0"),
+        ]);
+        for symbol in ["BTC/USDT", "DOGE/USDT", "ETH/USDT"] {
+            store(&scanner, quote("binance", symbol, "99", "100", 0));
+            store(&scanner, quote("okx", symbol, "100.3", "101", 0));
+        }
+
+        // 0.3%: enough for BTC's override, not for DOGE's or the global default
+        assert!(scanner.find_arbitrage("BTC/USDT").is_some());
+        assert!(scanner.find_arbitrage("DOGE/USDT").is_none());
+        assert!(scanner.find_arbitrage("ETH/USDT").is_none());
+    }
+
+    #[test]
+    fn test_min_notional_filter() {
+        let vars = [("MIN_SPREAD_PERCENT", "0.5"), ("FEE_DEFAULT", "0"), ("MIN_NOTIONAL_USD", "500")];
+        let scanner = scanner(&vars);
+        let mut buy = quote("binance", "BTC/USDT", "99", "100", 0);
+        buy.ask_size = dec("10");
+        let mut sell = quote("okx", "BTC/USDT", "102", "103", 0);
+        sell.bid_size = dec("4");
+        store(&scanner, buy.clone());
+        store(&scanner, sell.clone());
+
+        // 1000 on the ask, 408 on the bid
+        assert!(scanner.find_arbitrage("BTC/USDT").is_none());
+
+        sell.bid_size = dec("5");
+        store(&scanner, sell.clone());
+        let opp = scanner.find_arbitrage("BTC/USDT").unwrap().best;
+        assert_eq!(opp.limiting_size, Some(dec("5")));
+
+        // Gate sends no size: passes by default, dropped when unsized quotes don't count
+        let mut gate = quote("gate", "BTC/USDT", "102", "103", 0);
+        (gate.bid_size, gate.ask_size) = (Decimal::ZERO, Decimal::ZERO);
+        let scanner = self::scanner(&vars);
+        store(&scanner, buy.clone());
+        store(&scanner, gate.clone());
+        assert_eq!(scanner.find_arbitrage("BTC/USDT").unwrap().best.limiting_size, None);
+
+        let strict = self::scanner(&[vars.as_slice(), &[("MIN_NOTIONAL_SKIP_UNSIZED", "false")]].concat());
+        store(&strict, buy);
+        store(&strict, gate);
+        assert!(strict.find_arbitrage("BTC/USDT").is_none());
+    }
+
+    #[test]
+    fn test_opportunity_keeps_real_pairs() {
+        let scanner = scanner(&[("MIN_SPREAD_PERCENT", "0.5"), ("FEE_DEFAULT", "0")]);
+        store(&scanner, quote("binance", "BTC/USDT", "99", "100", 0));
+        // Matched as BTC/USDT through stablecoin equivalence
+        let mut usd = quote("coinbase", "BTC/USDT", "102", "103", 0);
+        usd.raw_symbol = "BTC-USD".to_string();
+        store(&scanner, usd);
+
+        let opp = scanner.find_arbitrage("BTC/USDT").unwrap().best;
+        assert_eq!(opp.buy_symbol, "BTCUSDT");
+        assert_eq!(opp.sell_symbol, "BTC-USD");
+    }
+
+    #[tokio::test]
+    async fn test_one_equivalent_pair_per_exchange() {
+        let scanner = scanner(&[("STABLECOIN_EQUIVALENCE", "true")]);
+        let pair = |raw: &str, bid: &str, age_ms: i64| {
+            let mut update = quote("binance", "BTC/USDT", bid, "200", age_ms);
+            update.raw_symbol = raw.to_string();
+            update
+        };
+        let stored = |scanner: &ArbitrageScanner| {
+            scanner.prices.get("BTC/USDT").unwrap().get("binance").unwrap().raw_symbol.clone()
+        };
+
+        // USDC ticks don't interleave with the USDT pair that STABLECOINS lists first
+        scanner.handle_price_update(pair("BTCUSDC", "99", 0)).await;
+        assert_eq!(stored(&scanner), "BTCUSDC");
+        scanner.handle_price_update(pair("BTCUSDT", "100", 0)).await;
+        scanner.handle_price_update(pair("BTCUSDC", "99", 0)).await;
+        assert_eq!(stored(&scanner), "BTCUSDT");
+
+        // ...unless the USDT pair went stale
+        let scanner = self::scanner(&[("STABLECOIN_EQUIVALENCE", "true"), ("MAX_PRICE_AGE_MS", "5000")]);
+        scanner.handle_price_update(pair("BTCUSDT", "100", 10_000)).await;
+        scanner.handle_price_update(pair("BTCUSDC", "99", 0)).await;
+        assert_eq!(stored(&scanner), "BTCUSDC");
+    }
+
+    #[test]
+    fn test_handle_reads_live_state() {
+        let scanner = scanner(&[("MIN_SPREAD_PERCENT", "0.5"), ("FEE_DEFAULT", "0")]);
+        let handle = scanner.handle();
+        store(&scanner, quote("okx", "BTC/USDT", "102", "103", 0));
+        store(&scanner, quote("binance", "BTC/USDT", "99", "100", 0));
+
+        let prices = handle.prices("BTC/USDT").unwrap();
+        let exchanges: Vec<&str> = prices.iter().map(|p| p.exchange.as_str()).collect();
+        assert_eq!(exchanges, ["binance", "okx"]);
+        assert!(handle.prices("ETH/USDT").is_none());
+
+        let found = scanner.find_arbitrage("BTC/USDT").unwrap();
+        scanner.recent.insert(found.best.symbol.clone(), found);
+        assert_eq!(handle.recent_opportunities().len(), 1);
+    }
+
+    #[test]
+    fn test_runner_up_is_next_best_pair() {
+        let scanner = scanner(&[("MIN_SPREAD_PERCENT", "0.5"), ("FEE_DEFAULT", "0")]);
+        store(&scanner, quote("binance", "BTC/USDT", "99", "100", 0));
+        store(&scanner, quote("kraken", "BTC/USDT", "99.5", "100.5", 0));
+        store(&scanner, quote("okx", "BTC/USDT", "102", "103", 0));
+
+        let found = scanner.find_arbitrage("BTC/USDT").unwrap();
+        assert_eq!((found.best.buy_exchange.as_str(), found.best.sell_exchange.as_str()), ("binance", "okx"));
+        let runner_up = found.runner_up.unwrap();
+        assert_eq!((runner_up.buy_exchange.as_str(), runner_up.sell_exchange.as_str()), ("kraken", "okx"));
+
+        // A runner-up below MIN_SPREAD_PERCENT is not reported
+        store(&scanner, quote("kraken", "BTC/USDT", "99.5", "101.9", 0));
+        assert!(scanner.find_arbitrage("BTC/USDT").unwrap().runner_up.is_none());
+    }
+
+    #[test]
+    fn test_filtered_best_pair_promotes_the_next() {
+        let scanner = scanner(&[("MIN_SPREAD_PERCENT", "0.5"), ("MAX_SPREAD_PERCENT", "10"), ("FEE_DEFAULT", "0")]);
+        store(&scanner, quote("binance", "BTC/USDT", "99", "100", 0));
+        store(&scanner, quote("kraken", "BTC/USDT", "99.5", "100.5", 0));
+        store(&scanner, quote("okx", "BTC/USDT", "102", "103", 0));
+        // Glitched venue: a 50% spread against everyone, dropped as an anomaly
+        store(&scanner, quote("mexc", "BTC/USDT", "150", "151", 0));
+
+        let found = scanner.find_arbitrage("BTC/USDT").unwrap();
+        assert_eq!((found.best.buy_exchange.as_str(), found.best.sell_exchange.as_str()), ("binance", "okx"));
+        let runner_up = found.runner_up.unwrap();
+        assert_eq!((runner_up.buy_exchange.as_str(), runner_up.sell_exchange.as_str()), ("kraken", "okx"));
+    }
+
+    #[tokio::test]
+    async fn test_direction_flip_shares_cooldown() {
+        let scanner = scanner(&[
+            ("MIN_SPREAD_PERCENT", "0.5"),
+            ("FEE_DEFAULT", "0"),
+            ("COOLDOWN_MS", "60000"),
+        ]);
+        let handle = scanner.handle();
+        let mut alerts = handle.subscribe();
+
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "99", "100", 0)).await;
+        scanner.handle_price_update(quote("okx", "BTC/USDT", "102", "103", 0)).await;
+        let first = alerts.try_recv().unwrap();
+        assert_eq!((first.buy_exchange.as_str(), first.sell_exchange.as_str()), ("binance", "okx"));
+
+        // Same two exchanges, now the other way round, still inside the cooldown
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "105", "106", 0)).await;
+        assert!(alerts.try_recv().is_err());
+
+        // Not alerted, but the current direction is what the API shows
+        let recent = handle.recent_opportunities();
+        assert_eq!(recent[0].best.buy_exchange, "okx");
+        assert_eq!(recent[0].best.sell_exchange, "binance");
+    }
+
+    #[tokio::test]
+    async fn test_store_records_detections_inside_cooldown() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(OpportunityStore::open(dir.path().join("opps.db")).unwrap());
+        let scanner = scanner(&[
+            ("MIN_SPREAD_PERCENT", "0.5"),
+            ("FEE_DEFAULT", "0"),
+            ("COOLDOWN_MS", "60000"),
+        ])
+        .with_store(store.clone());
+        let mut alerts = scanner.handle().subscribe();
+
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "99", "100", 0)).await;
+        scanner.handle_price_update(quote("okx", "BTC/USDT", "102", "103", 0)).await;
+        scanner.handle_price_update(quote("okx", "BTC/USDT", "104", "105", 0)).await;
+        store.flush().await;
+
+        // One alert, but both detections are in the history
+        assert!(alerts.try_recv().is_ok());
+        assert!(alerts.try_recv().is_err());
+        let stored = store.top_spreads(0, i64::MAX, 10).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].sell_price, dec("104"));
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_shutdown() {
+        let config = Arc::new(Config::from_lookup(|_| None).unwrap());
+        // Keep the sender alive so only the shutdown token can end the loop
+        let (price_tx, price_rx) = broadcast::channel(16);
+        let scanner = ArbitrageScanner::new(
+            config.clone(),
+            Arc::new(TickerMatcher::new()),
+            Arc::new(Notifier::new(config).unwrap()),
+            price_rx,
+        );
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn(scanner.run(shutdown.clone()));
+
+        shutdown.cancel();
+
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), task).await;
+        assert!(result.unwrap().unwrap().is_ok());
+        drop(price_tx);
+    }
+
+    #[tokio::test]
+    async fn test_cooldowns_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cooldowns.json");
+        let vars = [("MIN_SPREAD_PERCENT", "0.5"), ("FEE_DEFAULT", "0"), ("COOLDOWN_MS", "60000")];
+
+        let first = scanner(&vars).with_cooldown_state(&path);
+        first.handle_price_update(quote("binance", "BTC/USDT", "99", "100", 0)).await;
+        first.handle_price_update(quote("okx", "BTC/USDT", "102", "103", 0)).await;
+        first.save_cooldowns().await;
+
+        // Same opportunity right after a restart stays quiet
+        let second = scanner(&vars).with_cooldown_state(&path);
+        let mut alerts = second.handle().subscribe();
+        second.handle_price_update(quote("binance", "BTC/USDT", "99", "100", 0)).await;
+        second.handle_price_update(quote("okx", "BTC/USDT", "102", "103", 0)).await;
+        assert!(alerts.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_crossed_book_is_dropped() {
+        let scanner = scanner(&[]);
+
+        scanner.handle_price_update(quote("mexc", "BTC/USDT", "101", "100", 0)).await;
+        scanner.handle_price_update(quote("mexc", "ETH/USDT", "10", "10", 0)).await;
+        assert!(scanner.prices.is_empty());
+
+        scanner.handle_price_update(quote("mexc", "BTC/USDT", "99", "100", 0)).await;
+        assert!(scanner.prices.contains_key("BTC/USDT"));
+    }
+
+    #[test]
+    fn test_stale_quotes_are_skipped() {
+        let scanner = scanner(&[
+            ("MIN_SPREAD_PERCENT", "0.5"),
+            ("FEE_DEFAULT", "0"),
+            ("MAX_PRICE_AGE_MS", "5000"),
+        ]);
+        store(&scanner, quote("binance", "BTC/USDT", "99", "100", 0));
+        // Frozen feed showing a 5% spread
+        store(&scanner, quote("okx", "BTC/USDT", "105", "106", 60_000));
+
+        assert!(scanner.find_arbitrage("BTC/USDT").is_none());
+
+        store(&scanner, quote("okx", "BTC/USDT", "105", "106", 100));
+        assert!(scanner.find_arbitrage("BTC/USDT").is_some());
+    }
+
+    #[test]
+    fn test_evict_stale_prices() {
+        let scanner = scanner(&[("MAX_PRICE_AGE_MS", "5000")]);
+        store(&scanner, quote("binance", "BTC/USDT", "99", "100", 0));
+        store(&scanner, quote("okx", "BTC/USDT", "99", "100", 60_000));
+        store(&scanner, quote("okx", "ETH/USDT", "9", "10", 60_000));
+
+        scanner.evict_stale_prices();
+
+        let btc = scanner.prices.get("BTC/USDT").unwrap();
+        assert!(btc.contains_key("binance"));
+        assert!(!btc.contains_key("okx"));
+        assert!(!scanner.prices.contains_key("ETH/USDT"));
+    }
+
+    #[test]
+    fn test_zero_max_age_keeps_everything() {
+        let scanner = scanner(&[("MAX_PRICE_AGE_MS", "0")]);
+        store(&scanner, quote("okx", "ETH/USDT", "9", "10", 3_600_000));
+
+        scanner.evict_stale_prices();
+
+        assert!(scanner.prices.contains_key("ETH/USDT"));
+    }
+
+    #[test]
+    fn test_net_spread_uses_per_exchange_fees() {
+        let scanner = scanner(&[
+            ("MIN_SPREAD_PERCENT", "0.1"),
+            ("FEE_BINANCE", "0.001"),
+            ("FEE_OKX", "0.002"),
+        ]);
+        store(&scanner, quote("binance", "BTC/USDT", "99", "100", 0));
+        store(&scanner, quote("okx", "BTC/USDT", "101", "102", 0));
+
+        let opp = scanner.find_arbitrage("BTC/USDT").unwrap().best;
+        // 101 * 0.998 - 100 * 1.001 = 0.698
+        assert_eq!(opp.net_spread_percent, dec("0.698"));
+        assert_eq!(opp.buy_fee, dec("0.001"));
+        assert_eq!(opp.sell_fee, dec("0.002"));
+    }
+
+    fn store(scanner: &ArbitrageScanner, update: PriceUpdate) {
+        if let Some((base, quote)) = update.symbol.split_once('/') {
+            for currency in [base, quote] {
+                scanner.symbols_by_currency
+                    .entry(currency.to_string())
+                    .or_default()
+                    .insert(update.symbol.clone());
+            }
+        }
+        scanner.prices
+            .entry(update.symbol.clone())
+            .or_default()
+            .insert(update.exchange.clone(), update);
+    }
+
+    const TRIANGULAR: [(&str, &str); 3] = [
+        ("ENABLE_TRIANGULAR", "true"),
+        ("MIN_SPREAD_PERCENT", "0.5"),
+        ("FEE_DEFAULT", "0"),
+    ];
+
+    fn triangular_scanner(extra: &[(&str, &str)]) -> ArbitrageScanner {
+        scanner(&TRIANGULAR.iter().chain(extra).copied().collect::<Vec<_>>())
+    }
+
+    #[tokio::test]
+    async fn test_triangular_cycle_found() {
+        let scanner = triangular_scanner(&[]);
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "49990", "50000", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/BTC", "0.0499", "0.05", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/USDT", "2600", "2601", 0)).await;
+
+        // The third quote completed the cycle and it alerted
+        assert!(scanner.last_alert.contains_key("tri-binance-BTC>ETH>USDT"));
+
+        let found = scanner.find_triangular("binance", "ETH/USDT");
+        assert_eq!(found.len(), 1);
+
+        // USDT -> BTC (buy at 50000) -> ETH (buy at 0.05) -> USDT (sell at 2600) = 4%
+        let opp = &found[0];
+        assert_eq!(opp.profit_percent, Decimal::from(4));
+        assert_eq!(opp.legs.iter().map(|l| l.side).collect::<Vec<_>>(), ["sell", "buy", "buy"]);
+        assert_eq!(opp.cycle_key(), "tri-binance-BTC>ETH>USDT");
+    }
+
+    #[tokio::test]
+    async fn test_triangular_cycle_deduplicated_across_legs() {
+        let scanner = triangular_scanner(&[]);
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "49990", "50000", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/BTC", "0.0499", "0.05", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/USDT", "2600", "2601", 0)).await;
+        // Re-quoting the other legs finds the same cycle, not a new one
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "49990", "50000", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/BTC", "0.0499", "0.05", 0)).await;
+
+        let keys: Vec<String> = scanner.last_alert
+            .iter()
+            .map(|e| e.key().clone())
+            .collect();
+        assert_eq!(keys, ["tri-binance-BTC>ETH>USDT"]);
+    }
+
+    #[tokio::test]
+    async fn test_triangular_requires_fresh_legs_on_same_exchange() {
+        let scanner = triangular_scanner(&[]);
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "49990", "50000", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/BTC", "0.0499", "0.05", 10_000)).await;
+        scanner.handle_price_update(quote("binance", "ETH/USDT", "2600", "2601", 0)).await;
+        assert!(scanner.last_alert.is_empty());
+
+        // A fresh ETH/BTC quote on another exchange doesn't complete the cycle
+        scanner.handle_price_update(quote("okx", "ETH/BTC", "0.0499", "0.05", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/USDT", "2600", "2601", 0)).await;
+        assert!(scanner.last_alert.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_triangular_below_threshold_ignored() {
+        let scanner = triangular_scanner(&[]);
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "50000", "50010", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/BTC", "0.05", "0.05001", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/USDT", "2500", "2501", 0)).await;
+
+        assert!(scanner.last_alert.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_triangular_uses_strictest_leg_override() {
+        // 4% clears the global minimum, but not ETH/BTC's own
+        let scanner = triangular_scanner(&[("SPREAD_OVERRIDES", "ETH/BTC:5")]);
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "49990", "50000", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/BTC", "0.0499", "0.05", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/USDT", "2600", "2601", 0)).await;
+        assert!(scanner.last_alert.is_empty());
+
+        // ...nor a per-leg maximum below it
+        let scanner = triangular_scanner(&[("MAX_SPREAD_OVERRIDES", "BTC/USDT:3")]);
+        scanner.handle_price_update(quote("binance", "BTC/USDT", "49990", "50000", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/BTC", "0.0499", "0.05", 0)).await;
+        scanner.handle_price_update(quote("binance", "ETH/USDT", "2600", "2601", 0)).await;
+        assert!(scanner.last_alert.is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
+use tracing::{error, info, warn};
+
+use crate::scanner::ArbitrageOpportunity;
+
+/// Opportunities buffered for the writer before new ones are dropped
+const QUEUE_SIZE: usize = 10_000;
+/// Max rows written per transaction
+const BATCH_SIZE: usize = 500;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS opportunities (
+        id                 INTEGER PRIMARY KEY AUTOINCREMENT,
+        symbol             TEXT    NOT NULL,
+        buy_exchange       TEXT    NOT NULL,
+        sell_exchange      TEXT    NOT NULL,
+        buy_symbol         TEXT    NOT NULL,
+        sell_symbol        TEXT    NOT NULL,
+        buy_price          TEXT    NOT NULL,
+        sell_price         TEXT    NOT NULL,
+        spread_percent     REAL    NOT NULL,
+        net_spread_percent REAL    NOT NULL,
+        available_volume   TEXT    NOT NULL,
+        timestamp          INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS opportunities_timestamp ON opportunities (timestamp);
+";
+
+/// Work for the writer thread
+// Nearly every message is an insert, so boxing it would only add an allocation per row
+#[allow(clippy::large_enum_variant)]
+enum WriterMessage {
+    Insert(ArbitrageOpportunity),
+    /// Acknowledged once everything queued before it is committed
+    Flush(oneshot::Sender<()>),
+}
+
+/// A row read back from the store
+#[derive(Debug, Clone)]
+pub struct StoredOpportunity {
+    pub symbol: String,
+    pub buy_exchange: String,
+    pub sell_exchange: String,
+    pub buy_symbol: String,
+    pub sell_symbol: String,
+    pub buy_price: Decimal,
+    pub sell_price: Decimal,
+    pub spread_percent: f64,
+    pub net_spread_percent: f64,
+    pub available_volume: Decimal,
+    pub timestamp: i64,
+}
+
+/// Persists opportunities to SQLite (`SQLITE_PATH`).
+/// `record` only enqueues; a dedicated writer thread drains the queue and
+/// inserts whatever has accumulated in one transaction, so disk I/O never blocks the scanner.
+pub struct OpportunityStore {
+    path: PathBuf,
+    tx: mpsc::Sender<WriterMessage>,
+}
+
+impl OpportunityStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut conn = open_connection(&path)?;
+        conn.execute_batch(SCHEMA).context("Failed to create opportunities table")?;
+
+        let (tx, mut rx) = mpsc::channel::<WriterMessage>(QUEUE_SIZE);
+
+        std::thread::Builder::new()
+            .name("sqlite-writer".to_string())
+            .spawn(move || {
+                while let Some(first) = rx.blocking_recv() {
+                    let mut batch = Vec::new();
+                    let mut flushes = Vec::new();
+                    let mut next = Some(first);
+                    while let Some(message) = next.take() {
+                        match message {
+                            WriterMessage::Insert(opportunity) => batch.push(opportunity),
+                            WriterMessage::Flush(ack) => flushes.push(ack),
+                        }
+                        if batch.len() < BATCH_SIZE {
+                            next = rx.try_recv().ok();
+                        }
+                    }
+
+                    if !batch.is_empty() {
+                        if let Err(e) = insert_batch(&mut conn, &batch) {
+                            error!(error = ?e, rows = batch.len(), "Failed to persist opportunities");
+                        }
+                    }
+                    for ack in flushes {
+                        let _ = ack.send(());
+                    }
+                }
+            })
+            .context("Failed to start SQLite writer")?;
+
+        info!(path = %path.display(), "Opportunity store opened");
+        Ok(Self { path, tx })
+    }
+
+    /// Queue an opportunity for writing; dropped (with a warning) if the writer is backed up
+    pub fn record(&self, opportunity: &ArbitrageOpportunity) {
+        match self.tx.try_send(WriterMessage::Insert(opportunity.clone())) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => warn!(symbol = %opportunity.symbol, "Opportunity store queue full, dropping"),
+            Err(TrySendError::Closed(_)) => warn!("Opportunity store writer stopped"),
+        }
+    }
+
+    /// Wait until every opportunity queued so far is committed
+    pub async fn flush(&self) {
+        let (ack, done) = oneshot::channel();
+        if self.tx.send(WriterMessage::Flush(ack)).await.is_err() || done.await.is_err() {
+            warn!("Opportunity store writer stopped before flushing");
+        }
+    }
+
+    /// Highest net spreads seen in `[from_ms, to_ms]`, best first
+    pub fn top_spreads(&self, from_ms: i64, to_ms: i64, limit: usize) -> Result<Vec<StoredOpportunity>> {
+        let conn = open_connection(&self.path)?;
+        query_top_spreads(&conn, from_ms, to_ms, limit)
+    }
+}
+
+fn open_connection(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)
+        .with_context(|| format!("Failed to open SQLite database {}", path.display()))?;
+    // WAL lets report queries read while the writer thread inserts
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    Ok(conn)
+}
+
+fn insert_batch(conn: &mut Connection, batch: &[ArbitrageOpportunity]) -> Result<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO opportunities (
+                symbol, buy_exchange, sell_exchange, buy_symbol, sell_symbol, buy_price, sell_price,
+                spread_percent, net_spread_percent, available_volume, timestamp
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+        for opp in batch {
+            stmt.execute(params![
+                opp.symbol,
+                opp.buy_exchange,
+                opp.sell_exchange,
+                opp.buy_symbol,
+                opp.sell_symbol,
+                opp.buy_price.to_string(),
+                opp.sell_price.to_string(),
+                opp.spread_percent.to_f64().unwrap_or(0.0),
+                opp.net_spread_percent.to_f64().unwrap_or(0.0),
+                opp.available_volume.to_string(),
+                opp.timestamp,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn query_top_spreads(conn: &Connection, from_ms: i64, to_ms: i64, limit: usize) -> Result<Vec<StoredOpportunity>> {
+    let mut stmt = conn.prepare(
+        "SELECT symbol, buy_exchange, sell_exchange, buy_symbol, sell_symbol, buy_price, sell_price,
+                spread_percent, net_spread_percent, available_volume, timestamp
+         FROM opportunities
+         WHERE timestamp BETWEEN ?1 AND ?2
+         ORDER BY net_spread_percent DESC
+         LIMIT ?3",
+    )?;
+
+    let decimal = |s: String| Decimal::from_str(&s).unwrap_or_default();
+    let rows = stmt.query_map(params![from_ms, to_ms, limit as i64], |row| {
+        Ok(StoredOpportunity {
+            symbol: row.get(0)?,
+            buy_exchange: row.get(1)?,
+            sell_exchange: row.get(2)?,
+            buy_symbol: row.get(3)?,
+            sell_symbol: row.get(4)?,
+            buy_price: decimal(row.get(5)?),
+            sell_price: decimal(row.get(6)?),
+            spread_percent: row.get(7)?,
+            net_spread_percent: row.get(8)?,
+            available_volume: decimal(row.get(9)?),
+            timestamp: row.get(10)?,
+        })
+    })?;
+
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opportunity(symbol: &str, net_spread: &str, timestamp: i64) -> ArbitrageOpportunity {
+        let dec = |s: &str| Decimal::from_str(s).unwrap();
+        ArbitrageOpportunity {
+            symbol: symbol.to_string(),
+            buy_exchange: "binance".to_string(),
+            sell_exchange: "okx".to_string(),
+            buy_symbol: symbol.replace('/', ""),
+            sell_symbol: symbol.replace('/', "-"),
+            buy_price: dec("100"),
+            sell_price: dec("101.25"),
+            spread_percent: dec("1.25"),
+            spread_usd: dec("1.25"),
+            net_spread_percent: dec(net_spread),
+            buy_fee: dec("0.001"),
+            sell_fee: dec("0.001"),
+            executable_quantity: dec("1"),
+            available_volume: dec("0.75"),
+            limiting_size: None,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_top_spreads_window() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+
+        insert_batch(&mut conn, &[
+            opportunity("BTC/USDT", "0.9", 1_000),
+            opportunity("ETH/USDT", "1.4", 2_000),
+            opportunity("SOL/USDT", "2.1", 3_000),
+            opportunity("XRP/USDT", "5.0", 9_000),
+        ])
+        .unwrap();
+
+        let top = query_top_spreads(&conn, 1_000, 3_000, 2).unwrap();
+        let symbols: Vec<&str> = top.iter().map(|o| o.symbol.as_str()).collect();
+        assert_eq!(symbols, ["SOL/USDT", "ETH/USDT"]);
+
+        assert_eq!(top[0].sell_symbol, "SOL-USDT");
+        assert_eq!(top[0].sell_price, Decimal::from_str("101.25").unwrap());
+        assert_eq!(top[0].available_volume, Decimal::from_str("0.75").unwrap());
+        assert_eq!(top[0].timestamp, 3_000);
+    }
+
+    #[tokio::test]
+    async fn test_flush_commits_queued_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = OpportunityStore::open(dir.path().join("opportunities.db")).unwrap();
+
+        store.record(&opportunity("BTC/USDT", "0.9", 1_000));
+        store.record(&opportunity("ETH/USDT", "1.4", 2_000));
+        store.flush().await;
+
+        assert_eq!(store.top_spreads(0, 10_000, 10).unwrap().len(), 2);
+    }
+}